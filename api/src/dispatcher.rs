@@ -0,0 +1,35 @@
+use std::time::Duration;
+use user_core::{EventSink, OutboxRepository, dispatch_pending_events};
+
+/// How long a claimed batch stays hidden from other replicas before it is
+/// handed out again.
+const EVENT_LEASE: Duration = Duration::from_secs(30);
+
+/// Background task delivering outbox events to `sink`.
+///
+/// A full batch is followed immediately by the next one so a backlog drains
+/// without waiting for the interval.
+pub async fn run<O: OutboxRepository, S: EventSink>(
+    outbox: O,
+    sink: S,
+    interval: Duration,
+    batch_size: i64,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        loop {
+            match dispatch_pending_events(&outbox, &sink, batch_size, EVENT_LEASE).await {
+                Ok(delivered) if delivered as i64 == batch_size => continue,
+                Ok(_) => break,
+                Err(e) => {
+                    tracing::warn!("User event dispatch failed: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
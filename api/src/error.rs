@@ -3,12 +3,28 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use thiserror::Error;
+use user_core::{FieldError, Suspension, ValidationErrors};
 
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suspension: Option<SuspensionBody>,
+}
+
+/// What a suspended user is told about their suspension; the moderator who
+/// issued it is left out.
+#[derive(Serialize)]
+struct SuspensionBody {
+    reason: String,
+    suspended_at: DateTime<Utc>,
+    /// Absent for a permanent suspension
+    expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Error)]
@@ -16,12 +32,27 @@ pub enum ApiError {
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Account suspended: {}", .0.reason)]
+    Suspended(Suspension),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Validation failed: {0}")]
+    Validation(ValidationErrors),
+
     #[error("Service unavailable: {0}")]
     ServiceUnavailable(String),
 
@@ -31,11 +62,28 @@ pub enum ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
-            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            ApiError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
+        let mut fields = Vec::new();
+        let mut suspension = None;
+        let (status, message) = match self {
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::Suspended(s) => {
+                suspension = Some(SuspensionBody {
+                    reason: s.reason,
+                    suspended_at: s.suspended_at,
+                    expires_at: s.expires_at,
+                });
+                (StatusCode::FORBIDDEN, "Account suspended".to_string())
+            }
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            ApiError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Validation(errors) => {
+                fields = errors.errors;
+                (StatusCode::BAD_REQUEST, "Validation failed".to_string())
+            }
+            ApiError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
             ApiError::InternalServerError(msg) => {
                 tracing::error!("Internal server error: {}", msg);
                 (
@@ -45,7 +93,11 @@ impl IntoResponse for ApiError {
             }
         };
 
-        let body = Json(ErrorResponse { error: message });
+        let body = Json(ErrorResponse {
+            error: message,
+            fields,
+            suspension,
+        });
         (status, body).into_response()
     }
 }
@@ -66,13 +118,17 @@ impl From<user_core::CoreError> for ApiError {
             }
             user_core::CoreError::NotFound(msg) => ApiError::NotFound(msg),
             user_core::CoreError::BadRequest(msg) => ApiError::BadRequest(msg),
+            user_core::CoreError::Validation(errors) => ApiError::Validation(errors),
             user_core::CoreError::Unauthorized(msg) => ApiError::Unauthorized(msg),
+            user_core::CoreError::Conflict(msg) => ApiError::Conflict(msg),
+            user_core::CoreError::TooManyRequests(msg) => ApiError::TooManyRequests(msg),
             user_core::CoreError::InternalError(msg) => ApiError::InternalServerError(msg),
             user_core::CoreError::KeycloakError(keycloak_err) => match keycloak_err {
                 user_core::KeycloakError::UserNotFound(_)
                 | user_core::KeycloakError::UserNotFoundByUsername(_) => {
                     ApiError::NotFound("User not found".to_string())
                 }
+                user_core::KeycloakError::Conflict(msg) => ApiError::Conflict(msg),
                 _ => {
                     tracing::error!("Keycloak error: {}", keycloak_err);
                     ApiError::ServiceUnavailable("Authentication service error".to_string())
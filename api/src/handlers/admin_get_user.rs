@@ -0,0 +1,35 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use std::sync::Arc;
+use user_core::{AdminUserInfo, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/admin/users/{sub}",
+    tag = "admin",
+    params(
+        ("sub" = Uuid, Path, description = "User sub (UUID)")
+    ),
+    responses(
+        (status = 200, description = "Every field of the user, deleted users included", body = AdminUserInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 403, description = "Forbidden - Missing the user-admin role"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn admin_get_user(
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AdminUserInfo>, ApiError> {
+    let info = state.service.user_service.admin_get_user(sub).await?;
+    Ok(Json(info))
+}
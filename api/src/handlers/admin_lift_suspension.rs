@@ -0,0 +1,41 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+};
+use std::sync::Arc;
+use user_core::{AdminUserInfo, AuditContext, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{sub}/suspension",
+    tag = "admin",
+    params(
+        ("sub" = Uuid, Path, description = "User sub (UUID)")
+    ),
+    responses(
+        (status = 200, description = "Suspension lifted; a user who is not suspended is returned unchanged", body = AdminUserInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 403, description = "Forbidden - Missing the user-admin role"),
+        (status = 404, description = "User not found"),
+        (status = 503, description = "Keycloak account could not be re-enabled"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn admin_lift_suspension(
+    Extension(audit): Extension<AuditContext>,
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AdminUserInfo>, ApiError> {
+    let info = state
+        .service
+        .user_service
+        .lift_suspension(sub, &audit)
+        .await?;
+    Ok(Json(info))
+}
@@ -0,0 +1,44 @@
+use crate::error::ApiError;
+use crate::handlers::SearchUsersQuery;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use std::sync::Arc;
+use user_core::{AdminUserPage, UserService};
+
+/// Maximum number of users returned per page
+const MAX_ADMIN_SEARCH_LIMIT: usize = 100;
+
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    params(SearchUsersQuery),
+    responses(
+        (status = 200, description = "Matching users, best match first, privacy settings and blocks ignored. A sub as query returns that user", body = AdminUserPage),
+        (status = 400, description = "Bad request - Empty query or invalid cursor"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 403, description = "Forbidden - Missing the user-admin role"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn admin_search_users(
+    Query(query): Query<SearchUsersQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AdminUserPage>, ApiError> {
+    let page = state
+        .service
+        .user_service
+        .admin_search_users(
+            &query.q,
+            query.cursor.as_deref(),
+            query.limit.min(MAX_ADMIN_SEARCH_LIMIT),
+        )
+        .await?;
+    Ok(Json(page))
+}
@@ -0,0 +1,44 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+};
+use std::sync::Arc;
+use user_core::{AdminUserInfo, AuditContext, SuspendUserRequest, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/admin/users/{sub}/suspension",
+    tag = "admin",
+    params(
+        ("sub" = Uuid, Path, description = "User sub (UUID)")
+    ),
+    request_body = SuspendUserRequest,
+    responses(
+        (status = 200, description = "User suspended, replacing any current suspension", body = AdminUserInfo),
+        (status = 400, description = "Bad request - Invalid input"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 403, description = "Forbidden - Missing the user-admin role"),
+        (status = 404, description = "User not found"),
+        (status = 503, description = "Keycloak account could not be disabled"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn admin_suspend_user(
+    Extension(audit): Extension<AuditContext>,
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SuspendUserRequest>,
+) -> Result<Json<AdminUserInfo>, ApiError> {
+    let info = state
+        .service
+        .user_service
+        .suspend_user(sub, req, &audit)
+        .await?;
+    Ok(Json(info))
+}
@@ -0,0 +1,45 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+};
+use std::sync::Arc;
+use user_core::{AdminUserInfo, AuditContext, UpdateUserRequest, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    put,
+    path = "/admin/users/{sub}",
+    tag = "admin",
+    params(
+        ("sub" = Uuid, Path, description = "User sub (UUID)")
+    ),
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "User updated; the change is audited under the moderator", body = AdminUserInfo),
+        (status = 400, description = "Bad request - Invalid input"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 403, description = "Forbidden - Missing the user-admin role"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Username reserved, taken, or too similar to an existing or recently released username"),
+        (status = 429, description = "Username changed too recently"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn admin_update_user(
+    Extension(audit): Extension<AuditContext>,
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateUserRequest>,
+) -> Result<Json<AdminUserInfo>, ApiError> {
+    let info = state
+        .service
+        .user_service
+        .admin_update_user(sub, req, &audit)
+        .await?;
+    Ok(Json(info))
+}
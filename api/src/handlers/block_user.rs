@@ -0,0 +1,36 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+};
+use std::sync::Arc;
+use user_core::{RelationshipInfo, User, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    put,
+    path = "/users/me/relationships/{sub}/block",
+    tag = "relationships",
+    params(
+        ("sub" = Uuid, Path, description = "Sub of the other user")
+    ),
+    responses(
+        (status = 200, description = "User blocked", body = RelationshipInfo),
+        (status = 400, description = "Bad request - Own sub"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn block_user(
+    Extension(user): Extension<User>,
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RelationshipInfo>, ApiError> {
+    let relationship = state.service.user_service.block_user(user.sub, sub).await?;
+    Ok(Json(relationship))
+}
@@ -0,0 +1,37 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{AuditContext, ConfirmProfilePictureRequest, User, UserBasicInfo, UserService};
+
+#[utoipa::path(
+    post,
+    path = "/users/me/banner/confirm",
+    tag = "users",
+    request_body = ConfirmProfilePictureRequest,
+    responses(
+        (status = 200, description = "Uploaded image is now the banner", body = UserBasicInfo),
+        (status = 400, description = "Bad request - Unknown key, missing upload, or unsupported image"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn confirm_banner(
+    Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ConfirmProfilePictureRequest>,
+) -> Result<Json<UserBasicInfo>, ApiError> {
+    let updated_user = state
+        .service
+        .user_service
+        .confirm_banner(&user, &req.key, &audit)
+        .await?;
+    Ok(Json(updated_user))
+}
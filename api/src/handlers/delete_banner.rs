@@ -0,0 +1,34 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{AuditContext, User, UserBasicInfo, UserService};
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/banner",
+    tag = "users",
+    responses(
+        (status = 200, description = "Banner removed", body = UserBasicInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_banner(
+    Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<UserBasicInfo>, ApiError> {
+    let updated_user = state
+        .service
+        .user_service
+        .remove_banner(&user, &audit)
+        .await?;
+    Ok(Json(updated_user))
+}
@@ -0,0 +1,35 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use user_core::{AccountDeletionInfo, AuditContext, User, UserService};
+
+#[utoipa::path(
+    delete,
+    path = "/users/me",
+    tag = "users",
+    responses(
+        (status = 202, description = "Account deletion scheduled", body = AccountDeletionInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_current_user(
+    Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<AccountDeletionInfo>), ApiError> {
+    let deletion = state
+        .service
+        .user_service
+        .request_account_deletion(&user, &audit)
+        .await?;
+    Ok((StatusCode::ACCEPTED, Json(deletion)))
+}
@@ -0,0 +1,43 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use user_core::{User, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/users/me/exports/{id}/download",
+    tag = "exports",
+    params(
+        ("id" = Uuid, Path, description = "Data export ID")
+    ),
+    responses(
+        (status = 200, description = "Export document with every section", body = Object),
+        (status = 400, description = "Bad request - Export not ready or failed"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 404, description = "Data export not found or expired"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn download_data_export(
+    Extension(user): Extension<User>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let document = state
+        .service
+        .user_service
+        .download_data_export(user.sub, id)
+        .await?;
+    let disposition = format!("attachment; filename=\"user-export-{}.json\"", id);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(document)))
+}
@@ -0,0 +1,58 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Query, State},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use user_core::{AuditPage, User, UserService};
+use utoipa::IntoParams;
+
+/// Maximum number of audit entries returned per page
+pub const MAX_AUDIT_LIMIT: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuditPageQuery {
+    /// Cursor returned by the previous page
+    pub cursor: Option<String>,
+    /// Maximum number of entries to return (default: 20, max: 100)
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/audit",
+    tag = "users",
+    params(AuditPageQuery),
+    responses(
+        (status = 200, description = "Changes made to the account, most recent first", body = AuditPage),
+        (status = 400, description = "Bad request - Invalid cursor"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_current_user_audit(
+    Extension(user): Extension<User>,
+    Query(page): Query<AuditPageQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AuditPage>, ApiError> {
+    let page = state
+        .service
+        .user_service
+        .get_audit_log(
+            user.sub,
+            page.cursor.as_deref(),
+            page.limit.min(MAX_AUDIT_LIMIT),
+        )
+        .await?;
+    Ok(Json(page))
+}
@@ -0,0 +1,29 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{ProfilePrivacy, User, UserService};
+
+#[utoipa::path(
+    get,
+    path = "/users/me/privacy",
+    tag = "users",
+    responses(
+        (status = 200, description = "Visibility of each profile field", body = ProfilePrivacy),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_current_user_privacy(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ProfilePrivacy>, ApiError> {
+    let privacy = state.service.user_service.get_privacy(&user).await?;
+    Ok(Json(privacy))
+}
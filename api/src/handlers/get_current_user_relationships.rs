@@ -0,0 +1,33 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{RelationshipInfo, User, UserService};
+
+#[utoipa::path(
+    get,
+    path = "/users/me/relationships",
+    tag = "relationships",
+    responses(
+        (status = 200, description = "Friends, pending friend requests and blocked users", body = Vec<RelationshipInfo>),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_current_user_relationships(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<RelationshipInfo>>, ApiError> {
+    let relationships = state
+        .service
+        .user_service
+        .list_relationships(user.sub)
+        .await?;
+    Ok(Json(relationships))
+}
@@ -14,7 +14,6 @@ use user_core::{Setting, User, UserService};
     responses(
         (status = 200, description = "User settings retrieved successfully", body = Setting),
         (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
-        (status = 404, description = "Settings not found"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -0,0 +1,39 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+};
+use std::sync::Arc;
+use user_core::{DataExportInfo, User, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/users/me/exports/{id}",
+    tag = "exports",
+    params(
+        ("id" = Uuid, Path, description = "Data export ID")
+    ),
+    responses(
+        (status = 200, description = "Data export status", body = DataExportInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 404, description = "Data export not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_data_export(
+    Extension(user): Extension<User>,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<DataExportInfo>, ApiError> {
+    let export = state
+        .service
+        .user_service
+        .get_data_export(user.sub, id)
+        .await?;
+    Ok(Json(export))
+}
@@ -2,10 +2,10 @@ use crate::error::ApiError;
 use crate::state::AppState;
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Extension, Path, State},
 };
 use std::sync::Arc;
-use user_core::{UserBasicInfo, UserService};
+use user_core::{User, UserBasicInfo, UserService};
 use uuid::Uuid;
 
 #[utoipa::path(
@@ -18,7 +18,7 @@ use uuid::Uuid;
     responses(
         (status = 200, description = "User information retrieved successfully", body = UserBasicInfo),
         (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
-        (status = 404, description = "User not found"),
+        (status = 404, description = "User not found or has blocked the caller"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -26,9 +26,14 @@ use uuid::Uuid;
     )
 )]
 pub async fn get_user_by_sub(
+    Extension(user): Extension<User>,
     Path(sub): Path<Uuid>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<UserBasicInfo>, ApiError> {
-    let user = state.service.user_service.get_user_by_sub(sub).await?;
-    Ok(Json(user))
+    let info = state
+        .service
+        .user_service
+        .get_user_by_sub(user.sub, sub)
+        .await?;
+    Ok(Json(info))
 }
@@ -0,0 +1,16 @@
+use crate::state::AppState;
+use axum::{Json, extract::State};
+use std::sync::Arc;
+use user_core::CacheMetricsSnapshot;
+
+#[utoipa::path(
+    get,
+    path = "/metrics/user-cache",
+    tag = "internal",
+    responses(
+        (status = 200, description = "User identity cache hit/miss counters", body = CacheMetricsSnapshot)
+    )
+)]
+pub async fn get_user_cache_metrics(State(state): State<Arc<AppState>>) -> Json<CacheMetricsSnapshot> {
+    Json(state.service.user_service.cache_metrics())
+}
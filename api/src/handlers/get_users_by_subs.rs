@@ -1,9 +1,12 @@
 use crate::error::ApiError;
 use crate::state::AppState;
-use axum::{Json, extract::State};
-use serde::{Deserialize, Serialize};
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use serde::Deserialize;
 use std::sync::Arc;
-use user_core::{UserBasicInfo, UserService};
+use user_core::{BatchOrder, User, UserBatchPage, UserService, Viewer};
 use uuid::Uuid;
 
 /// Maximum number of subs that can be requested at once
@@ -13,9 +16,11 @@ const MAX_SUBS_PER_REQUEST: usize = 100;
 pub struct GetUsersBySubsRequest {
     /// List of user sub IDs to fetch (max 100)
     pub subs: Vec<Uuid>,
-    /// Number of results to skip (for pagination)
+    /// Order of the returned users (default: requested)
     #[serde(default)]
-    pub offset: usize,
+    pub order: BatchOrder,
+    /// Cursor returned by the previous page, for the same subs and order
+    pub cursor: Option<String>,
     /// Maximum number of results to return (default: 20, max: 100)
     #[serde(default = "default_limit")]
     pub limit: usize,
@@ -25,27 +30,16 @@ fn default_limit() -> usize {
     20
 }
 
-#[derive(Debug, Serialize, utoipa::ToSchema)]
-pub struct GetUsersBySubsResponse {
-    /// List of users found
-    pub users: Vec<UserBasicInfo>,
-    /// Total number of users found (before pagination)
-    pub total: usize,
-    /// Current offset
-    pub offset: usize,
-    /// Current limit
-    pub limit: usize,
-}
-
 #[utoipa::path(
     post,
     path = "/users/bart",
     tag = "users",
     request_body = GetUsersBySubsRequest,
     responses(
-        (status = 200, description = "Users information retrieved successfully", body = GetUsersBySubsResponse),
-        (status = 400, description = "Bad request - Too many subs requested"),
+        (status = 200, description = "Users information retrieved successfully", body = UserBatchPage),
+        (status = 400, description = "Bad request - Too many subs requested, or invalid cursor"),
         (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 403, description = "Service client without the users:read scope"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -53,9 +47,10 @@ pub struct GetUsersBySubsResponse {
     )
 )]
 pub async fn get_users_by_subs(
+    user: Option<Extension<User>>,
     State(state): State<Arc<AppState>>,
     Json(request): Json<GetUsersBySubsRequest>,
-) -> Result<Json<GetUsersBySubsResponse>, ApiError> {
+) -> Result<Json<UserBatchPage>, ApiError> {
     // Validate request
     if request.subs.len() > MAX_SUBS_PER_REQUEST {
         return Err(ApiError::BadRequest(format!(
@@ -64,28 +59,19 @@ pub async fn get_users_by_subs(
         )));
     }
 
-    let limit = request.limit.min(MAX_SUBS_PER_REQUEST);
+    // Service clients look users up on behalf of no one
+    let viewer = user.map_or(Viewer::Anonymous, |Extension(user)| Viewer::User(user.sub));
 
-    // Fetch all matching users
-    let all_users = state
+    let page = state
         .service
         .user_service
-        .get_users_by_subs(&request.subs)
+        .get_users_by_subs(
+            viewer,
+            &request.subs,
+            request.order,
+            request.cursor.as_deref(),
+            request.limit.min(MAX_SUBS_PER_REQUEST),
+        )
         .await?;
-
-    let total = all_users.len();
-
-    // Apply pagination
-    let users: Vec<UserBasicInfo> = all_users
-        .into_iter()
-        .skip(request.offset)
-        .take(limit)
-        .collect();
-
-    Ok(Json(GetUsersBySubsResponse {
-        users,
-        total,
-        offset: request.offset,
-        limit,
-    }))
+    Ok(Json(page))
 }
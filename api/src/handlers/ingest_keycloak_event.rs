@@ -0,0 +1,50 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode};
+use std::sync::Arc;
+use user_core::UserService;
+use user_core::keycloak_events::{SIGNATURE_HEADER, parse_keycloak_event, verify_signature};
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/keycloak",
+    tag = "internal",
+    request_body(
+        content = String,
+        content_type = "application/json",
+        description = "Keycloak user or admin event, signed with the `X-Keycloak-Signature` header (hex HMAC-SHA256 of the body)"
+    ),
+    responses(
+        (status = 204, description = "Event applied, or ignored if it does not affect users"),
+        (status = 400, description = "Bad request - Malformed event"),
+        (status = 401, description = "Unauthorized - Missing or invalid signature"),
+        (status = 503, description = "Service unavailable - No webhook secret configured"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn ingest_keycloak_event(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    let secret = state.keycloak_webhook_secret.as_deref().ok_or_else(|| {
+        ApiError::ServiceUnavailable("Keycloak webhook is not configured".to_string())
+    })?;
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::Unauthorized("Missing signature".to_string()))?;
+    if !verify_signature(secret.as_bytes(), &body, signature) {
+        return Err(ApiError::Unauthorized("Invalid signature".to_string()));
+    }
+
+    let Some(event) = parse_keycloak_event(&body)? else {
+        return Ok(StatusCode::NO_CONTENT);
+    };
+    state
+        .service
+        .user_service
+        .sync_keycloak_event(&event)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
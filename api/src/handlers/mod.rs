@@ -1,17 +1,79 @@
+mod accept_friend_request;
+mod admin_get_user;
+mod admin_lift_suspension;
+mod admin_search_users;
+mod admin_suspend_user;
+mod admin_update_user;
+mod block_user;
+mod cancel_friend_request;
+mod confirm_banner;
+mod confirm_profile_picture;
+mod decline_friend_request;
+mod delete_banner;
+mod delete_current_user;
+mod download_data_export;
 mod get_current_user;
+mod get_current_user_audit;
+mod get_current_user_privacy;
+mod get_current_user_relationships;
 mod get_current_user_settings;
+mod get_data_export;
 mod get_user_by_sub;
 mod get_user_by_username;
+mod get_user_cache_metrics;
 mod get_users_by_subs;
+mod ingest_keycloak_event;
+mod post_banner_request;
+mod query_audit_log;
+mod remove_friend;
+mod request_data_export;
+mod restore_current_user;
+mod search_users;
+mod send_friend_request;
+mod unblock_user;
 mod update_current_user;
+mod update_current_user_privacy;
 mod update_current_user_settings;
+mod update_current_user_status;
+mod verify_current_user_email;
 mod post_profile_picture_request;
 
+pub use accept_friend_request::*;
+pub use admin_get_user::*;
+pub use admin_lift_suspension::*;
+pub use admin_search_users::*;
+pub use admin_suspend_user::*;
+pub use admin_update_user::*;
+pub use block_user::*;
+pub use cancel_friend_request::*;
+pub use confirm_banner::*;
+pub use confirm_profile_picture::*;
+pub use decline_friend_request::*;
+pub use delete_banner::*;
+pub use delete_current_user::*;
+pub use download_data_export::*;
 pub use get_current_user::*;
+pub use get_current_user_audit::*;
+pub use get_current_user_privacy::*;
+pub use get_current_user_relationships::*;
 pub use get_current_user_settings::*;
+pub use get_data_export::*;
 pub use get_user_by_sub::*;
 pub use get_user_by_username::*;
+pub use get_user_cache_metrics::*;
 pub use get_users_by_subs::*;
+pub use ingest_keycloak_event::*;
+pub use post_banner_request::*;
+pub use query_audit_log::*;
+pub use remove_friend::*;
+pub use request_data_export::*;
+pub use restore_current_user::*;
+pub use search_users::*;
+pub use send_friend_request::*;
+pub use unblock_user::*;
 pub use update_current_user::*;
+pub use update_current_user_privacy::*;
 pub use update_current_user_settings::*;
+pub use update_current_user_status::*;
+pub use verify_current_user_email::*;
 pub use post_profile_picture_request::*;
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, extract::State};
+use user_core::{ProfilePictureRequest, User, UserService};
+
+use crate::{error::ApiError, state::AppState};
+
+#[utoipa::path(
+    post,
+    path = "/users/me/banner",
+    tag = "users",
+    responses(
+        (status = 200, description = "Upload URL for a new banner", body = ProfilePictureRequest),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn post_banner_request(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ProfilePictureRequest>, ApiError> {
+    let request = state
+        .service
+        .user_service
+        .request_banner_upload(&user)
+        .await?;
+    Ok(Json(request))
+}
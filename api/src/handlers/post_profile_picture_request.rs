@@ -10,7 +10,7 @@ use crate::{error::ApiError, state::AppState};
     path = "/users/me/profile-picture",
     tag = "users",
     responses(
-        (status = 200, description = "Profile picture updated successfully"),
+        (status = 200, description = "Upload URL for a new profile picture", body = ProfilePictureRequest),
         (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
         (status = 500, description = "Internal server error")
     ),
@@ -22,6 +22,6 @@ pub async fn post_profile_picture_request(
     Extension(user): Extension<User>,
     State(state): State<Arc<AppState>>,
     ) -> Result<Json<ProfilePictureRequest>, ApiError> {
-    let url = state.service.user_service.generate_profile_picture_url(&user).await?;
-    Ok(Json(ProfilePictureRequest::new(url)))
+    let request = state.service.user_service.request_profile_picture_upload(&user).await?;
+    Ok(Json(request))
 }
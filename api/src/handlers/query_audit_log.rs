@@ -0,0 +1,37 @@
+use crate::error::ApiError;
+use crate::handlers::{AuditPageQuery, MAX_AUDIT_LIMIT};
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use std::sync::Arc;
+use user_core::{AuditLogQuery, AuditPage, UserService};
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    tag = "internal",
+    params(AuditLogQuery, AuditPageQuery),
+    responses(
+        (status = 200, description = "Matching audit entries, most recent first", body = AuditPage),
+        (status = 400, description = "Bad request - Invalid filter or cursor"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn query_audit_log(
+    Query(query): Query<AuditLogQuery>,
+    Query(page): Query<AuditPageQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AuditPage>, ApiError> {
+    let page = state
+        .service
+        .user_service
+        .query_audit_log(
+            query,
+            page.cursor.as_deref(),
+            page.limit.min(MAX_AUDIT_LIMIT),
+        )
+        .await?;
+    Ok(Json(page))
+}
@@ -0,0 +1,35 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use user_core::{User, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/relationships/{sub}/friend",
+    tag = "relationships",
+    params(
+        ("sub" = Uuid, Path, description = "Sub of the other user")
+    ),
+    responses(
+        (status = 204, description = "Friend removed"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 404, description = "Friend not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn remove_friend(
+    Extension(user): Extension<User>,
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, ApiError> {
+    state.service.user_service.remove_friend(user.sub, sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,34 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use user_core::{DataExportInfo, User, UserService};
+
+#[utoipa::path(
+    post,
+    path = "/users/me/export",
+    tag = "exports",
+    responses(
+        (status = 202, description = "Data export queued, or the export already in progress", body = DataExportInfo),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn request_data_export(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<DataExportInfo>), ApiError> {
+    let export = state
+        .service
+        .user_service
+        .request_data_export(user.sub)
+        .await?;
+    Ok((StatusCode::ACCEPTED, Json(export)))
+}
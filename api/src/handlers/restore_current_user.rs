@@ -0,0 +1,36 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use user_core::{AuditContext, User, UserService};
+
+#[utoipa::path(
+    post,
+    path = "/users/me/restore",
+    tag = "users",
+    responses(
+        (status = 204, description = "Account deletion cancelled"),
+        (status = 400, description = "Bad request - Account purge already started"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 404, description = "No pending account deletion"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn restore_current_user(
+    Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .service
+        .user_service
+        .cancel_account_deletion(&user, &audit)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
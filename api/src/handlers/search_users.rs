@@ -0,0 +1,61 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, Query, State},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use user_core::{User, UserSearchPage, UserService};
+use utoipa::IntoParams;
+
+/// Maximum number of users returned per page
+const MAX_SEARCH_LIMIT: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+pub struct SearchUsersQuery {
+    /// Text to match against display names and usernames
+    pub q: String,
+    /// Cursor returned by the previous page
+    pub cursor: Option<String>,
+    /// Maximum number of results to return (default: 20, max: 50)
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/search",
+    tag = "users",
+    params(SearchUsersQuery),
+    responses(
+        (status = 200, description = "Matching users, best match first", body = UserSearchPage),
+        (status = 400, description = "Bad request - Empty or too long query, or invalid cursor"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn search_users(
+    Extension(user): Extension<User>,
+    Query(query): Query<SearchUsersQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<UserSearchPage>, ApiError> {
+    let page = state
+        .service
+        .user_service
+        .search_users(
+            user.sub,
+            &query.q,
+            query.cursor.as_deref(),
+            query.limit.min(MAX_SEARCH_LIMIT),
+        )
+        .await?;
+    Ok(Json(page))
+}
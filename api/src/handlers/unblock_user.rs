@@ -0,0 +1,35 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use user_core::{User, UserService};
+use uuid::Uuid;
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/relationships/{sub}/block",
+    tag = "relationships",
+    params(
+        ("sub" = Uuid, Path, description = "Sub of the other user")
+    ),
+    responses(
+        (status = 204, description = "User unblocked"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 404, description = "Blocked user not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unblock_user(
+    Extension(user): Extension<User>,
+    Path(sub): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, ApiError> {
+    state.service.user_service.unblock_user(user.sub, sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -5,7 +5,7 @@ use axum::{
     extract::{Extension, State},
 };
 use std::sync::Arc;
-use user_core::{UpdateUserRequest, User, UserBasicInfo, UserService};
+use user_core::{AuditContext, UpdateUserRequest, User, UserBasicInfo, UserService};
 
 #[utoipa::path(
     put,
@@ -16,6 +16,8 @@ use user_core::{UpdateUserRequest, User, UserBasicInfo, UserService};
         (status = 200, description = "User updated successfully", body = UserBasicInfo),
         (status = 400, description = "Bad request - Invalid input"),
         (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 409, description = "Username reserved, taken, or too similar to an existing or recently released username"),
+        (status = 429, description = "Username changed too recently"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -24,9 +26,14 @@ use user_core::{UpdateUserRequest, User, UserBasicInfo, UserService};
 )]
 pub async fn update_current_user(
     Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
     State(state): State<Arc<AppState>>,
     Json(req): Json<UpdateUserRequest>,
 ) -> Result<Json<UserBasicInfo>, ApiError> {
-    let updated_user = state.service.user_service.update_user(&user, req).await?;
+    let updated_user = state
+        .service
+        .user_service
+        .update_user(&user, req, &audit)
+        .await?;
     Ok(Json(updated_user))
 }
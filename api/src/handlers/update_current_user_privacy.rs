@@ -0,0 +1,37 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{AuditContext, ProfilePrivacy, UpdatePrivacyRequest, User, UserService};
+
+#[utoipa::path(
+    put,
+    path = "/users/me/privacy",
+    tag = "users",
+    request_body = UpdatePrivacyRequest,
+    responses(
+        (status = 200, description = "Profile privacy updated", body = ProfilePrivacy),
+        (status = 400, description = "Bad request - Invalid input"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_current_user_privacy(
+    Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdatePrivacyRequest>,
+) -> Result<Json<ProfilePrivacy>, ApiError> {
+    let privacy = state
+        .service
+        .user_service
+        .update_privacy(&user, req, &audit)
+        .await?;
+    Ok(Json(privacy))
+}
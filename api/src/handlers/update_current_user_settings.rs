@@ -5,7 +5,7 @@ use axum::{
     extract::{Extension, State},
 };
 use std::sync::Arc;
-use user_core::{Setting, UpdateSettingRequest, User, UserService};
+use user_core::{AuditContext, Setting, UpdateSettingRequest, User, UserService};
 
 #[utoipa::path(
     put,
@@ -24,13 +24,14 @@ use user_core::{Setting, UpdateSettingRequest, User, UserService};
 )]
 pub async fn update_current_user_settings(
     Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
     State(state): State<Arc<AppState>>,
     Json(req): Json<UpdateSettingRequest>,
 ) -> Result<Json<Setting>, ApiError> {
     let setting = state
         .service
         .user_service
-        .update_user_settings(user.sub, req)
+        .update_user_settings(user.sub, req, &audit)
         .await?;
     Ok(Json(setting))
 }
@@ -0,0 +1,36 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{UpdateStatusRequest, User, UserBasicInfo, UserService};
+
+#[utoipa::path(
+    put,
+    path = "/users/me/status",
+    tag = "users",
+    request_body = UpdateStatusRequest,
+    responses(
+        (status = 200, description = "Status updated successfully", body = UserBasicInfo),
+        (status = 400, description = "Bad request - Invalid status"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_current_user_status(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateStatusRequest>,
+) -> Result<Json<UserBasicInfo>, ApiError> {
+    let updated_user = state
+        .service
+        .user_service
+        .update_user_status(&user, req)
+        .await?;
+    Ok(Json(updated_user))
+}
@@ -0,0 +1,38 @@
+use crate::error::ApiError;
+use crate::state::AppState;
+use axum::{
+    Json,
+    extract::{Extension, State},
+};
+use std::sync::Arc;
+use user_core::{AuditContext, User, UserFullInfo, UserService, VerifyEmailRequest};
+
+#[utoipa::path(
+    post,
+    path = "/users/me/email/verify",
+    tag = "users",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email changed and marked as verified", body = UserFullInfo),
+        (status = 400, description = "Invalid, expired or superseded verification token"),
+        (status = 401, description = "Unauthorized - Invalid or missing JWT token"),
+        (status = 409, description = "Email already used by another account"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn verify_current_user_email(
+    Extension(user): Extension<User>,
+    Extension(audit): Extension<AuditContext>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> Result<Json<UserFullInfo>, ApiError> {
+    let info = state
+        .service
+        .user_service
+        .verify_email_change(&user, req, &audit)
+        .await?;
+    Ok(Json(info))
+}
@@ -0,0 +1,104 @@
+use config::Config;
+use std::future::Future;
+use std::time::Duration;
+use user_core::{ApplicationService, CoreError, UserService};
+
+/// Accounts purged per run; the next batch waits for the following tick.
+const PURGE_BATCH_SIZE: i64 = 50;
+
+/// Exports built per run.
+const EXPORT_BATCH_SIZE: i64 = 10;
+
+/// Replaced avatars deleted per run.
+const CLEANUP_BATCH_SIZE: i64 = 100;
+
+/// Suspensions lifted per run.
+const EXPIRY_BATCH_SIZE: i64 = 100;
+
+/// Keycloak repairs replayed per run.
+const REPAIR_BATCH_SIZE: i64 = 50;
+
+/// Spawns a task running `job` on up to `batch` items every `interval`.
+///
+/// A run that outlasts the interval delays the next one rather than
+/// triggering a burst of catch-up runs.
+pub fn spawn_periodic<F, Fut>(name: &'static str, interval: Duration, batch: i64, job: F)
+where
+    F: Fn(i64) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<usize, CoreError>> + Send,
+{
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            match job(batch).await {
+                Ok(0) => {}
+                Ok(processed) => tracing::info!("{}: processed {}", name, processed),
+                Err(e) => tracing::warn!("{} failed: {}", name, e),
+            }
+        }
+    });
+}
+
+/// Spawns the background jobs of the user service.
+pub fn spawn_all(service: &ApplicationService, config: &Config) {
+    let seconds = Duration::from_secs;
+
+    let purge = service.clone();
+    spawn_periodic(
+        "Account purge",
+        seconds(config.account_purge_interval_seconds),
+        PURGE_BATCH_SIZE,
+        move |batch| {
+            let service = purge.clone();
+            async move { service.user_service.purge_deleted_accounts(batch).await }
+        },
+    );
+
+    let export = service.clone();
+    spawn_periodic(
+        "Data export",
+        seconds(config.data_export_interval_seconds),
+        EXPORT_BATCH_SIZE,
+        move |batch| {
+            let service = export.clone();
+            async move { service.user_service.process_data_exports(batch).await }
+        },
+    );
+
+    let cleanup = service.clone();
+    spawn_periodic(
+        "Avatar cleanup",
+        seconds(config.avatar_cleanup_interval_seconds),
+        CLEANUP_BATCH_SIZE,
+        move |batch| {
+            let service = cleanup.clone();
+            async move { service.user_service.cleanup_replaced_avatars(batch).await }
+        },
+    );
+
+    let expiry = service.clone();
+    spawn_periodic(
+        "Suspension expiry",
+        seconds(config.suspension_expiry_interval_seconds),
+        EXPIRY_BATCH_SIZE,
+        move |batch| {
+            let service = expiry.clone();
+            async move { service.user_service.expire_suspensions(batch).await }
+        },
+    );
+
+    let repair = service.clone();
+    spawn_periodic(
+        "Keycloak repair",
+        seconds(config.keycloak_repair_interval_seconds),
+        REPAIR_BATCH_SIZE,
+        move |batch| {
+            let service = repair.clone();
+            async move { service.user_service.repair_keycloak_users(batch).await }
+        },
+    );
+}
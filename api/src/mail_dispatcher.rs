@@ -0,0 +1,33 @@
+use std::time::Duration;
+use user_core::{MailOutboxRepository, Mailer, dispatch_pending_emails};
+
+/// How long a claimed batch stays hidden from other replicas before it is
+/// handed out again.
+const EMAIL_LEASE: Duration = Duration::from_secs(60);
+
+/// Emails handed to the mailer per batch.
+const EMAIL_BATCH_SIZE: i64 = 50;
+
+/// Background task handing queued emails to `mailer`.
+///
+/// A full batch is followed immediately by the next one so a backlog drains
+/// without waiting for the interval.
+pub async fn run<O: MailOutboxRepository, M: Mailer>(outbox: O, mailer: M, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        loop {
+            match dispatch_pending_emails(&outbox, &mailer, EMAIL_BATCH_SIZE, EMAIL_LEASE).await {
+                Ok(sent) if sent as i64 == EMAIL_BATCH_SIZE => continue,
+                Ok(_) => break,
+                Err(e) => {
+                    tracing::warn!("Email dispatch failed: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
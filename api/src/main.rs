@@ -1,32 +1,45 @@
+mod dispatcher;
 mod error;
 mod handlers;
+mod jobs;
+mod mail_dispatcher;
 mod middleware;
 mod openapi;
 mod state;
 
 use crate::{
     handlers::{
-        get_current_user, get_current_user_settings, get_user_by_sub, get_user_by_username, get_users_by_subs, post_profile_picture_request, update_current_user, update_current_user_settings
+        accept_friend_request, admin_get_user, admin_lift_suspension, admin_search_users, admin_suspend_user, admin_update_user, block_user, cancel_friend_request, confirm_banner, confirm_profile_picture, decline_friend_request, delete_banner, delete_current_user, download_data_export, get_current_user, get_current_user_audit, get_current_user_privacy, get_current_user_relationships, get_current_user_settings, get_data_export, get_user_by_sub, get_user_by_username, get_user_cache_metrics, get_users_by_subs, ingest_keycloak_event, post_banner_request, post_profile_picture_request, query_audit_log, remove_friend, request_data_export, restore_current_user, search_users, send_friend_request, unblock_user, update_current_user, update_current_user_privacy, update_current_user_settings, update_current_user_status, verify_current_user_email
+    },
+    middleware::{
+        AllowService, RequireRole, RequireUser, USER_ADMIN_ROLE, USERS_READ_SCOPE, auth_middleware,
     },
-    middleware::auth_middleware,
     openapi::ApiDoc,
     state::AppState,
 };
 use axum::{
     Json, Router, middleware as axum_middleware,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use beep_auth::KeycloakAuthRepository;
 use clap::{Parser, Subcommand};
 use config::Config;
 use sqlx::postgres::PgPoolOptions;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::{
     cors::{Any, CorsLayer},
     trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
 };
 use tracing::Level;
-use user_core::{ApplicationService, KeycloakService, PostgresUserRepository, services::content::ContentServiceClientImpl};
+use user_core::{
+    ApplicationService, EmailVerification, FileEventSink, FileMailer, ImportCheckpoint,
+    ImportFormat, KeycloakService, LogEventSink, PostgresUserRepository, StdoutMailer, UserCache,
+    UserImporter, UserService, UsernamePolicy, import::DEFAULT_IMPORT_BATCH_SIZE,
+    services::content::ContentServiceClientImpl,
+};
 use utoipa::OpenApi;
 use utoipa_scalar::{Scalar, Servable};
 
@@ -44,6 +57,66 @@ enum Commands {
     Run,
     /// Run database migrations
     Migrate,
+    /// Compare Keycloak accounts with the users table and print a JSON report
+    Reconcile {
+        /// Create missing local users and flag orphaned ones for deletion
+        #[arg(long, conflicts_with = "dry_run")]
+        fix: bool,
+        /// Only report differences (default)
+        #[arg(long)]
+        dry_run: bool,
+        /// Accounts fetched per Keycloak and database page
+        #[arg(long, default_value_t = 100)]
+        page_size: usize,
+    },
+    /// Create or update users from a CSV or JSONL file and print a JSON report
+    Import {
+        /// File to import; `sub` and `display_name` are required per row
+        file: PathBuf,
+        /// `csv` or `jsonl`; guessed from the file extension by default
+        #[arg(long)]
+        format: Option<ImportFormat>,
+        /// Rows written per transaction
+        #[arg(long, default_value_t = DEFAULT_IMPORT_BATCH_SIZE)]
+        batch_size: usize,
+        /// File recording the last imported line, to resume an interrupted import
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+    },
+}
+
+fn application_service(config: &Config, user_repo: PostgresUserRepository) -> ApplicationService {
+    let keycloak_service = KeycloakService::new(
+        config.keycloak_internal_url.clone(),
+        config.keycloak_realm.clone(),
+        config.keycloak_client_id.clone(),
+        config.keycloak_client_secret.clone(),
+    );
+    let content_service = ContentServiceClientImpl::new(config.content_service_url.clone());
+
+    let user_cache = UserCache::new(
+        config.user_cache_capacity,
+        Duration::from_secs(config.user_cache_ttl_seconds),
+    );
+
+    ApplicationService::new(
+        user_repo,
+        keycloak_service,
+        content_service,
+        user_cache,
+        Duration::from_secs(config.account_deletion_grace_days * 24 * 3600),
+        UsernamePolicy::new(
+            Duration::from_secs(config.username_change_cooldown_days * 24 * 3600),
+            Duration::from_secs(config.username_hold_days * 24 * 3600),
+        )
+        .with_reserved(&config.reserved_usernames)
+        .with_blocked(&config.blocked_usernames),
+        EmailVerification::new(
+            config.email_verification_secret.clone(),
+            Duration::from_secs(config.email_verification_ttl_hours * 3600),
+            config.email_verification_url.clone(),
+        ),
+    )
 }
 
 #[tokio::main]
@@ -71,6 +144,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             sqlx::migrate!("../migrations").run(&pool).await?;
             tracing::info!("Migrations completed successfully");
         }
+        Commands::Reconcile { fix, page_size, .. } => {
+            // Logs go to stderr so stdout only holds the report
+            tracing_subscriber::fmt()
+                .with_env_filter(
+                    tracing_subscriber::EnvFilter::try_from_default_env()
+                        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+                )
+                .with_writer(std::io::stderr)
+                .init();
+
+            let pool = PgPoolOptions::new()
+                .max_connections(5)
+                .connect(&config.database_url)
+                .await?;
+            let service = application_service(&config, PostgresUserRepository::new(pool));
+
+            let report = service
+                .user_service
+                .reconcile_keycloak_users(!fix, page_size)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Commands::Import {
+            file,
+            format,
+            batch_size,
+            checkpoint,
+        } => {
+            // Logs go to stderr so stdout only holds the report
+            tracing_subscriber::fmt()
+                .with_env_filter(
+                    tracing_subscriber::EnvFilter::try_from_default_env()
+                        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+                )
+                .with_writer(std::io::stderr)
+                .init();
+
+            let format = format
+                .or_else(|| ImportFormat::from_path(&file))
+                .ok_or("Cannot guess the import format from the file name, pass --format")?;
+            let input = std::fs::File::open(&file)?;
+
+            let pool = PgPoolOptions::new()
+                .max_connections(5)
+                .connect(&config.database_url)
+                .await?;
+            let mut importer = UserImporter::new(PostgresUserRepository::new(pool))
+                .with_batch_size(batch_size);
+            if let Some(checkpoint) = checkpoint {
+                importer = importer.with_checkpoint(ImportCheckpoint::new(checkpoint));
+            }
+
+            let report = importer.run(input, format).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
         Commands::Run => {
             // Full telemetry with OTLP for the running service
             let telemetry_config = beep_telemetry::domain::models::Config {
@@ -86,6 +214,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             tracing::info!("Initializing services...");
             let user_repo = PostgresUserRepository::new(pool);
+
+            let dispatch_interval = Duration::from_millis(config.event_dispatch_interval_ms);
+            let dispatch_batch_size = config.event_dispatch_batch_size;
+            match config.event_sink_file.clone() {
+                Some(path) => {
+                    tracing::info!("Writing user events to {}", path);
+                    tokio::spawn(dispatcher::run(
+                        user_repo.clone(),
+                        FileEventSink::new(path),
+                        dispatch_interval,
+                        dispatch_batch_size,
+                    ));
+                }
+                None => {
+                    tokio::spawn(dispatcher::run(
+                        user_repo.clone(),
+                        LogEventSink,
+                        dispatch_interval,
+                        dispatch_batch_size,
+                    ));
+                }
+            }
+
+            let mail_interval = Duration::from_secs(config.mail_dispatch_interval_seconds);
+            match config.mailer_file.clone() {
+                Some(path) => {
+                    tracing::info!("Writing outgoing emails to {}", path);
+                    tokio::spawn(mail_dispatcher::run(
+                        user_repo.clone(),
+                        FileMailer::new(path),
+                        mail_interval,
+                    ));
+                }
+                None => {
+                    tokio::spawn(mail_dispatcher::run(
+                        user_repo.clone(),
+                        StdoutMailer,
+                        mail_interval,
+                    ));
+                }
+            }
             let auth_repository = KeycloakAuthRepository::new(
                 format!(
                     "{}/realms/{}",
@@ -93,17 +262,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 ),
                 None,
             );
-            let keycloak_service = KeycloakService::new(
-                config.keycloak_internal_url,
-                config.keycloak_realm,
-                config.keycloak_client_id,
-                config.keycloak_client_secret,
-            );
-            let content_service = ContentServiceClientImpl::new(config.content_service_url);
+            let service = application_service(&config, user_repo);
 
-            let service = ApplicationService::new(user_repo, keycloak_service, content_service);
+            jobs::spawn_all(&service, &config);
 
-            let app_state = Arc::new(AppState::new(service, auth_repository));
+            // Rows provisioned for service clients by earlier versions
+            let cleanup = service.clone();
+            tokio::spawn(async move {
+                match cleanup.user_service.flag_service_account_users().await {
+                    Ok(0) => {}
+                    Ok(flagged) => tracing::info!("Flagged {} service account rows", flagged),
+                    Err(e) => tracing::warn!("Flagging service account rows failed: {}", e),
+                }
+            });
+
+            let app_state = Arc::new(AppState::new(
+                service,
+                auth_repository,
+                config.keycloak_client_id.clone(),
+                config.keycloak_webhook_secret.clone(),
+                config.trusted_proxies.clone(),
+            ));
 
             let cors = CorsLayer::new()
                 .allow_origin(Any)
@@ -115,15 +294,79 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .on_request(DefaultOnRequest::new().level(Level::INFO))
                 .on_response(DefaultOnResponse::new().level(Level::INFO));
 
+            // Moderation routes, for callers with the user-admin role
+            let admin_routes = Router::new()
+                .route("/admin/users", get(admin_search_users))
+                .route(
+                    "/admin/users/:sub",
+                    get(admin_get_user).put(admin_update_user),
+                )
+                .route(
+                    "/admin/users/:sub/suspension",
+                    post(admin_suspend_user).delete(admin_lift_suspension),
+                )
+                .route_layer(RequireRole(USER_ADMIN_ROLE));
+
+            // Routes service clients may call, given the scope
+            let service_routes = Router::new()
+                .route("/users/bart", post(get_users_by_subs))
+                .route_layer(AllowService(USERS_READ_SCOPE));
+
             let protected_routes = Router::new()
-                .route("/users/me", get(get_current_user).put(update_current_user))
+                .route(
+                    "/users/me",
+                    get(get_current_user)
+                        .put(update_current_user)
+                        .delete(delete_current_user),
+                )
+                .route("/users/me/restore", post(restore_current_user))
+                .route("/users/me/email/verify", post(verify_current_user_email))
+                .route("/users/me/audit", get(get_current_user_audit))
+                .route("/users/me/export", post(request_data_export))
+                .route("/users/me/exports/:id", get(get_data_export))
+                .route("/users/me/exports/:id/download", get(download_data_export))
                 .route(
                     "/users/me/settings",
                     get(get_current_user_settings).put(update_current_user_settings),
                 )
+                .route("/users/me/status", put(update_current_user_status))
+                .route(
+                    "/users/me/privacy",
+                    get(get_current_user_privacy).put(update_current_user_privacy),
+                )
+                .route("/users/me/relationships", get(get_current_user_relationships))
+                .route(
+                    "/users/me/relationships/:sub/friend-request",
+                    post(send_friend_request).delete(cancel_friend_request),
+                )
+                .route(
+                    "/users/me/relationships/:sub/accept",
+                    post(accept_friend_request),
+                )
+                .route(
+                    "/users/me/relationships/:sub/decline",
+                    post(decline_friend_request),
+                )
+                .route("/users/me/relationships/:sub/friend", delete(remove_friend))
+                .route(
+                    "/users/me/relationships/:sub/block",
+                    put(block_user).delete(unblock_user),
+                )
                 .route("/users/me/profile-picture", post(post_profile_picture_request))
-                .route("/users/bart", post(get_users_by_subs))
+                .route(
+                    "/users/me/profile-picture/confirm",
+                    post(confirm_profile_picture),
+                )
+                .route(
+                    "/users/me/banner",
+                    post(post_banner_request).delete(delete_banner),
+                )
+                .route("/users/me/banner/confirm", post(confirm_banner))
+                .route("/users/search", get(search_users))
                 .route("/users/:sub", get(get_user_by_sub))
+                .merge(admin_routes)
+                .route_layer(RequireUser)
+                .merge(service_routes)
                 .layer(axum_middleware::from_fn_with_state(
                     app_state.clone(),
                     auth_middleware,
@@ -146,6 +389,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     get(|| async { Json(serde_json::json!({ "status": "ok" })) }),
                 )
                 .route("/users/username/:username", get(get_user_by_username))
+                .route("/metrics/user-cache", get(get_user_cache_metrics))
+                .route("/admin/audit", get(query_audit_log))
+                .route("/webhooks/keycloak", post(ingest_keycloak_event))
                 .with_state(app_state);
 
             let api_addr = format!("{}:{}", config.server_host, config.server_port);
@@ -158,7 +404,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             tracing::info!("Health server listening on {}", health_addr);
 
             tokio::try_join!(
-                axum::serve(api_listener, app),
+                axum::serve(
+                    api_listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                ),
                 axum::serve(health_listener, internal_router),
             )?;
         }
@@ -0,0 +1,62 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::header::USER_AGENT,
+};
+use std::net::{IpAddr, SocketAddr};
+use user_core::AuditContext;
+use uuid::Uuid;
+
+/// Header each proxy appends the address it received the request from to.
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Audit context of a request made by `actor_sub`.
+///
+/// The client address is the TCP peer, unless the peer is one of
+/// `trusted_proxies`: `X-Forwarded-For` is then read from the right, and the
+/// first hop that is not a trusted proxy is the client. Hops further left are
+/// written by the client and may be forged.
+pub fn audit_context(
+    req: &Request<Body>,
+    actor_sub: Uuid,
+    trusted_proxies: &[IpAddr],
+) -> AuditContext {
+    let headers = req.headers();
+
+    let forwarded_for: Vec<&str> = headers
+        .get_all(FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    let ip_address = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| client_ip(addr.ip(), &forwarded_for, trusted_proxies))
+        .map(|ip| ip.to_string());
+
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    AuditContext {
+        actor_sub,
+        ip_address,
+        user_agent,
+    }
+}
+
+fn client_ip(peer: IpAddr, forwarded_for: &[&str], trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    for hop in forwarded_for.iter().rev() {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+    client
+}
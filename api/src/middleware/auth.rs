@@ -1,10 +1,13 @@
+use crate::error::ApiError;
+use crate::middleware::audit::audit_context;
+use crate::middleware::authorization::{Permissions, ServicePrincipal};
 use crate::state::AppState;
 use axum::{
     body::Body,
     extract::{Request, State},
     http::{StatusCode, header::AUTHORIZATION},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use beep_auth::{AuthRepository, Identity};
 use std::sync::Arc;
@@ -28,38 +31,67 @@ pub async fn auth_middleware(
 
     let token = extract_token_from_bearer(auth_header).ok_or(StatusCode::UNAUTHORIZED)?;
 
-    let identity = state.auth_repository.identify(token).await.map_err(|e| {
+    let claims = state.auth_repository.validate_token(token).await.map_err(|e| {
         tracing::error!("Authentication failed: {:?}", e);
         StatusCode::UNAUTHORIZED
     })?;
+    let permissions = Permissions::from_claims(&claims, &state.keycloak_client_id);
 
-    let (sub_str, username) = match &identity {
-        Identity::User(user) => (&user.id, user.username.as_str()),
-        Identity::Client(client) => (&client.id, client.client_id.as_str()),
-    };
+    let mut identity = Identity::from(claims);
+    match &mut identity {
+        Identity::User(user) => user.roles = permissions.roles().map(str::to_string).collect(),
+        Identity::Client(client) => {
+            client.roles = permissions.roles().map(str::to_string).collect();
+            client.scopes = permissions.scopes().map(str::to_string).collect();
+        }
+    }
 
-    let sub = Uuid::parse_str(sub_str).map_err(|e| {
+    let sub = Uuid::parse_str(identity.id()).map_err(|e| {
         tracing::error!("Invalid sub UUID: {}", e);
         StatusCode::UNAUTHORIZED
     })?;
 
-    // Auto-create user if not exists (first connection after Keycloak registration)
-    // TODO: This is not ideal - we're making a DB call on every request.
-    // This should be refactored to use a cache or session-based approach to avoid
-    // the performance overhead of checking user existence on each authenticated request.
-    // For now, we accept this trade-off for simplicity.
-    let user = state
-        .service
-        .user_service
-        .get_or_create_user(sub, username)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to get or create user: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    match &identity {
+        Identity::User(identity_user) => {
+            // Auto-create user if not exists (first connection after Keycloak registration).
+            // Known users are served from the identity cache without a DB round trip.
+            let user = state
+                .service
+                .user_service
+                .get_or_create_user(sub, &identity_user.username)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to get or create user: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+
+            if let Some(suspension) = user.active_suspension() {
+                return Ok(ApiError::Suspended(suspension).into_response());
+            }
+
+            req.extensions_mut().insert(user);
+        }
+        // Service clients are not users: they get no row in `users` and are
+        // only let through on routes scoped for them
+        Identity::Client(client) => {
+            let principal = ServicePrincipal {
+                sub,
+                client_id: client.client_id.clone(),
+            };
+            tracing::debug!(
+                client_id = %principal.client_id,
+                sub = %principal.sub,
+                "Service client authenticated"
+            );
+            req.extensions_mut().insert(principal);
+        }
+    }
+
+    let audit = audit_context(&req, sub, &state.trusted_proxies);
 
     req.extensions_mut().insert(identity);
-    req.extensions_mut().insert(user);
+    req.extensions_mut().insert(permissions);
+    req.extensions_mut().insert(audit);
 
     Ok(next.run(req).await)
 }
@@ -0,0 +1,207 @@
+use crate::error::ApiError;
+use axum::{
+    body::Body,
+    extract::Request,
+    response::{IntoResponse, Response},
+};
+use beep_auth::Claims;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+/// Realm or client role of moderators, who may view and edit any user.
+pub const USER_ADMIN_ROLE: &str = "user-admin";
+
+/// Scope letting service clients look users up by sub.
+pub const USERS_READ_SCOPE: &str = "users:read";
+
+/// Service client calling with a client credentials token. Attached by
+/// `auth_middleware` instead of a `User`: service clients have no profile.
+#[derive(Debug, Clone)]
+pub struct ServicePrincipal {
+    /// Sub of the client's service account
+    pub sub: Uuid,
+    pub client_id: String,
+}
+
+/// Roles and scopes granted by the access token of a request.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    roles: HashSet<String>,
+    scopes: HashSet<String>,
+}
+
+impl Permissions {
+    /// Realm roles, roles of `client_id` under `resource_access` and the
+    /// space-separated `scope` claim.
+    pub fn from_claims(claims: &Claims, client_id: &str) -> Self {
+        let mut roles: HashSet<String> = roles_of(claims.extra.get("realm_access")).collect();
+        roles.extend(roles_of(
+            claims
+                .extra
+                .get("resource_access")
+                .and_then(|resource_access| resource_access.get(client_id)),
+        ));
+
+        Self {
+            roles,
+            scopes: claims
+                .scope
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    pub fn roles(&self) -> impl Iterator<Item = &str> {
+        self.roles.iter().map(String::as_str)
+    }
+
+    pub fn scopes(&self) -> impl Iterator<Item = &str> {
+        self.scopes.iter().map(String::as_str)
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+}
+
+/// Roles listed in a `{"roles": [...]}` access claim.
+fn roles_of(access: Option<&Value>) -> impl Iterator<Item = String> + '_ {
+    access
+        .and_then(|access| access.get("roles"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+}
+
+/// What a guarded route expects of the caller.
+#[derive(Debug, Clone, Copy)]
+enum Requirement {
+    Role(&'static str),
+    User,
+    UserOrServiceScope(&'static str),
+}
+
+impl Requirement {
+    /// Why the request is refused, if it is.
+    fn refusal(&self, req: &Request<Body>) -> Option<String> {
+        let extensions = req.extensions();
+        let permissions = extensions.get::<Permissions>();
+        let is_service = extensions.get::<ServicePrincipal>().is_some();
+
+        match *self {
+            Requirement::Role(role) if !permissions.is_some_and(|p| p.has_role(role)) => {
+                Some(format!("Missing role: {}", role))
+            }
+            Requirement::User if is_service => {
+                Some("Service clients cannot call this endpoint".to_string())
+            }
+            Requirement::UserOrServiceScope(scope)
+                if is_service && !permissions.is_some_and(|p| p.has_scope(scope)) =>
+            {
+                Some(format!("Missing scope: {}", scope))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Layer answering 403 to requests whose token lacks the role. Must run
+/// after `auth_middleware`, which attaches the `Permissions`.
+///
+/// ```ignore
+/// Router::new()
+///     .route("/admin/users", get(admin_search_users))
+///     .route_layer(RequireRole(USER_ADMIN_ROLE))
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RequireRole(pub &'static str);
+
+impl<S> Layer<S> for RequireRole {
+    type Service = GuardService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GuardService {
+            inner,
+            requirement: Requirement::Role(self.0),
+        }
+    }
+}
+
+/// Layer answering 403 to service clients, on routes acting on behalf of
+/// the signed-in user. Must run after `auth_middleware`.
+#[derive(Debug, Clone, Copy)]
+pub struct RequireUser;
+
+impl<S> Layer<S> for RequireUser {
+    type Service = GuardService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GuardService {
+            inner,
+            requirement: Requirement::User,
+        }
+    }
+}
+
+/// Layer letting service clients through when their token has the scope.
+/// Users are always let through. Must run after `auth_middleware`.
+///
+/// ```ignore
+/// Router::new()
+///     .route("/users/bart", post(get_users_by_subs))
+///     .route_layer(AllowService(USERS_READ_SCOPE))
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AllowService(pub &'static str);
+
+impl<S> Layer<S> for AllowService {
+    type Service = GuardService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GuardService {
+            inner,
+            requirement: Requirement::UserOrServiceScope(self.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GuardService<S> {
+    inner: S,
+    requirement: Requirement,
+}
+
+impl<S> Service<Request<Body>> for GuardService<S>
+where
+    S: Service<Request<Body>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        if let Some(refusal) = self.requirement.refusal(&req) {
+            let response = ApiError::Forbidden(refusal).into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+
+        Box::pin(self.inner.call(req))
+    }
+}
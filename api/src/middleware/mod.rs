@@ -1,3 +1,6 @@
+pub mod audit;
 pub mod auth;
+pub mod authorization;
 
 pub use auth::auth_middleware;
+pub use authorization::{AllowService, RequireRole, RequireUser, USER_ADMIN_ROLE, USERS_READ_SCOPE};
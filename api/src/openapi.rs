@@ -1,5 +1,12 @@
-use crate::handlers::{GetUsersBySubsRequest, GetUsersBySubsResponse};
-use user_core::{ProfilePictureRequest, Setting, UpdateSettingRequest, UpdateUserRequest, UserBasicInfo, UserFullInfo};
+use crate::handlers::GetUsersBySubsRequest;
+use user_core::{
+    AccountDeletionInfo, AdminUserInfo, AdminUserPage, AuditAction, AuditEntry, AuditPage,
+    BatchOrder, CacheMetricsSnapshot, ConfirmProfilePictureRequest, CustomStatus, DataExportInfo,
+    DataExportStatus, FieldChange, FieldVisibility, ProfileLink, ProfilePictureRequest,
+    ProfilePrivacy, RelationshipInfo, RelationshipKind, Setting, SuspendUserRequest, Suspension,
+    UpdatePrivacyRequest, UpdateSettingRequest, UpdateStatusRequest, UpdateUserRequest,
+    UserBasicInfo, UserBatchPage, UserFullInfo, UserSearchPage, UserStatus, VerifyEmailRequest,
+};
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
@@ -11,7 +18,7 @@ use utoipa::OpenApi;
 
 ## Data Storage
 - **Keycloak Database**: Stores authentication data (username, email)
-- **User Service Database**: Stores application-specific data (display_name, profile_picture, description, settings)"#,
+- **User Service Database**: Stores application-specific data (display_name, profile_picture, banner, accent_color, pronouns, links, description, status, settings)"#,
         contact(
             name = "API Support",
         )
@@ -21,29 +28,88 @@ use utoipa::OpenApi;
     ),
     paths(
         crate::handlers::get_current_user,
+        crate::handlers::get_current_user_audit,
         crate::handlers::update_current_user,
+        crate::handlers::verify_current_user_email,
+        crate::handlers::delete_current_user,
+        crate::handlers::restore_current_user,
+        crate::handlers::request_data_export,
+        crate::handlers::get_data_export,
+        crate::handlers::download_data_export,
+        crate::handlers::update_current_user_status,
+        crate::handlers::get_current_user_privacy,
+        crate::handlers::update_current_user_privacy,
         crate::handlers::get_current_user_settings,
         crate::handlers::post_profile_picture_request,
+        crate::handlers::confirm_profile_picture,
+        crate::handlers::post_banner_request,
+        crate::handlers::confirm_banner,
+        crate::handlers::delete_banner,
         crate::handlers::update_current_user_settings,
         crate::handlers::get_user_by_sub,
         crate::handlers::get_user_by_username,
+        crate::handlers::get_user_cache_metrics,
+        crate::handlers::query_audit_log,
+        crate::handlers::ingest_keycloak_event,
         crate::handlers::get_users_by_subs,
+        crate::handlers::search_users,
+        crate::handlers::get_current_user_relationships,
+        crate::handlers::send_friend_request,
+        crate::handlers::accept_friend_request,
+        crate::handlers::decline_friend_request,
+        crate::handlers::cancel_friend_request,
+        crate::handlers::remove_friend,
+        crate::handlers::block_user,
+        crate::handlers::unblock_user,
+        crate::handlers::admin_search_users,
+        crate::handlers::admin_get_user,
+        crate::handlers::admin_update_user,
+        crate::handlers::admin_suspend_user,
+        crate::handlers::admin_lift_suspension,
     ),
     components(
         schemas(
             UserBasicInfo,
             UserFullInfo,
+            ProfileLink,
             UpdateUserRequest,
+            VerifyEmailRequest,
+            FieldVisibility,
+            ProfilePrivacy,
+            UpdatePrivacyRequest,
+            AccountDeletionInfo,
+            DataExportStatus,
+            DataExportInfo,
+            AuditAction,
+            FieldChange,
+            AuditEntry,
+            AuditPage,
+            UserStatus,
+            CustomStatus,
+            UpdateStatusRequest,
             Setting,
             UpdateSettingRequest,
             GetUsersBySubsRequest,
-            GetUsersBySubsResponse,
+            BatchOrder,
+            UserBatchPage,
+            UserSearchPage,
+            RelationshipKind,
+            RelationshipInfo,
             ProfilePictureRequest,
+            ConfirmProfilePictureRequest,
+            CacheMetricsSnapshot,
+            AdminUserInfo,
+            AdminUserPage,
+            Suspension,
+            SuspendUserRequest,
         )
     ),
     tags(
         (name = "users", description = "User management endpoints"),
         (name = "settings", description = "User settings endpoints"),
+        (name = "relationships", description = "Friends, friend requests and blocked users"),
+        (name = "exports", description = "Downloadable exports of the data stored about a user"),
+        (name = "admin", description = "Moderation endpoints, restricted to the user-admin role"),
         (name = "internal", description = "Internal endpoints for service-to-service calls (no auth required)")
     ),
     modifiers(&SecurityAddon)
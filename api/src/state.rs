@@ -1,4 +1,5 @@
 use beep_auth::KeycloakAuthRepository;
+use std::net::IpAddr;
 use std::sync::Arc;
 use user_core::ApplicationService;
 
@@ -8,13 +9,28 @@ use user_core::ApplicationService;
 pub struct AppState {
     pub service: ApplicationService,
     pub auth_repository: Arc<KeycloakAuthRepository>,
+    /// Client whose roles, under `resource_access`, are granted to callers
+    pub keycloak_client_id: String,
+    /// Shared secret signing Keycloak events; ingestion is disabled without it
+    pub keycloak_webhook_secret: Option<String>,
+    /// Proxies whose `X-Forwarded-For` hops are trusted for audit addresses
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl AppState {
-    pub fn new(service: ApplicationService, auth_repository: KeycloakAuthRepository) -> Self {
+    pub fn new(
+        service: ApplicationService,
+        auth_repository: KeycloakAuthRepository,
+        keycloak_client_id: String,
+        keycloak_webhook_secret: Option<String>,
+        trusted_proxies: Vec<IpAddr>,
+    ) -> Self {
         Self {
             service,
             auth_repository: Arc::new(auth_repository),
+            keycloak_client_id,
+            keycloak_webhook_secret,
+            trusted_proxies,
         }
     }
 }
@@ -1,6 +1,14 @@
+use crate::cache::UserCache;
+use crate::email_verification::EmailVerification;
+use crate::export::{
+    AuditLogSection, EmailChangesSection, ExportRegistry, RelationshipsSection,
+    UsernameHistorySection,
+};
 use crate::repository::PostgresUserRepository;
 use crate::services::content::ContentServiceClientImpl;
 use crate::services::{KeycloakService, UserServiceImpl};
+use crate::username::UsernamePolicy;
+use std::time::Duration;
 
 // Type aliases for concrete implementations
 type UserRepo = PostgresUserRepository;
@@ -15,9 +23,28 @@ pub struct ApplicationService {
 }
 
 impl ApplicationService {
-    pub fn new(user_repo: UserRepo, keycloak_service: KeycloakService, content_service: ContentServiceClientImpl) -> Self {
+    pub fn new(
+        user_repo: UserRepo,
+        keycloak_service: KeycloakService,
+        content_service: ContentServiceClientImpl,
+        user_cache: UserCache,
+        deletion_grace_period: Duration,
+        username_policy: UsernamePolicy,
+        email_verification: EmailVerification,
+    ) -> Self {
         Self {
-            user_service: UserServiceImpl::new(user_repo, keycloak_service, content_service),
+            user_service: UserServiceImpl::new(user_repo, keycloak_service, content_service)
+                .with_user_cache(user_cache)
+                .with_deletion_grace_period(deletion_grace_period)
+                .with_username_policy(username_policy)
+                .with_email_verification(email_verification)
+                .with_export_registry(
+                    ExportRegistry::builtin()
+                        .register(RelationshipsSection)
+                        .register(AuditLogSection)
+                        .register(UsernameHistorySection)
+                        .register(EmailChangesSection),
+                ),
         }
     }
 }
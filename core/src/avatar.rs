@@ -0,0 +1,147 @@
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How long an avatar upload URL stays valid.
+pub const AVATAR_UPLOAD_URL_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long a signed avatar read URL stays valid.
+pub const AVATAR_READ_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Largest accepted avatar, in bytes.
+pub const MAX_AVATAR_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Largest accepted banner, in bytes.
+pub const MAX_BANNER_SIZE: u64 = 10 * 1024 * 1024;
+
+pub const ALLOWED_AVATAR_CONTENT_TYPES: &[&str] =
+    &["image/png", "image/jpeg", "image/webp", "image/gif"];
+
+/// Number of signed read URLs kept for reuse.
+const SIGNED_URL_CACHE_CAPACITY: usize = 10_000;
+
+/// Fresh object key for an avatar of `sub`. Keys are never reused, so a
+/// replaced avatar can be deleted without racing its successor.
+pub fn new_avatar_key(sub: Uuid) -> String {
+    format!("{}/{}", sub, Uuid::new_v4())
+}
+
+/// Whether `key` was issued by `new_avatar_key` for `sub`.
+pub fn is_avatar_key_of(sub: Uuid, key: &str) -> bool {
+    key.split_once('/').is_some_and(|(owner, id)| {
+        Uuid::parse_str(owner).is_ok_and(|owner| owner == sub) && Uuid::parse_str(id).is_ok()
+    })
+}
+
+/// Fresh object key for a banner of `sub`, kept apart from avatar keys.
+pub fn new_banner_key(sub: Uuid) -> String {
+    format!("{}/banners/{}", sub, Uuid::new_v4())
+}
+
+/// Whether `key` was issued by `new_banner_key` for `sub`.
+pub fn is_banner_key_of(sub: Uuid, key: &str) -> bool {
+    key.strip_prefix(&format!("{}/banners/", sub))
+        .is_some_and(|id| Uuid::parse_str(id).is_ok())
+}
+
+/// Signed read URLs by object key.
+///
+/// A URL is reused for half of its lifetime, so every URL handed out stays
+/// valid for at least half of `AVATAR_READ_URL_TTL`. Clones share the same
+/// storage.
+#[derive(Clone)]
+pub struct SignedUrlCache {
+    urls: Arc<Mutex<LruCache<String, (String, Instant)>>>,
+    reuse_for: Duration,
+}
+
+impl Default for SignedUrlCache {
+    fn default() -> Self {
+        Self::new(SIGNED_URL_CACHE_CAPACITY, AVATAR_READ_URL_TTL / 2)
+    }
+}
+
+impl SignedUrlCache {
+    pub fn new(capacity: usize, reuse_for: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            urls: Arc::new(Mutex::new(LruCache::new(capacity))),
+            reuse_for,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let mut urls = self.urls.lock().unwrap();
+        match urls.get(key) {
+            Some((url, signed_at)) if signed_at.elapsed() < self.reuse_for => Some(url.clone()),
+            Some(_) => {
+                urls.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: &str, url: String) {
+        self.urls
+            .lock()
+            .unwrap()
+            .put(key.to_string(), (url, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avatar_key_belongs_to_its_user() {
+        let sub = Uuid::new_v4();
+        let key = new_avatar_key(sub);
+
+        assert!(is_avatar_key_of(sub, &key));
+        assert!(!is_avatar_key_of(Uuid::new_v4(), &key));
+    }
+
+    #[test]
+    fn rejects_foreign_or_malformed_keys() {
+        let sub = Uuid::new_v4();
+
+        assert!(!is_avatar_key_of(sub, &sub.to_string()));
+        assert!(!is_avatar_key_of(sub, &format!("{}/../other", sub)));
+        assert!(!is_avatar_key_of(
+            sub,
+            &format!("{}/{}/x", sub, Uuid::new_v4())
+        ));
+    }
+
+    #[test]
+    fn banner_keys_are_not_avatar_keys() {
+        let sub = Uuid::new_v4();
+        let banner = new_banner_key(sub);
+
+        assert!(is_banner_key_of(sub, &banner));
+        assert!(!is_avatar_key_of(sub, &banner));
+        assert!(!is_banner_key_of(sub, &new_avatar_key(sub)));
+        assert!(!is_banner_key_of(Uuid::new_v4(), &banner));
+    }
+
+    #[test]
+    fn signed_url_is_reused_until_half_life() {
+        let cache = SignedUrlCache::new(10, Duration::from_secs(60));
+        cache.insert("key", "https://cdn/key?sig=1".to_string());
+
+        assert_eq!(cache.get("key").as_deref(), Some("https://cdn/key?sig=1"));
+        assert_eq!(cache.get("other"), None);
+    }
+
+    #[test]
+    fn stale_signed_url_is_dropped() {
+        let cache = SignedUrlCache::new(10, Duration::ZERO);
+        cache.insert("key", "https://cdn/key?sig=1".to_string());
+
+        assert_eq!(cache.get("key"), None);
+    }
+}
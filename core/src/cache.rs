@@ -0,0 +1,393 @@
+use crate::models::User;
+use lru::LruCache;
+use serde::Serialize;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Default number of identities kept in the in-process cache.
+pub const DEFAULT_USER_CACHE_CAPACITY: usize = 10_000;
+
+/// Default time-to-live of a cached identity.
+pub const DEFAULT_USER_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Default time-to-live of a username found to be unknown. Shorter than
+/// `DEFAULT_USER_CACHE_TTL`, so a newly registered username soon shows up in
+/// search.
+pub const DEFAULT_USERNAME_MISS_TTL: Duration = Duration::from_secs(10);
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Optional second-level cache shared between service replicas (e.g. Redis).
+///
+/// Implementations are expected to apply their own expiry. Failures should be
+/// swallowed and reported as a miss: the database remains the source of truth.
+pub trait SharedUserCache: Send + Sync {
+    fn get(&self, sub: Uuid) -> BoxFuture<'_, Option<User>>;
+    fn set(&self, user: User) -> BoxFuture<'_, ()>;
+    fn invalidate(&self, sub: Uuid) -> BoxFuture<'_, ()>;
+}
+
+/// Hit/miss counters for the user identity cache.
+#[derive(Debug, Default)]
+struct CacheMetrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CacheMetricsSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+}
+
+struct CachedEntry {
+    user: User,
+    inserted_at: Instant,
+}
+
+/// Cache of authenticated users, keyed by sub.
+///
+/// Lookups go through an in-process LRU with a TTL first, then through the
+/// optional shared backend. Cloning is cheap and clones share the same storage.
+#[derive(Clone)]
+pub struct UserCache {
+    local: Arc<Mutex<LruCache<Uuid, CachedEntry>>>,
+    shared: Option<Arc<dyn SharedUserCache>>,
+    ttl: Duration,
+    metrics: Arc<CacheMetrics>,
+}
+
+impl UserCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            local: Arc::new(Mutex::new(LruCache::new(capacity))),
+            shared: None,
+            ttl,
+            metrics: Arc::new(CacheMetrics::default()),
+        }
+    }
+
+    pub fn with_shared_backend(mut self, backend: Arc<dyn SharedUserCache>) -> Self {
+        self.shared = Some(backend);
+        self
+    }
+
+    pub async fn get(&self, sub: Uuid) -> Option<User> {
+        if let Some(user) = self.get_local(sub) {
+            self.metrics.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(user);
+        }
+
+        if let Some(shared) = &self.shared
+            && let Some(user) = shared.get(sub).await
+        {
+            self.metrics.hits.fetch_add(1, Ordering::Relaxed);
+            self.insert_local(user.clone());
+            return Some(user);
+        }
+
+        self.metrics.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    pub async fn insert(&self, user: User) {
+        self.insert_local(user.clone());
+        if let Some(shared) = &self.shared {
+            shared.set(user).await;
+        }
+    }
+
+    pub async fn invalidate(&self, sub: Uuid) {
+        self.local.lock().unwrap().pop(&sub);
+        if let Some(shared) = &self.shared {
+            shared.invalidate(sub).await;
+        }
+        self.metrics.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn metrics(&self) -> CacheMetricsSnapshot {
+        CacheMetricsSnapshot {
+            hits: self.metrics.hits.load(Ordering::Relaxed),
+            misses: self.metrics.misses.load(Ordering::Relaxed),
+            invalidations: self.metrics.invalidations.load(Ordering::Relaxed),
+        }
+    }
+
+    fn get_local(&self, sub: Uuid) -> Option<User> {
+        let mut local = self.local.lock().unwrap();
+        let expired = match local.get(&sub) {
+            Some(entry) if entry.inserted_at.elapsed() < self.ttl => {
+                return Some(entry.user.clone());
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            local.pop(&sub);
+        }
+        None
+    }
+
+    fn insert_local(&self, user: User) {
+        let entry = CachedEntry {
+            user,
+            inserted_at: Instant::now(),
+        };
+        self.local.lock().unwrap().put(entry.user.sub, entry);
+    }
+}
+
+impl Default for UserCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_USER_CACHE_CAPACITY, DEFAULT_USER_CACHE_TTL)
+    }
+}
+
+/// Recent username lookups against the identity provider, keyed by
+/// username. Unknown usernames are kept too, as most search queries are not
+/// usernames, but for `miss_ttl` only. Cloning is cheap and clones share the
+/// same storage.
+#[derive(Clone)]
+pub struct UsernameLookupCache {
+    lookups: Arc<Mutex<LruCache<String, CachedLookup>>>,
+    ttl: Duration,
+    miss_ttl: Duration,
+}
+
+struct CachedLookup {
+    sub: Option<Uuid>,
+    looked_up_at: Instant,
+}
+
+impl UsernameLookupCache {
+    /// Keeps found usernames for `ttl` and unknown ones for `miss_ttl`.
+    pub fn new(capacity: usize, ttl: Duration, miss_ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            lookups: Arc::new(Mutex::new(LruCache::new(capacity))),
+            ttl,
+            miss_ttl,
+        }
+    }
+
+    /// The sub owning `username`, `Some(None)` if none did when it was
+    /// looked up, or `None` if it was not looked up recently.
+    pub fn get(&self, username: &str) -> Option<Option<Uuid>> {
+        let mut lookups = self.lookups.lock().unwrap();
+        let lookup = lookups.get(username)?;
+        let ttl = if lookup.sub.is_some() {
+            self.ttl
+        } else {
+            self.miss_ttl
+        };
+        if lookup.looked_up_at.elapsed() < ttl {
+            return Some(lookup.sub);
+        }
+        lookups.pop(username);
+        None
+    }
+
+    pub fn insert(&self, username: &str, sub: Option<Uuid>) {
+        let lookup = CachedLookup {
+            sub,
+            looked_up_at: Instant::now(),
+        };
+        self.lookups.lock().unwrap().put(username.to_string(), lookup);
+    }
+}
+
+impl Default for UsernameLookupCache {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_USER_CACHE_CAPACITY,
+            DEFAULT_USER_CACHE_TTL,
+            DEFAULT_USERNAME_MISS_TTL,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProfilePrivacy;
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    fn create_test_user(sub: Uuid) -> User {
+        let now = Utc::now();
+        User {
+            sub,
+            display_name: "Test User".to_string(),
+            profile_picture: String::new(),
+            avatar_key: None,
+            banner_key: None,
+            accent_color: None,
+            pronouns: None,
+            links: Vec::new(),
+            privacy: ProfilePrivacy::default(),
+            description: String::new(),
+            status: Default::default(),
+            custom_status_text: None,
+            custom_status_emoji: None,
+            custom_status_expires_at: None,
+            deleted_at: None,
+            suspended_at: None,
+            suspended_until: None,
+            suspended_by: None,
+            suspension_reason: None,
+            suspension_disabled_keycloak: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[derive(Default)]
+    struct MockSharedCache {
+        users: Mutex<HashMap<Uuid, User>>,
+    }
+
+    impl SharedUserCache for MockSharedCache {
+        fn get(&self, sub: Uuid) -> BoxFuture<'_, Option<User>> {
+            let user = self.users.lock().unwrap().get(&sub).cloned();
+            Box::pin(async move { user })
+        }
+
+        fn set(&self, user: User) -> BoxFuture<'_, ()> {
+            self.users.lock().unwrap().insert(user.sub, user);
+            Box::pin(async {})
+        }
+
+        fn invalidate(&self, sub: Uuid) -> BoxFuture<'_, ()> {
+            self.users.lock().unwrap().remove(&sub);
+            Box::pin(async {})
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_inserted_user_and_counts_hit() {
+        let sub = Uuid::new_v4();
+        let cache = UserCache::default();
+
+        cache.insert(create_test_user(sub)).await;
+        let cached = cache.get(sub).await;
+
+        assert_eq!(cached.map(|u| u.sub), Some(sub));
+        assert_eq!(cache.metrics().hits, 1);
+        assert_eq!(cache.metrics().misses, 0);
+    }
+
+    #[tokio::test]
+    async fn counts_miss_for_unknown_user() {
+        let cache = UserCache::default();
+
+        assert!(cache.get(Uuid::new_v4()).await.is_none());
+        assert_eq!(cache.metrics().misses, 1);
+    }
+
+    #[tokio::test]
+    async fn expires_entries_after_ttl() {
+        let sub = Uuid::new_v4();
+        let cache = UserCache::new(10, Duration::ZERO);
+
+        cache.insert(create_test_user(sub)).await;
+
+        assert!(cache.get(sub).await.is_none());
+        assert_eq!(cache.metrics().misses, 1);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_entry() {
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let cache = UserCache::new(1, DEFAULT_USER_CACHE_TTL);
+
+        cache.insert(create_test_user(first)).await;
+        cache.insert(create_test_user(second)).await;
+
+        assert!(cache.get(first).await.is_none());
+        assert!(cache.get(second).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn invalidate_removes_entry() {
+        let sub = Uuid::new_v4();
+        let cache = UserCache::default();
+
+        cache.insert(create_test_user(sub)).await;
+        cache.invalidate(sub).await;
+
+        assert!(cache.get(sub).await.is_none());
+        assert_eq!(cache.metrics().invalidations, 1);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_shared_backend_and_populates_local() {
+        let sub = Uuid::new_v4();
+        let shared = Arc::new(MockSharedCache::default());
+        shared.users.lock().unwrap().insert(sub, create_test_user(sub));
+        let cache = UserCache::default().with_shared_backend(shared.clone());
+
+        assert!(cache.get(sub).await.is_some());
+        shared.users.lock().unwrap().clear();
+        assert!(cache.get(sub).await.is_some());
+        assert_eq!(cache.metrics().hits, 2);
+    }
+
+    #[tokio::test]
+    async fn invalidate_propagates_to_shared_backend() {
+        let sub = Uuid::new_v4();
+        let shared = Arc::new(MockSharedCache::default());
+        let cache = UserCache::default().with_shared_backend(shared.clone());
+
+        cache.insert(create_test_user(sub)).await;
+        cache.invalidate(sub).await;
+
+        assert!(shared.users.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn returns_looked_up_usernames() {
+        let sub = Uuid::new_v4();
+        let cache = UsernameLookupCache::default();
+
+        cache.insert("john", Some(sub));
+        cache.insert("nobody", None);
+
+        assert_eq!(cache.get("john"), Some(Some(sub)));
+        assert_eq!(cache.get("nobody"), Some(None));
+        assert_eq!(cache.get("jane"), None);
+    }
+
+    #[test]
+    fn expires_lookups_after_ttl() {
+        let cache = UsernameLookupCache::new(10, Duration::ZERO, DEFAULT_USER_CACHE_TTL);
+
+        cache.insert("john", Some(Uuid::new_v4()));
+
+        assert_eq!(cache.get("john"), None);
+    }
+
+    #[test]
+    fn expires_unknown_usernames_after_miss_ttl() {
+        let sub = Uuid::new_v4();
+        let cache = UsernameLookupCache::new(10, DEFAULT_USER_CACHE_TTL, Duration::ZERO);
+
+        cache.insert("john", Some(sub));
+        cache.insert("nobody", None);
+
+        assert_eq!(cache.get("john"), Some(Some(sub)));
+        assert_eq!(cache.get("nobody"), None);
+    }
+}
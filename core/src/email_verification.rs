@@ -0,0 +1,181 @@
+use crate::models::{EmailMessage, redact_email};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Default lifetime of an email verification link.
+pub const DEFAULT_EMAIL_VERIFICATION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Default page the verification link points to. It is expected to post the
+/// token to `POST /users/me/email/verify`.
+pub const DEFAULT_EMAIL_VERIFICATION_URL: &str = "http://localhost:3000/verify-email";
+
+/// Content of an email verification token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailChangeClaims {
+    pub sub: Uuid,
+    pub email: String,
+    /// Nonce of the pending change the token confirms
+    pub nonce: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Signs and checks email verification tokens, and writes the emails sent
+/// around an email change.
+///
+/// A token is its base64url JSON claims and their HMAC-SHA256, separated by
+/// a dot.
+#[derive(Clone)]
+pub struct EmailVerification {
+    secret: Vec<u8>,
+    ttl: Duration,
+    link_base: String,
+}
+
+impl Default for EmailVerification {
+    /// Signs with a random secret, so tokens do not survive a restart.
+    fn default() -> Self {
+        let secret = [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat();
+        Self::new(
+            secret,
+            DEFAULT_EMAIL_VERIFICATION_TTL,
+            DEFAULT_EMAIL_VERIFICATION_URL,
+        )
+    }
+}
+
+impl EmailVerification {
+    pub fn new(secret: impl Into<Vec<u8>>, ttl: Duration, link_base: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            ttl,
+            link_base: link_base.into(),
+        }
+    }
+
+    /// Expiry of a token issued now.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        Utc::now() + self.ttl
+    }
+
+    pub fn sign(&self, claims: &EmailChangeClaims) -> String {
+        // Serializing the claims cannot fail
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap_or_default());
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Claims of `token` if it is signed with this secret and not expired.
+    pub fn verify(&self, token: &str) -> Option<EmailChangeClaims> {
+        let (payload, signature) = token.trim().split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(payload).verify_slice(&signature).ok()?;
+
+        let claims: EmailChangeClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())?;
+        (claims.expires_at > Utc::now()).then_some(claims)
+    }
+
+    /// Email sent to the new address, with the link confirming it.
+    pub fn verification_email(&self, claims: &EmailChangeClaims) -> EmailMessage {
+        EmailMessage {
+            recipient: claims.email.clone(),
+            subject: "Confirm your new email address".to_string(),
+            body: format!(
+                "Open this link to use this address for your account:\n\n{}?token={}\n\n\
+                 The link expires at {}. If you did not ask for this change, ignore this email.",
+                self.link_base,
+                self.sign(claims),
+                claims.expires_at.to_rfc3339()
+            ),
+        }
+    }
+
+    /// Email sent to the previous address once the change is applied.
+    pub fn change_notice(&self, old_email: &str, new_email: &str) -> EmailMessage {
+        EmailMessage {
+            recipient: old_email.to_string(),
+            subject: "Your email address was changed".to_string(),
+            body: format!(
+                "The email address of your account was changed to {}. \
+                 If you did not make this change, contact support right away.",
+                redact_email(new_email)
+            ),
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        // HMAC accepts keys of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC key");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(expires_at: DateTime<Utc>) -> EmailChangeClaims {
+        EmailChangeClaims {
+            sub: Uuid::new_v4(),
+            email: "new@example.com".to_string(),
+            nonce: Uuid::new_v4(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn token_round_trips() {
+        let verification = EmailVerification::default();
+        let claims = claims(verification.expires_at());
+
+        let token = verification.sign(&claims);
+
+        assert_eq!(verification.verify(&token), Some(claims));
+    }
+
+    #[test]
+    fn rejects_tampered_foreign_and_expired_tokens() {
+        let verification = EmailVerification::default();
+        let token = verification.sign(&claims(verification.expires_at()));
+        let (_, signature) = token.split_once('.').unwrap();
+        let mut forged = claims(verification.expires_at());
+        forged.email = "attacker@example.com".to_string();
+        let forged_payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+
+        let tampered = format!("{}.{}", forged_payload, signature);
+        let foreign = EmailVerification::default().sign(&forged);
+        let expired = verification.sign(&claims(Utc::now() - chrono::Duration::minutes(1)));
+
+        assert_eq!(verification.verify(&tampered), None);
+        assert_eq!(verification.verify(&foreign), None);
+        assert_eq!(verification.verify(&expired), None);
+        assert_eq!(verification.verify("garbage"), None);
+    }
+
+    #[test]
+    fn verification_email_links_to_the_token() {
+        let verification = EmailVerification::new(
+            "secret",
+            DEFAULT_EMAIL_VERIFICATION_TTL,
+            "https://app.example.com/verify-email",
+        );
+        let claims = claims(verification.expires_at());
+
+        let email = verification.verification_email(&claims);
+
+        assert_eq!(email.recipient, "new@example.com");
+        assert!(email.body.contains(&format!(
+            "https://app.example.com/verify-email?token={}",
+            verification.sign(&claims)
+        )));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::services::KeycloakError;
+use crate::validation::ValidationErrors;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,9 +13,18 @@ pub enum CoreError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Bad request: {0}")]
+    Validation(ValidationErrors),
+
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 
@@ -41,6 +51,18 @@ mod tests {
         assert_eq!(err.to_string(), "Bad request: Invalid input");
     }
 
+    #[test]
+    fn core_error_validation_lists_every_field() {
+        let mut errors = ValidationErrors::default();
+        errors.add("display_name", "must not be empty");
+        errors.add("email", "must be a valid email address");
+        let err = CoreError::Validation(errors);
+        assert_eq!(
+            err.to_string(),
+            "Bad request: display_name: must not be empty; email: must be a valid email address"
+        );
+    }
+
     #[test]
     fn core_error_unauthorized_displays_correctly() {
         let err = CoreError::Unauthorized("Invalid token".to_string());
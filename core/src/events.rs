@@ -0,0 +1,271 @@
+use crate::error::CoreError;
+use crate::models::UserEvent;
+use crate::repository::OutboxRepository;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Destination of user domain events (message broker, webhook, file...).
+///
+/// Delivery is at-least-once: an event may be published again if the
+/// dispatcher stops before recording it as delivered, so consumers must be
+/// idempotent on `UserEvent::id`.
+pub trait EventSink: Send + Sync + Clone {
+    fn publish(&self, event: &UserEvent) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Keeps published events in memory. Intended for tests.
+#[derive(Clone, Default)]
+pub struct InMemoryEventSink {
+    events: Arc<Mutex<Vec<UserEvent>>>,
+}
+
+impl InMemoryEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<UserEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl EventSink for InMemoryEventSink {
+    async fn publish(&self, event: &UserEvent) -> Result<(), String> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+/// Appends events to a file, one JSON document per line.
+#[derive(Clone)]
+pub struct FileEventSink {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FileEventSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+impl EventSink for FileEventSink {
+    async fn publish(&self, event: &UserEvent) -> Result<(), String> {
+        let line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+
+        let _guard = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// Logs events through `tracing`. Used when no other sink is configured.
+#[derive(Clone, Default)]
+pub struct LogEventSink;
+
+impl EventSink for LogEventSink {
+    async fn publish(&self, event: &UserEvent) -> Result<(), String> {
+        let payload = serde_json::to_string(&event.payload).map_err(|e| e.to_string())?;
+        tracing::info!(
+            event_id = event.id,
+            sub = %event.sub,
+            event_type = %event.event_type,
+            %payload,
+            "User event"
+        );
+        Ok(())
+    }
+}
+
+/// Delivers one batch of pending outbox events to `sink`, in order.
+///
+/// Delivery stops at the first failure, and the outbox holds back the events
+/// of a user while older ones are claimed elsewhere, so events of a user are
+/// published in order as long as a batch is delivered within its lease. The
+/// remaining events are retried once their lease expires. Returns the number
+/// of events delivered.
+pub async fn dispatch_pending_events<O: OutboxRepository, S: EventSink>(
+    outbox: &O,
+    sink: &S,
+    batch_size: i64,
+    lease: Duration,
+) -> Result<usize, CoreError> {
+    let events = outbox.claim_pending_events(batch_size, lease).await?;
+
+    let mut delivered = Vec::with_capacity(events.len());
+    let mut failure = None;
+    for event in &events {
+        match sink.publish(event).await {
+            Ok(()) => delivered.push(event.id),
+            Err(e) => {
+                failure = Some((event.id, e));
+                break;
+            }
+        }
+    }
+
+    outbox.mark_events_dispatched(&delivered).await?;
+
+    match failure {
+        Some((event_id, e)) => Err(CoreError::InternalError(format!(
+            "Failed to publish event {}: {}",
+            event_id, e
+        ))),
+        None => Ok(delivered.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UserEventKind;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    #[derive(Clone, Default)]
+    struct MockOutbox {
+        pending: Arc<Mutex<Vec<UserEvent>>>,
+        dispatched: Arc<Mutex<Vec<i64>>>,
+    }
+
+    impl MockOutbox {
+        fn with_events(count: i64) -> Self {
+            let outbox = Self::default();
+            let sub = Uuid::new_v4();
+            for id in 1..=count {
+                outbox.pending.lock().unwrap().push(UserEvent {
+                    id,
+                    sub,
+                    event_type: "user.created".to_string(),
+                    payload: UserEventKind::UserCreated {
+                        display_name: format!("user-{}", id),
+                    },
+                    created_at: Utc::now(),
+                });
+            }
+            outbox
+        }
+    }
+
+    impl OutboxRepository for MockOutbox {
+        async fn claim_pending_events(
+            &self,
+            limit: i64,
+            _lease: Duration,
+        ) -> Result<Vec<UserEvent>, sqlx::Error> {
+            let dispatched = self.dispatched.lock().unwrap();
+            Ok(self
+                .pending
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| !dispatched.contains(&event.id))
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn mark_events_dispatched(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+            self.dispatched.lock().unwrap().extend_from_slice(ids);
+            Ok(())
+        }
+    }
+
+    /// Sink that fails on a given event id.
+    #[derive(Clone)]
+    struct FailingSink {
+        inner: InMemoryEventSink,
+        fail_on: i64,
+    }
+
+    impl EventSink for FailingSink {
+        async fn publish(&self, event: &UserEvent) -> Result<(), String> {
+            if event.id == self.fail_on {
+                return Err("broker unavailable".to_string());
+            }
+            self.inner.publish(event).await
+        }
+    }
+
+    const LEASE: Duration = Duration::from_secs(30);
+
+    #[tokio::test]
+    async fn delivers_pending_events_in_order_and_marks_them() {
+        let outbox = MockOutbox::with_events(3);
+        let sink = InMemoryEventSink::new();
+
+        let delivered = dispatch_pending_events(&outbox, &sink, 10, LEASE)
+            .await
+            .unwrap();
+
+        assert_eq!(delivered, 3);
+        let ids: Vec<i64> = sink.events().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(*outbox.dispatched.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn respects_batch_size() {
+        let outbox = MockOutbox::with_events(5);
+        let sink = InMemoryEventSink::new();
+
+        let delivered = dispatch_pending_events(&outbox, &sink, 2, LEASE)
+            .await
+            .unwrap();
+
+        assert_eq!(delivered, 2);
+    }
+
+    #[tokio::test]
+    async fn stops_at_first_failure_and_keeps_the_rest_pending() {
+        let outbox = MockOutbox::with_events(3);
+        let sink = FailingSink {
+            inner: InMemoryEventSink::new(),
+            fail_on: 2,
+        };
+
+        let result = dispatch_pending_events(&outbox, &sink, 10, LEASE).await;
+
+        assert!(matches!(result, Err(CoreError::InternalError(_))));
+        assert_eq!(*outbox.dispatched.lock().unwrap(), vec![1]);
+
+        let retry_sink = InMemoryEventSink::new();
+        dispatch_pending_events(&outbox, &retry_sink, 10, LEASE)
+            .await
+            .unwrap();
+        let ids: Vec<i64> = retry_sink.events().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_one_json_line_per_event() {
+        let path = std::env::temp_dir().join(format!("user-events-{}.jsonl", Uuid::new_v4()));
+        let outbox = MockOutbox::with_events(2);
+        let sink = FileEventSink::new(&path);
+
+        dispatch_pending_events(&outbox, &sink, 10, LEASE)
+            .await
+            .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let events: Vec<UserEvent> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].id, 2);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::error::CoreError;
+use crate::models::AuditLogQuery;
+use crate::repository::{
+    AuditRepository, EmailChangeRepository, RelationshipRepository, UserRepository,
+    UsernameRepository,
+};
+use crate::services::{KeycloakClient, KeycloakError};
+use chrono::Utc;
+use serde_json::{Map, Value, json};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Version of the export document layout, bumped on breaking changes.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Audit entries read per query by `AuditLogSection`.
+const AUDIT_LOG_PAGE_SIZE: i64 = 500;
+
+pub type SectionFuture<'a> = Pin<Box<dyn Future<Output = Result<Value, CoreError>> + Send + 'a>>;
+
+/// Part of a data export, stored under its own key in the document.
+///
+/// Each store holding per-user data contributes a section, so a new table only
+/// needs a new section registered with the `ExportRegistry`.
+pub trait ExportSection<R, K>: Send + Sync {
+    /// Key of the section in the export document.
+    fn name(&self) -> &'static str;
+    fn collect<'a>(
+        &'a self,
+        user_repo: &'a R,
+        keycloak_client: &'a K,
+        sub: Uuid,
+    ) -> SectionFuture<'a>;
+}
+
+/// The `User` row.
+pub struct ProfileSection;
+
+impl<R: UserRepository, K: KeycloakClient> ExportSection<R, K> for ProfileSection {
+    fn name(&self) -> &'static str {
+        "profile"
+    }
+
+    fn collect<'a>(&'a self, user_repo: &'a R, _: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move {
+            let user = user_repo
+                .get_user_by_sub(sub)
+                .await?
+                .ok_or_else(|| CoreError::NotFound(format!("User with sub {} not found", sub)))?;
+            to_value(&user)
+        })
+    }
+}
+
+/// The stored `Setting` values, without defaults.
+pub struct SettingsSection;
+
+impl<R: UserRepository, K: KeycloakClient> ExportSection<R, K> for SettingsSection {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn collect<'a>(&'a self, user_repo: &'a R, _: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move { to_value(&user_repo.get_setting_by_sub(sub).await?) })
+    }
+}
+
+/// Identity data held by Keycloak. Empty once the Keycloak account is gone.
+pub struct KeycloakSection;
+
+impl<R: UserRepository, K: KeycloakClient> ExportSection<R, K> for KeycloakSection {
+    fn name(&self) -> &'static str {
+        "keycloak"
+    }
+
+    fn collect<'a>(&'a self, _: &'a R, keycloak_client: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move {
+            match keycloak_client.get_user_info(sub).await {
+                Ok(info) => to_value(&info),
+                Err(KeycloakError::UserNotFound(_)) => Ok(Value::Null),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+}
+
+/// Friends, pending requests and blocks, as seen from the user's side.
+pub struct RelationshipsSection;
+
+impl<R: RelationshipRepository, K: KeycloakClient> ExportSection<R, K> for RelationshipsSection {
+    fn name(&self) -> &'static str {
+        "relationships"
+    }
+
+    fn collect<'a>(&'a self, user_repo: &'a R, _: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move { to_value(&user_repo.list_relationships(sub).await?) })
+    }
+}
+
+/// Usernames the user has held, most recent first, with how long released
+/// ones stay reserved for them.
+pub struct UsernameHistorySection;
+
+impl<R: UsernameRepository, K: KeycloakClient> ExportSection<R, K> for UsernameHistorySection {
+    fn name(&self) -> &'static str {
+        "username_history"
+    }
+
+    fn collect<'a>(&'a self, user_repo: &'a R, _: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move { to_value(&user_repo.list_username_history(sub).await?) })
+    }
+}
+
+/// Email change waiting for the new address to be confirmed, if any. The
+/// nonce binding it to a verification link is left out.
+pub struct EmailChangesSection;
+
+impl<R: EmailChangeRepository, K: KeycloakClient> ExportSection<R, K> for EmailChangesSection {
+    fn name(&self) -> &'static str {
+        "email_changes"
+    }
+
+    fn collect<'a>(&'a self, user_repo: &'a R, _: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move {
+            let pending: Vec<_> = user_repo.get_email_change(sub).await?.into_iter().collect();
+            to_value(&pending)
+        })
+    }
+}
+
+/// Audit entries about the account, most recent first. The address and user
+/// agent of changes made by someone else, such as a moderator, are left out.
+pub struct AuditLogSection;
+
+impl<R: AuditRepository, K: KeycloakClient> ExportSection<R, K> for AuditLogSection {
+    fn name(&self) -> &'static str {
+        "audit_log"
+    }
+
+    fn collect<'a>(&'a self, user_repo: &'a R, _: &'a K, sub: Uuid) -> SectionFuture<'a> {
+        Box::pin(async move {
+            let query = AuditLogQuery {
+                target_sub: Some(sub),
+                ..AuditLogQuery::default()
+            };
+            let mut entries = Vec::new();
+            let mut before_id = None;
+            loop {
+                let page = user_repo
+                    .list_audit_entries(&query, before_id, AUDIT_LOG_PAGE_SIZE)
+                    .await?;
+                let last_page = (page.len() as i64) < AUDIT_LOG_PAGE_SIZE;
+                before_id = page.last().map(|entry| entry.id);
+                entries.extend(page.into_iter().map(|mut entry| {
+                    if entry.actor_sub != sub {
+                        entry.ip_address = None;
+                        entry.user_agent = None;
+                    }
+                    entry
+                }));
+                if last_page {
+                    break;
+                }
+            }
+            to_value(&entries)
+        })
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Result<Value, CoreError> {
+    serde_json::to_value(value).map_err(|e| CoreError::InternalError(e.to_string()))
+}
+
+/// Sections making up a data export, in document order.
+pub struct ExportRegistry<R, K> {
+    sections: Vec<Arc<dyn ExportSection<R, K>>>,
+}
+
+impl<R, K> Default for ExportRegistry<R, K> {
+    fn default() -> Self {
+        Self {
+            sections: Vec::new(),
+        }
+    }
+}
+
+impl<R, K> Clone for ExportRegistry<R, K> {
+    fn clone(&self) -> Self {
+        Self {
+            sections: self.sections.clone(),
+        }
+    }
+}
+
+impl<R: UserRepository, K: KeycloakClient> ExportRegistry<R, K> {
+    /// Profile, settings and Keycloak identity.
+    pub fn builtin() -> Self {
+        Self::default()
+            .register(ProfileSection)
+            .register(SettingsSection)
+            .register(KeycloakSection)
+    }
+}
+
+impl<R, K> ExportRegistry<R, K> {
+    /// Adds a section, replacing any section with the same name.
+    pub fn register(mut self, section: impl ExportSection<R, K> + 'static) -> Self {
+        self.sections.retain(|s| s.name() != section.name());
+        self.sections.push(Arc::new(section));
+        self
+    }
+
+    /// Collects every section for `sub` into one document. Any failing
+    /// section fails the whole export, so a document is never partial.
+    pub async fn export(
+        &self,
+        user_repo: &R,
+        keycloak_client: &K,
+        sub: Uuid,
+    ) -> Result<Value, CoreError> {
+        let mut sections = Map::new();
+        for section in &self.sections {
+            let value = section.collect(user_repo, keycloak_client, sub).await?;
+            sections.insert(section.name().to_string(), value);
+        }
+
+        Ok(json!({
+            "format_version": EXPORT_FORMAT_VERSION,
+            "sub": sub,
+            "generated_at": Utc::now(),
+            "sections": sections,
+        }))
+    }
+}
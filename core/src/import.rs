@@ -0,0 +1,449 @@
+use crate::error::CoreError;
+use crate::models::{ImportLineError, ImportReport, ImportedUser, UpdateUserRequest};
+use crate::repository::ImportRepository;
+use crate::validation::validate_update_user_request;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Rows written per transaction unless configured otherwise.
+pub const DEFAULT_IMPORT_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Comma-separated values with a header row naming the columns
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl ImportFormat {
+    /// Format matching the extension of `path`, if any.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ImportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ImportFormat::Jsonl),
+            other => Err(format!("Unsupported import format: {}", other)),
+        }
+    }
+}
+
+/// Line of an import file. Only `sub` and `display_name` are required.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportRow {
+    pub sub: Uuid,
+    pub display_name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub profile_picture: Option<String>,
+}
+
+impl ImportRow {
+    /// Applies the rules of `UpdateUserRequest` to the row.
+    pub fn validate(self) -> Result<ImportedUser, String> {
+        let req = validate_update_user_request(UpdateUserRequest {
+            display_name: Some(self.display_name),
+            profile_picture: self.profile_picture,
+            description: self.description,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+            username: None,
+            email: None,
+        })
+        .map_err(|errors| errors.to_string())?;
+
+        Ok(ImportedUser {
+            sub: self.sub,
+            display_name: req.display_name.unwrap_or_default(),
+            description: req.description,
+            profile_picture: req.profile_picture,
+        })
+    }
+}
+
+/// Last committed line of an import, stored in a file so an interrupted
+/// import can resume where it stopped.
+#[derive(Debug, Clone)]
+pub struct ImportCheckpoint {
+    path: PathBuf,
+}
+
+impl ImportCheckpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Line to resume after; 0 when no checkpoint was written yet.
+    pub fn load(&self) -> Result<u64, CoreError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => content.trim().parse().map_err(|_| {
+                CoreError::InternalError(format!("Invalid checkpoint in {}", self.path.display()))
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    /// Replaces the checkpoint atomically, so a crash never leaves it torn.
+    pub fn save(&self, line: u64) -> Result<(), CoreError> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, line.to_string()).map_err(|e| self.error(e))?;
+        fs::rename(&tmp, &self.path).map_err(|e| self.error(e))
+    }
+
+    fn error(&self, e: io::Error) -> CoreError {
+        CoreError::InternalError(format!("Checkpoint {}: {}", self.path.display(), e))
+    }
+}
+
+/// Line number and parsed row, or why the line could not be parsed.
+type ParsedLine = (u64, Result<ImportRow, String>);
+
+type ParsedLines<'a> = Box<dyn Iterator<Item = Result<ParsedLine, CoreError>> + Send + 'a>;
+
+fn read_error(e: impl std::fmt::Display) -> CoreError {
+    CoreError::InternalError(format!("Failed to read import: {}", e))
+}
+
+fn jsonl_lines<'a>(input: impl Read + Send + 'a) -> ParsedLines<'a> {
+    Box::new(
+        BufReader::new(input)
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let line_number = index as u64 + 1;
+                match line {
+                    Ok(line) if line.trim().is_empty() => None,
+                    Ok(line) => Some(Ok((
+                        line_number,
+                        serde_json::from_str(&line).map_err(|e| e.to_string()),
+                    ))),
+                    Err(e) => Some(Err(read_error(e))),
+                }
+            }),
+    )
+}
+
+fn csv_lines<'a>(input: impl Read + Send + 'a) -> ParsedLines<'a> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return Box::new(std::iter::once(Err(read_error(e)))),
+    };
+
+    Box::new(reader.into_records().map(move |record| {
+        let record = record.map_err(read_error)?;
+        let line = record.position().map_or(0, |position| position.line());
+        Ok((
+            line,
+            record
+                .deserialize(Some(&headers))
+                .map_err(|e| e.to_string()),
+        ))
+    }))
+}
+
+/// Streams users from a CSV or JSONL file into the `users` table.
+///
+/// Rows are validated one by one and written in batches; invalid rows are
+/// reported with their line number and skipped. With a checkpoint, the last
+/// committed line is saved after each batch and lines up to it are skipped on
+/// the next run.
+pub struct UserImporter<R> {
+    repo: R,
+    batch_size: usize,
+    checkpoint: Option<ImportCheckpoint>,
+}
+
+impl<R: ImportRepository> UserImporter<R> {
+    pub fn new(repo: R) -> Self {
+        Self {
+            repo,
+            batch_size: DEFAULT_IMPORT_BATCH_SIZE,
+            checkpoint: None,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_checkpoint(mut self, checkpoint: ImportCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub async fn run(
+        &self,
+        input: impl Read + Send,
+        format: ImportFormat,
+    ) -> Result<ImportReport, CoreError> {
+        let resumed_after = match &self.checkpoint {
+            Some(checkpoint) => checkpoint.load()?,
+            None => 0,
+        };
+        let mut report = ImportReport {
+            resumed_after,
+            last_line: resumed_after,
+            ..ImportReport::default()
+        };
+
+        let lines = match format {
+            ImportFormat::Csv => csv_lines(input),
+            ImportFormat::Jsonl => jsonl_lines(input),
+        };
+
+        let mut batch = Vec::with_capacity(self.batch_size);
+        // Position of each sub in the batch: a later line replaces an earlier one
+        let mut positions = HashMap::new();
+        let mut batch_end = resumed_after;
+        for parsed in lines {
+            let (line, row) = parsed?;
+            if line <= resumed_after {
+                continue;
+            }
+            report.rows += 1;
+            batch_end = line;
+
+            match row.and_then(ImportRow::validate) {
+                Ok(user) => match positions.get(&user.sub) {
+                    Some(&position) => batch[position] = user,
+                    None => {
+                        positions.insert(user.sub, batch.len());
+                        batch.push(user);
+                    }
+                },
+                Err(error) => report.errors.push(ImportLineError { line, error }),
+            }
+
+            if batch.len() >= self.batch_size {
+                self.flush(&mut batch, batch_end, &mut report).await?;
+                positions.clear();
+            }
+        }
+        self.flush(&mut batch, batch_end, &mut report).await?;
+
+        Ok(report)
+    }
+
+    /// Writes the batch and moves the checkpoint to `batch_end`.
+    async fn flush(
+        &self,
+        batch: &mut Vec<ImportedUser>,
+        batch_end: u64,
+        report: &mut ImportReport,
+    ) -> Result<(), CoreError> {
+        if !batch.is_empty() {
+            let result = self.repo.upsert_imported_users(batch).await?;
+            report.inserted += result.inserted;
+            report.updated += result.updated;
+            batch.clear();
+        }
+
+        if batch_end > report.last_line {
+            if let Some(checkpoint) = &self.checkpoint {
+                checkpoint.save(batch_end)?;
+            }
+            report.last_line = batch_end;
+            tracing::info!(line = batch_end, "Import checkpoint");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ImportBatchResult;
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct MockImport {
+        users: Arc<Mutex<HashMap<Uuid, ImportedUser>>>,
+        batches: Arc<Mutex<Vec<usize>>>,
+        fail_after_batches: Option<usize>,
+    }
+
+    impl ImportRepository for MockImport {
+        async fn upsert_imported_users(
+            &self,
+            users: &[ImportedUser],
+        ) -> Result<ImportBatchResult, sqlx::Error> {
+            let mut batches = self.batches.lock().unwrap();
+            if self.fail_after_batches == Some(batches.len()) {
+                return Err(sqlx::Error::PoolTimedOut);
+            }
+            batches.push(users.len());
+
+            let subs: HashSet<Uuid> = users.iter().map(|u| u.sub).collect();
+            assert_eq!(subs.len(), users.len(), "duplicate sub in batch");
+
+            let mut stored = self.users.lock().unwrap();
+            let mut result = ImportBatchResult::default();
+            for user in users {
+                match stored.insert(user.sub, user.clone()) {
+                    Some(_) => result.updated += 1,
+                    None => result.inserted += 1,
+                }
+            }
+            Ok(result)
+        }
+    }
+
+    fn temp_checkpoint() -> ImportCheckpoint {
+        ImportCheckpoint::new(std::env::temp_dir().join(format!("import-{}.ckpt", Uuid::new_v4())))
+    }
+
+    fn jsonl(rows: &[serde_json::Value]) -> String {
+        rows.iter()
+            .map(|row| row.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn format_is_detected_from_extension() {
+        assert_eq!(
+            ImportFormat::from_path(Path::new("users.CSV")),
+            Some(ImportFormat::Csv)
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("users.jsonl")),
+            Some(ImportFormat::Jsonl)
+        );
+        assert_eq!(ImportFormat::from_path(Path::new("users.txt")), None);
+    }
+
+    #[tokio::test]
+    async fn imports_csv_in_batches() {
+        let repo = MockImport::default();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let input = format!(
+            "sub,display_name,description,profile_picture\n\
+             {a},Alice,Hello,https://cdn.example.com/a.png\n\
+             {b},  Bob  ,,\n\
+             {c},Carol,,\n"
+        );
+
+        let report = UserImporter::new(repo.clone())
+            .with_batch_size(2)
+            .run(input.as_bytes(), ImportFormat::Csv)
+            .await
+            .unwrap();
+
+        assert_eq!((report.rows, report.inserted, report.last_line), (3, 3, 4));
+        assert!(report.errors.is_empty());
+        assert_eq!(*repo.batches.lock().unwrap(), vec![2, 1]);
+        let users = repo.users.lock().unwrap();
+        assert_eq!(users[&b].display_name, "Bob");
+        assert_eq!(users[&b].description, None);
+        assert_eq!(
+            users[&a].profile_picture.as_deref(),
+            Some("https://cdn.example.com/a.png")
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_lines_and_imports_the_rest() {
+        let repo = MockImport::default();
+        let valid = Uuid::new_v4();
+        let input = jsonl(&[
+            serde_json::json!({ "sub": valid, "display_name": "Valid" }),
+            serde_json::json!({ "sub": Uuid::new_v4(), "display_name": "" }),
+            serde_json::json!({ "sub": "not-a-uuid", "display_name": "Broken" }),
+            serde_json::json!({
+                "sub": Uuid::new_v4(),
+                "display_name": "Bad avatar",
+                "profile_picture": "ftp://example.com/a.png"
+            }),
+        ]);
+
+        let report = UserImporter::new(repo.clone())
+            .run(input.as_bytes(), ImportFormat::Jsonl)
+            .await
+            .unwrap();
+
+        assert_eq!(report.inserted, 1);
+        let lines: Vec<u64> = report.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert!(report.errors[0].error.contains("display_name"));
+        assert!(report.errors[2].error.contains("profile_picture"));
+        assert!(repo.users.lock().unwrap().contains_key(&valid));
+    }
+
+    #[tokio::test]
+    async fn later_line_wins_within_a_batch() {
+        let repo = MockImport::default();
+        let sub = Uuid::new_v4();
+        let input = jsonl(&[
+            serde_json::json!({ "sub": sub, "display_name": "First" }),
+            serde_json::json!({ "sub": sub, "display_name": "Second" }),
+        ]);
+
+        UserImporter::new(repo.clone())
+            .run(input.as_bytes(), ImportFormat::Jsonl)
+            .await
+            .unwrap();
+
+        assert_eq!(repo.users.lock().unwrap()[&sub].display_name, "Second");
+    }
+
+    #[tokio::test]
+    async fn resumes_from_checkpoint_after_failure() {
+        let checkpoint = temp_checkpoint();
+        let subs: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let input = jsonl(
+            &subs
+                .iter()
+                .map(|sub| serde_json::json!({ "sub": sub, "display_name": "User" }))
+                .collect::<Vec<_>>(),
+        );
+        let failing = MockImport {
+            fail_after_batches: Some(1),
+            ..MockImport::default()
+        };
+
+        let first = UserImporter::new(failing.clone())
+            .with_batch_size(2)
+            .with_checkpoint(checkpoint.clone())
+            .run(input.as_bytes(), ImportFormat::Jsonl)
+            .await;
+        assert!(first.is_err());
+        assert_eq!(checkpoint.load().unwrap(), 2);
+
+        let repo = MockImport::default();
+        let report = UserImporter::new(repo.clone())
+            .with_batch_size(2)
+            .with_checkpoint(checkpoint.clone())
+            .run(input.as_bytes(), ImportFormat::Jsonl)
+            .await
+            .unwrap();
+
+        assert_eq!(report.resumed_after, 2);
+        assert_eq!((report.rows, report.inserted, report.last_line), (3, 3, 5));
+        assert_eq!(checkpoint.load().unwrap(), 5);
+        let imported = repo.users.lock().unwrap();
+        assert!(!imported.contains_key(&subs[0]));
+        assert!(subs[2..].iter().all(|sub| imported.contains_key(sub)));
+        fs::remove_file(&checkpoint.path).unwrap();
+    }
+}
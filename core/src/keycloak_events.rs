@@ -0,0 +1,254 @@
+use crate::error::CoreError;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Header carrying the hex-encoded HMAC-SHA256 of the request body.
+pub const SIGNATURE_HEADER: &str = "x-keycloak-signature";
+
+/// What happened to a Keycloak account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeycloakEventKind {
+    Registered,
+    ProfileUpdated,
+    AccountDeleted,
+}
+
+/// A Keycloak user or admin event relevant to the local `users` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeycloakEvent {
+    pub kind: KeycloakEventKind,
+    pub sub: Uuid,
+    /// Username carried by the event, when Keycloak includes it.
+    pub username: Option<String>,
+}
+
+/// Checks `signature` against the HMAC-SHA256 of `body` keyed with `secret`.
+/// A `sha256=` prefix is accepted. The comparison runs in constant time.
+pub fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let Ok(expected) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Shape shared by Keycloak user events (`type`, `userId`, `details`) and
+/// admin events (`operationType`, `resourceType`, `resourcePath`).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawEvent {
+    #[serde(rename = "type")]
+    event_type: Option<String>,
+    user_id: Option<String>,
+    #[serde(default)]
+    details: HashMap<String, String>,
+    operation_type: Option<String>,
+    resource_type: Option<String>,
+    resource_path: Option<String>,
+    /// JSON-encoded user representation, when the realm includes it
+    representation: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserRepresentation {
+    username: Option<String>,
+}
+
+/// Parses a Keycloak event. Returns `None` for events that do not affect
+/// local users, such as logins or admin operations on other resources.
+pub fn parse_keycloak_event(body: &[u8]) -> Result<Option<KeycloakEvent>, CoreError> {
+    let raw: RawEvent = serde_json::from_slice(body)
+        .map_err(|e| CoreError::BadRequest(format!("Invalid Keycloak event: {}", e)))?;
+
+    if let Some(operation) = raw.operation_type.as_deref() {
+        return parse_admin_event(operation, &raw);
+    }
+
+    let kind = match raw.event_type.as_deref() {
+        Some("REGISTER") => KeycloakEventKind::Registered,
+        Some("UPDATE_PROFILE") | Some("UPDATE_EMAIL") => KeycloakEventKind::ProfileUpdated,
+        Some("DELETE_ACCOUNT") => KeycloakEventKind::AccountDeleted,
+        _ => return Ok(None),
+    };
+    let sub = parse_sub(raw.user_id.as_deref())?;
+
+    Ok(Some(KeycloakEvent {
+        kind,
+        sub,
+        username: raw.details.get("username").cloned(),
+    }))
+}
+
+fn parse_admin_event(operation: &str, raw: &RawEvent) -> Result<Option<KeycloakEvent>, CoreError> {
+    if raw.resource_type.as_deref() != Some("USER") {
+        return Ok(None);
+    }
+
+    // Operations on sub-resources (credentials, role mappings...) have a
+    // longer path and leave the user itself untouched.
+    let Some(id) = raw
+        .resource_path
+        .as_deref()
+        .and_then(|path| path.strip_prefix("users/"))
+        .filter(|id| !id.contains('/'))
+    else {
+        return Ok(None);
+    };
+
+    let kind = match operation {
+        "CREATE" => KeycloakEventKind::Registered,
+        "UPDATE" => KeycloakEventKind::ProfileUpdated,
+        "DELETE" => KeycloakEventKind::AccountDeleted,
+        _ => return Ok(None),
+    };
+    let username = raw
+        .representation
+        .as_deref()
+        .and_then(|json| serde_json::from_str::<UserRepresentation>(json).ok())
+        .and_then(|user| user.username);
+
+    Ok(Some(KeycloakEvent {
+        kind,
+        sub: parse_sub(Some(id))?,
+        username,
+    }))
+}
+
+fn parse_sub(id: Option<&str>) -> Result<Uuid, CoreError> {
+    id.and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| CoreError::BadRequest("Keycloak event has no valid user id".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sign(secret: &[u8], body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn verify_signature_accepts_valid_signature() {
+        let body = br#"{"type":"REGISTER"}"#;
+        let signature = sign(b"secret", body);
+
+        assert!(verify_signature(b"secret", body, &signature));
+        assert!(verify_signature(
+            b"secret",
+            body,
+            &format!("sha256={}", signature)
+        ));
+    }
+
+    #[test]
+    fn verify_signature_rejects_wrong_secret_or_body() {
+        let body = br#"{"type":"REGISTER"}"#;
+        let signature = sign(b"secret", body);
+
+        assert!(!verify_signature(b"other", body, &signature));
+        assert!(!verify_signature(b"secret", b"{}", &signature));
+        assert!(!verify_signature(b"secret", body, "not-hex"));
+    }
+
+    #[test]
+    fn parses_user_event() {
+        let sub = Uuid::new_v4();
+        let body = json!({
+            "type": "REGISTER",
+            "userId": sub,
+            "details": { "username": "john", "email": "john@example.com" }
+        });
+
+        let event = parse_keycloak_event(body.to_string().as_bytes()).unwrap();
+
+        assert_eq!(
+            event,
+            Some(KeycloakEvent {
+                kind: KeycloakEventKind::Registered,
+                sub,
+                username: Some("john".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_admin_event() {
+        let sub = Uuid::new_v4();
+        let body = json!({
+            "operationType": "DELETE",
+            "resourceType": "USER",
+            "resourcePath": format!("users/{}", sub),
+        });
+
+        let event = parse_keycloak_event(body.to_string().as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.kind, KeycloakEventKind::AccountDeleted);
+        assert_eq!(event.sub, sub);
+    }
+
+    #[test]
+    fn admin_event_reads_username_from_representation() {
+        let sub = Uuid::new_v4();
+        let body = json!({
+            "operationType": "UPDATE",
+            "resourceType": "USER",
+            "resourcePath": format!("users/{}", sub),
+            "representation": json!({ "username": "renamed" }).to_string(),
+        });
+
+        let event = parse_keycloak_event(body.to_string().as_bytes())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.kind, KeycloakEventKind::ProfileUpdated);
+        assert_eq!(event.username.as_deref(), Some("renamed"));
+    }
+
+    #[test]
+    fn ignores_unrelated_events() {
+        let sub = Uuid::new_v4();
+        let login = json!({ "type": "LOGIN", "userId": sub });
+        let reset_password = json!({
+            "operationType": "ACTION",
+            "resourceType": "USER",
+            "resourcePath": format!("users/{}/reset-password", sub),
+        });
+        let group = json!({
+            "operationType": "CREATE",
+            "resourceType": "GROUP",
+            "resourcePath": "groups/admins",
+        });
+
+        for body in [login, reset_password, group] {
+            assert_eq!(
+                parse_keycloak_event(body.to_string().as_bytes()).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_events() {
+        assert!(matches!(
+            parse_keycloak_event(b"not json"),
+            Err(CoreError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_keycloak_event(br#"{"type":"REGISTER","userId":"nope"}"#),
+            Err(CoreError::BadRequest(_))
+        ));
+    }
+}
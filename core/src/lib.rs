@@ -1,11 +1,43 @@
 pub mod application;
+pub mod avatar;
+pub mod cache;
+pub mod email_verification;
 pub mod error;
+pub mod events;
+pub mod export;
+pub mod import;
+pub mod keycloak_events;
+pub mod mail;
 pub mod models;
+pub mod pagination;
 pub mod repository;
 pub mod services;
+pub mod settings;
+pub mod username;
+pub mod validation;
 
 pub use application::ApplicationService;
+pub use cache::{CacheMetricsSnapshot, SharedUserCache, UserCache};
+pub use email_verification::EmailVerification;
 pub use error::CoreError;
+pub use events::{
+    EventSink, FileEventSink, InMemoryEventSink, LogEventSink, dispatch_pending_events,
+};
+pub use export::{
+    AuditLogSection, EmailChangesSection, ExportRegistry, ExportSection, RelationshipsSection,
+    UsernameHistorySection,
+};
+pub use import::{ImportCheckpoint, ImportFormat, UserImporter};
+pub use keycloak_events::{KeycloakEvent, KeycloakEventKind};
+pub use mail::{FileMailer, InMemoryMailer, Mailer, StdoutMailer, dispatch_pending_emails};
 pub use models::*;
-pub use repository::{PostgresUserRepository, UserRepository};
+pub use repository::{
+    AuditRepository, AvatarRepository, DeletionRepository, EmailChangeRepository,
+    ExportRepository, ImportRepository, MailOutboxRepository, OutboxRepository,
+    PostgresUserRepository, RelationshipRepository, SuspensionRepository, UserRepository,
+    UsernameRepository,
+};
 pub use services::{KeycloakClient, KeycloakError, KeycloakService, UserService, UserServiceImpl};
+pub use settings::{SettingDefinition, SettingKind, SettingsRegistry};
+pub use username::UsernamePolicy;
+pub use validation::{FieldError, ValidationErrors};
@@ -0,0 +1,213 @@
+use crate::error::CoreError;
+use crate::models::EmailMessage;
+use crate::repository::MailOutboxRepository;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Delivers emails (SMTP relay, transactional email API, file...).
+///
+/// Emails are queued in the outbox with the change they belong to and
+/// handed to the mailer by `dispatch_pending_emails`. An email may be sent
+/// again if the dispatcher stops before recording it as sent.
+pub trait Mailer: Send + Sync + Clone {
+    fn send(&self, message: &EmailMessage) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Keeps sent emails in memory. Intended for tests.
+#[derive(Clone, Default)]
+pub struct InMemoryMailer {
+    sent: Arc<Mutex<Vec<EmailMessage>>>,
+}
+
+impl InMemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sent(&self) -> Vec<EmailMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl Mailer for InMemoryMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        self.sent.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
+
+/// Appends emails to a file, one JSON document per line. For local testing.
+#[derive(Clone)]
+pub struct FileMailer {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FileMailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+impl Mailer for FileMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        let line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+
+        let _guard = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// Prints emails to stdout. Used when no other mailer is configured.
+#[derive(Clone, Default)]
+pub struct StdoutMailer;
+
+impl Mailer for StdoutMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+        println!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            message.recipient, message.subject, message.body
+        );
+        Ok(())
+    }
+}
+
+/// Hands one batch of queued emails to `mailer`. Emails that fail stay
+/// queued and are retried once their lease expires. Returns the number of
+/// emails sent.
+pub async fn dispatch_pending_emails<O: MailOutboxRepository, M: Mailer>(
+    outbox: &O,
+    mailer: &M,
+    batch_size: i64,
+    lease: Duration,
+) -> Result<usize, CoreError> {
+    let emails = outbox.claim_pending_emails(batch_size, lease).await?;
+
+    let mut sent = Vec::with_capacity(emails.len());
+    for email in &emails {
+        match mailer.send(&email.message).await {
+            Ok(()) => sent.push(email.id),
+            Err(e) => tracing::warn!(email_id = email.id, error = %e, "Failed to send email"),
+        }
+    }
+
+    outbox.delete_sent_emails(&sent).await?;
+    Ok(sent.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OutgoingEmail;
+
+    #[derive(Clone, Default)]
+    struct MockOutbox {
+        pending: Arc<Mutex<Vec<OutgoingEmail>>>,
+    }
+
+    impl MockOutbox {
+        fn with_emails(recipients: &[&str]) -> Self {
+            let outbox = Self::default();
+            for (id, recipient) in recipients.iter().enumerate() {
+                outbox.pending.lock().unwrap().push(OutgoingEmail {
+                    id: id as i64 + 1,
+                    message: EmailMessage {
+                        recipient: recipient.to_string(),
+                        subject: "Hello".to_string(),
+                        body: "Hi there".to_string(),
+                    },
+                });
+            }
+            outbox
+        }
+    }
+
+    impl MailOutboxRepository for MockOutbox {
+        async fn claim_pending_emails(
+            &self,
+            limit: i64,
+            _lease: Duration,
+        ) -> Result<Vec<OutgoingEmail>, sqlx::Error> {
+            Ok(self
+                .pending
+                .lock()
+                .unwrap()
+                .iter()
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn delete_sent_emails(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+            self.pending
+                .lock()
+                .unwrap()
+                .retain(|email| !ids.contains(&email.id));
+            Ok(())
+        }
+    }
+
+    /// Mailer that rejects one recipient.
+    #[derive(Clone)]
+    struct BouncingMailer {
+        inner: InMemoryMailer,
+        bounce: &'static str,
+    }
+
+    impl Mailer for BouncingMailer {
+        async fn send(&self, message: &EmailMessage) -> Result<(), String> {
+            if message.recipient == self.bounce {
+                return Err("mailbox unavailable".to_string());
+            }
+            self.inner.send(message).await
+        }
+    }
+
+    const LEASE: Duration = Duration::from_secs(30);
+
+    #[tokio::test]
+    async fn sends_and_removes_queued_emails() {
+        let outbox = MockOutbox::with_emails(&["a@example.com", "b@example.com"]);
+        let mailer = InMemoryMailer::new();
+
+        let sent = dispatch_pending_emails(&outbox, &mailer, 10, LEASE)
+            .await
+            .unwrap();
+
+        assert_eq!(sent, 2);
+        assert_eq!(mailer.sent()[1].recipient, "b@example.com");
+        assert!(outbox.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_emails_stay_queued() {
+        let outbox =
+            MockOutbox::with_emails(&["a@example.com", "bounce@example.com", "c@example.com"]);
+        let mailer = BouncingMailer {
+            inner: InMemoryMailer::new(),
+            bounce: "bounce@example.com",
+        };
+
+        let sent = dispatch_pending_emails(&outbox, &mailer, 10, LEASE)
+            .await
+            .unwrap();
+
+        assert_eq!(sent, 2);
+        let pending = outbox.pending.lock().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].message.recipient, "bounce@example.com");
+    }
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Display name, profile picture, description, username or email
+    ProfileUpdated,
+    /// New email waiting for verification; applied as `ProfileUpdated`
+    EmailChangeRequested,
+    SettingsUpdated,
+    /// Visibility of profile fields
+    PrivacyUpdated,
+    UserSuspended,
+    SuspensionLifted,
+    AccountDeletionRequested,
+    AccountDeletionCancelled,
+}
+
+/// Before and after values of a changed field. `None` means unset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl FieldChange {
+    pub fn new(field: &str, before: Option<Value>, after: Option<Value>) -> Self {
+        Self {
+            field: field.to_string(),
+            before,
+            after,
+        }
+    }
+}
+
+/// Who made a change, and from where.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor_sub: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl AuditContext {
+    /// Context of a change made outside of an HTTP request.
+    pub fn new(actor_sub: Uuid) -> Self {
+        Self {
+            actor_sub,
+            ip_address: None,
+            user_agent: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_sub: Uuid,
+    pub target_sub: Uuid,
+    pub action: AuditAction,
+    #[sqlx(json)]
+    pub changes: Vec<FieldChange>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters of an audit log query. Every filter is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct AuditLogQuery {
+    pub actor_sub: Option<Uuid>,
+    pub target_sub: Option<Uuid>,
+    pub action: Option<AuditAction>,
+    /// Entries created at or after this date
+    pub from: Option<DateTime<Utc>>,
+    /// Entries created before this date
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AuditPage {
+    /// Most recent first
+    pub entries: Vec<AuditEntry>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Keeps the first character of the local part and the domain, so support can
+/// tell addresses apart without the log holding them in clear.
+pub fn redact_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first = local.chars().next().map(String::from).unwrap_or_default();
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_email_keeps_first_letter_and_domain() {
+        assert_eq!(redact_email("john.doe@example.com"), "j***@example.com");
+    }
+
+    #[test]
+    fn redact_email_hides_malformed_addresses() {
+        assert_eq!(redact_email("not-an-email"), "***");
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Pending or completed erasure of an account.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserDeletion {
+    pub sub: Uuid,
+    pub requested_at: DateTime<Utc>,
+    /// The account is purged once this date has passed
+    pub purge_after: DateTime<Utc>,
+    pub avatar_deleted_at: Option<DateTime<Utc>>,
+    pub keycloak_deleted_at: Option<DateTime<Utc>>,
+    pub anonymized_at: Option<DateTime<Utc>>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+impl UserDeletion {
+    /// Whether an irreversible purge step already ran.
+    pub fn purge_started(&self) -> bool {
+        self.avatar_deleted_at.is_some()
+            || self.keycloak_deleted_at.is_some()
+            || self.anonymized_at.is_some()
+    }
+}
+
+/// Purge steps run outside the local database, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletionStep {
+    Avatar,
+    Keycloak,
+}
+
+impl DeletionStep {
+    pub fn column(self) -> &'static str {
+        match self {
+            DeletionStep::Avatar => "avatar_deleted_at",
+            DeletionStep::Keycloak => "keycloak_deleted_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AccountDeletionInfo {
+    pub requested_at: DateTime<Utc>,
+    /// Until this date the deletion can be cancelled
+    pub purge_after: DateTime<Utc>,
+}
+
+impl From<UserDeletion> for AccountDeletionInfo {
+    fn from(deletion: UserDeletion) -> Self {
+        Self {
+            requested_at: deletion.requested_at,
+            purge_after: deletion.purge_after,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Email change waiting for the new address to be confirmed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow)]
+pub struct PendingEmailChange {
+    pub sub: Uuid,
+    /// Address at request time, notified once the change is applied
+    pub old_email: String,
+    pub new_email: String,
+    /// Only the token carrying this nonce can confirm the change
+    #[serde(skip_serializing)]
+    pub nonce: Uuid,
+    pub requested_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PendingEmailChange {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct VerifyEmailRequest {
+    /// Token from the link sent to the new address
+    pub token: String,
+}
+
+/// Email to be handed to the mailer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow)]
+pub struct EmailMessage {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Email queued in the outbox.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct OutgoingEmail {
+    pub id: i64,
+    #[sqlx(flatten)]
+    pub message: EmailMessage,
+}
@@ -0,0 +1,114 @@
+use crate::models::{ProfileLink, ProfilePrivacy, SettingValues};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// What happened to a user. Serialized with a `type` tag so consumers can
+/// dispatch on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UserEventKind {
+    UserCreated {
+        display_name: String,
+    },
+    /// Only the profile fields that changed are set.
+    ProfileUpdated {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        profile_picture: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        accent_color: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pronouns: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        links: Option<Vec<ProfileLink>>,
+    },
+    SettingsUpdated {
+        values: SettingValues,
+        reset: Vec<String>,
+    },
+    /// A new avatar has been uploaded; consumers sign their own read URLs.
+    AvatarUpdated {
+        avatar_key: String,
+    },
+    /// The banner has been replaced, or removed when `banner_key` is null.
+    BannerUpdated {
+        banner_key: Option<String>,
+    },
+    /// Field visibility has changed; consumers holding profile copies
+    /// should hide the fields no longer public.
+    PrivacyUpdated {
+        privacy: ProfilePrivacy,
+    },
+    /// A moderator has suspended the account, until `expires_at` if set.
+    UserSuspended {
+        expires_at: Option<DateTime<Utc>>,
+    },
+    /// The suspension has been lifted or has expired.
+    SuspensionLifted,
+    /// The account has been purged; consumers should erase their copies.
+    UserDeleted,
+}
+
+impl UserEventKind {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            UserEventKind::UserCreated { .. } => "user.created",
+            UserEventKind::ProfileUpdated { .. } => "user.profile_updated",
+            UserEventKind::SettingsUpdated { .. } => "user.settings_updated",
+            UserEventKind::AvatarUpdated { .. } => "user.avatar_updated",
+            UserEventKind::BannerUpdated { .. } => "user.banner_updated",
+            UserEventKind::PrivacyUpdated { .. } => "user.privacy_updated",
+            UserEventKind::UserSuspended { .. } => "user.suspended",
+            UserEventKind::SuspensionLifted => "user.suspension_lifted",
+            UserEventKind::UserDeleted => "user.deleted",
+        }
+    }
+}
+
+/// Domain event stored in the outbox until it has been delivered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct UserEvent {
+    pub id: i64,
+    pub sub: Uuid,
+    pub event_type: String,
+    #[sqlx(json)]
+    pub payload: UserEventKind,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_updated_serializes_only_changed_fields() {
+        let kind = UserEventKind::ProfileUpdated {
+            display_name: Some("John".to_string()),
+            profile_picture: None,
+            description: None,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+        };
+
+        let json = serde_json::to_value(&kind).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "profile_updated", "display_name": "John" })
+        );
+    }
+
+    #[test]
+    fn event_type_matches_kind() {
+        let kind = UserEventKind::UserCreated {
+            display_name: "John".to_string(),
+        };
+        assert_eq!(kind.event_type(), "user.created");
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "data_export_status", rename_all = "snake_case")]
+pub enum DataExportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    /// The document was dropped after its retention period
+    Expired,
+}
+
+/// A data export job. The document itself is only loaded for download.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DataExport {
+    pub id: Uuid,
+    pub sub: Uuid,
+    pub status: DataExportStatus,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct DataExportInfo {
+    pub id: Uuid,
+    pub status: DataExportStatus,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Until this date the document can be downloaded
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Set once the export is completed
+    pub download_url: Option<String>,
+}
+
+impl From<DataExport> for DataExportInfo {
+    fn from(export: DataExport) -> Self {
+        let download_url = (export.status == DataExportStatus::Completed)
+            .then(|| format!("/users/me/exports/{}/download", export.id));
+        Self {
+            id: export.id,
+            status: export.status,
+            created_at: export.created_at,
+            completed_at: export.completed_at,
+            expires_at: export.expires_at,
+            download_url,
+        }
+    }
+}
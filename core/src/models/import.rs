@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Validated user of an import file. Unset fields keep their current value,
+/// or the column default for new users.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedUser {
+    pub sub: Uuid,
+    pub display_name: String,
+    pub description: Option<String>,
+    pub profile_picture: Option<String>,
+}
+
+/// Rows written by one import batch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportBatchResult {
+    pub inserted: u64,
+    pub updated: u64,
+}
+
+/// Input line rejected by an import.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportLineError {
+    pub line: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Lines up to this one were imported by a previous run
+    pub resumed_after: u64,
+    /// Last line of the input that has been committed
+    pub last_line: u64,
+    /// Rows read by this run, rejected ones included
+    pub rows: u64,
+    pub inserted: u64,
+    pub updated: u64,
+    pub errors: Vec<ImportLineError>,
+}
@@ -1,3 +1,25 @@
+pub mod audit;
+pub mod deletion;
+pub mod email;
+pub mod event;
+pub mod export;
+pub mod import;
+pub mod moderation;
+pub mod privacy;
+pub mod reconcile;
+pub mod relationship;
 pub mod user;
+pub mod username;
 
+pub use audit::*;
+pub use deletion::*;
+pub use email::*;
+pub use event::*;
+pub use export::*;
+pub use import::*;
+pub use moderation::*;
+pub use privacy::*;
+pub use reconcile::*;
+pub use relationship::*;
 pub use user::*;
+pub use username::*;
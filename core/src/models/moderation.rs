@@ -0,0 +1,52 @@
+use crate::models::{KeycloakUserInfo, User};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Everything stored about a user, privacy settings ignored. Only served to
+/// moderators.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AdminUserInfo {
+    #[serde(flatten)]
+    pub user: User,
+    /// Keycloak identity, absent once the Keycloak account is gone
+    pub keycloak: Option<KeycloakUserInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AdminUserPage {
+    /// Matching users, best match first
+    pub users: Vec<User>,
+    /// Cursor of the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Suspension of an account by a moderator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct Suspension {
+    pub reason: String,
+    /// Moderator who issued the suspension
+    pub suspended_by: Option<Uuid>,
+    pub suspended_at: DateTime<Utc>,
+    /// The suspension is lifted automatically at this time; it lasts until
+    /// lifted by a moderator when absent
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SuspendUserRequest {
+    /// Shown to the suspended user
+    pub reason: String,
+    /// Permanent suspension when absent
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Also disable the Keycloak account, so that no new token is issued
+    #[serde(default)]
+    pub disable_keycloak: bool,
+}
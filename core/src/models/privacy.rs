@@ -0,0 +1,260 @@
+use crate::models::{User, UserStatus};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Who is looking at a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewer {
+    /// Internal service-to-service calls, made on behalf of no user
+    Anonymous,
+    User(Uuid),
+}
+
+/// Who may see a profile field. The owner always sees their own fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(tag = "visibility", rename_all = "snake_case")]
+pub enum FieldVisibility {
+    #[default]
+    Everyone,
+    /// Any signed-in user
+    Authenticated,
+    OnlyMe,
+    /// Only the listed users
+    AllowList {
+        allowed: Vec<Uuid>,
+    },
+}
+
+impl FieldVisibility {
+    pub fn allows(&self, owner: Uuid, viewer: Viewer) -> bool {
+        match (self, viewer) {
+            (_, Viewer::User(sub)) if sub == owner => true,
+            (FieldVisibility::Everyone, _) => true,
+            (FieldVisibility::Authenticated, Viewer::User(_)) => true,
+            (FieldVisibility::AllowList { allowed }, Viewer::User(sub)) => allowed.contains(&sub),
+            _ => false,
+        }
+    }
+}
+
+/// Visibility of each optional profile field. Display name and status are
+/// always visible.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(default)]
+pub struct ProfilePrivacy {
+    pub description: FieldVisibility,
+    /// Uploaded avatar or external profile picture
+    pub avatar: FieldVisibility,
+    pub banner: FieldVisibility,
+    pub accent_color: FieldVisibility,
+    pub pronouns: FieldVisibility,
+    pub links: FieldVisibility,
+}
+
+impl ProfilePrivacy {
+    /// Fields by name, in declaration order.
+    pub fn fields(&self) -> [(&'static str, &FieldVisibility); 6] {
+        [
+            ("description", &self.description),
+            ("avatar", &self.avatar),
+            ("banner", &self.banner),
+            ("accent_color", &self.accent_color),
+            ("pronouns", &self.pronouns),
+            ("links", &self.links),
+        ]
+    }
+
+    /// Applies the fields set in `req`.
+    pub fn apply(&mut self, req: UpdatePrivacyRequest) {
+        let fields = [
+            (&mut self.description, req.description),
+            (&mut self.avatar, req.avatar),
+            (&mut self.banner, req.banner),
+            (&mut self.accent_color, req.accent_color),
+            (&mut self.pronouns, req.pronouns),
+            (&mut self.links, req.links),
+        ];
+        for (field, visibility) in fields {
+            if let Some(visibility) = visibility {
+                *field = visibility;
+            }
+        }
+    }
+}
+
+/// Visibility changes; omitted fields are left unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct UpdatePrivacyRequest {
+    pub description: Option<FieldVisibility>,
+    pub avatar: Option<FieldVisibility>,
+    pub banner: Option<FieldVisibility>,
+    pub accent_color: Option<FieldVisibility>,
+    pub pronouns: Option<FieldVisibility>,
+    pub links: Option<FieldVisibility>,
+}
+
+impl User {
+    /// The user as `viewer` may see it: fields hidden from `viewer` are
+    /// cleared and an invisible status reads as offline to others, without
+    /// the custom status that would give it away.
+    ///
+    /// Every view of a user goes through this projection before images are
+    /// signed, so neither hidden values nor their URLs can leak.
+    pub fn visible_to(mut self, viewer: Viewer) -> Self {
+        let owner = self.sub;
+        let privacy = std::mem::take(&mut self.privacy);
+
+        if !privacy.description.allows(owner, viewer) {
+            self.description.clear();
+        }
+        if !privacy.avatar.allows(owner, viewer) {
+            self.profile_picture.clear();
+            self.avatar_key = None;
+        }
+        if !privacy.banner.allows(owner, viewer) {
+            self.banner_key = None;
+        }
+        if !privacy.accent_color.allows(owner, viewer) {
+            self.accent_color = None;
+        }
+        if !privacy.pronouns.allows(owner, viewer) {
+            self.pronouns = None;
+        }
+        if !privacy.links.allows(owner, viewer) {
+            self.links.clear();
+        }
+
+        if viewer != Viewer::User(owner) {
+            if self.status == UserStatus::Invisible {
+                self.custom_status_text = None;
+                self.custom_status_emoji = None;
+                self.custom_status_expires_at = None;
+            }
+            self.status = self.status.public();
+        } else {
+            self.privacy = privacy;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn create_user(privacy: ProfilePrivacy) -> User {
+        let now = Utc::now();
+        User {
+            sub: Uuid::new_v4(),
+            display_name: "John".to_string(),
+            profile_picture: "https://example.com/pic.jpg".to_string(),
+            avatar_key: None,
+            banner_key: Some("banner".to_string()),
+            accent_color: Some("#5865f2".to_string()),
+            pronouns: Some("he/him".to_string()),
+            links: Vec::new(),
+            privacy,
+            description: "Hello".to_string(),
+            status: UserStatus::Invisible,
+            custom_status_text: None,
+            custom_status_emoji: None,
+            custom_status_expires_at: None,
+            deleted_at: None,
+            suspended_at: None,
+            suspended_until: None,
+            suspended_by: None,
+            suspension_reason: None,
+            suspension_disabled_keycloak: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn visibility_rules() {
+        let owner = Uuid::new_v4();
+        let friend = Uuid::new_v4();
+        let stranger = Viewer::User(Uuid::new_v4());
+        let allow_list = FieldVisibility::AllowList {
+            allowed: vec![friend],
+        };
+
+        assert!(FieldVisibility::Everyone.allows(owner, Viewer::Anonymous));
+        assert!(!FieldVisibility::Authenticated.allows(owner, Viewer::Anonymous));
+        assert!(FieldVisibility::Authenticated.allows(owner, stranger));
+        assert!(!FieldVisibility::OnlyMe.allows(owner, stranger));
+        assert!(FieldVisibility::OnlyMe.allows(owner, Viewer::User(owner)));
+        assert!(allow_list.allows(owner, Viewer::User(friend)));
+        assert!(!allow_list.allows(owner, stranger));
+        assert!(!allow_list.allows(owner, Viewer::Anonymous));
+    }
+
+    #[test]
+    fn hidden_fields_are_cleared_for_others() {
+        let user = create_user(ProfilePrivacy {
+            description: FieldVisibility::OnlyMe,
+            avatar: FieldVisibility::Authenticated,
+            banner: FieldVisibility::OnlyMe,
+            ..ProfilePrivacy::default()
+        });
+
+        let seen = user.clone().visible_to(Viewer::Anonymous);
+
+        assert_eq!(seen.description, "");
+        assert_eq!(seen.profile_picture, "");
+        assert_eq!(seen.banner_key, None);
+        assert_eq!(seen.pronouns, user.pronouns);
+        assert_eq!(seen.status, UserStatus::Offline);
+        assert_eq!(seen.privacy, ProfilePrivacy::default());
+    }
+
+    #[test]
+    fn owner_sees_everything() {
+        let privacy = ProfilePrivacy {
+            description: FieldVisibility::OnlyMe,
+            ..ProfilePrivacy::default()
+        };
+        let user = create_user(privacy.clone());
+
+        let seen = user.clone().visible_to(Viewer::User(user.sub));
+
+        assert_eq!(seen.description, "Hello");
+        assert_eq!(seen.status, UserStatus::Invisible);
+        assert_eq!(seen.privacy, privacy);
+    }
+
+    #[test]
+    fn invisible_users_show_no_custom_status_to_others() {
+        let mut user = create_user(ProfilePrivacy::default());
+        user.custom_status_text = Some("Coding".to_string());
+        user.custom_status_emoji = Some(":computer:".to_string());
+
+        let seen = user.clone().visible_to(Viewer::Anonymous);
+        let own = user.clone().visible_to(Viewer::User(user.sub));
+
+        assert!(seen.custom_status().is_none());
+        assert_eq!(own.custom_status_text.as_deref(), Some("Coding"));
+    }
+
+    #[test]
+    fn visibility_serializes_with_tag() {
+        let sub = Uuid::nil();
+        let json = serde_json::to_value(FieldVisibility::AllowList { allowed: vec![sub] }).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({ "visibility": "allow_list", "allowed": [sub] })
+        );
+        let privacy: ProfilePrivacy =
+            serde_json::from_str(r#"{"links":{"visibility":"only_me"}}"#).unwrap();
+        assert_eq!(privacy.links, FieldVisibility::OnlyMe);
+        assert_eq!(privacy.description, FieldVisibility::Everyone);
+    }
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Account listed by the Keycloak admin API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeycloakAccount {
+    pub sub: Uuid,
+    pub username: String,
+    /// Client owning the account when it is a service account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account_client_id: Option<String>,
+}
+
+/// Account that could not be fixed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileFailure {
+    pub sub: Uuid,
+    pub error: String,
+}
+
+/// Differences between Keycloak and the `users` table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// Nothing was changed when set
+    pub dry_run: bool,
+    pub keycloak_users: usize,
+    pub local_users: usize,
+    /// Keycloak accounts without a local row
+    pub missing_local: Vec<KeycloakAccount>,
+    /// Active local rows whose Keycloak account is gone
+    pub orphaned_local: Vec<Uuid>,
+    /// Active local rows of client service accounts, provisioned before
+    /// service clients were told apart from users
+    pub service_accounts: Vec<Uuid>,
+    /// Local rows created from Keycloak accounts
+    pub created: usize,
+    /// Orphaned and service account rows scheduled for deletion
+    pub flagged: usize,
+    /// Keycloak usernames recorded as the current username of their user
+    pub usernames_claimed: usize,
+    pub failures: Vec<ReconcileFailure>,
+}
@@ -0,0 +1,43 @@
+use crate::models::UserBasicInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Relationship of a user towards another one.
+///
+/// Friendships and pending requests are stored on both sides; a block only
+/// exists on the side of the user who blocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "relationship_kind", rename_all = "snake_case")]
+pub enum RelationshipKind {
+    Friend,
+    /// Friend request sent by the user
+    PendingOutgoing,
+    /// Friend request received by the user
+    PendingIncoming,
+    Blocked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Relationship {
+    pub user_sub: Uuid,
+    pub target_sub: Uuid,
+    pub kind: RelationshipKind,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RelationshipInfo {
+    pub user: UserBasicInfo,
+    pub kind: RelationshipKind,
+    /// When the relationship reached its current state
+    pub since: DateTime<Utc>,
+}
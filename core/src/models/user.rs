@@ -1,3 +1,4 @@
+use crate::models::{ProfilePrivacy, Suspension};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -6,47 +7,274 @@ use uuid::Uuid;
 #[cfg(feature = "openapi")]
 use utoipa::ToSchema;
 
+/// Presence status of a user.
+///
+/// `Offline` is never stored: it is what other users see when someone is
+/// `Invisible`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "user_status", rename_all = "snake_case")]
+pub enum UserStatus {
+    #[default]
+    Online,
+    Idle,
+    DoNotDisturb,
+    Invisible,
+    Offline,
+}
+
+impl UserStatus {
+    /// Status as seen by other users.
+    pub fn public(self) -> Self {
+        match self {
+            UserStatus::Invisible => UserStatus::Offline,
+            status => status,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 #[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct User {
     pub sub: Uuid,
     pub display_name: String,
+    /// External picture URL, used when no avatar has been uploaded
     pub profile_picture: String,
+    /// Content service key of the uploaded avatar
+    pub avatar_key: Option<String>,
+    /// Content service key of the uploaded banner
+    pub banner_key: Option<String>,
+    /// Hex color, e.g. `#5865f2`
+    pub accent_color: Option<String>,
+    pub pronouns: Option<String>,
+    #[sqlx(json)]
+    pub links: Vec<ProfileLink>,
+    /// Who may see each optional profile field
+    #[sqlx(json)]
+    pub privacy: ProfilePrivacy,
     pub description: String,
+    pub status: UserStatus,
+    pub custom_status_text: Option<String>,
+    pub custom_status_emoji: Option<String>,
+    pub custom_status_expires_at: Option<DateTime<Utc>>,
+    /// Set once the user has requested the deletion of their account
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Set while a moderator has suspended the account
+    pub suspended_at: Option<DateTime<Utc>>,
+    /// End of the suspension; it lasts until lifted when unset
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub suspended_by: Option<Uuid>,
+    pub suspension_reason: Option<String>,
+    /// Whether the Keycloak account has been disabled for the suspension
+    pub suspension_disabled_keycloak: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl User {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Custom status, unless it is empty or has expired.
+    pub fn custom_status(&self) -> Option<CustomStatus> {
+        if self.custom_status_text.is_none() && self.custom_status_emoji.is_none() {
+            return None;
+        }
+        if self
+            .custom_status_expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return None;
+        }
+        Some(CustomStatus {
+            text: self.custom_status_text.clone(),
+            emoji: self.custom_status_emoji.clone(),
+            expires_at: self.custom_status_expires_at,
+        })
+    }
+
+    /// Suspension in force, unless lifted or expired.
+    pub fn active_suspension(&self) -> Option<Suspension> {
+        let suspended_at = self.suspended_at?;
+        if self
+            .suspended_until
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return None;
+        }
+        Some(Suspension {
+            reason: self.suspension_reason.clone().unwrap_or_default(),
+            suspended_by: self.suspended_by,
+            suspended_at,
+            expires_at: self.suspended_until,
+        })
+    }
+}
+
+/// Link shown on a profile, such as a website or a GitHub account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ProfileLink {
+    pub label: String,
+    /// http(s) URL
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CustomStatus {
+    pub text: Option<String>,
+    pub emoji: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Setting values keyed by setting name (see `SettingsRegistry`).
+pub type SettingValues = serde_json::Map<String, serde_json::Value>;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 #[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct Setting {
     pub sub: Uuid,
-    pub theme: Option<String>,
-    pub lang: Option<String>,
+    /// Setting values, e.g. `"theme": "dark"`
+    #[serde(flatten)]
+    #[sqlx(rename = "settings", json)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
+    pub values: SettingValues,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Setting {
+    /// Settings of a user who has no stored settings row.
+    pub fn defaults(sub: Uuid) -> Self {
+        let now = Utc::now();
+        Self {
+            sub,
+            values: SettingValues::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&serde_json::Value> {
+        self.values.get(key)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct UserBasicInfo {
     pub sub: Uuid,
     pub display_name: String,
     pub profile_picture: String,
+    /// Signed URL of the banner image
+    pub banner: Option<String>,
+    pub accent_color: Option<String>,
+    pub pronouns: Option<String>,
+    /// Profile links, in the order chosen by the user
+    pub links: Vec<ProfileLink>,
     pub description: String,
+    pub status: UserStatus,
+    pub custom_status: Option<CustomStatus>,
 }
 
+/// Copy of a user already projected with [`User::visible_to`]. The banner
+/// URL has to be signed by the caller.
 impl From<User> for UserBasicInfo {
     fn from(user: User) -> Self {
         Self {
+            custom_status: user.custom_status(),
+            status: user.status,
             sub: user.sub,
             display_name: user.display_name,
             profile_picture: user.profile_picture,
+            banner: None,
+            accent_color: user.accent_color,
+            pronouns: user.pronouns,
+            links: user.links,
             description: user.description,
         }
     }
 }
 
+/// A user matching a search query, with its relevance score.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserSearchHit {
+    #[sqlx(flatten)]
+    pub user: User,
+    pub score: f32,
+}
+
+/// Position after the last returned search hit. Results are ordered by
+/// descending score, then descending sub.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SearchCursor {
+    pub score: f32,
+    pub sub: Uuid,
+}
+
+impl SearchCursor {
+    /// Position before the first result.
+    pub fn start() -> Self {
+        Self {
+            score: f32::MAX,
+            sub: Uuid::max(),
+        }
+    }
+}
+
+impl From<&UserSearchHit> for SearchCursor {
+    fn from(hit: &UserSearchHit) -> Self {
+        Self {
+            score: hit.score,
+            sub: hit.user.sub,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct UserSearchPage {
+    /// Matching users, best match first
+    pub users: Vec<UserBasicInfo>,
+    /// Cursor of the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Order of the users returned by a batch lookup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOrder {
+    /// Order in which the subs were requested
+    #[default]
+    Requested,
+    /// Ascending sub
+    Sub,
+}
+
+/// A user found by a batch lookup, with the 1-based position of its sub in
+/// the looked up list.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserBatchHit {
+    #[sqlx(flatten)]
+    pub user: User,
+    pub position: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct UserBatchPage {
+    /// Users found, in the requested order
+    pub users: Vec<UserBasicInfo>,
+    /// Subs covered by this page that match no visible user
+    pub not_found: Vec<Uuid>,
+    /// Cursor of the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct KeycloakUserInfo {
@@ -60,9 +288,38 @@ pub struct UserFullInfo {
     pub sub: Uuid,
     pub display_name: String,
     pub profile_picture: String,
+    pub banner: Option<String>,
+    pub accent_color: Option<String>,
+    pub pronouns: Option<String>,
+    pub links: Vec<ProfileLink>,
     pub description: String,
+    pub status: UserStatus,
+    pub custom_status: Option<CustomStatus>,
     pub username: String,
     pub email: String,
+    /// New email address waiting for verification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_email: Option<String>,
+}
+
+impl UserFullInfo {
+    pub fn new(basic: UserBasicInfo, keycloak_info: KeycloakUserInfo) -> Self {
+        Self {
+            sub: basic.sub,
+            display_name: basic.display_name,
+            profile_picture: basic.profile_picture,
+            banner: basic.banner,
+            accent_color: basic.accent_color,
+            pronouns: basic.pronouns,
+            links: basic.links,
+            description: basic.description,
+            status: basic.status,
+            custom_status: basic.custom_status,
+            username: keycloak_info.username,
+            email: keycloak_info.email,
+            pending_email: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,41 +337,173 @@ pub struct UpdateUserRequest {
     pub profile_picture: Option<String>,
     /// User description (stored in User Service Database)
     pub description: Option<String>,
+    /// Hex accent color such as `#5865f2`, empty to clear it
+    pub accent_color: Option<String>,
+    /// Pronouns, empty to clear them
+    pub pronouns: Option<String>,
+    /// Replaces all profile links, in order
+    pub links: Option<Vec<ProfileLink>>,
     /// Username (stored in Keycloak Database)
     pub username: Option<String>,
-    /// Email address (stored in Keycloak Database)
+    /// Email address (stored in Keycloak Database). Only applied once the
+    /// new address is verified through `POST /users/me/email/verify`
     pub email: Option<String>,
 }
 
 impl UpdateUserRequest {
     pub fn has_local_fields(&self) -> bool {
-        self.display_name.is_some() || self.profile_picture.is_some() || self.description.is_some()
+        self.display_name.is_some()
+            || self.profile_picture.is_some()
+            || self.description.is_some()
+            || self.accent_color.is_some()
+            || self.pronouns.is_some()
+            || self.links.is_some()
     }
 
     pub fn has_keycloak_fields(&self) -> bool {
         self.username.is_some() || self.email.is_some()
     }
+
+    /// The username and email of this request only.
+    pub fn keycloak_fields(&self) -> Self {
+        Self {
+            display_name: None,
+            profile_picture: None,
+            description: None,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+            username: self.username.clone(),
+            email: self.email.clone(),
+        }
+    }
+}
+
+/// Keycloak values left behind by an update whose local write failed and
+/// whose restore failed too.
+#[derive(Debug, Clone, FromRow)]
+pub struct KeycloakRepair {
+    pub id: i64,
+    pub sub: Uuid,
+    /// Values Keycloak held before the update
+    #[sqlx(json)]
+    pub restore: UpdateUserRequest,
+    /// Values the update wrote to Keycloak
+    #[sqlx(json)]
+    pub applied: UpdateUserRequest,
+}
+
+impl KeycloakRepair {
+    /// Whether Keycloak still holds the values the update wrote. Once they
+    /// changed again, restoring would undo a later change.
+    pub fn is_pending(&self, current: &KeycloakUserInfo) -> bool {
+        let unchanged = |applied: &Option<String>, current: &str| {
+            applied
+                .as_deref()
+                .is_none_or(|applied| applied.eq_ignore_ascii_case(current))
+        };
+        unchanged(&self.applied.username, &current.username)
+            && unchanged(&self.applied.email, &current.email)
+    }
+}
+
+/// Maximum length (in characters) of a custom status text.
+pub const MAX_CUSTOM_STATUS_TEXT_LEN: usize = 128;
+
+/// Maximum length (in characters) of a custom status emoji.
+pub const MAX_CUSTOM_STATUS_EMOJI_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct UpdateStatusRequest {
+    /// Presence status (online, idle, do_not_disturb, invisible)
+    pub status: UserStatus,
+    /// Custom status text, cleared when omitted
+    pub custom_text: Option<String>,
+    /// Custom status emoji, cleared when omitted
+    pub custom_emoji: Option<String>,
+    /// When the custom status should disappear (never if omitted)
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl UpdateStatusRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.status == UserStatus::Offline {
+            return Err("status 'offline' cannot be set, use 'invisible' instead".to_string());
+        }
+        if self
+            .custom_text
+            .as_ref()
+            .is_some_and(|text| text.chars().count() > MAX_CUSTOM_STATUS_TEXT_LEN)
+        {
+            return Err(format!(
+                "custom_text must be at most {} characters",
+                MAX_CUSTOM_STATUS_TEXT_LEN
+            ));
+        }
+        if self
+            .custom_emoji
+            .as_ref()
+            .is_some_and(|emoji| emoji.chars().count() > MAX_CUSTOM_STATUS_EMOJI_LEN)
+        {
+            return Err(format!(
+                "custom_emoji must be at most {} characters",
+                MAX_CUSTOM_STATUS_EMOJI_LEN
+            ));
+        }
+        if self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err("expires_at must be in the future".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct UpdateSettingRequest {
-    /// Theme preference (e.g., "dark", "light")
-    pub theme: Option<String>,
-    /// Language preference (e.g., "en", "fr")
-    pub lang: Option<String>,
+    /// Settings to change (e.g., `"theme": "dark"`); `null` resets a setting to its default
+    #[serde(flatten)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Object))]
+    pub values: SettingValues,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl UpdateSettingRequest {
+    /// Keys explicitly reset to their default with a `null` value.
+    pub fn reset_keys(&self) -> Vec<String> {
+        self.values
+            .iter()
+            .filter(|(_, value)| value.is_null())
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Values to store, without the reset keys.
+    pub fn changed_values(&self) -> SettingValues {
+        self.values
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// Upload intent of a new avatar or banner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct ProfilePictureRequest {
+    /// Signed URL to `PUT` the image to
     pub url: String,
+    /// Key to confirm once the upload is done
+    pub key: String,
+    /// The upload URL stops working after this date
+    pub expires_at: DateTime<Utc>,
 }
 
-impl ProfilePictureRequest {
-    pub fn new(url: String) -> Self {
-        Self { url }
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ConfirmProfilePictureRequest {
+    /// Key returned with the upload URL
+    pub key: String,
 }
 
 #[cfg(test)]
@@ -130,6 +519,9 @@ mod tests {
                 display_name: Some("John".to_string()),
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: None,
                 email: None,
             };
@@ -142,6 +534,9 @@ mod tests {
                 display_name: None,
                 profile_picture: Some("https://example.com/pic.jpg".to_string()),
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: None,
                 email: None,
             };
@@ -154,6 +549,24 @@ mod tests {
                 display_name: None,
                 profile_picture: None,
                 description: Some("A description".to_string()),
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            };
+            assert!(req.has_local_fields());
+        }
+
+        #[test]
+        fn has_local_fields_returns_true_when_links_are_set() {
+            let req = UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: Some(Vec::new()),
                 username: None,
                 email: None,
             };
@@ -166,6 +579,9 @@ mod tests {
                 display_name: None,
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: Some("john_doe".to_string()),
                 email: Some("john@example.com".to_string()),
             };
@@ -178,6 +594,9 @@ mod tests {
                 display_name: None,
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: None,
                 email: None,
             };
@@ -190,6 +609,9 @@ mod tests {
                 display_name: None,
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: Some("john_doe".to_string()),
                 email: None,
             };
@@ -202,6 +624,9 @@ mod tests {
                 display_name: None,
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: None,
                 email: Some("john@example.com".to_string()),
             };
@@ -214,6 +639,9 @@ mod tests {
                 display_name: Some("John".to_string()),
                 profile_picture: Some("https://example.com/pic.jpg".to_string()),
                 description: Some("A description".to_string()),
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: None,
                 email: None,
             };
@@ -226,6 +654,9 @@ mod tests {
                 display_name: None,
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: None,
                 email: None,
             };
@@ -238,6 +669,9 @@ mod tests {
                 display_name: Some("John".to_string()),
                 profile_picture: None,
                 description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
                 username: Some("john_doe".to_string()),
                 email: None,
             };
@@ -256,7 +690,16 @@ mod tests {
                 sub,
                 display_name: "John Doe".to_string(),
                 profile_picture: "https://example.com/pic.jpg".to_string(),
+                banner: None,
+                accent_color: Some("#5865f2".to_string()),
+                pronouns: Some("they/them".to_string()),
+                links: vec![ProfileLink {
+                    label: "Website".to_string(),
+                    url: "https://example.com".to_string(),
+                }],
                 description: "A developer".to_string(),
+                status: UserStatus::Idle,
+                custom_status: None,
             };
 
             let json = serde_json::to_string(&info).unwrap();
@@ -266,6 +709,9 @@ mod tests {
             assert_eq!(parsed.display_name, "John Doe");
             assert_eq!(parsed.profile_picture, "https://example.com/pic.jpg");
             assert_eq!(parsed.description, "A developer");
+            assert_eq!(parsed.accent_color.as_deref(), Some("#5865f2"));
+            assert_eq!(parsed.links, info.links);
+            assert_eq!(parsed.status, UserStatus::Idle);
         }
 
         #[test]
@@ -275,9 +721,16 @@ mod tests {
                 sub,
                 display_name: "John Doe".to_string(),
                 profile_picture: "https://example.com/pic.jpg".to_string(),
+                banner: None,
+                accent_color: None,
+                pronouns: None,
+                links: Vec::new(),
                 description: "A developer".to_string(),
+                status: UserStatus::Online,
+                custom_status: None,
                 username: "john_doe".to_string(),
                 email: "john@example.com".to_string(),
+                pending_email: None,
             };
 
             let json = serde_json::to_string(&info).unwrap();
@@ -302,11 +755,26 @@ mod tests {
 
         #[test]
         fn update_setting_request_deserializes_partial_json() {
-            let json = r#"{"theme": "dark"}"#;
+            let json = r#"{"theme": "dark", "lang": null}"#;
             let req: UpdateSettingRequest = serde_json::from_str(json).unwrap();
 
-            assert_eq!(req.theme, Some("dark".to_string()));
-            assert!(req.lang.is_none());
+            assert_eq!(req.values["theme"], "dark");
+            assert_eq!(req.changed_values().len(), 1);
+            assert_eq!(req.reset_keys(), vec!["lang".to_string()]);
+        }
+
+        #[test]
+        fn setting_serializes_values_at_top_level() {
+            let sub = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+            let mut setting = Setting::defaults(sub);
+            setting
+                .values
+                .insert("theme".to_string(), serde_json::json!("dark"));
+
+            let json = serde_json::to_value(&setting).unwrap();
+
+            assert_eq!(json["theme"], "dark");
+            assert_eq!(json["sub"], sub.to_string());
         }
 
         #[test]
@@ -323,4 +791,108 @@ mod tests {
             assert_eq!(parsed.email, "john@example.com");
         }
     }
+
+    mod presence {
+        use super::*;
+        use crate::models::Viewer;
+        use chrono::Duration;
+
+        fn create_user(status: UserStatus) -> User {
+            let now = Utc::now();
+            User {
+                sub: Uuid::new_v4(),
+                display_name: "John".to_string(),
+                profile_picture: String::new(),
+                avatar_key: None,
+                banner_key: None,
+                accent_color: None,
+                pronouns: None,
+                links: Vec::new(),
+                privacy: ProfilePrivacy::default(),
+                description: String::new(),
+                status,
+                custom_status_text: Some("Coding".to_string()),
+                custom_status_emoji: Some(":computer:".to_string()),
+                custom_status_expires_at: None,
+                deleted_at: None,
+                suspended_at: None,
+                suspended_until: None,
+                suspended_by: None,
+                suspension_reason: None,
+                suspension_disabled_keycloak: false,
+                created_at: now,
+                updated_at: now,
+            }
+        }
+
+        #[test]
+        fn invisible_user_appears_offline_to_others() {
+            let user = create_user(UserStatus::Invisible);
+            let info: UserBasicInfo = user.visible_to(Viewer::User(Uuid::new_v4())).into();
+            assert_eq!(info.status, UserStatus::Offline);
+        }
+
+        #[test]
+        fn invisible_user_sees_own_status() {
+            let user = create_user(UserStatus::Invisible);
+            let sub = user.sub;
+            let info: UserBasicInfo = user.visible_to(Viewer::User(sub)).into();
+            assert_eq!(info.status, UserStatus::Invisible);
+        }
+
+        #[test]
+        fn custom_status_is_hidden_once_expired() {
+            let mut user = create_user(UserStatus::Online);
+            user.custom_status_expires_at = Some(Utc::now() - Duration::minutes(1));
+            assert!(user.custom_status().is_none());
+        }
+
+        #[test]
+        fn custom_status_is_kept_before_expiry() {
+            let mut user = create_user(UserStatus::DoNotDisturb);
+            user.custom_status_expires_at = Some(Utc::now() + Duration::minutes(1));
+
+            let custom = user.custom_status().unwrap();
+            assert_eq!(custom.text.as_deref(), Some("Coding"));
+        }
+
+        #[test]
+        fn status_serializes_as_snake_case() {
+            let json = serde_json::to_string(&UserStatus::DoNotDisturb).unwrap();
+            assert_eq!(json, r#""do_not_disturb""#);
+        }
+
+        #[test]
+        fn update_status_request_rejects_offline() {
+            let req = UpdateStatusRequest {
+                status: UserStatus::Offline,
+                custom_text: None,
+                custom_emoji: None,
+                expires_at: None,
+            };
+            assert!(req.validate().is_err());
+        }
+
+        #[test]
+        fn update_status_request_rejects_past_expiry() {
+            let req = UpdateStatusRequest {
+                status: UserStatus::Online,
+                custom_text: Some("Lunch".to_string()),
+                custom_emoji: None,
+                expires_at: Some(Utc::now() - Duration::minutes(1)),
+            };
+            assert!(req.validate().is_err());
+        }
+
+        #[test]
+        fn update_status_request_rejects_long_text() {
+            let req = UpdateStatusRequest {
+                status: UserStatus::Idle,
+                custom_text: Some("a".repeat(MAX_CUSTOM_STATUS_TEXT_LEN + 1)),
+                custom_emoji: None,
+                expires_at: None,
+            };
+            assert!(req.validate().is_err());
+        }
+    }
 }
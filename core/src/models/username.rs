@@ -0,0 +1,14 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Username of a user, current or released and held until `held_until`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow)]
+pub struct UsernameClaim {
+    pub sub: Uuid,
+    pub username: String,
+    pub claimed_at: DateTime<Utc>,
+    pub released_at: Option<DateTime<Utc>>,
+    pub held_until: Option<DateTime<Utc>>,
+}
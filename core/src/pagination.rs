@@ -0,0 +1,82 @@
+use crate::error::CoreError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Encodes a pagination position as an opaque, URL-safe cursor.
+pub fn encode_cursor<T: Serialize>(position: &T) -> String {
+    // Serializing plain position structs cannot fail
+    let json = serde_json::to_vec(position).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+/// Decodes a cursor produced by `encode_cursor`.
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Result<T, CoreError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| CoreError::BadRequest("Invalid cursor".to_string()))
+}
+
+/// Turns items fetched after the current position into a page of at most
+/// `limit` items. Callers fetch `limit + 1` items: the extra one only tells
+/// that there is a next page, whose cursor is the position of the last item
+/// kept.
+pub fn paginate<T, P: Serialize>(
+    mut items: Vec<T>,
+    limit: usize,
+    position: impl Fn(&T) -> P,
+) -> (Vec<T>, Option<String>) {
+    if items.len() <= limit {
+        return (items, None);
+    }
+
+    items.truncate(limit);
+    let next_cursor = items.last().map(|item| encode_cursor(&position(item)));
+    (items, next_cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use uuid::Uuid;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        score: f32,
+        sub: Uuid,
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let position = Position {
+            score: 0.428_571_43,
+            sub: Uuid::new_v4(),
+        };
+
+        let cursor = encode_cursor(&position);
+
+        assert_eq!(decode_cursor::<Position>(&cursor).unwrap(), position);
+    }
+
+    #[test]
+    fn rejects_tampered_cursor() {
+        let result = decode_cursor::<Position>("not-a-cursor");
+
+        assert!(matches!(result, Err(CoreError::BadRequest(_))));
+    }
+
+    #[test]
+    fn paginate_keeps_limit_and_points_after_last_item() {
+        let (page, next_cursor) = paginate(vec![1, 2, 3], 2, |item| *item);
+        let (last_page, end) = paginate(vec![3], 2, |item| *item);
+
+        assert_eq!(page, vec![1, 2]);
+        assert_eq!(decode_cursor::<i32>(&next_cursor.unwrap()).unwrap(), 2);
+        assert_eq!(last_page, vec![3]);
+        assert!(end.is_none());
+    }
+}
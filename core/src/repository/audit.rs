@@ -0,0 +1,83 @@
+use crate::models::{AuditAction, AuditContext, AuditEntry, AuditLogQuery, FieldChange};
+use crate::repository::PostgresUserRepository;
+use sqlx::types::Json;
+use std::future::Future;
+use uuid::Uuid;
+
+pub trait AuditRepository: Send + Sync {
+    fn record_audit(
+        &self,
+        target: Uuid,
+        action: AuditAction,
+        changes: &[FieldChange],
+        context: &AuditContext,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Entries matching `query`, most recent first, with an id lower than
+    /// `before_id` when set.
+    fn list_audit_entries(
+        &self,
+        query: &AuditLogQuery,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<AuditEntry>, sqlx::Error>> + Send;
+}
+
+impl AuditRepository for PostgresUserRepository {
+    async fn record_audit(
+        &self,
+        target: Uuid,
+        action: AuditAction,
+        changes: &[FieldChange],
+        context: &AuditContext,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_audit_log (actor_sub, target_sub, action, changes, ip_address, user_agent)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(context.actor_sub)
+        .bind(target)
+        .bind(action)
+        .bind(Json(changes))
+        .bind(&context.ip_address)
+        .bind(&context.user_agent)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_audit_entries(
+        &self,
+        query: &AuditLogQuery,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            r#"
+            SELECT id, actor_sub, target_sub, action, changes, ip_address, user_agent, created_at
+            FROM user_audit_log
+            WHERE ($1::uuid IS NULL OR actor_sub = $1)
+              AND ($2::uuid IS NULL OR target_sub = $2)
+              AND ($3::audit_action IS NULL OR action = $3)
+              AND ($4::timestamptz IS NULL OR created_at >= $4)
+              AND ($5::timestamptz IS NULL OR created_at < $5)
+              AND ($6::bigint IS NULL OR id < $6)
+            ORDER BY id DESC
+            LIMIT $7
+            "#,
+        )
+        .bind(query.actor_sub)
+        .bind(query.target_sub)
+        .bind(query.action)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,203 @@
+use crate::models::{User, UserEventKind};
+use crate::repository::PostgresUserRepository;
+use crate::repository::user::USER_COLUMNS;
+use std::future::Future;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub trait AvatarRepository: Send + Sync {
+    /// Makes `key` the avatar of `sub` and clears the external picture URL.
+    /// The replaced avatar, if any, is queued for cleanup in the same
+    /// transaction.
+    fn set_avatar(
+        &self,
+        sub: Uuid,
+        key: &str,
+    ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    /// Makes `key` the banner of `sub`, or removes the banner when `None`.
+    /// The replaced banner is queued for cleanup like avatars.
+    fn set_banner(
+        &self,
+        sub: Uuid,
+        key: Option<&str>,
+    ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    /// Claims up to `limit` queued avatar keys, oldest first. Claimed keys are
+    /// hidden from other workers for `lease`.
+    fn claim_avatar_cleanups(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> impl Future<Output = Result<Vec<String>, sqlx::Error>> + Send;
+    fn complete_avatar_cleanup(
+        &self,
+        key: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    fn record_avatar_cleanup_failure(
+        &self,
+        key: &str,
+        error: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+}
+
+impl PostgresUserRepository {
+    /// Queues the image stored in `column` (`avatar_key` or `banner_key`)
+    /// of `sub` for cleanup unless it is `keep`, and locks the user row
+    /// until the end of the transaction. A kept key is taken off the queue,
+    /// so a key confirmed again survives its pending cleanup.
+    pub(crate) async fn queue_image_cleanup(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        sub: Uuid,
+        column: &str,
+        keep: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            r#"
+            WITH previous AS (
+                SELECT {column} AS object_key
+                FROM users
+                WHERE sub = $1
+                FOR UPDATE
+            )
+            INSERT INTO avatar_cleanups (object_key)
+            SELECT object_key
+            FROM previous
+            WHERE object_key IS NOT NULL AND object_key IS DISTINCT FROM $2
+            ON CONFLICT (object_key) DO NOTHING
+            "#
+        ))
+        .bind(sub)
+        .bind(keep)
+        .execute(&mut **tx)
+        .await?;
+
+        if let Some(keep) = keep {
+            sqlx::query("DELETE FROM avatar_cleanups WHERE object_key = $1")
+                .bind(keep)
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl AvatarRepository for PostgresUserRepository {
+    async fn set_avatar(&self, sub: Uuid, key: &str) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        Self::queue_image_cleanup(&mut tx, sub, "avatar_key", Some(key)).await?;
+
+        let user = sqlx::query_as::<_, User>(&format!(
+            r#"
+            UPDATE users
+            SET avatar_key = $2, profile_picture = '', updated_at = NOW()
+            WHERE sub = $1
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(key)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::AvatarUpdated {
+                avatar_key: key.to_string(),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn set_banner(&self, sub: Uuid, key: Option<&str>) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        Self::queue_image_cleanup(&mut tx, sub, "banner_key", key).await?;
+
+        let user = sqlx::query_as::<_, User>(&format!(
+            r#"
+            UPDATE users
+            SET banner_key = $2, updated_at = NOW()
+            WHERE sub = $1
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(key)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::BannerUpdated {
+                banner_key: key.map(str::to_string),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn claim_avatar_cleanups(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let keys = sqlx::query_scalar::<_, String>(
+            r#"
+            UPDATE avatar_cleanups
+            SET locked_until = NOW() + make_interval(secs => $2)
+            WHERE object_key IN (
+                SELECT object_key
+                FROM avatar_cleanups
+                WHERE locked_until IS NULL OR locked_until < NOW()
+                ORDER BY queued_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING object_key
+            "#,
+        )
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    async fn complete_avatar_cleanup(&self, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM avatar_cleanups WHERE object_key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_avatar_cleanup_failure(
+        &self,
+        key: &str,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE avatar_cleanups
+            SET attempts = attempts + 1, last_error = $2
+            WHERE object_key = $1
+            "#,
+        )
+        .bind(key)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use crate::models::{DeletionStep, UserDeletion, UserEventKind};
+use crate::repository::PostgresUserRepository;
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Columns selected whenever a `UserDeletion` row is returned.
+const DELETION_COLUMNS: &str = "sub, requested_at, purge_after, avatar_deleted_at, \
+    keycloak_deleted_at, anonymized_at, attempts, last_error";
+
+/// Display name left on purged accounts.
+pub const DELETED_USER_DISPLAY_NAME: &str = "Deleted User";
+
+pub trait DeletionRepository: Send + Sync {
+    /// Soft-deletes the user and schedules the purge. Requesting it again
+    /// returns the existing schedule.
+    fn schedule_deletion(
+        &self,
+        sub: Uuid,
+        purge_after: DateTime<Utc>,
+    ) -> impl Future<Output = Result<UserDeletion, sqlx::Error>> + Send;
+    fn get_deletion(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<Option<UserDeletion>, sqlx::Error>> + Send;
+    /// Restores a soft-deleted user whose purge has not started yet.
+    /// Returns whether a deletion was cancelled.
+    fn cancel_deletion(&self, sub: Uuid) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+    /// Claims up to `limit` deletions whose grace period is over and that are
+    /// not fully purged. Claimed deletions are hidden from other workers for
+    /// `lease`.
+    fn claim_due_deletions(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> impl Future<Output = Result<Vec<UserDeletion>, sqlx::Error>> + Send;
+    fn mark_deletion_step(
+        &self,
+        sub: Uuid,
+        step: DeletionStep,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    fn record_deletion_failure(
+        &self,
+        sub: Uuid,
+        error: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Final purge step: anonymizes the profile, drops settings,
+    /// relationships and data exports, and publishes `UserDeleted`, in one
+    /// transaction.
+    fn anonymize_user(&self, sub: Uuid) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+}
+
+impl DeletionRepository for PostgresUserRepository {
+    async fn schedule_deletion(
+        &self,
+        sub: Uuid,
+        purge_after: DateTime<Utc>,
+    ) -> Result<UserDeletion, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET deleted_at = NOW(), updated_at = NOW()
+            WHERE sub = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(sub)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_deletions (sub, purge_after)
+            VALUES ($1, $2)
+            ON CONFLICT (sub) DO NOTHING
+            "#,
+        )
+        .bind(sub)
+        .bind(purge_after)
+        .execute(&mut *tx)
+        .await?;
+
+        let deletion = sqlx::query_as::<_, UserDeletion>(&format!(
+            r#"
+            SELECT {DELETION_COLUMNS}
+            FROM user_deletions
+            WHERE sub = $1
+            "#
+        ))
+        .bind(sub)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(deletion)
+    }
+
+    async fn get_deletion(&self, sub: Uuid) -> Result<Option<UserDeletion>, sqlx::Error> {
+        let deletion = sqlx::query_as::<_, UserDeletion>(&format!(
+            r#"
+            SELECT {DELETION_COLUMNS}
+            FROM user_deletions
+            WHERE sub = $1
+            "#
+        ))
+        .bind(sub)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(deletion)
+    }
+
+    async fn cancel_deletion(&self, sub: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let cancelled = sqlx::query(
+            r#"
+            DELETE FROM user_deletions
+            WHERE sub = $1
+              AND avatar_deleted_at IS NULL
+              AND keycloak_deleted_at IS NULL
+              AND anonymized_at IS NULL
+            "#,
+        )
+        .bind(sub)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if cancelled {
+            sqlx::query(
+                r#"
+                UPDATE users
+                SET deleted_at = NULL, updated_at = NOW()
+                WHERE sub = $1
+                "#,
+            )
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(cancelled)
+    }
+
+    async fn claim_due_deletions(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<UserDeletion>, sqlx::Error> {
+        let deletions = sqlx::query_as::<_, UserDeletion>(&format!(
+            r#"
+            UPDATE user_deletions
+            SET locked_until = NOW() + make_interval(secs => $2)
+            WHERE sub IN (
+                SELECT sub
+                FROM user_deletions
+                WHERE anonymized_at IS NULL
+                  AND purge_after <= NOW()
+                  AND (locked_until IS NULL OR locked_until < NOW())
+                ORDER BY purge_after
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {DELETION_COLUMNS}
+            "#
+        ))
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deletions)
+    }
+
+    async fn mark_deletion_step(&self, sub: Uuid, step: DeletionStep) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            r#"
+            UPDATE user_deletions
+            SET {column} = COALESCE({column}, NOW())
+            WHERE sub = $1
+            "#,
+            column = step.column()
+        ))
+        .bind(sub)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_deletion_failure(&self, sub: Uuid, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE user_deletions
+            SET attempts = attempts + 1, last_error = $2
+            WHERE sub = $1
+            "#,
+        )
+        .bind(sub)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn anonymize_user(&self, sub: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let anonymized = sqlx::query(
+            r#"
+            UPDATE user_deletions
+            SET anonymized_at = NOW(), last_error = NULL, locked_until = NULL
+            WHERE sub = $1 AND anonymized_at IS NULL
+            "#,
+        )
+        .bind(sub)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        // Already purged by a previous run
+        if !anonymized {
+            tx.rollback().await?;
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET display_name = $2,
+                description = '',
+                profile_picture = '',
+                avatar_key = NULL,
+                banner_key = NULL,
+                accent_color = NULL,
+                pronouns = NULL,
+                links = '[]',
+                privacy = '{}',
+                status = 'online',
+                custom_status_text = NULL,
+                custom_status_emoji = NULL,
+                custom_status_expires_at = NULL,
+                suspension_reason = NULL,
+                suspended_by = NULL,
+                deleted_at = COALESCE(deleted_at, NOW()),
+                updated_at = NOW()
+            WHERE sub = $1
+            "#,
+        )
+        .bind(sub)
+        .bind(DELETED_USER_DISPLAY_NAME)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM param WHERE sub = $1")
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM relationships WHERE user_sub = $1 OR target_sub = $1")
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM data_exports WHERE sub = $1")
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM email_changes WHERE sub = $1")
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM username_history WHERE sub = $1")
+            .bind(sub)
+            .execute(&mut *tx)
+            .await?;
+
+        // Audit entries stay for accountability, without the values of the
+        // user's changes nor where any change involving the user came from.
+        // The append-only trigger lets this transaction through
+        sqlx::query("SELECT set_config('user_service.audit_redaction', 'on', true)")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE user_audit_log
+            SET changes = CASE
+                    WHEN target_sub = $1 THEN (
+                        SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                            'field', change->'field', 'before', NULL, 'after', NULL
+                        )), '[]')
+                        FROM jsonb_array_elements(changes) AS change
+                    )
+                    ELSE changes
+                END,
+                ip_address = NULL,
+                user_agent = NULL
+            WHERE target_sub = $1 OR actor_sub = $1
+            "#,
+        )
+        .bind(sub)
+        .execute(&mut *tx)
+        .await?;
+
+        Self::insert_event(&mut tx, sub, &UserEventKind::UserDeleted).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
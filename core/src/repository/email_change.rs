@@ -0,0 +1,104 @@
+use crate::models::{EmailMessage, PendingEmailChange};
+use crate::repository::PostgresUserRepository;
+use std::future::Future;
+use uuid::Uuid;
+
+pub trait EmailChangeRepository: Send + Sync {
+    /// Records `change`, replacing any pending change of the same user, and
+    /// queues `verification` in the same transaction.
+    fn request_email_change(
+        &self,
+        change: &PendingEmailChange,
+        verification: &EmailMessage,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    fn get_email_change(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<Option<PendingEmailChange>, sqlx::Error>> + Send;
+    /// Removes the pending change carrying `nonce` and queues `notice`.
+    /// Returns whether the change was still pending.
+    fn complete_email_change(
+        &self,
+        sub: Uuid,
+        nonce: Uuid,
+        notice: &EmailMessage,
+    ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+}
+
+impl EmailChangeRepository for PostgresUserRepository {
+    async fn request_email_change(
+        &self,
+        change: &PendingEmailChange,
+        verification: &EmailMessage,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO email_changes (sub, old_email, new_email, nonce, requested_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (sub) DO UPDATE
+            SET old_email = EXCLUDED.old_email,
+                new_email = EXCLUDED.new_email,
+                nonce = EXCLUDED.nonce,
+                requested_at = EXCLUDED.requested_at,
+                expires_at = EXCLUDED.expires_at
+            "#,
+        )
+        .bind(change.sub)
+        .bind(&change.old_email)
+        .bind(&change.new_email)
+        .bind(change.nonce)
+        .bind(change.requested_at)
+        .bind(change.expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        Self::insert_email(&mut tx, verification).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_email_change(&self, sub: Uuid) -> Result<Option<PendingEmailChange>, sqlx::Error> {
+        let change = sqlx::query_as::<_, PendingEmailChange>(
+            r#"
+            SELECT sub, old_email, new_email, nonce, requested_at, expires_at
+            FROM email_changes
+            WHERE sub = $1
+            "#,
+        )
+        .bind(sub)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(change)
+    }
+
+    async fn complete_email_change(
+        &self,
+        sub: Uuid,
+        nonce: Uuid,
+        notice: &EmailMessage,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let completed = sqlx::query("DELETE FROM email_changes WHERE sub = $1 AND nonce = $2")
+            .bind(sub)
+            .bind(nonce)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+            > 0;
+
+        if !completed {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        Self::insert_email(&mut tx, notice).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
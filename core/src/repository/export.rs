@@ -0,0 +1,206 @@
+use crate::models::DataExport;
+use crate::repository::PostgresUserRepository;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::types::Json;
+use std::future::Future;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Columns selected whenever a `DataExport` row is returned.
+const EXPORT_COLUMNS: &str = "id, sub, status, error, created_at, completed_at, expires_at";
+
+pub trait ExportRepository: Send + Sync {
+    /// Queues an export for `sub`. If one is already pending or running, it is
+    /// returned instead.
+    fn create_export(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<DataExport, sqlx::Error>> + Send;
+    fn get_export(
+        &self,
+        sub: Uuid,
+        id: Uuid,
+    ) -> impl Future<Output = Result<Option<DataExport>, sqlx::Error>> + Send;
+    /// Claims up to `limit` queued exports, oldest first, and marks them as
+    /// running. Claimed exports are handed out again after `lease`.
+    fn claim_pending_exports(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> impl Future<Output = Result<Vec<DataExport>, sqlx::Error>> + Send;
+    fn complete_export(
+        &self,
+        id: Uuid,
+        document: &Value,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    fn fail_export(
+        &self,
+        id: Uuid,
+        error: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Document of a completed export that has not expired yet.
+    fn get_export_document(
+        &self,
+        sub: Uuid,
+        id: Uuid,
+    ) -> impl Future<Output = Result<Option<Value>, sqlx::Error>> + Send;
+    /// Drops the documents of exports past their retention period. Returns the
+    /// number of exports expired.
+    fn expire_exports(&self) -> impl Future<Output = Result<u64, sqlx::Error>> + Send;
+}
+
+impl ExportRepository for PostgresUserRepository {
+    async fn create_export(&self, sub: Uuid) -> Result<DataExport, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let created = sqlx::query_as::<_, DataExport>(&format!(
+            r#"
+            INSERT INTO data_exports (id, sub)
+            VALUES ($1, $2)
+            ON CONFLICT (sub) WHERE status IN ('pending', 'running') DO NOTHING
+            RETURNING {EXPORT_COLUMNS}
+            "#
+        ))
+        .bind(Uuid::new_v4())
+        .bind(sub)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let export = match created {
+            Some(export) => export,
+            None => {
+                sqlx::query_as::<_, DataExport>(&format!(
+                    r#"
+                    SELECT {EXPORT_COLUMNS}
+                    FROM data_exports
+                    WHERE sub = $1 AND status IN ('pending', 'running')
+                    "#
+                ))
+                .bind(sub)
+                .fetch_one(&mut *tx)
+                .await?
+            }
+        };
+
+        tx.commit().await?;
+
+        Ok(export)
+    }
+
+    async fn get_export(&self, sub: Uuid, id: Uuid) -> Result<Option<DataExport>, sqlx::Error> {
+        let export = sqlx::query_as::<_, DataExport>(&format!(
+            r#"
+            SELECT {EXPORT_COLUMNS}
+            FROM data_exports
+            WHERE id = $1 AND sub = $2
+            "#
+        ))
+        .bind(id)
+        .bind(sub)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(export)
+    }
+
+    async fn claim_pending_exports(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<DataExport>, sqlx::Error> {
+        let exports = sqlx::query_as::<_, DataExport>(&format!(
+            r#"
+            UPDATE data_exports
+            SET status = 'running', locked_until = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM data_exports
+                WHERE status IN ('pending', 'running')
+                  AND (locked_until IS NULL OR locked_until < NOW())
+                ORDER BY created_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {EXPORT_COLUMNS}
+            "#
+        ))
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(exports)
+    }
+
+    async fn complete_export(
+        &self,
+        id: Uuid,
+        document: &Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'completed', document = $2, completed_at = NOW(), expires_at = $3,
+                locked_until = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(Json(document))
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fail_export(&self, id: Uuid, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'failed', error = $2, completed_at = NOW(), locked_until = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_export_document(&self, sub: Uuid, id: Uuid) -> Result<Option<Value>, sqlx::Error> {
+        let document = sqlx::query_scalar::<_, Json<Value>>(
+            r#"
+            SELECT document
+            FROM data_exports
+            WHERE id = $1 AND sub = $2 AND status = 'completed' AND expires_at > NOW()
+            "#,
+        )
+        .bind(id)
+        .bind(sub)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(document.map(|Json(document)| document))
+    }
+
+    async fn expire_exports(&self) -> Result<u64, sqlx::Error> {
+        let expired = sqlx::query(
+            r#"
+            UPDATE data_exports
+            SET status = 'expired', document = NULL
+            WHERE status = 'completed' AND expires_at <= NOW()
+            "#,
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(expired)
+    }
+}
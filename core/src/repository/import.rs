@@ -0,0 +1,126 @@
+use crate::models::{ImportBatchResult, ImportedUser, UserEventKind};
+use crate::repository::PostgresUserRepository;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::future::Future;
+use uuid::Uuid;
+
+pub trait ImportRepository: Send + Sync {
+    /// Creates or updates `users` in one transaction, publishing `UserCreated`
+    /// or `ProfileUpdated` for each of them. Deleted users are left untouched.
+    /// Each sub must appear at most once.
+    fn upsert_imported_users(
+        &self,
+        users: &[ImportedUser],
+    ) -> impl Future<Output = Result<ImportBatchResult, sqlx::Error>> + Send;
+}
+
+impl ImportRepository for PostgresUserRepository {
+    async fn upsert_imported_users(
+        &self,
+        users: &[ImportedUser],
+    ) -> Result<ImportBatchResult, sqlx::Error> {
+        let subs: Vec<Uuid> = users.iter().map(|u| u.sub).collect();
+        let display_names: Vec<&str> = users.iter().map(|u| u.display_name.as_str()).collect();
+        let descriptions: Vec<Option<&str>> =
+            users.iter().map(|u| u.description.as_deref()).collect();
+        let profile_pictures: Vec<Option<&str>> =
+            users.iter().map(|u| u.profile_picture.as_deref()).collect();
+
+        let mut tx = self.pool.begin().await?;
+
+        let updated: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            UPDATE users u
+            SET display_name = i.display_name,
+                description = COALESCE(i.description, u.description),
+                profile_picture = COALESCE(i.profile_picture, u.profile_picture),
+                updated_at = NOW()
+            FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[])
+                AS i(sub, display_name, description, profile_picture)
+            WHERE u.sub = i.sub AND u.deleted_at IS NULL
+            RETURNING u.sub
+            "#,
+        )
+        .bind(&subs)
+        .bind(&display_names)
+        .bind(&descriptions)
+        .bind(&profile_pictures)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let inserted: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            INSERT INTO users (sub, display_name, description, profile_picture)
+            SELECT sub, display_name, COALESCE(description, ''), COALESCE(profile_picture, '')
+            FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[])
+                AS i(sub, display_name, description, profile_picture)
+            ON CONFLICT (sub) DO NOTHING
+            RETURNING sub
+            "#,
+        )
+        .bind(&subs)
+        .bind(&display_names)
+        .bind(&descriptions)
+        .bind(&profile_pictures)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO param (sub)
+            SELECT UNNEST($1::uuid[])
+            ON CONFLICT (sub) DO NOTHING
+            "#,
+        )
+        .bind(&inserted)
+        .execute(&mut *tx)
+        .await?;
+
+        let inserted: HashSet<Uuid> = inserted.into_iter().collect();
+        let updated: HashSet<Uuid> = updated.into_iter().collect();
+        let mut event_subs = Vec::with_capacity(users.len());
+        let mut event_types = Vec::with_capacity(users.len());
+        let mut payloads = Vec::with_capacity(users.len());
+        for user in users {
+            let kind = if inserted.contains(&user.sub) {
+                UserEventKind::UserCreated {
+                    display_name: user.display_name.clone(),
+                }
+            } else if updated.contains(&user.sub) {
+                UserEventKind::ProfileUpdated {
+                    display_name: Some(user.display_name.clone()),
+                    profile_picture: user.profile_picture.clone(),
+                    description: user.description.clone(),
+                    accent_color: None,
+                    pronouns: None,
+                    links: None,
+                }
+            } else {
+                continue;
+            };
+            event_subs.push(user.sub);
+            event_types.push(kind.event_type());
+            payloads.push(serde_json::to_value(&kind).unwrap_or(Value::Null));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_events (sub, event_type, payload)
+            SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::jsonb[])
+            "#,
+        )
+        .bind(&event_subs)
+        .bind(&event_types)
+        .bind(&payloads)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(ImportBatchResult {
+            inserted: inserted.len() as u64,
+            updated: updated.len() as u64,
+        })
+    }
+}
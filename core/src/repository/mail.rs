@@ -0,0 +1,87 @@
+use crate::models::{EmailMessage, OutgoingEmail};
+use crate::repository::PostgresUserRepository;
+use std::future::Future;
+use std::time::Duration;
+
+/// Read side of the email outbox. Emails are queued in the same transaction
+/// as the change they announce.
+pub trait MailOutboxRepository: Send + Sync {
+    /// Claims up to `limit` queued emails, oldest first. Claimed emails are
+    /// hidden from other dispatchers for `lease`.
+    fn claim_pending_emails(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> impl Future<Output = Result<Vec<OutgoingEmail>, sqlx::Error>> + Send;
+    /// Drops sent emails, which hold addresses and tokens, from the outbox.
+    fn delete_sent_emails(
+        &self,
+        ids: &[i64],
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+}
+
+impl PostgresUserRepository {
+    /// Queues `message` as part of the caller's transaction.
+    pub(crate) async fn insert_email(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        message: &EmailMessage,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO outgoing_emails (recipient, subject, body)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(&message.recipient)
+        .bind(&message.subject)
+        .bind(&message.body)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl MailOutboxRepository for PostgresUserRepository {
+    async fn claim_pending_emails(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<OutgoingEmail>, sqlx::Error> {
+        let mut emails = sqlx::query_as::<_, OutgoingEmail>(
+            r#"
+            UPDATE outgoing_emails
+            SET locked_until = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM outgoing_emails
+                WHERE locked_until IS NULL OR locked_until < NOW()
+                ORDER BY id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, body
+            "#,
+        )
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+
+        emails.sort_by_key(|email| email.id);
+        Ok(emails)
+    }
+
+    async fn delete_sent_emails(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query("DELETE FROM outgoing_emails WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
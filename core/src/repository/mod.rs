@@ -1,3 +1,25 @@
+pub mod audit;
+pub mod avatar;
+pub mod deletion;
+pub mod email_change;
+pub mod export;
+pub mod import;
+pub mod mail;
+pub mod outbox;
+pub mod relationship;
+pub mod suspension;
 pub mod user;
+pub mod username;
 
+pub use audit::AuditRepository;
+pub use avatar::AvatarRepository;
+pub use deletion::{DELETED_USER_DISPLAY_NAME, DeletionRepository};
+pub use email_change::EmailChangeRepository;
+pub use export::ExportRepository;
+pub use import::ImportRepository;
+pub use mail::MailOutboxRepository;
+pub use outbox::OutboxRepository;
+pub use relationship::RelationshipRepository;
+pub use suspension::SuspensionRepository;
 pub use user::{PostgresUserRepository, UserRepository};
+pub use username::UsernameRepository;
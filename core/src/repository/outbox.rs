@@ -0,0 +1,90 @@
+use crate::models::UserEvent;
+use crate::repository::PostgresUserRepository;
+use std::future::Future;
+use std::time::Duration;
+
+/// Read side of the domain event outbox. Events are written by
+/// `UserRepository` in the same transaction as the change they describe.
+pub trait OutboxRepository: Send + Sync {
+    /// Claims up to `limit` undelivered events, oldest first. Claimed events are
+    /// hidden from other dispatchers for `lease`, after which they are handed
+    /// out again unless marked as dispatched. Events of a sub whose older
+    /// events are claimed by another dispatcher are left for later.
+    fn claim_pending_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> impl Future<Output = Result<Vec<UserEvent>, sqlx::Error>> + Send;
+    fn mark_events_dispatched(
+        &self,
+        ids: &[i64],
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+}
+
+impl OutboxRepository for PostgresUserRepository {
+    async fn claim_pending_events(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<UserEvent>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Claims are serialized so that each one sees the leases taken by
+        // the others, which a row lock skipped by a concurrent claim hides
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('user_events_claim'))")
+            .execute(&mut *tx)
+            .await?;
+
+        let mut events = sqlx::query_as::<_, UserEvent>(
+            r#"
+            UPDATE user_events
+            SET locked_until = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM user_events e
+                WHERE dispatched_at IS NULL
+                  AND (locked_until IS NULL OR locked_until < NOW())
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM user_events older
+                      WHERE older.sub = e.sub
+                        AND older.id < e.id
+                        AND older.dispatched_at IS NULL
+                        AND older.locked_until >= NOW()
+                  )
+                ORDER BY id
+                LIMIT $1
+            )
+            RETURNING id, sub, event_type, payload, created_at
+            "#,
+        )
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        // RETURNING does not preserve the subquery order
+        events.sort_by_key(|event| event.id);
+        Ok(events)
+    }
+
+    async fn mark_events_dispatched(&self, ids: &[i64]) -> Result<(), sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE user_events
+            SET dispatched_at = NOW(), locked_until = NULL
+            WHERE id = ANY($1)
+            "#,
+        )
+        .bind(ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
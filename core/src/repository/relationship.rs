@@ -0,0 +1,257 @@
+use crate::models::{Relationship, RelationshipKind};
+use crate::repository::PostgresUserRepository;
+use std::future::Future;
+use uuid::Uuid;
+
+/// Columns selected whenever a `Relationship` row is returned.
+const RELATIONSHIP_COLUMNS: &str = "user_sub, target_sub, kind, created_at, updated_at";
+
+pub trait RelationshipRepository: Send + Sync {
+    /// Relationship of `sub` towards `target`, as seen from `sub`'s side.
+    fn get_relationship(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<Option<Relationship>, sqlx::Error>> + Send;
+    fn list_relationships(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<Vec<Relationship>, sqlx::Error>> + Send;
+    /// Records a pending friend request from `sub` to `target` on both sides.
+    fn create_friend_request(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<Relationship, sqlx::Error>> + Send;
+    /// Turns the pending request between both users into a friendship.
+    fn accept_friend_request(
+        &self,
+        sub: Uuid,
+        requester: Uuid,
+    ) -> impl Future<Output = Result<Relationship, sqlx::Error>> + Send;
+    /// Removes a pending request or friendship between both users, on both
+    /// sides. Blocks are left untouched.
+    fn delete_relationship(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Blocks `target`, dropping any friendship or pending request between
+    /// both users.
+    fn block_user(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<Relationship, sqlx::Error>> + Send;
+    fn unblock_user(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Users among `subs` who have blocked `viewer`.
+    fn get_blockers(
+        &self,
+        viewer: Uuid,
+        subs: &[Uuid],
+    ) -> impl Future<Output = Result<Vec<Uuid>, sqlx::Error>> + Send;
+}
+
+impl PostgresUserRepository {
+    async fn delete_pair(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        sub: Uuid,
+        target: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM relationships
+            WHERE ((user_sub = $1 AND target_sub = $2) OR (user_sub = $2 AND target_sub = $1))
+              AND kind <> 'blocked'
+            "#,
+        )
+        .bind(sub)
+        .bind(target)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl RelationshipRepository for PostgresUserRepository {
+    async fn get_relationship(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> Result<Option<Relationship>, sqlx::Error> {
+        let relationship = sqlx::query_as::<_, Relationship>(&format!(
+            r#"
+            SELECT {RELATIONSHIP_COLUMNS}
+            FROM relationships
+            WHERE user_sub = $1 AND target_sub = $2
+            "#
+        ))
+        .bind(sub)
+        .bind(target)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(relationship)
+    }
+
+    async fn list_relationships(&self, sub: Uuid) -> Result<Vec<Relationship>, sqlx::Error> {
+        let relationships = sqlx::query_as::<_, Relationship>(&format!(
+            r#"
+            SELECT {RELATIONSHIP_COLUMNS}
+            FROM relationships
+            WHERE user_sub = $1
+            ORDER BY updated_at DESC
+            "#
+        ))
+        .bind(sub)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(relationships)
+    }
+
+    async fn create_friend_request(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> Result<Relationship, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let relationship = sqlx::query_as::<_, Relationship>(&format!(
+            r#"
+            INSERT INTO relationships (user_sub, target_sub, kind)
+            VALUES ($1, $2, $3)
+            RETURNING {RELATIONSHIP_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(target)
+        .bind(RelationshipKind::PendingOutgoing)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO relationships (user_sub, target_sub, kind)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(target)
+        .bind(sub)
+        .bind(RelationshipKind::PendingIncoming)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(relationship)
+    }
+
+    async fn accept_friend_request(
+        &self,
+        sub: Uuid,
+        requester: Uuid,
+    ) -> Result<Relationship, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let relationship = sqlx::query_as::<_, Relationship>(&format!(
+            r#"
+            UPDATE relationships
+            SET kind = 'friend', updated_at = NOW()
+            WHERE user_sub = $1 AND target_sub = $2 AND kind = 'pending_incoming'
+            RETURNING {RELATIONSHIP_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(requester)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE relationships
+            SET kind = 'friend', updated_at = NOW()
+            WHERE user_sub = $2 AND target_sub = $1 AND kind = 'pending_outgoing'
+            "#,
+        )
+        .bind(sub)
+        .bind(requester)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(relationship)
+    }
+
+    async fn delete_relationship(&self, sub: Uuid, target: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::delete_pair(&mut tx, sub, target).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn block_user(&self, sub: Uuid, target: Uuid) -> Result<Relationship, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        Self::delete_pair(&mut tx, sub, target).await?;
+        let relationship = sqlx::query_as::<_, Relationship>(&format!(
+            r#"
+            INSERT INTO relationships (user_sub, target_sub, kind)
+            VALUES ($1, $2, 'blocked')
+            ON CONFLICT (user_sub, target_sub) DO UPDATE
+            SET kind = 'blocked', updated_at = NOW()
+            RETURNING {RELATIONSHIP_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(target)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(relationship)
+    }
+
+    async fn unblock_user(&self, sub: Uuid, target: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM relationships
+            WHERE user_sub = $1 AND target_sub = $2 AND kind = 'blocked'
+            "#,
+        )
+        .bind(sub)
+        .bind(target)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_blockers(&self, viewer: Uuid, subs: &[Uuid]) -> Result<Vec<Uuid>, sqlx::Error> {
+        if subs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let blockers = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT user_sub
+            FROM relationships
+            WHERE target_sub = $1 AND user_sub = ANY($2) AND kind = 'blocked'
+            "#,
+        )
+        .bind(viewer)
+        .bind(subs)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(blockers)
+    }
+}
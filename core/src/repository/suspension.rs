@@ -0,0 +1,145 @@
+use crate::models::{Suspension, User, UserEventKind};
+use crate::repository::PostgresUserRepository;
+use crate::repository::user::USER_COLUMNS;
+use std::future::Future;
+use uuid::Uuid;
+
+pub trait SuspensionRepository: Send + Sync {
+    /// Records `suspension`, replacing any previous one.
+    fn suspend_user(
+        &self,
+        sub: Uuid,
+        suspension: &Suspension,
+        disabled_keycloak: bool,
+    ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    fn lift_suspension(&self, sub: Uuid) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    /// Up to `limit` users whose suspension has expired but is still recorded,
+    /// oldest expiry first.
+    fn list_expired_suspensions(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<User>, sqlx::Error>> + Send;
+    /// Lifts the suspension of `sub` if it has expired. Returns whether it was
+    /// lifted, which is not the case if the user has been suspended again.
+    fn lift_expired_suspension(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+}
+
+impl SuspensionRepository for PostgresUserRepository {
+    async fn suspend_user(
+        &self,
+        sub: Uuid,
+        suspension: &Suspension,
+        disabled_keycloak: bool,
+    ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as::<_, User>(&format!(
+            r#"
+            UPDATE users
+            SET suspended_at = $2,
+                suspended_until = $3,
+                suspended_by = $4,
+                suspension_reason = $5,
+                suspension_disabled_keycloak = $6,
+                updated_at = NOW()
+            WHERE sub = $1
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(suspension.suspended_at)
+        .bind(suspension.expires_at)
+        .bind(suspension.suspended_by)
+        .bind(&suspension.reason)
+        .bind(disabled_keycloak)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::UserSuspended {
+                expires_at: suspension.expires_at,
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn lift_suspension(&self, sub: Uuid) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as::<_, User>(&format!(
+            r#"
+            UPDATE users
+            SET suspended_at = NULL,
+                suspended_until = NULL,
+                suspended_by = NULL,
+                suspension_reason = NULL,
+                suspension_disabled_keycloak = FALSE,
+                updated_at = NOW()
+            WHERE sub = $1
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_event(&mut tx, sub, &UserEventKind::SuspensionLifted).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn list_expired_suspensions(&self, limit: i64) -> Result<Vec<User>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(&format!(
+            r#"
+            SELECT {USER_COLUMNS}
+            FROM users
+            WHERE suspended_at IS NOT NULL AND suspended_until <= NOW()
+            ORDER BY suspended_until
+            LIMIT $1
+            "#
+        ))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn lift_expired_suspension(&self, sub: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let lifted = sqlx::query(
+            r#"
+            UPDATE users
+            SET suspended_at = NULL,
+                suspended_until = NULL,
+                suspended_by = NULL,
+                suspension_reason = NULL,
+                suspension_disabled_keycloak = FALSE,
+                updated_at = NOW()
+            WHERE sub = $1 AND suspended_at IS NOT NULL AND suspended_until <= NOW()
+            "#,
+        )
+        .bind(sub)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if lifted {
+            Self::insert_event(&mut tx, sub, &UserEventKind::SuspensionLifted).await?;
+        }
+        tx.commit().await?;
+
+        Ok(lifted)
+    }
+}
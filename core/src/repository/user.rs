@@ -1,8 +1,19 @@
-use crate::models::{Setting, UpdateSettingRequest, UpdateUserRequest, User};
+use crate::models::{
+    KeycloakRepair, ProfilePrivacy, SearchCursor, Setting, UpdateSettingRequest,
+    UpdateStatusRequest, UpdateUserRequest, User, UserBatchHit, UserEventKind, UserSearchHit,
+};
+use sqlx::types::Json;
 use sqlx::PgPool;
 use std::future::Future;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Columns selected whenever a `User` row is returned.
+pub(crate) const USER_COLUMNS: &str = "sub, display_name, profile_picture, avatar_key, \
+    banner_key, accent_color, pronouns, links, privacy, description, status, custom_status_text, \
+    custom_status_emoji, custom_status_expires_at, deleted_at, suspended_at, suspended_until, \
+    suspended_by, suspension_reason, suspension_disabled_keycloak, created_at, updated_at";
+
 pub trait UserRepository: Send + Sync {
     fn create_user(
         &self,
@@ -17,16 +28,79 @@ pub trait UserRepository: Send + Sync {
         &self,
         subs: &[Uuid],
     ) -> impl Future<Output = Result<Vec<User>, sqlx::Error>> + Send;
+    /// Users whose sub is in `subs`, in the order of `subs` and strictly
+    /// after position `after`. Deleted users and users who blocked `viewer`,
+    /// if any, are left out.
+    fn lookup_users(
+        &self,
+        viewer: Option<Uuid>,
+        subs: &[Uuid],
+        after: i64,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<UserBatchHit>, sqlx::Error>> + Send;
     fn get_or_create_user(
         &self,
         sub: Uuid,
         username: &str,
     ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    /// Every user, deleted ones included, ordered by sub and strictly after
+    /// `after`.
+    fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<User>, sqlx::Error>> + Send;
+    /// Users whose display name starts with or resembles `query` (lowercase),
+    /// best match first, strictly after `after`. Deleted users and users who
+    /// blocked `viewer`, if any, are left out.
+    fn search_users(
+        &self,
+        viewer: Option<Uuid>,
+        query: &str,
+        after: SearchCursor,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<UserSearchHit>, sqlx::Error>> + Send;
     fn update_user(
         &self,
         sub: Uuid,
         req: UpdateUserRequest,
     ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    fn update_status(
+        &self,
+        sub: Uuid,
+        req: UpdateStatusRequest,
+    ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    fn update_privacy(
+        &self,
+        sub: Uuid,
+        privacy: &ProfilePrivacy,
+    ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+    /// Records Keycloak values that could not be restored after a failed
+    /// update, along with the values the update `applied`, so they can be
+    /// repaired later.
+    fn record_keycloak_repair(
+        &self,
+        sub: Uuid,
+        restore: &UpdateUserRequest,
+        applied: &UpdateUserRequest,
+        error: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Claims up to `limit` recorded repairs, newest first. Claimed repairs
+    /// are hidden from other workers for `lease`.
+    fn claim_keycloak_repairs(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> impl Future<Output = Result<Vec<KeycloakRepair>, sqlx::Error>> + Send;
+    fn complete_keycloak_repair(
+        &self,
+        id: i64,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    fn record_keycloak_repair_failure(
+        &self,
+        id: i64,
+        error: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
     fn get_setting_by_sub(
         &self,
         sub: Uuid,
@@ -44,40 +118,101 @@ pub trait UserRepository: Send + Sync {
 
 #[derive(Clone)]
 pub struct PostgresUserRepository {
-    pool: PgPool,
+    pub(crate) pool: PgPool,
 }
 
 impl PostgresUserRepository {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
+
+    async fn insert_default_setting(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        sub: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO param (sub)
+            VALUES ($1)
+            ON CONFLICT (sub) DO NOTHING
+            "#,
+        )
+        .bind(sub)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Writes a domain event to the outbox as part of the caller's transaction.
+    pub(crate) async fn insert_event(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        sub: Uuid,
+        kind: &UserEventKind,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_events (sub, event_type, payload)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(sub)
+        .bind(kind.event_type())
+        .bind(Json(kind))
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Escapes the `LIKE` wildcards of a user-supplied pattern.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 impl UserRepository for PostgresUserRepository {
     async fn create_user(&self, sub: Uuid, username: &str) -> Result<User, sqlx::Error> {
-        let user = sqlx::query_as::<_, User>(
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as::<_, User>(&format!(
             r#"
             INSERT INTO users (sub, display_name)
             VALUES ($1, $2)
-            RETURNING sub, display_name, profile_picture, description, created_at, updated_at
-            "#,
-        )
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
         .bind(sub)
         .bind(username)
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_default_setting(&mut tx, sub).await?;
+        Self::insert_username_claim(&mut tx, sub, username).await?;
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::UserCreated {
+                display_name: user.display_name.clone(),
+            },
+        )
         .await?;
+        tx.commit().await?;
 
         Ok(user)
     }
 
     async fn get_user_by_sub(&self, sub: Uuid) -> Result<Option<User>, sqlx::Error> {
-        let user = sqlx::query_as::<_, User>(
+        let user = sqlx::query_as::<_, User>(&format!(
             r#"
-            SELECT sub, display_name, profile_picture, description, created_at, updated_at
+            SELECT {USER_COLUMNS}
             FROM users
             WHERE sub = $1
-            "#,
-        )
+            "#
+        ))
         .bind(sub)
         .fetch_optional(&self.pool)
         .await?;
@@ -90,13 +225,13 @@ impl UserRepository for PostgresUserRepository {
             return Ok(Vec::new());
         }
 
-        let users = sqlx::query_as::<_, User>(
+        let users = sqlx::query_as::<_, User>(&format!(
             r#"
-            SELECT sub, display_name, profile_picture, description, created_at, updated_at
+            SELECT {USER_COLUMNS}
             FROM users
             WHERE sub = ANY($1)
-            "#,
-        )
+            "#
+        ))
         .bind(subs)
         .fetch_all(&self.pool)
         .await?;
@@ -104,11 +239,150 @@ impl UserRepository for PostgresUserRepository {
         Ok(users)
     }
 
+    async fn lookup_users(
+        &self,
+        viewer: Option<Uuid>,
+        subs: &[Uuid],
+        after: i64,
+        limit: i64,
+    ) -> Result<Vec<UserBatchHit>, sqlx::Error> {
+        if subs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let hits = sqlx::query_as::<_, UserBatchHit>(&format!(
+            r#"
+            SELECT {USER_COLUMNS}, requested.position
+            FROM unnest($1::uuid[]) WITH ORDINALITY AS requested(requested_sub, position)
+            JOIN users ON users.sub = requested.requested_sub
+            WHERE requested.position > $2
+              AND deleted_at IS NULL
+              AND ($4::uuid IS NULL OR NOT EXISTS (
+                  SELECT 1
+                  FROM relationships
+                  WHERE user_sub = users.sub AND target_sub = $4 AND kind = 'blocked'
+              ))
+            ORDER BY requested.position
+            LIMIT $3
+            "#
+        ))
+        .bind(subs)
+        .bind(after)
+        .bind(limit)
+        .bind(viewer)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(hits)
+    }
+
     async fn get_or_create_user(&self, sub: Uuid, username: &str) -> Result<User, sqlx::Error> {
         if let Some(user) = self.get_user_by_sub(sub).await? {
             return Ok(user);
         }
-        self.create_user(sub, username).await
+
+        // Concurrent first requests may race to create the same user: the loser
+        // inserts nothing and reads back the winner's row.
+        let mut tx = self.pool.begin().await?;
+
+        let created = sqlx::query_as::<_, User>(&format!(
+            r#"
+            INSERT INTO users (sub, display_name)
+            VALUES ($1, $2)
+            ON CONFLICT (sub) DO NOTHING
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(username)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(user) = created else {
+            tx.rollback().await?;
+            return self
+                .get_user_by_sub(sub)
+                .await?
+                .ok_or(sqlx::Error::RowNotFound);
+        };
+
+        Self::insert_default_setting(&mut tx, sub).await?;
+        Self::insert_username_claim(&mut tx, sub, username).await?;
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::UserCreated {
+                display_name: user.display_name.clone(),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn list_users(&self, after: Option<Uuid>, limit: i64) -> Result<Vec<User>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(&format!(
+            r#"
+            SELECT {USER_COLUMNS}
+            FROM users
+            WHERE $1::uuid IS NULL OR sub > $1
+            ORDER BY sub
+            LIMIT $2
+            "#
+        ))
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn search_users(
+        &self,
+        viewer: Option<Uuid>,
+        query: &str,
+        after: SearchCursor,
+        limit: i64,
+    ) -> Result<Vec<UserSearchHit>, sqlx::Error> {
+        // Exact and prefix matches rank above trigram-only matches
+        let hits = sqlx::query_as::<_, UserSearchHit>(&format!(
+            r#"
+            WITH ranked AS (
+                SELECT {USER_COLUMNS},
+                    (similarity(lower(display_name), $1)
+                        + CASE
+                            WHEN lower(display_name) = $1 THEN 2
+                            WHEN lower(display_name) LIKE $2 THEN 1
+                            ELSE 0
+                        END)::real AS score
+                FROM users
+                WHERE (lower(display_name) LIKE $2 OR lower(display_name) % $1)
+                  AND deleted_at IS NULL
+                  AND ($6::uuid IS NULL OR NOT EXISTS (
+                      SELECT 1
+                      FROM relationships
+                      WHERE user_sub = users.sub AND target_sub = $6 AND kind = 'blocked'
+                  ))
+            )
+            SELECT *
+            FROM ranked
+            WHERE (score, sub) < ($3::real, $4)
+            ORDER BY score DESC, sub DESC
+            LIMIT $5
+            "#
+        ))
+        .bind(query)
+        .bind(format!("{}%", escape_like(query)))
+        .bind(after.score)
+        .bind(after.sub)
+        .bind(limit)
+        .bind(viewer)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(hits)
     }
 
     async fn update_user(&self, sub: Uuid, req: UpdateUserRequest) -> Result<User, sqlx::Error> {
@@ -121,7 +395,7 @@ impl UserRepository for PostgresUserRepository {
         }
 
         if let Some(profile_picture) = &req.profile_picture {
-            builder.push(", profile_picture = ");
+            builder.push(", avatar_key = NULL, profile_picture = ");
             builder.push_bind(profile_picture);
         }
 
@@ -130,24 +404,205 @@ impl UserRepository for PostgresUserRepository {
             builder.push_bind(description);
         }
 
+        // An empty value clears the field
+        if let Some(accent_color) = &req.accent_color {
+            builder.push(", accent_color = NULLIF(");
+            builder.push_bind(accent_color);
+            builder.push(", '')");
+        }
+
+        if let Some(pronouns) = &req.pronouns {
+            builder.push(", pronouns = NULLIF(");
+            builder.push_bind(pronouns);
+            builder.push(", '')");
+        }
+
+        if let Some(links) = &req.links {
+            builder.push(", links = ");
+            builder.push_bind(Json(links));
+        }
+
         builder.push(" WHERE sub = ");
         builder.push_bind(sub);
-        builder.push(
-            " RETURNING sub, display_name, profile_picture, description, created_at, updated_at",
-        );
+        builder.push(" RETURNING ");
+        builder.push(USER_COLUMNS);
+
+        let mut tx = self.pool.begin().await?;
+
+        // An external picture replaces the uploaded avatar
+        if req.profile_picture.is_some() {
+            Self::queue_image_cleanup(&mut tx, sub, "avatar_key", None).await?;
+        }
 
         let user = builder
             .build_query_as::<User>()
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *tx)
             .await?;
 
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::ProfileUpdated {
+                display_name: req.display_name,
+                profile_picture: req.profile_picture,
+                description: req.description,
+                accent_color: req.accent_color,
+                pronouns: req.pronouns,
+                links: req.links,
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn update_status(&self, sub: Uuid, req: UpdateStatusRequest) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            r#"
+            UPDATE users
+            SET status = $2,
+                custom_status_text = $3,
+                custom_status_emoji = $4,
+                custom_status_expires_at = $5,
+                updated_at = NOW()
+            WHERE sub = $1
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(req.status)
+        .bind(req.custom_text)
+        .bind(req.custom_emoji)
+        .bind(req.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn update_privacy(
+        &self,
+        sub: Uuid,
+        privacy: &ProfilePrivacy,
+    ) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as::<_, User>(&format!(
+            r#"
+            UPDATE users
+            SET privacy = $2, updated_at = NOW()
+            WHERE sub = $1
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(sub)
+        .bind(Json(privacy))
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::PrivacyUpdated {
+                privacy: privacy.clone(),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
         Ok(user)
     }
 
+    async fn record_keycloak_repair(
+        &self,
+        sub: Uuid,
+        restore: &UpdateUserRequest,
+        applied: &UpdateUserRequest,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO keycloak_repair_outbox (sub, restore, applied, error)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(sub)
+        .bind(Json(restore))
+        .bind(Json(applied))
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn claim_keycloak_repairs(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<KeycloakRepair>, sqlx::Error> {
+        let repairs = sqlx::query_as::<_, KeycloakRepair>(
+            r#"
+            WITH claimed AS (
+                UPDATE keycloak_repair_outbox
+                SET locked_until = NOW() + make_interval(secs => $2)
+                WHERE id IN (
+                    SELECT id
+                    FROM keycloak_repair_outbox
+                    WHERE locked_until IS NULL OR locked_until < NOW()
+                    ORDER BY created_at DESC
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, sub, restore, applied, created_at
+            )
+            SELECT id, sub, restore, applied
+            FROM claimed
+            ORDER BY created_at DESC, id DESC
+            "#,
+        )
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(repairs)
+    }
+
+    async fn complete_keycloak_repair(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM keycloak_repair_outbox WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_keycloak_repair_failure(
+        &self,
+        id: i64,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE keycloak_repair_outbox
+            SET attempts = attempts + 1, error = $2
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
     async fn get_setting_by_sub(&self, sub: Uuid) -> Result<Option<Setting>, sqlx::Error> {
         let setting = sqlx::query_as::<_, Setting>(
             r#"
-            SELECT sub, theme, lang, created_at, updated_at
+            SELECT sub, settings, created_at, updated_at
             FROM param
             WHERE sub = $1
             "#,
@@ -164,7 +619,7 @@ impl UserRepository for PostgresUserRepository {
             r#"
             INSERT INTO param (sub)
             VALUES ($1)
-            RETURNING sub, theme, lang, created_at, updated_at
+            RETURNING sub, settings, created_at, updated_at
             "#,
         )
         .bind(sub)
@@ -179,27 +634,36 @@ impl UserRepository for PostgresUserRepository {
         sub: Uuid,
         req: UpdateSettingRequest,
     ) -> Result<Setting, sqlx::Error> {
-        let mut builder: sqlx::QueryBuilder<sqlx::Postgres> =
-            sqlx::QueryBuilder::new("UPDATE param SET updated_at = NOW()");
+        let reset_keys = req.reset_keys();
+        let changed_values = req.changed_values();
+        let mut tx = self.pool.begin().await?;
 
-        if let Some(theme) = &req.theme {
-            builder.push(", theme = ");
-            builder.push_bind(theme);
-        }
-
-        if let Some(lang) = &req.lang {
-            builder.push(", lang = ");
-            builder.push_bind(lang);
-        }
-
-        builder.push(" WHERE sub = ");
-        builder.push_bind(sub);
-        builder.push(" RETURNING sub, theme, lang, created_at, updated_at");
+        let setting = sqlx::query_as::<_, Setting>(
+            r#"
+            INSERT INTO param (sub, settings)
+            VALUES ($1, $2)
+            ON CONFLICT (sub) DO UPDATE
+            SET settings = (param.settings || EXCLUDED.settings) - $3::text[],
+                updated_at = NOW()
+            RETURNING sub, settings, created_at, updated_at
+            "#,
+        )
+        .bind(sub)
+        .bind(Json(&changed_values))
+        .bind(&reset_keys)
+        .fetch_one(&mut *tx)
+        .await?;
 
-        let setting = builder
-            .build_query_as::<Setting>()
-            .fetch_one(&self.pool)
-            .await?;
+        Self::insert_event(
+            &mut tx,
+            sub,
+            &UserEventKind::SettingsUpdated {
+                values: changed_values,
+                reset: reset_keys,
+            },
+        )
+        .await?;
+        tx.commit().await?;
 
         Ok(setting)
     }
@@ -0,0 +1,275 @@
+use crate::models::UsernameClaim;
+use crate::repository::PostgresUserRepository;
+use crate::username::username_skeleton;
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use uuid::Uuid;
+
+pub trait UsernameRepository: Send + Sync {
+    /// A username of another user than `sub` that looks like `username`:
+    /// either current or released and still held.
+    fn find_username_conflict(
+        &self,
+        sub: Uuid,
+        username: &str,
+    ) -> impl Future<Output = Result<Option<UsernameClaim>, sqlx::Error>> + Send;
+    /// Usernames claimed by `sub`, most recent first.
+    fn list_username_history(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<Vec<UsernameClaim>, sqlx::Error>> + Send;
+    /// When `sub` last released a username, if ever.
+    fn last_username_change(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<Option<DateTime<Utc>>, sqlx::Error>> + Send;
+    /// Releases `previous`, held for `sub` until `held_until`, and claims
+    /// `username`. Returns false, changing nothing, when another user
+    /// currently claims a username confusable with `username`.
+    fn record_username_change(
+        &self,
+        sub: Uuid,
+        previous: &str,
+        username: &str,
+        held_until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+    /// Undoes `record_username_change` when the rename could not be applied.
+    fn revert_username_change(
+        &self,
+        sub: Uuid,
+        previous: &str,
+        username: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    /// Records `username`, read from Keycloak, as the current username of
+    /// `sub`, releasing the previous one until `held_until`. Renames made in
+    /// Keycloak cannot be refused, so the claim is skipped when another user
+    /// currently claims a confusable username. Returns whether `username`
+    /// was newly claimed.
+    fn sync_username(
+        &self,
+        sub: Uuid,
+        username: &str,
+        held_until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+}
+
+impl PostgresUserRepository {
+    /// Records `username` as the current username of `sub`. Returns false
+    /// when `sub` already has a current username, or another user currently
+    /// claims a confusable one.
+    pub(crate) async fn insert_username_claim(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        sub: Uuid,
+        username: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO username_history (sub, username, skeleton)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(sub)
+        .bind(username)
+        .bind(username_skeleton(username))
+        .execute(&mut **tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        Ok(claimed)
+    }
+
+    /// Releases the current username of `sub`, held until `held_until`.
+    /// Returns false if `sub` had none.
+    async fn release_username_claim(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        sub: Uuid,
+        held_until: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let released = sqlx::query(
+            r#"
+            UPDATE username_history
+            SET released_at = NOW(), held_until = $2
+            WHERE sub = $1 AND released_at IS NULL
+            "#,
+        )
+        .bind(sub)
+        .bind(held_until)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        Ok(released)
+    }
+}
+
+impl UsernameRepository for PostgresUserRepository {
+    async fn find_username_conflict(
+        &self,
+        sub: Uuid,
+        username: &str,
+    ) -> Result<Option<UsernameClaim>, sqlx::Error> {
+        let claim = sqlx::query_as::<_, UsernameClaim>(
+            r#"
+            SELECT sub, username, claimed_at, released_at, held_until
+            FROM username_history
+            WHERE skeleton = $2
+              AND sub <> $1
+              AND (released_at IS NULL OR held_until > NOW())
+            ORDER BY released_at DESC NULLS FIRST
+            LIMIT 1
+            "#,
+        )
+        .bind(sub)
+        .bind(username_skeleton(username))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(claim)
+    }
+
+    async fn list_username_history(&self, sub: Uuid) -> Result<Vec<UsernameClaim>, sqlx::Error> {
+        let claims = sqlx::query_as::<_, UsernameClaim>(
+            r#"
+            SELECT sub, username, claimed_at, released_at, held_until
+            FROM username_history
+            WHERE sub = $1
+            ORDER BY claimed_at DESC, id DESC
+            "#,
+        )
+        .bind(sub)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(claims)
+    }
+
+    async fn last_username_change(&self, sub: Uuid) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let released_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(released_at)
+            FROM username_history
+            WHERE sub = $1
+            "#,
+        )
+        .bind(sub)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(released_at)
+    }
+
+    async fn record_username_change(
+        &self,
+        sub: Uuid,
+        previous: &str,
+        username: &str,
+        held_until: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Users whose claim was skipped have no current row
+        if !Self::release_username_claim(&mut tx, sub, held_until).await? {
+            sqlx::query(
+                r#"
+                INSERT INTO username_history (sub, username, skeleton, released_at, held_until)
+                VALUES ($1, $2, $3, NOW(), $4)
+                "#,
+            )
+            .bind(sub)
+            .bind(previous)
+            .bind(username_skeleton(previous))
+            .bind(held_until)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // The unique index on current skeletons settles concurrent renames
+        // that both passed `find_username_conflict`
+        if !Self::insert_username_claim(&mut tx, sub, username).await? {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn revert_username_change(
+        &self,
+        sub: Uuid,
+        previous: &str,
+        username: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM username_history
+            WHERE sub = $1 AND username = $2 AND released_at IS NULL
+            "#,
+        )
+        .bind(sub)
+        .bind(username)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE username_history
+            SET released_at = NULL, held_until = NULL
+            WHERE id = (
+                SELECT id
+                FROM username_history
+                WHERE sub = $1 AND username = $2
+                ORDER BY released_at DESC
+                LIMIT 1
+            )
+            "#,
+        )
+        .bind(sub)
+        .bind(previous)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn sync_username(
+        &self,
+        sub: Uuid,
+        username: &str,
+        held_until: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let current: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT username
+            FROM username_history
+            WHERE sub = $1 AND released_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(sub)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if current.as_deref() == Some(username) {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        Self::release_username_claim(&mut tx, sub, held_until).await?;
+        let claimed = Self::insert_username_claim(&mut tx, sub, username).await?;
+        if !claimed {
+            tracing::warn!(%sub, "Username from Keycloak is confusable with another user's");
+        }
+        tx.commit().await?;
+
+        Ok(claimed)
+    }
+}
@@ -1,7 +1,37 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
+/// Operation allowed by a signed URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignedUrlAction {
+    Get,
+    Put,
+}
+
+/// What the content service knows about a stored object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMetadata {
+    pub content_type: String,
+    pub size: u64,
+}
+
+/// Profile pictures are stored under an object key: the user's sub for
+/// pictures uploaded before avatar keys existed, `<sub>/<id>` since.
 pub trait ContentServiceClient: Send + Sync + Clone {
-    fn get_profile_picture_url(&self, url: &str) -> impl Future<Output = Result<String, String>> + Send;
+    /// URL allowing `action` on the picture at `key` for `expires_in`.
+    fn sign_profile_picture_url(
+        &self,
+        key: &str,
+        action: SignedUrlAction,
+        expires_in: Duration,
+    ) -> impl Future<Output = Result<String, String>> + Send;
+    /// Metadata of the picture at `key`, `None` if nothing was uploaded there.
+    fn get_profile_picture_metadata(
+        &self,
+        key: &str,
+    ) -> impl Future<Output = Result<Option<ObjectMetadata>, String>> + Send;
+    /// Deletes the picture at `key`. Deleting a missing picture succeeds.
+    fn delete_profile_picture(&self, key: &str) -> impl Future<Output = Result<(), String>> + Send;
 }
 
 
@@ -23,7 +53,7 @@ impl ContentServiceClientImpl {
 #[derive(Serialize, Deserialize)]
 struct ContentSigningPayload {
     #[serde(rename = "action")]
-    action: String,
+    action: SignedUrlAction,
     #[serde(rename = "expires_in_ms")]
     expires_in: u64,
 }
@@ -33,34 +63,161 @@ struct ContentSigningResponse {
     url: String,
 }
 
+impl ContentServiceClientImpl {
+    fn profile_picture_url(&self, key: &str) -> String {
+        format!("{}/profile_picture/{}", self.base_url, key)
+    }
+}
+
 impl ContentServiceClient for ContentServiceClientImpl {
-    async fn get_profile_picture_url(&self, user_id: &str) -> Result<String, String> {
-        let url = format!("{}/profile_picture/{}", self.base_url, user_id);
+    async fn sign_profile_picture_url(
+        &self,
+        key: &str,
+        action: SignedUrlAction,
+        expires_in: Duration,
+    ) -> Result<String, String> {
         let payload = ContentSigningPayload {
-            action: "Put".to_string(),
-            expires_in: 60 * 60 * 24 * 7,
+            action,
+            expires_in: expires_in.as_millis() as u64,
         };
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert(
-            reqwest::header::CONTENT_TYPE,
-            reqwest::header::HeaderValue::from_static("application/json"),
-        );
 
         let response = self
             .client
-            .post(url)
-            .headers(headers)
+            .post(self.profile_picture_url(key))
             .json(&payload)
             .send()
             .await
-            .map_err(|e| format!("Failed to get profile picture: {}", e))?;
+            .map_err(|e| format!("Failed to sign profile picture URL: {}", e))?;
 
         if !response.status().is_success() {
             return Err(format!("HTTP {}", response.status()));
         }
 
-        let parsed_response = response.json::<ContentSigningResponse>().await.map_err(|e| format!("Failed to parse response: {}", e))?;
+        let parsed_response = response
+            .json::<ContentSigningResponse>()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
         Ok(parsed_response.url)
     }
+
+    async fn get_profile_picture_metadata(
+        &self,
+        key: &str,
+    ) -> Result<Option<ObjectMetadata>, String> {
+        let response = self
+            .client
+            .head(self.profile_picture_url(key))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to get profile picture metadata: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        let headers = response.headers();
+        let content_type = headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let size = headers
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| "Missing Content-Length".to_string())?;
+
+        Ok(Some(ObjectMetadata { content_type, size }))
+    }
+
+    async fn delete_profile_picture(&self, key: &str) -> Result<(), String> {
+        let response = self
+            .client
+            .delete(self.profile_picture_url(key))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to delete profile picture: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        Ok(())
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn signs_url_with_expiry_in_milliseconds() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/profile_picture/sub/avatar"))
+            .and(body_json(json!({ "action": "Get", "expires_in_ms": 900_000 })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "url": "https://cdn/signed" })),
+            )
+            .mount(&server)
+            .await;
+        let client = ContentServiceClientImpl::new(server.uri());
+
+        let url = client
+            .sign_profile_picture_url("sub/avatar", SignedUrlAction::Get, Duration::from_secs(900))
+            .await
+            .unwrap();
+
+        assert_eq!(url, "https://cdn/signed");
+    }
+
+    #[tokio::test]
+    async fn reads_metadata_from_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/profile_picture/sub/avatar"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/png")
+                    .insert_header("content-length", "2048"),
+            )
+            .mount(&server)
+            .await;
+        let client = ContentServiceClientImpl::new(server.uri());
+
+        let metadata = client.get_profile_picture_metadata("sub/avatar").await.unwrap();
+
+        assert_eq!(
+            metadata,
+            Some(ObjectMetadata {
+                content_type: "image/png".to_string(),
+                size: 2048,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_object_has_no_metadata() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let client = ContentServiceClientImpl::new(server.uri());
+
+        let metadata = client.get_profile_picture_metadata("sub/avatar").await.unwrap();
+
+        assert_eq!(metadata, None);
+    }
+}
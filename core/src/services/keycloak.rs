@@ -1,4 +1,5 @@
-use crate::models::{KeycloakUserInfo, UpdateUserRequest};
+use crate::models::{KeycloakAccount, KeycloakUserInfo, UpdateUserRequest};
+use crate::services::keycloak_token::{AdminTokenManager, IssuedToken};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -24,6 +25,13 @@ pub enum KeycloakError {
     #[error("Failed to update user: {0}")]
     UpdateUserError(String),
 
+    /// Another account already has the username or email
+    #[error("Conflicting user: {0}")]
+    Conflict(String),
+
+    #[error("Failed to delete user: {0}")]
+    DeleteUserError(String),
+
     #[error("HTTP request failed: {0}")]
     HttpError(#[from] reqwest::Error),
 
@@ -49,12 +57,48 @@ pub trait KeycloakClient: Send + Sync + Clone {
         sub: Uuid,
         update_req: &UpdateUserRequest,
     ) -> impl Future<Output = Result<(), KeycloakError>> + Send;
+
+    /// Replaces the email of the Keycloak account and sets its verified flag.
+    fn update_email(
+        &self,
+        sub: Uuid,
+        email: &str,
+        verified: bool,
+    ) -> impl Future<Output = Result<(), KeycloakError>> + Send;
+
+    /// Deletes the Keycloak account. Deleting a missing account succeeds.
+    fn delete_user(&self, sub: Uuid) -> impl Future<Output = Result<(), KeycloakError>> + Send;
+
+    /// Enables or disables login to the Keycloak account.
+    fn set_user_enabled(
+        &self,
+        sub: Uuid,
+        enabled: bool,
+    ) -> impl Future<Output = Result<(), KeycloakError>> + Send;
+
+    /// Account of `sub`, service accounts included.
+    fn get_account(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<KeycloakAccount, KeycloakError>> + Send;
+
+    /// Up to `max` accounts of the realm, skipping the first `first`.
+    /// Service accounts are not listed.
+    fn list_users(
+        &self,
+        first: usize,
+        max: usize,
+    ) -> impl Future<Output = Result<Vec<KeycloakAccount>, KeycloakError>> + Send;
+
+    /// Service accounts of the realm's clients, which `list_users` leaves out.
+    fn list_service_accounts(
+        &self,
+    ) -> impl Future<Output = Result<Vec<KeycloakAccount>, KeycloakError>> + Send;
 }
 
 #[derive(Debug, Deserialize)]
 struct KeycloakTokenResponse {
     access_token: String,
-    #[allow(dead_code)]
     expires_in: u64,
 }
 
@@ -70,6 +114,43 @@ struct KeycloakUserIdOnly {
     id: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct KeycloakUserBrief {
+    id: String,
+    username: String,
+    #[serde(rename = "serviceAccountClientId")]
+    service_account_client_id: Option<String>,
+}
+
+impl TryFrom<KeycloakUserBrief> for KeycloakAccount {
+    type Error = KeycloakError;
+
+    fn try_from(user: KeycloakUserBrief) -> Result<Self, Self::Error> {
+        let sub = Uuid::parse_str(&user.id)
+            .map_err(|e| KeycloakError::ParseError(format!("Invalid UUID: {}", e)))?;
+        Ok(KeycloakAccount {
+            sub,
+            username: user.username,
+            service_account_client_id: user.service_account_client_id,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KeycloakClientBrief {
+    id: String,
+    #[serde(rename = "clientId")]
+    client_id: String,
+    #[serde(rename = "serviceAccountsEnabled", default)]
+    service_accounts_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeycloakErrorBody {
+    #[serde(rename = "errorMessage")]
+    error_message: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct KeycloakService {
     client: Client,
@@ -77,6 +158,7 @@ pub struct KeycloakService {
     realm: String,
     client_id: String,
     client_secret: String,
+    token_manager: AdminTokenManager,
 }
 
 impl KeycloakService {
@@ -87,10 +169,35 @@ impl KeycloakService {
             realm,
             client_id,
             client_secret,
+            token_manager: AdminTokenManager::default(),
         }
     }
 
     async fn get_admin_token(&self) -> Result<String, KeycloakError> {
+        self.token_manager
+            .get_or_refresh(|| self.fetch_admin_token())
+            .await
+    }
+
+    /// Sends an admin API request with the cached token. If Keycloak rejects the
+    /// token (e.g. it was revoked), the request is retried once with a new one.
+    async fn send_with_admin_token<F>(&self, build: F) -> Result<reqwest::Response, KeycloakError>
+    where
+        F: Fn(&str) -> reqwest::RequestBuilder,
+    {
+        let token = self.get_admin_token().await?;
+        let response = build(&token).send().await?;
+
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        self.token_manager.invalidate(&token).await;
+        let token = self.get_admin_token().await?;
+        Ok(build(&token).send().await?)
+    }
+
+    async fn fetch_admin_token(&self) -> Result<IssuedToken, KeycloakError> {
         let token_url = format!(
             "{}/realms/{}/protocol/openid-connect/token",
             self.base_url, self.realm
@@ -114,22 +221,20 @@ impl KeycloakService {
             .json()
             .await
             .map_err(|e| KeycloakError::ParseError(e.to_string()))?;
-        Ok(token_response.access_token)
+        Ok(IssuedToken {
+            access_token: token_response.access_token,
+            expires_in: token_response.expires_in,
+        })
     }
 
     pub async fn get_user_info(&self, sub: Uuid) -> Result<KeycloakUserInfo, KeycloakError> {
-        let token = self.get_admin_token().await?;
-
         let user_url = format!(
             "{}/admin/realms/{}/users/{}",
             self.base_url, self.realm, sub
         );
 
         let response = self
-            .client
-            .get(&user_url)
-            .bearer_auth(&token)
-            .send()
+            .send_with_admin_token(|token| self.client.get(&user_url).bearer_auth(token))
             .await?;
 
         if response.status() == reqwest::StatusCode::NOT_FOUND {
@@ -155,18 +260,13 @@ impl KeycloakService {
     }
 
     pub async fn get_user_id_by_username(&self, username: &str) -> Result<Uuid, KeycloakError> {
-        let token = self.get_admin_token().await?;
-
         let users_url = format!(
             "{}/admin/realms/{}/users?username={}&exact=true",
             self.base_url, self.realm, username
         );
 
         let response = self
-            .client
-            .get(&users_url)
-            .bearer_auth(&token)
-            .send()
+            .send_with_admin_token(|token| self.client.get(&users_url).bearer_auth(token))
             .await?;
 
         if !response.status().is_success() {
@@ -195,8 +295,6 @@ impl KeycloakService {
         sub: Uuid,
         update_req: &UpdateUserRequest,
     ) -> Result<(), KeycloakError> {
-        let token = self.get_admin_token().await?;
-
         let user_url = format!(
             "{}/admin/realms/{}/users/{}",
             self.base_url, self.realm, sub
@@ -211,11 +309,209 @@ impl KeycloakService {
         }
 
         let response = self
-            .client
-            .put(&user_url)
-            .bearer_auth(&token)
-            .json(&update_data)
-            .send()
+            .send_with_admin_token(|token| {
+                self.client
+                    .put(&user_url)
+                    .bearer_auth(token)
+                    .json(&update_data)
+            })
+            .await?;
+
+        Self::check_update_response(sub, response).await
+    }
+
+    pub async fn update_email(
+        &self,
+        sub: Uuid,
+        email: &str,
+        verified: bool,
+    ) -> Result<(), KeycloakError> {
+        let user_url = format!(
+            "{}/admin/realms/{}/users/{}",
+            self.base_url, self.realm, sub
+        );
+
+        let response = self
+            .send_with_admin_token(|token| {
+                self.client
+                    .put(&user_url)
+                    .bearer_auth(token)
+                    .json(&serde_json::json!({ "email": email, "emailVerified": verified }))
+            })
+            .await?;
+
+        Self::check_update_response(sub, response).await
+    }
+
+    async fn check_update_response(
+        sub: Uuid,
+        response: reqwest::Response,
+    ) -> Result<(), KeycloakError> {
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(KeycloakError::UserNotFound(sub));
+        }
+
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            let message = response
+                .json::<KeycloakErrorBody>()
+                .await
+                .ok()
+                .and_then(|body| body.error_message)
+                .unwrap_or_else(|| "User exists with the same username or email".to_string());
+            return Err(KeycloakError::Conflict(message));
+        }
+
+        if !response.status().is_success() {
+            return Err(KeycloakError::UpdateUserError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_users(
+        &self,
+        first: usize,
+        max: usize,
+    ) -> Result<Vec<KeycloakAccount>, KeycloakError> {
+        let users_url = format!(
+            "{}/admin/realms/{}/users?first={}&max={}&briefRepresentation=true",
+            self.base_url, self.realm, first, max
+        );
+
+        let response = self
+            .send_with_admin_token(|token| self.client.get(&users_url).bearer_auth(token))
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(KeycloakError::GetUserError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+
+        let users: Vec<KeycloakUserBrief> = response
+            .json()
+            .await
+            .map_err(|e| KeycloakError::ParseError(e.to_string()))?;
+
+        users.into_iter().map(KeycloakAccount::try_from).collect()
+    }
+
+    pub async fn get_account(&self, sub: Uuid) -> Result<KeycloakAccount, KeycloakError> {
+        let user_url = format!(
+            "{}/admin/realms/{}/users/{}",
+            self.base_url, self.realm, sub
+        );
+
+        let response = self
+            .send_with_admin_token(|token| self.client.get(&user_url).bearer_auth(token))
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(KeycloakError::UserNotFound(sub));
+        }
+
+        if !response.status().is_success() {
+            return Err(KeycloakError::GetUserError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+
+        let user: KeycloakUserBrief = response
+            .json()
+            .await
+            .map_err(|e| KeycloakError::ParseError(e.to_string()))?;
+        user.try_into()
+    }
+
+    pub async fn list_service_accounts(&self) -> Result<Vec<KeycloakAccount>, KeycloakError> {
+        let clients_url = format!("{}/admin/realms/{}/clients", self.base_url, self.realm);
+
+        let response = self
+            .send_with_admin_token(|token| self.client.get(&clients_url).bearer_auth(token))
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(KeycloakError::GetUserError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+
+        let clients: Vec<KeycloakClientBrief> = response
+            .json()
+            .await
+            .map_err(|e| KeycloakError::ParseError(e.to_string()))?;
+
+        let mut accounts = Vec::new();
+        for client in clients.into_iter().filter(|c| c.service_accounts_enabled) {
+            let user_url = format!("{}/{}/service-account-user", clients_url, client.id);
+            let response = self
+                .send_with_admin_token(|token| self.client.get(&user_url).bearer_auth(token))
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(KeycloakError::GetUserError(format!(
+                    "HTTP {}",
+                    response.status()
+                )));
+            }
+
+            let user: KeycloakUserBrief = response
+                .json()
+                .await
+                .map_err(|e| KeycloakError::ParseError(e.to_string()))?;
+            // Keycloak fills serviceAccountClientId with the internal id of
+            // the client, report its client_id instead
+            let mut account = KeycloakAccount::try_from(user)?;
+            account.service_account_client_id = Some(client.client_id);
+            accounts.push(account);
+        }
+
+        Ok(accounts)
+    }
+
+    pub async fn delete_user(&self, sub: Uuid) -> Result<(), KeycloakError> {
+        let user_url = format!(
+            "{}/admin/realms/{}/users/{}",
+            self.base_url, self.realm, sub
+        );
+
+        let response = self
+            .send_with_admin_token(|token| self.client.delete(&user_url).bearer_auth(token))
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+
+        if !response.status().is_success() {
+            return Err(KeycloakError::DeleteUserError(format!(
+                "HTTP {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_user_enabled(&self, sub: Uuid, enabled: bool) -> Result<(), KeycloakError> {
+        let user_url = format!(
+            "{}/admin/realms/{}/users/{}",
+            self.base_url, self.realm, sub
+        );
+
+        let response = self
+            .send_with_admin_token(|token| {
+                self.client
+                    .put(&user_url)
+                    .bearer_auth(token)
+                    .json(&serde_json::json!({ "enabled": enabled }))
+            })
             .await?;
 
         if response.status() == reqwest::StatusCode::NOT_FOUND {
@@ -249,4 +545,245 @@ impl KeycloakClient for KeycloakService {
     ) -> Result<(), KeycloakError> {
         KeycloakService::update_user_info(self, sub, update_req).await
     }
+
+    async fn update_email(
+        &self,
+        sub: Uuid,
+        email: &str,
+        verified: bool,
+    ) -> Result<(), KeycloakError> {
+        KeycloakService::update_email(self, sub, email, verified).await
+    }
+
+    async fn delete_user(&self, sub: Uuid) -> Result<(), KeycloakError> {
+        KeycloakService::delete_user(self, sub).await
+    }
+
+    async fn set_user_enabled(&self, sub: Uuid, enabled: bool) -> Result<(), KeycloakError> {
+        KeycloakService::set_user_enabled(self, sub, enabled).await
+    }
+
+    async fn get_account(&self, sub: Uuid) -> Result<KeycloakAccount, KeycloakError> {
+        KeycloakService::get_account(self, sub).await
+    }
+
+    async fn list_users(
+        &self,
+        first: usize,
+        max: usize,
+    ) -> Result<Vec<KeycloakAccount>, KeycloakError> {
+        KeycloakService::list_users(self, first, max).await
+    }
+
+    async fn list_service_accounts(&self) -> Result<Vec<KeycloakAccount>, KeycloakError> {
+        KeycloakService::list_service_accounts(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn keycloak_server() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/realms/test/protocol/openid-connect/token"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "access_token": "admin-token", "expires_in": 300 })),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn service(server: &MockServer) -> KeycloakService {
+        KeycloakService::new(
+            server.uri(),
+            "test".to_string(),
+            "user-service".to_string(),
+            "secret".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn list_users_requests_page_with_admin_token() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path("/admin/realms/test/users"))
+            .and(query_param("first", "20"))
+            .and(query_param("max", "10"))
+            .and(header("authorization", "Bearer admin-token"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([{ "id": sub, "username": "john" }])),
+            )
+            .mount(&server)
+            .await;
+
+        let accounts = service(&server).list_users(20, 10).await.unwrap();
+
+        assert_eq!(
+            accounts,
+            vec![KeycloakAccount {
+                sub,
+                username: "john".to_string(),
+                service_account_client_id: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_account_tells_service_accounts_apart() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path(format!("/admin/realms/test/users/{}", sub)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": sub,
+                "username": "service-account-payments",
+                "serviceAccountClientId": "payments"
+            })))
+            .mount(&server)
+            .await;
+
+        let account = service(&server).get_account(sub).await.unwrap();
+
+        assert_eq!(account.service_account_client_id.as_deref(), Some("payments"));
+    }
+
+    #[tokio::test]
+    async fn list_users_fails_on_error_status() {
+        let server = keycloak_server().await;
+        Mock::given(method("GET"))
+            .and(path("/admin/realms/test/users"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let result = service(&server).list_users(0, 10).await;
+
+        assert!(matches!(result, Err(KeycloakError::GetUserError(_))));
+    }
+
+    #[tokio::test]
+    async fn list_service_accounts_skips_clients_without_one() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path("/admin/realms/test/clients"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": "c1", "clientId": "payments", "serviceAccountsEnabled": true },
+                { "id": "c2", "clientId": "frontend", "serviceAccountsEnabled": false },
+                { "id": "c3", "clientId": "legacy" }
+            ])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/admin/realms/test/clients/c1/service-account-user"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": sub,
+                "username": "service-account-payments",
+                "serviceAccountClientId": "c1"
+            })))
+            .mount(&server)
+            .await;
+
+        let accounts = service(&server).list_service_accounts().await.unwrap();
+
+        assert_eq!(
+            accounts,
+            vec![KeycloakAccount {
+                sub,
+                username: "service-account-payments".to_string(),
+                service_account_client_id: Some("payments".to_string()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_user_info_maps_404_to_user_not_found() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path(format!("/admin/realms/test/users/{}", sub)))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let result = service(&server).get_user_info(sub).await;
+
+        assert!(matches!(result, Err(KeycloakError::UserNotFound(s)) if s == sub));
+    }
+
+    #[tokio::test]
+    async fn set_user_enabled_puts_enabled_flag() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("PUT"))
+            .and(path(format!("/admin/realms/test/users/{}", sub)))
+            .and(body_json(json!({ "enabled": false })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        service(&server).set_user_enabled(sub, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_user_info_reports_conflicts() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("PUT"))
+            .and(path(format!("/admin/realms/test/users/{}", sub)))
+            .respond_with(
+                ResponseTemplate::new(409)
+                    .set_body_json(json!({ "errorMessage": "User exists with same username" })),
+            )
+            .mount(&server)
+            .await;
+        let req = UpdateUserRequest {
+            display_name: None,
+            profile_picture: None,
+            description: None,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+            username: Some("taken".to_string()),
+            email: None,
+        };
+
+        let err = service(&server)
+            .update_user_info(sub, &req)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            KeycloakError::Conflict(message) if message == "User exists with same username"
+        ));
+    }
+
+    #[tokio::test]
+    async fn update_email_marks_email_verified() {
+        let server = keycloak_server().await;
+        let sub = Uuid::new_v4();
+        Mock::given(method("PUT"))
+            .and(path(format!("/admin/realms/test/users/{}", sub)))
+            .and(body_json(json!({ "email": "new@example.com", "emailVerified": true })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        service(&server)
+            .update_email(sub, "new@example.com", true)
+            .await
+            .unwrap();
+    }
 }
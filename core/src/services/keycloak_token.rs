@@ -0,0 +1,214 @@
+use crate::services::KeycloakError;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long before expiry a cached admin token is considered stale.
+pub const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+/// A freshly issued admin token and its lifetime in seconds, as returned by
+/// the Keycloak token endpoint.
+#[derive(Debug, Clone)]
+pub struct IssuedToken {
+    pub access_token: String,
+    pub expires_in: u64,
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    access_token: String,
+    refresh_at: Instant,
+}
+
+/// Caches the client-credentials admin token used for the Keycloak admin API.
+///
+/// The lock is held while a refresh is in flight, so concurrent callers wait
+/// for the single outstanding request instead of issuing their own.
+#[derive(Clone)]
+pub struct AdminTokenManager {
+    cached: Arc<Mutex<Option<CachedToken>>>,
+    refresh_margin: Duration,
+}
+
+impl AdminTokenManager {
+    pub fn new(refresh_margin: Duration) -> Self {
+        Self {
+            cached: Arc::new(Mutex::new(None)),
+            refresh_margin,
+        }
+    }
+
+    /// Returns the cached token, or calls `fetch` to obtain a new one when the
+    /// cached token is missing or about to expire.
+    pub async fn get_or_refresh<F, Fut>(&self, fetch: F) -> Result<String, KeycloakError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<IssuedToken, KeycloakError>>,
+    {
+        let mut cached = self.cached.lock().await;
+
+        if let Some(token) = cached.as_ref()
+            && Instant::now() < token.refresh_at
+        {
+            return Ok(token.access_token.clone());
+        }
+
+        let issued = fetch().await?;
+        let lifetime = Duration::from_secs(issued.expires_in).saturating_sub(self.refresh_margin);
+        *cached = Some(CachedToken {
+            access_token: issued.access_token.clone(),
+            refresh_at: Instant::now() + lifetime,
+        });
+
+        Ok(issued.access_token)
+    }
+
+    /// Drops the cached token if it is the one that was rejected. A token
+    /// refreshed concurrently by another caller is kept.
+    pub async fn invalidate(&self, rejected_token: &str) {
+        let mut cached = self.cached.lock().await;
+        if cached
+            .as_ref()
+            .is_some_and(|token| token.access_token == rejected_token)
+        {
+            *cached = None;
+        }
+    }
+}
+
+impl Default for AdminTokenManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_REFRESH_MARGIN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn issue(calls: &AtomicUsize, expires_in: u64) -> Result<IssuedToken, KeycloakError> {
+        let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(IssuedToken {
+            access_token: format!("token-{}", n),
+            expires_in,
+        })
+    }
+
+    #[tokio::test]
+    async fn reuses_cached_token_until_refresh_margin() {
+        let manager = AdminTokenManager::default();
+        let calls = AtomicUsize::new(0);
+
+        let first = manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+        let second = manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+
+        assert_eq!(first, "token-1");
+        assert_eq!(second, "token-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn refreshes_token_expiring_within_margin() {
+        let manager = AdminTokenManager::default();
+        let calls = AtomicUsize::new(0);
+
+        manager
+            .get_or_refresh(|| async { issue(&calls, 10) })
+            .await
+            .unwrap();
+        let second = manager
+            .get_or_refresh(|| async { issue(&calls, 10) })
+            .await
+            .unwrap();
+
+        assert_eq!(second, "token-2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn coalesces_concurrent_refreshes() {
+        let manager = AdminTokenManager::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let manager = manager.clone();
+                let calls = calls.clone();
+                tokio::spawn(async move {
+                    manager
+                        .get_or_refresh(|| async {
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            issue(&calls, 300)
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap(), "token-1");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn invalidate_forces_refresh_of_rejected_token() {
+        let manager = AdminTokenManager::default();
+        let calls = AtomicUsize::new(0);
+
+        let first = manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+        manager.invalidate(&first).await;
+        let second = manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+
+        assert_eq!(second, "token-2");
+    }
+
+    #[tokio::test]
+    async fn invalidate_keeps_token_refreshed_by_someone_else() {
+        let manager = AdminTokenManager::default();
+        let calls = AtomicUsize::new(0);
+
+        manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+        manager.invalidate("stale-token").await;
+        let token = manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+
+        assert_eq!(token, "token-1");
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_failed_fetch() {
+        let manager = AdminTokenManager::default();
+        let calls = AtomicUsize::new(0);
+
+        let failed = manager
+            .get_or_refresh(|| async { Err(KeycloakError::TokenError("HTTP 500".into())) })
+            .await;
+        let token = manager
+            .get_or_refresh(|| async { issue(&calls, 300) })
+            .await
+            .unwrap();
+
+        assert!(matches!(failed, Err(KeycloakError::TokenError(_))));
+        assert_eq!(token, "token-1");
+    }
+}
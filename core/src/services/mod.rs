@@ -1,7 +1,8 @@
 pub mod keycloak;
+pub mod keycloak_token;
 pub mod user;
 pub mod content;
 
 pub use keycloak::{KeycloakClient, KeycloakError, KeycloakService};
 pub use user::{UserService, UserServiceImpl};
-pub use content::ContentServiceClient;
+pub use content::{ContentServiceClient, ObjectMetadata, SignedUrlAction};
@@ -1,35 +1,135 @@
+use crate::avatar::{
+    ALLOWED_AVATAR_CONTENT_TYPES, AVATAR_READ_URL_TTL, AVATAR_UPLOAD_URL_TTL, MAX_AVATAR_SIZE,
+    MAX_BANNER_SIZE, SignedUrlCache, is_avatar_key_of, is_banner_key_of, new_avatar_key,
+    new_banner_key,
+};
+use crate::cache::{CacheMetricsSnapshot, UserCache, UsernameLookupCache};
+use crate::email_verification::{EmailChangeClaims, EmailVerification};
 use crate::error::CoreError;
+use crate::export::ExportRegistry;
+use crate::keycloak_events::{KeycloakEvent, KeycloakEventKind};
 use crate::models::{
-    Setting, UpdateSettingRequest, UpdateUserRequest, User, UserBasicInfo, UserFullInfo,
+    AccountDeletionInfo, AdminUserInfo, AdminUserPage, AuditAction, AuditContext, AuditLogQuery,
+    AuditPage, BatchOrder, DataExport, DataExportInfo, DataExportStatus, DeletionStep, FieldChange,
+    KeycloakRepair, KeycloakUserInfo, PendingEmailChange, ProfilePictureRequest, ProfilePrivacy,
+    ReconcileFailure, ReconcileReport, Relationship, RelationshipInfo, RelationshipKind,
+    SearchCursor, Setting, SettingValues, SuspendUserRequest, Suspension, UpdatePrivacyRequest,
+    UpdateSettingRequest, UpdateStatusRequest, UpdateUserRequest, User, UserBasicInfo,
+    UserBatchPage, UserDeletion, UserFullInfo, UserSearchPage, VerifyEmailRequest, Viewer,
+    redact_email,
+};
+use crate::pagination::{decode_cursor, encode_cursor, paginate};
+use crate::repository::{
+    AuditRepository, AvatarRepository, DeletionRepository, EmailChangeRepository, ExportRepository,
+    RelationshipRepository, SuspensionRepository, UserRepository, UsernameRepository,
 };
-use crate::repository::UserRepository;
-use crate::services::{ContentServiceClient, KeycloakClient};
+use crate::services::{ContentServiceClient, KeycloakClient, KeycloakError, SignedUrlAction};
+use crate::settings::SettingsRegistry;
+use crate::username::{UsernamePolicy, UsernameRejection};
+use crate::validation::{
+    ValidationErrors, is_valid_username, normalize, validate_suspend_user_request,
+    validate_update_privacy_request, validate_update_user_request,
+};
+use std::collections::HashMap;
 use std::future::Future;
+use std::time::Duration;
+use tracing::Instrument;
 use uuid::Uuid;
 
+/// Maximum length (in characters) of a search query.
+pub const MAX_SEARCH_QUERY_LEN: usize = 64;
+
+/// Default delay between an account deletion request and its purge.
+pub const DEFAULT_DELETION_GRACE_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How long a purge worker keeps a claimed deletion before another one may
+/// retry it.
+const DELETION_PURGE_LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// How long a completed data export stays available for download.
+pub const DATA_EXPORT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How long an export worker keeps a claimed export before another one may
+/// build it.
+const DATA_EXPORT_LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// How long a cleanup worker keeps a claimed avatar before another one may
+/// retry it.
+const AVATAR_CLEANUP_LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// How long a repair worker keeps a claimed Keycloak repair before another
+/// one may retry it.
+const KEYCLOAK_REPAIR_LEASE: Duration = Duration::from_secs(5 * 60);
+
 pub trait UserService: Send + Sync {
+    /// User as seen by `viewer`. Users who blocked `viewer` are not found.
     fn get_user_by_sub(
         &self,
+        viewer: Uuid,
         sub: Uuid,
     ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
     fn get_user_by_username(
         &self,
         username: &str,
     ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
+    /// One page of the users of `subs` as seen by `viewer`. Repeated subs
+    /// count once. Subs of unknown or deleted users, and of users who blocked
+    /// `viewer`, are reported as not found.
     fn get_users_by_subs(
         &self,
+        viewer: Viewer,
         subs: &[Uuid],
-    ) -> impl Future<Output = Result<Vec<UserBasicInfo>, CoreError>> + Send;
+        order: BatchOrder,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> impl Future<Output = Result<UserBatchPage, CoreError>> + Send;
+    /// Searches users by display name. A user whose username is exactly the
+    /// query comes first.
+    fn search_users(
+        &self,
+        viewer: Uuid,
+        query: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> impl Future<Output = Result<UserSearchPage, CoreError>> + Send;
     fn get_current_user_info(
         &self,
         user: &User,
         full_info: bool,
     ) -> impl Future<Output = Result<serde_json::Value, CoreError>> + Send;
+    /// Updates the profile. A new email is not applied: it is recorded as
+    /// pending and a verification link is sent to the new address.
     fn update_user(
         &self,
         user: &User,
         req: UpdateUserRequest,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
+    /// Applies the pending email change confirmed by `req`, marks the email
+    /// as verified in Keycloak and notifies the previous address.
+    fn verify_email_change(
+        &self,
+        user: &User,
+        req: VerifyEmailRequest,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<UserFullInfo, CoreError>> + Send;
+    fn update_user_status(
+        &self,
+        user: &User,
+        req: UpdateStatusRequest,
     ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
+    fn get_privacy(
+        &self,
+        user: &User,
+    ) -> impl Future<Output = Result<ProfilePrivacy, CoreError>> + Send;
+    /// Changes who may see each profile field. Omitted fields keep their
+    /// visibility.
+    fn update_privacy(
+        &self,
+        user: &User,
+        req: UpdatePrivacyRequest,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<ProfilePrivacy, CoreError>> + Send;
     fn get_user_settings(
         &self,
         sub: Uuid,
@@ -38,13 +138,216 @@ pub trait UserService: Send + Sync {
         &self,
         sub: Uuid,
         req: UpdateSettingRequest,
+        audit: &AuditContext,
     ) -> impl Future<Output = Result<Setting, CoreError>> + Send;
     fn get_or_create_user(
         &self,
         sub: Uuid,
         username: &str,
     ) -> impl Future<Output = Result<User, CoreError>> + Send;
-    fn generate_profile_picture_url(&self, user: &User) -> impl Future<Output = Result<String, CoreError>> + Send;
+    /// Reserves a new avatar key and returns a short-lived URL to upload the
+    /// image to. The avatar only changes once the upload is confirmed.
+    fn request_profile_picture_upload(
+        &self,
+        user: &User,
+    ) -> impl Future<Output = Result<ProfilePictureRequest, CoreError>> + Send;
+    /// Checks the object uploaded at `key` and makes it the user's avatar.
+    /// The replaced avatar is deleted later by `cleanup_replaced_avatars`.
+    fn confirm_profile_picture(
+        &self,
+        user: &User,
+        key: &str,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
+    /// Same as `request_profile_picture_upload`, for the profile banner.
+    fn request_banner_upload(
+        &self,
+        user: &User,
+    ) -> impl Future<Output = Result<ProfilePictureRequest, CoreError>> + Send;
+    /// Checks the object uploaded at `key` and makes it the user's banner.
+    fn confirm_banner(
+        &self,
+        user: &User,
+        key: &str,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
+    fn remove_banner(
+        &self,
+        user: &User,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<UserBasicInfo, CoreError>> + Send;
+    /// Deletes up to `limit` replaced avatars and banners from the content
+    /// service.
+    /// Returns how many were deleted.
+    fn cleanup_replaced_avatars(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<usize, CoreError>> + Send;
+    /// Restores up to `limit` Keycloak users left modified by a failed
+    /// update. Returns how many were restored; repairs superseded by a later
+    /// change are dropped, and failed ones retried on a later run.
+    fn repair_keycloak_users(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<usize, CoreError>> + Send;
+    fn list_relationships(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<Vec<RelationshipInfo>, CoreError>> + Send;
+    /// Sends a friend request, or accepts the one `target` already sent.
+    fn send_friend_request(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<RelationshipInfo, CoreError>> + Send;
+    fn accept_friend_request(
+        &self,
+        sub: Uuid,
+        requester: Uuid,
+    ) -> impl Future<Output = Result<RelationshipInfo, CoreError>> + Send;
+    fn decline_friend_request(
+        &self,
+        sub: Uuid,
+        requester: Uuid,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+    fn cancel_friend_request(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+    fn remove_friend(
+        &self,
+        sub: Uuid,
+        friend: Uuid,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+    fn block_user(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<RelationshipInfo, CoreError>> + Send;
+    fn unblock_user(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+    /// Soft-deletes the account; it is purged once the grace period is over.
+    fn request_account_deletion(
+        &self,
+        user: &User,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<AccountDeletionInfo, CoreError>> + Send;
+    fn cancel_account_deletion(
+        &self,
+        user: &User,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+    /// Purges up to `limit` accounts whose grace period is over. Returns the
+    /// number of accounts fully purged; failed purges resume on a later run.
+    fn purge_deleted_accounts(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<usize, CoreError>> + Send;
+    /// Queues an export of everything stored about the user. An export already
+    /// in progress is returned instead of queuing another one.
+    fn request_data_export(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<DataExportInfo, CoreError>> + Send;
+    fn get_data_export(
+        &self,
+        sub: Uuid,
+        id: Uuid,
+    ) -> impl Future<Output = Result<DataExportInfo, CoreError>> + Send;
+    /// Document of a completed export that has not expired yet.
+    fn download_data_export(
+        &self,
+        sub: Uuid,
+        id: Uuid,
+    ) -> impl Future<Output = Result<serde_json::Value, CoreError>> + Send;
+    /// Builds up to `limit` queued exports and expires old ones. Returns the
+    /// number of exports completed.
+    fn process_data_exports(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<usize, CoreError>> + Send;
+    /// Changes made to the user's account, most recent first.
+    fn get_audit_log(
+        &self,
+        sub: Uuid,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> impl Future<Output = Result<AuditPage, CoreError>> + Send;
+    /// Audit entries matching `query`, most recent first, for support tooling.
+    fn query_audit_log(
+        &self,
+        query: AuditLogQuery,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> impl Future<Output = Result<AuditPage, CoreError>> + Send;
+    /// Any user, deleted ones included, with every field and the Keycloak
+    /// identity. For moderators.
+    fn admin_get_user(
+        &self,
+        sub: Uuid,
+    ) -> impl Future<Output = Result<AdminUserInfo, CoreError>> + Send;
+    /// Users whose display name matches `query`, or whose sub is `query`,
+    /// regardless of blocks and privacy settings. For moderators.
+    fn admin_search_users(
+        &self,
+        query: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> impl Future<Output = Result<AdminUserPage, CoreError>> + Send;
+    /// Updates the profile of any user. The change is audited under the
+    /// moderator of `audit`.
+    fn admin_update_user(
+        &self,
+        sub: Uuid,
+        req: UpdateUserRequest,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<AdminUserInfo, CoreError>> + Send;
+    /// Suspends the user, replacing any current suspension. With
+    /// `disable_keycloak`, the Keycloak account is disabled as well so the
+    /// user cannot sign in anywhere.
+    fn suspend_user(
+        &self,
+        sub: Uuid,
+        req: SuspendUserRequest,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<AdminUserInfo, CoreError>> + Send;
+    /// Lifts the suspension of the user and re-enables their Keycloak account
+    /// if the suspension disabled it.
+    fn lift_suspension(
+        &self,
+        sub: Uuid,
+        audit: &AuditContext,
+    ) -> impl Future<Output = Result<AdminUserInfo, CoreError>> + Send;
+    /// Lifts up to `limit` suspensions that have expired. Returns the number
+    /// lifted; those whose Keycloak account could not be re-enabled are
+    /// retried on a later run.
+    fn expire_suspensions(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<usize, CoreError>> + Send;
+    /// Applies a Keycloak event to the local `users` table: registrations
+    /// create the row, updates refresh it and deleted accounts are purged
+    /// right away.
+    fn sync_keycloak_event(
+        &self,
+        event: &KeycloakEvent,
+    ) -> impl Future<Output = Result<(), CoreError>> + Send;
+    /// Compares Keycloak accounts with the `users` table, `page_size` rows at
+    /// a time. Unless `dry_run`, missing rows are created and orphaned rows
+    /// are scheduled for deletion.
+    fn reconcile_keycloak_users(
+        &self,
+        dry_run: bool,
+        page_size: usize,
+    ) -> impl Future<Output = Result<ReconcileReport, CoreError>> + Send;
+    /// Schedules the deletion of rows provisioned for Keycloak service
+    /// accounts before service clients were told apart from users. Returns
+    /// how many were flagged.
+    fn flag_service_account_users(&self) -> impl Future<Output = Result<usize, CoreError>> + Send;
 }
 
 #[derive(Clone)]
@@ -52,6 +355,13 @@ pub struct UserServiceImpl<R: UserRepository, K: KeycloakClient, C: ContentServi
     user_repo: R,
     keycloak_client: K,
     content_client: C,
+    user_cache: UserCache,
+    username_lookups: UsernameLookupCache,
+    deletion_grace_period: Duration,
+    export_registry: ExportRegistry<R, K>,
+    image_urls: SignedUrlCache,
+    username_policy: UsernamePolicy,
+    email_verification: EmailVerification,
 }
 
 impl<R: UserRepository, K: KeycloakClient, C: ContentServiceClient> UserServiceImpl<R, K, C> {
@@ -60,739 +370,6692 @@ impl<R: UserRepository, K: KeycloakClient, C: ContentServiceClient> UserServiceI
             user_repo,
             keycloak_client,
             content_client,
+            user_cache: UserCache::default(),
+            username_lookups: UsernameLookupCache::default(),
+            deletion_grace_period: DEFAULT_DELETION_GRACE_PERIOD,
+            export_registry: ExportRegistry::builtin(),
+            image_urls: SignedUrlCache::default(),
+            username_policy: UsernamePolicy::default(),
+            email_verification: EmailVerification::default(),
         }
     }
-}
-
-impl<R: UserRepository + Clone, K: KeycloakClient, C: ContentServiceClient> UserService for UserServiceImpl<R, K, C> {
-    async fn get_user_by_sub(&self, sub: Uuid) -> Result<UserBasicInfo, CoreError> {
-        let user = self
-            .user_repo
-            .get_user_by_sub(sub)
-            .await?
-            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
 
-        Ok(user.into())
+    pub fn with_user_cache(mut self, user_cache: UserCache) -> Self {
+        self.user_cache = user_cache;
+        self
     }
 
-    async fn get_user_by_username(&self, username: &str) -> Result<UserBasicInfo, CoreError> {
-        // Get user ID from Keycloak by username
-        let sub = self
-            .keycloak_client
-            .get_user_id_by_username(username)
-            .await?;
+    pub fn with_deletion_grace_period(mut self, grace_period: Duration) -> Self {
+        self.deletion_grace_period = grace_period;
+        self
+    }
 
-        // Get user from local DB
-        let user = self
-            .user_repo
-            .get_user_by_sub(sub)
-            .await?
-            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+    pub fn with_export_registry(mut self, export_registry: ExportRegistry<R, K>) -> Self {
+        self.export_registry = export_registry;
+        self
+    }
 
-        Ok(user.into())
+    pub fn with_username_policy(mut self, username_policy: UsernamePolicy) -> Self {
+        self.username_policy = username_policy;
+        self
     }
 
-    async fn get_users_by_subs(&self, subs: &[Uuid]) -> Result<Vec<UserBasicInfo>, CoreError> {
-        let users = self.user_repo.get_users_by_subs(subs).await?;
+    pub fn with_email_verification(mut self, email_verification: EmailVerification) -> Self {
+        self.email_verification = email_verification;
+        self
+    }
 
-        Ok(users.into_iter().map(Into::into).collect())
+    pub fn cache_metrics(&self) -> CacheMetricsSnapshot {
+        self.user_cache.metrics()
     }
 
-    async fn get_current_user_info(
-        &self,
-        user: &User,
-        full_info: bool,
-    ) -> Result<serde_json::Value, CoreError> {
-        if full_info {
-            let keycloak_info = self.keycloak_client.get_user_info(user.sub).await?;
+    /// Signed read URL of the uploaded image at `key`, reused while fresh.
+    /// Empty when the content service cannot sign it, so that the profile
+    /// still loads.
+    async fn image_url(&self, sub: Uuid, key: &str) -> String {
+        if let Some(url) = self.image_urls.get(key) {
+            return url;
+        }
 
-            let full = UserFullInfo {
-                sub: user.sub,
-                display_name: user.display_name.clone(),
-                profile_picture: user.profile_picture.clone(),
-                description: user.description.clone(),
-                username: keycloak_info.username,
-                email: keycloak_info.email,
-            };
-            serde_json::to_value(full).map_err(|e| CoreError::InternalError(e.to_string()))
-        } else {
-            let basic: UserBasicInfo = user.clone().into();
-            serde_json::to_value(basic).map_err(|e| CoreError::InternalError(e.to_string()))
+        match self
+            .content_client
+            .sign_profile_picture_url(key, SignedUrlAction::Get, AVATAR_READ_URL_TTL)
+            .await
+        {
+            Ok(url) => {
+                self.image_urls.insert(key, url.clone());
+                url
+            }
+            Err(e) => {
+                tracing::warn!(%sub, key, error = %e, "Failed to sign image URL");
+                String::new()
+            }
         }
     }
 
-    async fn update_user(
-        &self,
-        user: &User,
-        req: UpdateUserRequest,
-    ) -> Result<UserBasicInfo, CoreError> {
-        // Update Keycloak first (if it fails, we don't touch the local DB)
-        if req.has_keycloak_fields() {
-            self.keycloak_client
-                .update_user_info(user.sub, &req)
-                .await?;
+    /// View of `user` as seen by `viewer`, with signed URLs for the uploaded
+    /// avatar and banner. Users without an uploaded avatar keep their
+    /// external picture URL.
+    async fn basic_info(&self, user: User, viewer: Viewer) -> UserBasicInfo {
+        // Project first so that hidden images are never signed
+        let mut user = user.visible_to(viewer);
+        if let Some(key) = user.avatar_key.clone() {
+            user.profile_picture = self.image_url(user.sub, &key).await;
         }
-
-        // Update local DB
-        let updated_user = if req.has_local_fields() {
-            self.user_repo.update_user(user.sub, req).await?
-        } else {
-            user.clone()
+        let banner = match user.banner_key.clone() {
+            Some(key) => Some(self.image_url(user.sub, &key).await),
+            None => None,
         };
 
-        Ok(updated_user.into())
-    }
-
-    async fn get_user_settings(&self, sub: Uuid) -> Result<Setting, CoreError> {
-        self.user_repo
-            .get_setting_by_sub(sub)
-            .await?
-            .ok_or_else(|| CoreError::NotFound("Settings not found".to_string()))
-    }
-
-    async fn update_user_settings(
-        &self,
-        sub: Uuid,
-        req: UpdateSettingRequest,
-    ) -> Result<Setting, CoreError> {
-        Ok(self.user_repo.update_setting(sub, req).await?)
+        let mut info = UserBasicInfo::from(user);
+        info.banner = banner;
+        info
     }
 
-    async fn get_or_create_user(&self, sub: Uuid, username: &str) -> Result<User, CoreError> {
-        Ok(self.user_repo.get_or_create_user(sub, username).await?)
+    async fn basic_infos(&self, users: Vec<User>, viewer: Viewer) -> Vec<UserBasicInfo> {
+        let mut infos = Vec::with_capacity(users.len());
+        for user in users {
+            infos.push(self.basic_info(user, viewer).await);
+        }
+        infos
     }
 
-    async fn generate_profile_picture_url(&self, user: &User) -> Result<String, CoreError> {
-        let url = self.content_client.get_profile_picture_url(user.sub.to_string().as_str()).await
-            .map_err(|e| CoreError::ContentServiceError(e))?;
-        Ok(url)
+    /// Upload intent for a freshly generated image `key`.
+    async fn image_upload_request(&self, key: String) -> Result<ProfilePictureRequest, CoreError> {
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::from_std(AVATAR_UPLOAD_URL_TTL)
+                .map_err(|e| CoreError::InternalError(e.to_string()))?;
+        let url = self
+            .content_client
+            .sign_profile_picture_url(&key, SignedUrlAction::Put, AVATAR_UPLOAD_URL_TTL)
+            .await
+            .map_err(CoreError::ContentServiceError)?;
+
+        Ok(ProfilePictureRequest {
+            url,
+            key,
+            expires_at,
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::models::{KeycloakUserInfo, Setting, User};
-    use crate::services::KeycloakError;
-    use chrono::Utc;
-    use std::collections::HashMap;
-    use std::sync::{Arc, Mutex};
+    /// Checks that an image was uploaded at `key` with an allowed type and
+    /// size. Rejected uploads are deleted right away.
+    async fn check_uploaded_image(
+        &self,
+        sub: Uuid,
+        key: &str,
+        max_size: u64,
+    ) -> Result<(), CoreError> {
+        let metadata = self
+            .content_client
+            .get_profile_picture_metadata(key)
+            .await
+            .map_err(CoreError::ContentServiceError)?
+            .ok_or_else(|| {
+                CoreError::BadRequest("No image was uploaded with this key".to_string())
+            })?;
+
+        let rejection = if !ALLOWED_AVATAR_CONTENT_TYPES.contains(&metadata.content_type.as_str()) {
+            format!("Unsupported image type: {}", metadata.content_type)
+        } else if metadata.size > max_size {
+            format!("Image must be at most {} bytes", max_size)
+        } else {
+            return Ok(());
+        };
 
-    // Mock KeycloakClient
-    #[derive(Clone)]
-    struct MockKeycloakClient {
-        users: Arc<Mutex<HashMap<Uuid, KeycloakUserInfo>>>,
-        should_fail: bool,
+        if let Err(e) = self.content_client.delete_profile_picture(key).await {
+            tracing::warn!(%sub, key, error = %e, "Failed to delete rejected image");
+        }
+        Err(CoreError::BadRequest(rejection))
     }
 
-    impl MockKeycloakClient {
-        fn new() -> Self {
-            Self {
-                users: Arc::new(Mutex::new(HashMap::new())),
-                should_fail: false,
-            }
-        }
+    /// Applies an update across Keycloak and the local DB as a small saga.
+    ///
+    /// Keycloak is updated first; if the local write then fails, the previous
+    /// Keycloak values are restored. A failed restore is recorded and replayed
+    /// by `repair_keycloak_users`.
+    /// Returns the updated user and, if Keycloak was updated, its previous
+    /// values.
+    async fn apply_user_update(
+        &self,
+        user: &User,
+        req: UpdateUserRequest,
+    ) -> Result<(User, Option<KeycloakUserInfo>), CoreError> {
+        let span = tracing::Span::current();
 
-        fn with_user(self, sub: Uuid, info: KeycloakUserInfo) -> Self {
-            self.users.lock().unwrap().insert(sub, info);
-            self
-        }
+        let previous = if req.has_keycloak_fields() {
+            let previous = self.keycloak_client.get_user_info(user.sub).await?;
+            self.keycloak_client.update_user_info(user.sub, &req).await?;
+            Some(previous)
+        } else {
+            None
+        };
 
-        fn failing() -> Self {
-            Self {
-                users: Arc::new(Mutex::new(HashMap::new())),
-                should_fail: true,
-            }
+        if !req.has_local_fields() {
+            span.record("outcome", "applied");
+            return Ok((user.clone(), previous));
         }
-    }
 
-    impl KeycloakClient for MockKeycloakClient {
-        async fn get_user_info(&self, sub: Uuid) -> Result<KeycloakUserInfo, KeycloakError> {
-            if self.should_fail {
-                return Err(KeycloakError::GetUserError("Keycloak unavailable".into()));
+        let applied = req.keycloak_fields();
+        let restore = previous.as_ref().map(|previous| UpdateUserRequest {
+            display_name: None,
+            profile_picture: None,
+            description: None,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+            username: req.username.as_ref().map(|_| previous.username.clone()),
+            email: req.email.as_ref().map(|_| previous.email.clone()),
+        });
+
+        let db_error = match self.user_repo.update_user(user.sub, req).await {
+            Ok(updated_user) => {
+                self.user_cache.invalidate(user.sub).await;
+                span.record("outcome", "applied");
+                return Ok((updated_user, previous));
             }
-            self.users
-                .lock()
-                .unwrap()
-                .get(&sub)
-                .cloned()
-                .ok_or_else(|| KeycloakError::UserNotFound(sub))
-        }
+            Err(e) => e,
+        };
 
-        async fn get_user_id_by_username(&self, username: &str) -> Result<Uuid, KeycloakError> {
-            if self.should_fail {
-                return Err(KeycloakError::GetUserError("Keycloak unavailable".into()));
-            }
-            let users = self.users.lock().unwrap();
-            for (sub, info) in users.iter() {
-                if info.username == username {
-                    return Ok(*sub);
-                }
-            }
-            Err(KeycloakError::UserNotFoundByUsername(username.to_string()))
-        }
+        let Some(restore) = restore else {
+            span.record("outcome", "failed");
+            return Err(db_error.into());
+        };
 
-        async fn update_user_info(
-            &self,
-            sub: Uuid,
-            update_req: &UpdateUserRequest,
-        ) -> Result<(), KeycloakError> {
-            if self.should_fail {
-                return Err(KeycloakError::UpdateUserError(
-                    "Keycloak unavailable".into(),
-                ));
+        tracing::warn!(error = %db_error, "Local update failed, restoring Keycloak user");
+        match self.keycloak_client.update_user_info(user.sub, &restore).await {
+            Ok(()) => {
+                span.record("outcome", "compensated");
             }
-            let mut users = self.users.lock().unwrap();
-            if let Some(user) = users.get_mut(&sub) {
-                if let Some(username) = &update_req.username {
-                    user.username = username.clone();
-                }
-                if let Some(email) = &update_req.email {
-                    user.email = email.clone();
+            Err(compensation_error) => {
+                span.record("outcome", "compensation_failed");
+                tracing::error!(
+                    error = %compensation_error,
+                    "Failed to restore Keycloak user, recording it for repair"
+                );
+                if let Err(e) = self
+                    .user_repo
+                    .record_keycloak_repair(
+                        user.sub,
+                        &restore,
+                        &applied,
+                        &compensation_error.to_string(),
+                    )
+                    .await
+                {
+                    tracing::error!(error = %e, "Failed to record Keycloak repair");
                 }
             }
-            Ok(())
         }
-    }
 
-    // Mock ContentServiceClient
-    #[derive(Clone)]
-    struct MockContentServiceClient {
-        profile_picture_urls: Arc<Mutex<HashMap<String, String>>>,
+        Err(db_error.into())
     }
 
-    impl MockContentServiceClient {
-        fn new() -> Self {
-            Self {
-                profile_picture_urls: Arc::new(Mutex::new(HashMap::new())),
-            }
+    /// Restores the values of `repair` unless Keycloak no longer holds the
+    /// ones it replaced. Returns whether they were restored.
+    async fn replay_keycloak_repair(&self, repair: &KeycloakRepair) -> Result<bool, KeycloakError> {
+        let current = match self.keycloak_client.get_user_info(repair.sub).await {
+            Ok(current) => current,
+            Err(KeycloakError::UserNotFound(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if !repair.is_pending(&current) {
+            return Ok(false);
         }
-    }
 
-    impl ContentServiceClient for MockContentServiceClient {
-        fn get_profile_picture_url(&self, url: &str) -> impl Future<Output = Result<String, String>> + Send {
-            let profile_picture_urls = self.profile_picture_urls.lock().unwrap();
-            let url = profile_picture_urls.get(url).cloned().ok_or_else(|| "Not found".to_string());
-            Box::pin(async move { url })
-        }
+        self.keycloak_client
+            .update_user_info(repair.sub, &repair.restore)
+            .await?;
+        Ok(true)
     }
 
-    // Mock UserRepository
-    #[derive(Clone)]
-    struct MockUserRepository {
-        users: Arc<Mutex<HashMap<Uuid, User>>>,
-        settings: Arc<Mutex<HashMap<Uuid, Setting>>>,
-    }
+    /// User whose Keycloak username is exactly `query`, if any and not
+    /// deleted. Keycloak failures only cost the boost, not the search.
+    ///
+    /// Queries that cannot be usernames, such as ones shorter than
+    /// `MIN_USERNAME_LEN`, never reach Keycloak, and lookups are reused for
+    /// a while so repeated searches do not either.
+    async fn find_exact_username_match(&self, query: &str) -> Result<Option<User>, CoreError> {
+        if !is_valid_username(query) {
+            return Ok(None);
+        }
 
-    impl MockUserRepository {
-        fn new() -> Self {
-            Self {
-                users: Arc::new(Mutex::new(HashMap::new())),
-                settings: Arc::new(Mutex::new(HashMap::new())),
+        let sub = match self.username_lookups.get(query) {
+            Some(sub) => sub,
+            None => {
+                let sub = match self.keycloak_client.get_user_id_by_username(query).await {
+                    Ok(sub) => Some(sub),
+                    Err(KeycloakError::UserNotFoundByUsername(_)) => None,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Exact username lookup failed during search");
+                        return Ok(None);
+                    }
+                };
+                self.username_lookups.insert(query, sub);
+                sub
             }
-        }
+        };
+        let Some(sub) = sub else {
+            return Ok(None);
+        };
 
-        fn with_user(self, user: User) -> Self {
-            self.users.lock().unwrap().insert(user.sub, user);
-            self
+        Ok(self.find_user(sub).await?.filter(|user| !user.is_deleted()))
+    }
+
+    /// Looks a user up by sub, going through the identity cache first.
+    async fn find_user(&self, sub: Uuid) -> Result<Option<User>, CoreError> {
+        if let Some(user) = self.user_cache.get(sub).await {
+            return Ok(Some(user));
         }
 
-        fn with_setting(self, setting: Setting) -> Self {
-            self.settings.lock().unwrap().insert(setting.sub, setting);
-            self
+        let user = self.user_repo.get_user_by_sub(sub).await?;
+        if let Some(user) = &user {
+            self.user_cache.insert(user.clone()).await;
         }
+        Ok(user)
     }
+}
 
-    impl UserRepository for MockUserRepository {
-        async fn create_user(&self, sub: Uuid, username: &str) -> Result<User, sqlx::Error> {
+impl<
+    R: UserRepository
+        + RelationshipRepository
+        + DeletionRepository
+        + ExportRepository
+        + AuditRepository
+        + AvatarRepository
+        + SuspensionRepository
+        + UsernameRepository
+        + EmailChangeRepository,
+    K: KeycloakClient,
+    C: ContentServiceClient,
+> UserServiceImpl<R, K, C>
+{
+    /// User `sub` unless missing, deleted or blocking `viewer`, in which case
+    /// it is reported as not found.
+    async fn find_visible_user(&self, viewer: Uuid, sub: Uuid) -> Result<User, CoreError> {
+        let user = self
+            .find_user(sub)
+            .await?
+            .filter(|user| !user.is_deleted())
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+
+        if viewer != sub && !self.user_repo.get_blockers(viewer, &[sub]).await?.is_empty() {
+            return Err(CoreError::NotFound("User not found".to_string()));
+        }
+        Ok(user)
+    }
+
+    /// Relationship of `sub` towards `target`, when it is of the given kind.
+    async fn find_relationship(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+        kind: RelationshipKind,
+        not_found: &str,
+    ) -> Result<Relationship, CoreError> {
+        self.user_repo
+            .get_relationship(sub, target)
+            .await?
+            .filter(|relationship| relationship.kind == kind)
+            .ok_or_else(|| CoreError::NotFound(not_found.to_string()))
+    }
+
+    async fn relationship_info(
+        &self,
+        relationship: Relationship,
+    ) -> Result<RelationshipInfo, CoreError> {
+        let user = self
+            .find_user(relationship.target_sub)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+
+        Ok(RelationshipInfo {
+            user: self.basic_info(user, Viewer::User(relationship.user_sub)).await,
+            kind: relationship.kind,
+            since: relationship.updated_at,
+        })
+    }
+
+    /// Runs the purge steps that have not completed yet. Each step is
+    /// idempotent, so a purge interrupted at any point can simply be rerun.
+    async fn purge_account(&self, deletion: &UserDeletion) -> Result<(), CoreError> {
+        let sub = deletion.sub;
+
+        if deletion.avatar_deleted_at.is_none() {
+            // Pictures uploaded before avatar keys existed are stored under
+            // the sub
+            let mut keys = vec![sub.to_string()];
+            if let Some(user) = self.user_repo.get_user_by_sub(sub).await? {
+                keys.extend(user.avatar_key);
+                keys.extend(user.banner_key);
+            }
+            for key in &keys {
+                self.content_client
+                    .delete_profile_picture(key)
+                    .await
+                    .map_err(CoreError::ContentServiceError)?;
+            }
+            self.user_repo
+                .mark_deletion_step(sub, DeletionStep::Avatar)
+                .await?;
+        }
+
+        if deletion.keycloak_deleted_at.is_none() {
+            self.keycloak_client.delete_user(sub).await?;
+            self.user_repo
+                .mark_deletion_step(sub, DeletionStep::Keycloak)
+                .await?;
+        }
+
+        self.user_repo.anonymize_user(sub).await?;
+        self.user_cache.invalidate(sub).await;
+        Ok(())
+    }
+
+    /// Username of the event, or the current one from Keycloak when the
+    /// event does not carry it. `None` if the Keycloak account is gone.
+    async fn event_username(&self, event: &KeycloakEvent) -> Result<Option<String>, CoreError> {
+        if let Some(username) = &event.username {
+            return Ok(Some(username.clone()));
+        }
+        match self.keycloak_client.get_user_info(event.sub).await {
+            Ok(info) => Ok(Some(info.username)),
+            Err(KeycloakError::UserNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Schedules the deletion of a user whose Keycloak account is gone or
+    /// belongs to a service client. The Keycloak account is left alone and
+    /// the grace period still applies, so a wrongly flagged user can be
+    /// restored.
+    async fn flag_orphaned_user(&self, sub: Uuid) -> Result<(), CoreError> {
+        let grace_period = chrono::Duration::from_std(self.deletion_grace_period)
+            .map_err(|e| CoreError::InternalError(e.to_string()))?;
+
+        self.user_repo
+            .schedule_deletion(sub, chrono::Utc::now() + grace_period)
+            .await?;
+        self.user_repo
+            .mark_deletion_step(sub, DeletionStep::Keycloak)
+            .await?;
+        self.user_cache.invalidate(sub).await;
+        Ok(())
+    }
+
+    async fn find_data_export(&self, sub: Uuid, id: Uuid) -> Result<DataExport, CoreError> {
+        self.user_repo
+            .get_export(sub, id)
+            .await?
+            .ok_or_else(|| CoreError::NotFound(format!("Data export {} not found", id)))
+    }
+
+    async fn build_data_export(&self, export: &DataExport) -> Result<(), CoreError> {
+        let document = self
+            .export_registry
+            .export(&self.user_repo, &self.keycloak_client, export.sub)
+            .await?;
+        let retention = chrono::Duration::from_std(DATA_EXPORT_RETENTION)
+            .map_err(|e| CoreError::InternalError(e.to_string()))?;
+
+        self.user_repo
+            .complete_export(export.id, &document, chrono::Utc::now() + retention)
+            .await?;
+        Ok(())
+    }
+
+    /// Enforces the username policy on renaming `sub` to `username`:
+    /// reserved and blocklisted names, the delay between changes, and names
+    /// taken, held or confusable with those of other users. Returns the
+    /// current username, or `None` if it does not change.
+    async fn check_username_change(
+        &self,
+        sub: Uuid,
+        username: &str,
+    ) -> Result<Option<String>, CoreError> {
+        let current = self.keycloak_client.get_user_info(sub).await?;
+        if current.username.eq_ignore_ascii_case(username) {
+            return Ok(None);
+        }
+
+        match self.username_policy.check(username) {
+            Ok(()) => {}
+            Err(UsernameRejection::Reserved) => {
+                return Err(CoreError::Conflict("Username is reserved".to_string()));
+            }
+            Err(UsernameRejection::Blocked) => {
+                let mut errors = ValidationErrors::default();
+                errors.add("username", "is not allowed");
+                return Err(CoreError::Validation(errors));
+            }
+        }
+
+        if let Some(next_change_at) = self
+            .user_repo
+            .last_username_change(sub)
+            .await?
+            .and_then(|last_change| self.username_policy.next_change_at(last_change))
+        {
+            return Err(CoreError::TooManyRequests(format!(
+                "Username can be changed again after {}",
+                next_change_at.to_rfc3339()
+            )));
+        }
+
+        match self.keycloak_client.get_user_id_by_username(username).await {
+            Ok(owner) if owner != sub => {
+                return Err(CoreError::Conflict("Username is already taken".to_string()));
+            }
+            Ok(_) | Err(KeycloakError::UserNotFoundByUsername(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if self
+            .user_repo
+            .find_username_conflict(sub, username)
+            .await?
+            .is_some()
+        {
+            return Err(username_too_similar());
+        }
+
+        Ok(Some(current.username))
+    }
+
+    /// Records `email` as the pending email of `sub` and queues the link
+    /// verifying it. Does nothing if it already is the current email.
+    async fn request_email_change(
+        &self,
+        sub: Uuid,
+        email: &str,
+        audit: &AuditContext,
+    ) -> Result<(), CoreError> {
+        let current = self.keycloak_client.get_user_info(sub).await?;
+        if current.email.eq_ignore_ascii_case(email) {
+            return Ok(());
+        }
+
+        let change = PendingEmailChange {
+            sub,
+            old_email: current.email.clone(),
+            new_email: email.to_string(),
+            nonce: Uuid::new_v4(),
+            requested_at: chrono::Utc::now(),
+            expires_at: self.email_verification.expires_at(),
+        };
+        let verification = self.email_verification.verification_email(&EmailChangeClaims {
+            sub,
+            email: change.new_email.clone(),
+            nonce: change.nonce,
+            expires_at: change.expires_at,
+        });
+        self.user_repo
+            .request_email_change(&change, &verification)
+            .await?;
+
+        let requested = FieldChange::new(
+            "email",
+            Some(redact_email(&current.email).into()),
+            Some(redact_email(email).into()),
+        );
+        self.record_audit(sub, AuditAction::EmailChangeRequested, &[requested], audit)
+            .await;
+        Ok(())
+    }
+
+    /// Appends to the audit log. The change itself already happened, so a
+    /// failed write is logged rather than failing the request.
+    async fn record_audit(
+        &self,
+        target: Uuid,
+        action: AuditAction,
+        changes: &[FieldChange],
+        audit: &AuditContext,
+    ) {
+        if let Err(e) = self
+            .user_repo
+            .record_audit(target, action, changes, audit)
+            .await
+        {
+            tracing::error!(sub = %target, ?action, error = %e, "Failed to record audit entry");
+        }
+    }
+}
+
+fn username_too_similar() -> CoreError {
+    CoreError::Conflict(
+        "Username is too similar to an existing or recently released username".to_string(),
+    )
+}
+
+/// Fields of `req` that differ from the current values. Emails are redacted.
+fn profile_changes(
+    user: &User,
+    previous: Option<&KeycloakUserInfo>,
+    req: &UpdateUserRequest,
+) -> Vec<FieldChange> {
+    let local = [
+        ("display_name", &user.display_name, &req.display_name),
+        ("profile_picture", &user.profile_picture, &req.profile_picture),
+        ("description", &user.description, &req.description),
+    ];
+    let keycloak = [
+        (
+            "username",
+            previous.map(|p| p.username.clone()),
+            req.username.clone(),
+        ),
+        (
+            "email",
+            previous.map(|p| redact_email(&p.email)),
+            req.email.as_deref().map(redact_email),
+        ),
+    ];
+
+    let mut changes: Vec<FieldChange> = local
+        .into_iter()
+        .filter_map(|(field, before, after)| {
+            let after = after.as_ref().filter(|after| *after != before)?;
+            Some(FieldChange::new(
+                field,
+                Some(before.clone().into()),
+                Some(after.clone().into()),
+            ))
+        })
+        .collect();
+    let clearable = [
+        ("accent_color", &user.accent_color, &req.accent_color),
+        ("pronouns", &user.pronouns, &req.pronouns),
+    ];
+    changes.extend(clearable.into_iter().filter_map(|(field, before, after)| {
+        // An empty value clears the field
+        let after = after.clone().map(|after| (!after.is_empty()).then_some(after))?;
+        (after != *before).then(|| {
+            FieldChange::new(field, before.clone().map(Into::into), after.map(Into::into))
+        })
+    }));
+    if let Some(links) = req.links.as_ref().filter(|links| **links != user.links) {
+        changes.push(FieldChange::new(
+            "links",
+            Some(serde_json::json!(user.links)),
+            Some(serde_json::json!(links)),
+        ));
+    }
+    changes.extend(keycloak.into_iter().filter_map(|(field, before, after)| {
+        let after = after.filter(|after| Some(after) != before.as_ref())?;
+        Some(FieldChange::new(
+            field,
+            before.map(Into::into),
+            Some(after.into()),
+        ))
+    }));
+    changes
+}
+
+/// Settings of `req` whose stored value changes. Resets are recorded with no
+/// `after` value.
+fn settings_changes(stored: &SettingValues, req: &UpdateSettingRequest) -> Vec<FieldChange> {
+    req.values
+        .iter()
+        .filter_map(|(key, value)| {
+            let before = stored.get(key).cloned();
+            let after = (!value.is_null()).then(|| value.clone());
+            (before != after).then(|| FieldChange::new(key, before, after))
+        })
+        .collect()
+}
+
+impl<
+    R: UserRepository
+        + RelationshipRepository
+        + DeletionRepository
+        + ExportRepository
+        + AuditRepository
+        + AvatarRepository
+        + SuspensionRepository
+        + UsernameRepository
+        + EmailChangeRepository
+        + Clone,
+    K: KeycloakClient,
+    C: ContentServiceClient,
+> UserService for UserServiceImpl<R, K, C>
+{
+    async fn get_user_by_sub(&self, viewer: Uuid, sub: Uuid) -> Result<UserBasicInfo, CoreError> {
+        let user = self.find_visible_user(viewer, sub).await?;
+
+        Ok(self.basic_info(user, Viewer::User(viewer)).await)
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<UserBasicInfo, CoreError> {
+        // Get user ID from Keycloak by username
+        let sub = self
+            .keycloak_client
+            .get_user_id_by_username(username)
+            .await?;
+
+        // Get user from local DB
+        let user = self
+            .find_user(sub)
+            .await?
+            .filter(|user| !user.is_deleted())
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+
+        Ok(self.basic_info(user, Viewer::Anonymous).await)
+    }
+
+    async fn get_users_by_subs(
+        &self,
+        viewer: Viewer,
+        subs: &[Uuid],
+        order: BatchOrder,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<UserBatchPage, CoreError> {
+        let limit = limit.max(1);
+        let mut requested = Vec::with_capacity(subs.len());
+        for sub in subs {
+            if !requested.contains(sub) {
+                requested.push(*sub);
+            }
+        }
+        if order == BatchOrder::Sub {
+            requested.sort();
+        }
+
+        // The cursor is the position of the last sub covered, 0 before the first page
+        let after = cursor
+            .map(decode_cursor::<i64>)
+            .transpose()?
+            .unwrap_or(0)
+            .clamp(0, requested.len() as i64);
+        let viewer_sub = match viewer {
+            Viewer::User(viewer) => Some(viewer),
+            Viewer::Anonymous => None,
+        };
+        let hits = self
+            .user_repo
+            .lookup_users(viewer_sub, &requested, after, limit as i64 + 1)
+            .await?;
+        let (hits, next_cursor) = paginate(hits, limit, |hit| hit.position);
+
+        // A page covers the subs up to its last user, or all remaining ones on the last page
+        let covered = match (&next_cursor, hits.last()) {
+            (Some(_), Some(last)) => last.position,
+            _ => requested.len() as i64,
+        };
+        let not_found = requested[after as usize..covered as usize]
+            .iter()
+            .filter(|sub| !hits.iter().any(|hit| hit.user.sub == **sub))
+            .copied()
+            .collect();
+        let users = hits.into_iter().map(|hit| hit.user).collect();
+
+        Ok(UserBatchPage {
+            users: self.basic_infos(users, viewer).await,
+            not_found,
+            next_cursor,
+        })
+    }
+
+    async fn search_users(
+        &self,
+        viewer: Uuid,
+        query: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<UserSearchPage, CoreError> {
+        let query = normalize(query).to_lowercase();
+        if query.is_empty() {
+            return Err(CoreError::BadRequest("Search query must not be empty".to_string()));
+        }
+        if query.chars().count() > MAX_SEARCH_QUERY_LEN {
+            return Err(CoreError::BadRequest(format!(
+                "Search query must be at most {} characters",
+                MAX_SEARCH_QUERY_LEN
+            )));
+        }
+        let limit = limit.max(1);
+        let after = cursor.map(decode_cursor::<SearchCursor>).transpose()?;
+
+        let exact_match = match self.find_exact_username_match(&query).await? {
+            Some(user) if self.user_repo.get_blockers(viewer, &[user.sub]).await?.is_empty() => {
+                Some(user)
+            }
+            _ => None,
+        };
+        let exact_sub = exact_match.as_ref().map(|user| user.sub);
+
+        // Extra hits tell whether there is a next page, even once the exact
+        // match has been filtered out
+        let hits = self
+            .user_repo
+            .search_users(
+                Some(viewer),
+                &query,
+                after.unwrap_or_else(SearchCursor::start),
+                limit as i64 + 2,
+            )
+            .await?;
+
+        let mut users = Vec::with_capacity(limit);
+        let mut position = after.unwrap_or_else(SearchCursor::start);
+        if after.is_none()
+            && let Some(user) = exact_match
+        {
+            users.push(self.basic_info(user, Viewer::User(viewer)).await);
+        }
+
+        let mut next_cursor = None;
+        for hit in hits.iter().filter(|hit| Some(hit.user.sub) != exact_sub) {
+            if users.len() == limit {
+                next_cursor = Some(encode_cursor(&position));
+                break;
+            }
+            position = SearchCursor::from(hit);
+            users.push(self.basic_info(hit.user.clone(), Viewer::User(viewer)).await);
+        }
+
+        Ok(UserSearchPage { users, next_cursor })
+    }
+
+    async fn get_current_user_info(
+        &self,
+        user: &User,
+        full_info: bool,
+    ) -> Result<serde_json::Value, CoreError> {
+        let basic = self.basic_info(user.clone(), Viewer::User(user.sub)).await;
+        if full_info {
+            let keycloak_info = self.keycloak_client.get_user_info(user.sub).await?;
+
+            let mut full = UserFullInfo::new(basic, keycloak_info);
+            full.pending_email = self
+                .user_repo
+                .get_email_change(user.sub)
+                .await?
+                .filter(|change| !change.is_expired())
+                .map(|change| change.new_email);
+            serde_json::to_value(full).map_err(|e| CoreError::InternalError(e.to_string()))
+        } else {
+            serde_json::to_value(basic).map_err(|e| CoreError::InternalError(e.to_string()))
+        }
+    }
+
+    async fn update_user(
+        &self,
+        user: &User,
+        req: UpdateUserRequest,
+        audit: &AuditContext,
+    ) -> Result<UserBasicInfo, CoreError> {
+        let mut req = validate_update_user_request(req).map_err(CoreError::Validation)?;
+        // Applied once verified, see `verify_email_change`
+        let new_email = req.email.take();
+
+        let span = tracing::info_span!(
+            "update_user",
+            sub = %user.sub,
+            keycloak_fields = req.has_keycloak_fields(),
+            local_fields = req.has_local_fields(),
+            outcome = tracing::field::Empty,
+        );
+
+        async move {
+            // The new username is claimed before it is applied, so that
+            // concurrent renames to confusable names cannot both succeed
+            let mut rename = None;
+            if let Some(username) = &req.username
+                && let Some(previous) = self.check_username_change(user.sub, username).await?
+            {
+                let claimed = self
+                    .user_repo
+                    .record_username_change(
+                        user.sub,
+                        &previous,
+                        username,
+                        self.username_policy.held_until(),
+                    )
+                    .await?;
+                if !claimed {
+                    return Err(username_too_similar());
+                }
+                rename = Some((previous, username.clone()));
+            }
+
+            let requested = req.clone();
+            let (updated_user, previous) = match self.apply_user_update(user, req).await {
+                Ok(applied) => applied,
+                Err(e) => {
+                    if let Some((previous, username)) = &rename
+                        && let Err(revert) = self
+                            .user_repo
+                            .revert_username_change(user.sub, previous, username)
+                            .await
+                    {
+                        tracing::error!(error = %revert, "Failed to revert username claim");
+                    }
+                    return Err(e);
+                }
+            };
+
+            let changes = profile_changes(user, previous.as_ref(), &requested);
+            if !changes.is_empty() {
+                self.record_audit(user.sub, AuditAction::ProfileUpdated, &changes, audit)
+                    .await;
+            }
+
+            if let Some(email) = &new_email {
+                self.request_email_change(user.sub, email, audit).await?;
+            }
+
+            Ok(self.basic_info(updated_user, Viewer::User(user.sub)).await)
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn verify_email_change(
+        &self,
+        user: &User,
+        req: VerifyEmailRequest,
+        audit: &AuditContext,
+    ) -> Result<UserFullInfo, CoreError> {
+        let invalid = || CoreError::BadRequest("Invalid or expired verification token".to_string());
+        let claims = self
+            .email_verification
+            .verify(&req.token)
+            .filter(|claims| claims.sub == user.sub)
+            .ok_or_else(invalid)?;
+        // A newer request replaces the nonce, invalidating older links
+        let change = self
+            .user_repo
+            .get_email_change(user.sub)
+            .await?
+            .filter(|change| {
+                change.nonce == claims.nonce
+                    && change.new_email == claims.email
+                    && !change.is_expired()
+            })
+            .ok_or_else(invalid)?;
+
+        let current = self.keycloak_client.get_user_info(user.sub).await?;
+        self.keycloak_client
+            .update_email(user.sub, &change.new_email, true)
+            .await?;
+
+        // Retrying after a failure here sets the same email again, so the
+        // pending change is only removed once the notice is queued. The
+        // notice goes to the address of the request: on a retry, Keycloak
+        // already holds the new one.
+        let notice = self
+            .email_verification
+            .change_notice(&change.old_email, &change.new_email);
+        self.user_repo
+            .complete_email_change(user.sub, change.nonce, &notice)
+            .await?;
+
+        let applied = FieldChange::new(
+            "email",
+            Some(redact_email(&change.old_email).into()),
+            Some(redact_email(&change.new_email).into()),
+        );
+        self.record_audit(user.sub, AuditAction::ProfileUpdated, &[applied], audit)
+            .await;
+
+        let basic = self.basic_info(user.clone(), Viewer::User(user.sub)).await;
+        Ok(UserFullInfo::new(
+            basic,
+            KeycloakUserInfo {
+                username: current.username,
+                email: change.new_email,
+            },
+        ))
+    }
+
+    async fn update_user_status(
+        &self,
+        user: &User,
+        req: UpdateStatusRequest,
+    ) -> Result<UserBasicInfo, CoreError> {
+        req.validate().map_err(CoreError::BadRequest)?;
+
+        let updated_user = self.user_repo.update_status(user.sub, req).await?;
+        self.user_cache.invalidate(user.sub).await;
+
+        Ok(self.basic_info(updated_user, Viewer::User(user.sub)).await)
+    }
+
+    async fn get_privacy(&self, user: &User) -> Result<ProfilePrivacy, CoreError> {
+        Ok(user.privacy.clone())
+    }
+
+    async fn update_privacy(
+        &self,
+        user: &User,
+        req: UpdatePrivacyRequest,
+        audit: &AuditContext,
+    ) -> Result<ProfilePrivacy, CoreError> {
+        let req = validate_update_privacy_request(req).map_err(CoreError::Validation)?;
+
+        let mut privacy = user.privacy.clone();
+        privacy.apply(req);
+        if privacy == user.privacy {
+            return Ok(privacy);
+        }
+
+        let updated_user = self.user_repo.update_privacy(user.sub, &privacy).await?;
+        self.user_cache.invalidate(user.sub).await;
+
+        let changes: Vec<FieldChange> = user
+            .privacy
+            .fields()
+            .into_iter()
+            .zip(updated_user.privacy.fields())
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((field, before), (_, after))| {
+                FieldChange::new(
+                    field,
+                    Some(serde_json::json!(before)),
+                    Some(serde_json::json!(after)),
+                )
+            })
+            .collect();
+        self.record_audit(user.sub, AuditAction::PrivacyUpdated, &changes, audit)
+            .await;
+
+        Ok(updated_user.privacy)
+    }
+
+    async fn get_user_settings(&self, sub: Uuid) -> Result<Setting, CoreError> {
+        let mut setting = self
+            .user_repo
+            .get_setting_by_sub(sub)
+            .await?
+            .unwrap_or_else(|| Setting::defaults(sub));
+        setting.values = SettingsRegistry::builtin().materialize(&setting.values);
+        Ok(setting)
+    }
+
+    async fn update_user_settings(
+        &self,
+        sub: Uuid,
+        req: UpdateSettingRequest,
+        audit: &AuditContext,
+    ) -> Result<Setting, CoreError> {
+        let registry = SettingsRegistry::builtin();
+        registry
+            .validate(&req.values)
+            .map_err(CoreError::BadRequest)?;
+
+        let stored = self
+            .user_repo
+            .get_setting_by_sub(sub)
+            .await?
+            .map(|setting| setting.values)
+            .unwrap_or_default();
+        let changes = settings_changes(&stored, &req);
+
+        let mut setting = self.user_repo.update_setting(sub, req).await?;
+        if !changes.is_empty() {
+            self.record_audit(sub, AuditAction::SettingsUpdated, &changes, audit)
+                .await;
+        }
+
+        setting.values = registry.materialize(&setting.values);
+        Ok(setting)
+    }
+
+    async fn get_or_create_user(&self, sub: Uuid, username: &str) -> Result<User, CoreError> {
+        if let Some(user) = self.user_cache.get(sub).await {
+            return Ok(user);
+        }
+
+        let user = self.user_repo.get_or_create_user(sub, username).await?;
+        self.user_cache.insert(user.clone()).await;
+        Ok(user)
+    }
+
+    async fn request_profile_picture_upload(
+        &self,
+        user: &User,
+    ) -> Result<ProfilePictureRequest, CoreError> {
+        self.image_upload_request(new_avatar_key(user.sub)).await
+    }
+
+    async fn confirm_profile_picture(
+        &self,
+        user: &User,
+        key: &str,
+        audit: &AuditContext,
+    ) -> Result<UserBasicInfo, CoreError> {
+        if !is_avatar_key_of(user.sub, key) {
+            return Err(CoreError::BadRequest(
+                "Unknown profile picture key".to_string(),
+            ));
+        }
+
+        self.check_uploaded_image(user.sub, key, MAX_AVATAR_SIZE).await?;
+
+        let updated_user = self.user_repo.set_avatar(user.sub, key).await?;
+        self.user_cache.invalidate(user.sub).await;
+
+        if user.avatar_key.as_deref() != Some(key) {
+            let before = user
+                .avatar_key
+                .clone()
+                .unwrap_or_else(|| user.profile_picture.clone());
+            let change = FieldChange::new("profile_picture", Some(before.into()), Some(key.into()));
+            self.record_audit(user.sub, AuditAction::ProfileUpdated, &[change], audit)
+                .await;
+        }
+
+        Ok(self.basic_info(updated_user, Viewer::User(user.sub)).await)
+    }
+
+    async fn request_banner_upload(&self, user: &User) -> Result<ProfilePictureRequest, CoreError> {
+        self.image_upload_request(new_banner_key(user.sub)).await
+    }
+
+    async fn confirm_banner(
+        &self,
+        user: &User,
+        key: &str,
+        audit: &AuditContext,
+    ) -> Result<UserBasicInfo, CoreError> {
+        if !is_banner_key_of(user.sub, key) {
+            return Err(CoreError::BadRequest("Unknown banner key".to_string()));
+        }
+        self.check_uploaded_image(user.sub, key, MAX_BANNER_SIZE).await?;
+
+        let updated_user = self.user_repo.set_banner(user.sub, Some(key)).await?;
+        self.user_cache.invalidate(user.sub).await;
+
+        if user.banner_key.as_deref() != Some(key) {
+            let change = FieldChange::new(
+                "banner",
+                user.banner_key.clone().map(Into::into),
+                Some(key.into()),
+            );
+            self.record_audit(user.sub, AuditAction::ProfileUpdated, &[change], audit)
+                .await;
+        }
+
+        Ok(self.basic_info(updated_user, Viewer::User(user.sub)).await)
+    }
+
+    async fn remove_banner(
+        &self,
+        user: &User,
+        audit: &AuditContext,
+    ) -> Result<UserBasicInfo, CoreError> {
+        let Some(previous) = &user.banner_key else {
+            return Ok(self.basic_info(user.clone(), Viewer::User(user.sub)).await);
+        };
+
+        let updated_user = self.user_repo.set_banner(user.sub, None).await?;
+        self.user_cache.invalidate(user.sub).await;
+
+        let change = FieldChange::new("banner", Some(previous.clone().into()), None);
+        self.record_audit(user.sub, AuditAction::ProfileUpdated, &[change], audit)
+            .await;
+
+        Ok(self.basic_info(updated_user, Viewer::User(user.sub)).await)
+    }
+
+    async fn cleanup_replaced_avatars(&self, limit: i64) -> Result<usize, CoreError> {
+        let keys = self
+            .user_repo
+            .claim_avatar_cleanups(limit, AVATAR_CLEANUP_LEASE)
+            .await?;
+
+        let mut deleted = 0;
+        for key in keys {
+            match self.content_client.delete_profile_picture(&key).await {
+                Ok(()) => {
+                    self.user_repo.complete_avatar_cleanup(&key).await?;
+                    deleted += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(key, error = %e, "Failed to delete replaced avatar");
+                    self.user_repo
+                        .record_avatar_cleanup_failure(&key, &e)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    async fn repair_keycloak_users(&self, limit: i64) -> Result<usize, CoreError> {
+        // Newest first, so that stacked repairs of one user unwind in order
+        let repairs = self
+            .user_repo
+            .claim_keycloak_repairs(limit, KEYCLOAK_REPAIR_LEASE)
+            .await?;
+
+        let mut restored = 0;
+        for repair in repairs {
+            let sub = repair.sub;
+            match self.replay_keycloak_repair(&repair).await {
+                Ok(applied) => {
+                    self.user_repo.complete_keycloak_repair(repair.id).await?;
+                    if applied {
+                        self.user_cache.invalidate(sub).await;
+                        restored += 1;
+                        tracing::info!(%sub, "Keycloak user restored");
+                    } else {
+                        tracing::info!(%sub, "Keycloak repair superseded, dropping it");
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(%sub, error = %e, "Keycloak repair failed, will retry");
+                    self.user_repo
+                        .record_keycloak_repair_failure(repair.id, &e.to_string())
+                        .await?;
+                }
+            }
+        }
+
+        Ok(restored)
+    }
+
+    async fn list_relationships(&self, sub: Uuid) -> Result<Vec<RelationshipInfo>, CoreError> {
+        let relationships = self.user_repo.list_relationships(sub).await?;
+        let targets: Vec<Uuid> = relationships.iter().map(|r| r.target_sub).collect();
+        let users = self.user_repo.get_users_by_subs(&targets).await?;
+
+        let mut infos = Vec::with_capacity(relationships.len());
+        for relationship in relationships {
+            // Accounts pending deletion are hidden, as in lookups
+            let Some(user) = users
+                .iter()
+                .find(|u| u.sub == relationship.target_sub && !u.is_deleted())
+            else {
+                continue;
+            };
+            infos.push(RelationshipInfo {
+                user: self.basic_info(user.clone(), Viewer::User(sub)).await,
+                kind: relationship.kind,
+                since: relationship.updated_at,
+            });
+        }
+        Ok(infos)
+    }
+
+    async fn send_friend_request(
+        &self,
+        sub: Uuid,
+        target: Uuid,
+    ) -> Result<RelationshipInfo, CoreError> {
+        if sub == target {
+            return Err(CoreError::BadRequest(
+                "You cannot send a friend request to yourself".to_string(),
+            ));
+        }
+        self.find_visible_user(sub, target).await?;
+
+        let existing = self.user_repo.get_relationship(sub, target).await?;
+        let relationship = match existing.as_ref().map(|r| r.kind) {
+            None => self.user_repo.create_friend_request(sub, target).await?,
+            Some(RelationshipKind::PendingIncoming) => {
+                self.user_repo.accept_friend_request(sub, target).await?
+            }
+            Some(RelationshipKind::PendingOutgoing) => existing.unwrap(),
+            Some(RelationshipKind::Friend) => {
+                return Err(CoreError::BadRequest(
+                    "You are already friends with this user".to_string(),
+                ));
+            }
+            Some(RelationshipKind::Blocked) => {
+                return Err(CoreError::BadRequest(
+                    "Unblock this user before sending a friend request".to_string(),
+                ));
+            }
+        };
+
+        self.relationship_info(relationship).await
+    }
+
+    async fn accept_friend_request(
+        &self,
+        sub: Uuid,
+        requester: Uuid,
+    ) -> Result<RelationshipInfo, CoreError> {
+        self.find_relationship(
+            sub,
+            requester,
+            RelationshipKind::PendingIncoming,
+            "Friend request not found",
+        )
+        .await?;
+
+        let relationship = self.user_repo.accept_friend_request(sub, requester).await?;
+        self.relationship_info(relationship).await
+    }
+
+    async fn decline_friend_request(&self, sub: Uuid, requester: Uuid) -> Result<(), CoreError> {
+        self.find_relationship(
+            sub,
+            requester,
+            RelationshipKind::PendingIncoming,
+            "Friend request not found",
+        )
+        .await?;
+
+        self.user_repo.delete_relationship(sub, requester).await?;
+        Ok(())
+    }
+
+    async fn cancel_friend_request(&self, sub: Uuid, target: Uuid) -> Result<(), CoreError> {
+        self.find_relationship(
+            sub,
+            target,
+            RelationshipKind::PendingOutgoing,
+            "Friend request not found",
+        )
+        .await?;
+
+        self.user_repo.delete_relationship(sub, target).await?;
+        Ok(())
+    }
+
+    async fn remove_friend(&self, sub: Uuid, friend: Uuid) -> Result<(), CoreError> {
+        self.find_relationship(sub, friend, RelationshipKind::Friend, "Friend not found")
+            .await?;
+
+        self.user_repo.delete_relationship(sub, friend).await?;
+        Ok(())
+    }
+
+    async fn block_user(&self, sub: Uuid, target: Uuid) -> Result<RelationshipInfo, CoreError> {
+        if sub == target {
+            return Err(CoreError::BadRequest("You cannot block yourself".to_string()));
+        }
+        self.find_user(target)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+
+        let relationship = self.user_repo.block_user(sub, target).await?;
+        self.relationship_info(relationship).await
+    }
+
+    async fn unblock_user(&self, sub: Uuid, target: Uuid) -> Result<(), CoreError> {
+        self.find_relationship(sub, target, RelationshipKind::Blocked, "Blocked user not found")
+            .await?;
+
+        self.user_repo.unblock_user(sub, target).await?;
+        Ok(())
+    }
+
+    async fn request_account_deletion(
+        &self,
+        user: &User,
+        audit: &AuditContext,
+    ) -> Result<AccountDeletionInfo, CoreError> {
+        let grace_period = chrono::Duration::from_std(self.deletion_grace_period)
+            .map_err(|e| CoreError::InternalError(e.to_string()))?;
+
+        let deletion = self
+            .user_repo
+            .schedule_deletion(user.sub, chrono::Utc::now() + grace_period)
+            .await?;
+        self.user_cache.invalidate(user.sub).await;
+        self.record_audit(
+            user.sub,
+            AuditAction::AccountDeletionRequested,
+            &[FieldChange::new(
+                "purge_after",
+                None,
+                Some(deletion.purge_after.to_rfc3339().into()),
+            )],
+            audit,
+        )
+        .await;
+
+        tracing::info!(sub = %user.sub, purge_after = %deletion.purge_after, "Account deletion requested");
+        Ok(deletion.into())
+    }
+
+    async fn cancel_account_deletion(
+        &self,
+        user: &User,
+        audit: &AuditContext,
+    ) -> Result<(), CoreError> {
+        let deletion = self
+            .user_repo
+            .get_deletion(user.sub)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("No pending account deletion".to_string()))?;
+        if deletion.purge_started() {
+            return Err(CoreError::BadRequest(
+                "The account is already being purged".to_string(),
+            ));
+        }
+
+        if !self.user_repo.cancel_deletion(user.sub).await? {
+            return Err(CoreError::BadRequest(
+                "The account is already being purged".to_string(),
+            ));
+        }
+        self.user_cache.invalidate(user.sub).await;
+        self.record_audit(user.sub, AuditAction::AccountDeletionCancelled, &[], audit)
+            .await;
+        Ok(())
+    }
+
+    async fn purge_deleted_accounts(&self, limit: i64) -> Result<usize, CoreError> {
+        let deletions = self
+            .user_repo
+            .claim_due_deletions(limit, DELETION_PURGE_LEASE)
+            .await?;
+
+        let mut purged = 0;
+        for deletion in &deletions {
+            match self.purge_account(deletion).await {
+                Ok(()) => {
+                    purged += 1;
+                    tracing::info!(sub = %deletion.sub, "Account purged");
+                }
+                Err(e) => {
+                    tracing::warn!(sub = %deletion.sub, error = %e, "Account purge failed, will retry");
+                    self.user_repo
+                        .record_deletion_failure(deletion.sub, &e.to_string())
+                        .await?;
+                }
+            }
+        }
+
+        Ok(purged)
+    }
+
+    async fn request_data_export(&self, sub: Uuid) -> Result<DataExportInfo, CoreError> {
+        let export = self.user_repo.create_export(sub).await?;
+        Ok(export.into())
+    }
+
+    async fn get_data_export(&self, sub: Uuid, id: Uuid) -> Result<DataExportInfo, CoreError> {
+        Ok(self.find_data_export(sub, id).await?.into())
+    }
+
+    async fn download_data_export(
+        &self,
+        sub: Uuid,
+        id: Uuid,
+    ) -> Result<serde_json::Value, CoreError> {
+        let export = self.find_data_export(sub, id).await?;
+        match export.status {
+            DataExportStatus::Pending | DataExportStatus::Running => {
+                return Err(CoreError::BadRequest(
+                    "Data export is not ready yet".to_string(),
+                ));
+            }
+            DataExportStatus::Failed => {
+                return Err(CoreError::BadRequest("Data export failed".to_string()));
+            }
+            DataExportStatus::Completed | DataExportStatus::Expired => {}
+        }
+
+        self.user_repo
+            .get_export_document(sub, id)
+            .await?
+            .ok_or_else(|| CoreError::NotFound(format!("Data export {} has expired", id)))
+    }
+
+    async fn process_data_exports(&self, limit: i64) -> Result<usize, CoreError> {
+        let expired = self.user_repo.expire_exports().await?;
+        if expired > 0 {
+            tracing::info!(expired, "Expired data exports");
+        }
+
+        let exports = self
+            .user_repo
+            .claim_pending_exports(limit, DATA_EXPORT_LEASE)
+            .await?;
+
+        let mut completed = 0;
+        for export in &exports {
+            match self.build_data_export(export).await {
+                Ok(()) => {
+                    completed += 1;
+                    tracing::info!(sub = %export.sub, export_id = %export.id, "Data export completed");
+                }
+                Err(e) => {
+                    tracing::warn!(sub = %export.sub, export_id = %export.id, error = %e, "Data export failed");
+                    self.user_repo.fail_export(export.id, &e.to_string()).await?;
+                }
+            }
+        }
+
+        Ok(completed)
+    }
+
+    async fn get_audit_log(
+        &self,
+        sub: Uuid,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<AuditPage, CoreError> {
+        let query = AuditLogQuery {
+            target_sub: Some(sub),
+            ..AuditLogQuery::default()
+        };
+        self.query_audit_log(query, cursor, limit).await
+    }
+
+    async fn query_audit_log(
+        &self,
+        query: AuditLogQuery,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<AuditPage, CoreError> {
+        let limit = limit.max(1);
+        let before_id = cursor.map(decode_cursor::<i64>).transpose()?;
+
+        let entries = self
+            .user_repo
+            .list_audit_entries(&query, before_id, limit as i64 + 1)
+            .await?;
+        let (entries, next_cursor) = paginate(entries, limit, |entry| entry.id);
+
+        Ok(AuditPage {
+            entries,
+            next_cursor,
+        })
+    }
+
+    async fn admin_get_user(&self, sub: Uuid) -> Result<AdminUserInfo, CoreError> {
+        let user = self
+            .find_user(sub)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+        let keycloak = match self.keycloak_client.get_user_info(sub).await {
+            Ok(info) => Some(info),
+            Err(KeycloakError::UserNotFound(_)) => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(AdminUserInfo { user, keycloak })
+    }
+
+    async fn admin_search_users(
+        &self,
+        query: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<AdminUserPage, CoreError> {
+        let query = normalize(query).to_lowercase();
+        if query.is_empty() {
+            return Err(CoreError::BadRequest("Search query must not be empty".to_string()));
+        }
+        if let Ok(sub) = Uuid::parse_str(&query) {
+            return Ok(AdminUserPage {
+                users: self.find_user(sub).await?.into_iter().collect(),
+                next_cursor: None,
+            });
+        }
+        let limit = limit.max(1);
+        let after = cursor
+            .map(decode_cursor::<SearchCursor>)
+            .transpose()?
+            .unwrap_or_else(SearchCursor::start);
+
+        let hits = self
+            .user_repo
+            .search_users(None, &query, after, limit as i64 + 1)
+            .await?;
+        let (hits, next_cursor) = paginate(hits, limit, |hit| SearchCursor::from(hit));
+
+        Ok(AdminUserPage {
+            users: hits.into_iter().map(|hit| hit.user).collect(),
+            next_cursor,
+        })
+    }
+
+    async fn admin_update_user(
+        &self,
+        sub: Uuid,
+        req: UpdateUserRequest,
+        audit: &AuditContext,
+    ) -> Result<AdminUserInfo, CoreError> {
+        let user = self
+            .find_user(sub)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+
+        self.update_user(&user, req, audit).await?;
+        self.admin_get_user(sub).await
+    }
+
+    async fn suspend_user(
+        &self,
+        sub: Uuid,
+        req: SuspendUserRequest,
+        audit: &AuditContext,
+    ) -> Result<AdminUserInfo, CoreError> {
+        let req = validate_suspend_user_request(req).map_err(CoreError::Validation)?;
+        let user = self
+            .find_user(sub)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+
+        // Keycloak first, so a suspension is only recorded as mirrored once
+        // the account really is disabled
+        if req.disable_keycloak != user.suspension_disabled_keycloak {
+            self.keycloak_client
+                .set_user_enabled(sub, !req.disable_keycloak)
+                .await?;
+        }
+
+        let suspension = Suspension {
+            reason: req.reason,
+            suspended_by: Some(audit.actor_sub),
+            suspended_at: chrono::Utc::now(),
+            expires_at: req.expires_at,
+        };
+        let updated_user = self
+            .user_repo
+            .suspend_user(sub, &suspension, req.disable_keycloak)
+            .await?;
+        self.user_cache.invalidate(sub).await;
+
+        let change = FieldChange::new(
+            "suspension",
+            user.active_suspension().map(|before| serde_json::json!(before)),
+            Some(serde_json::json!(suspension)),
+        );
+        self.record_audit(sub, AuditAction::UserSuspended, &[change], audit)
+            .await;
+        tracing::info!(%sub, expires_at = ?updated_user.suspended_until, "User suspended");
+
+        self.admin_get_user(sub).await
+    }
+
+    async fn lift_suspension(
+        &self,
+        sub: Uuid,
+        audit: &AuditContext,
+    ) -> Result<AdminUserInfo, CoreError> {
+        let user = self
+            .find_user(sub)
+            .await?
+            .ok_or_else(|| CoreError::NotFound("User not found".to_string()))?;
+        if user.suspended_at.is_none() {
+            return self.admin_get_user(sub).await;
+        }
+
+        if user.suspension_disabled_keycloak {
+            match self.keycloak_client.set_user_enabled(sub, true).await {
+                Ok(()) | Err(KeycloakError::UserNotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let before = user.active_suspension();
+        self.user_repo.lift_suspension(sub).await?;
+        self.user_cache.invalidate(sub).await;
+
+        let change = FieldChange::new(
+            "suspension",
+            before.map(|before| serde_json::json!(before)),
+            None,
+        );
+        self.record_audit(sub, AuditAction::SuspensionLifted, &[change], audit)
+            .await;
+        tracing::info!(%sub, "Suspension lifted");
+
+        self.admin_get_user(sub).await
+    }
+
+    async fn expire_suspensions(&self, limit: i64) -> Result<usize, CoreError> {
+        let users = self.user_repo.list_expired_suspensions(limit).await?;
+        let mut lifted = 0;
+
+        for user in users {
+            let sub = user.sub;
+            if user.suspension_disabled_keycloak {
+                match self.keycloak_client.set_user_enabled(sub, true).await {
+                    Ok(()) | Err(KeycloakError::UserNotFound(_)) => {}
+                    Err(e) => {
+                        tracing::warn!(
+                            %sub,
+                            error = %e,
+                            "Failed to re-enable Keycloak account, will retry"
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            if self.user_repo.lift_expired_suspension(sub).await? {
+                self.user_cache.invalidate(sub).await;
+                lifted += 1;
+                tracing::info!(%sub, "Expired suspension lifted");
+            }
+        }
+
+        Ok(lifted)
+    }
+
+    async fn sync_keycloak_event(&self, event: &KeycloakEvent) -> Result<(), CoreError> {
+        let sub = event.sub;
+        match event.kind {
+            KeycloakEventKind::Registered | KeycloakEventKind::ProfileUpdated => {
+                if self.user_repo.get_user_by_sub(sub).await?.is_some() {
+                    // Username and email are read from Keycloak, so dropping
+                    // the cached copy is enough to reflect the change. The
+                    // username claim still has to follow renames made there.
+                    self.user_cache.invalidate(sub).await;
+                    if let Some(username) = self.event_username(event).await? {
+                        self.user_repo
+                            .sync_username(sub, &username, self.username_policy.held_until())
+                            .await?;
+                    }
+                    return Ok(());
+                }
+                let Some(username) = self.event_username(event).await? else {
+                    tracing::info!(%sub, "Keycloak account already gone, not creating user");
+                    return Ok(());
+                };
+                self.get_or_create_user(sub, &username).await?;
+                tracing::info!(%sub, "User created from Keycloak event");
+            }
+            KeycloakEventKind::AccountDeleted => {
+                if self.user_repo.get_user_by_sub(sub).await?.is_none() {
+                    return Ok(());
+                }
+                self.user_repo
+                    .schedule_deletion(sub, chrono::Utc::now())
+                    .await?;
+                self.user_repo
+                    .mark_deletion_step(sub, DeletionStep::Keycloak)
+                    .await?;
+                let deletion = self
+                    .user_repo
+                    .get_deletion(sub)
+                    .await?
+                    .ok_or_else(|| {
+                        CoreError::InternalError("Deletion not scheduled".to_string())
+                    })?;
+                if deletion.anonymized_at.is_none() {
+                    self.purge_account(&deletion).await?;
+                }
+                self.user_cache.invalidate(sub).await;
+                tracing::info!(%sub, "User purged after Keycloak account deletion");
+            }
+        }
+        Ok(())
+    }
+
+    async fn reconcile_keycloak_users(
+        &self,
+        dry_run: bool,
+        page_size: usize,
+    ) -> Result<ReconcileReport, CoreError> {
+        let page_size = page_size.max(1);
+        let mut report = ReconcileReport {
+            dry_run,
+            ..ReconcileReport::default()
+        };
+
+        // Accounts created mid-run shift later ones onto the next page, so
+        // the offset counts rows fetched rather than distinct accounts
+        let mut keycloak_accounts = HashMap::new();
+        let mut fetched = 0;
+        loop {
+            let page = self.keycloak_client.list_users(fetched, page_size).await?;
+            fetched += page.len();
+            let last_page = page.len() < page_size;
+            keycloak_accounts.extend(page.into_iter().map(|account| (account.sub, account)));
+            if last_page {
+                break;
+            }
+        }
+        report.keycloak_users = keycloak_accounts.len();
+
+        let mut synced = Vec::new();
+        let mut after = None;
+        loop {
+            let users = self.user_repo.list_users(after, page_size as i64).await?;
+            report.local_users += users.len();
+            for user in &users {
+                let account = keycloak_accounts.remove(&user.sub);
+                if user.is_deleted() {
+                    continue;
+                }
+                if let Some(account) = account {
+                    synced.push(account);
+                    continue;
+                }
+                // The account may have been created after its page was
+                // listed. Service accounts are never listed: their rows were
+                // provisioned before service clients were told apart from users
+                match self.keycloak_client.get_account(user.sub).await {
+                    Ok(account) if account.service_account_client_id.is_some() => {
+                        report.service_accounts.push(user.sub)
+                    }
+                    Ok(_) => {}
+                    Err(KeycloakError::UserNotFound(_)) => report.orphaned_local.push(user.sub),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            match users.last() {
+                Some(last) if users.len() == page_size => after = Some(last.sub),
+                _ => break,
+            }
+        }
+
+        report.missing_local = keycloak_accounts.into_values().collect();
+        report.missing_local.sort_by_key(|account| account.sub);
+
+        if dry_run {
+            return Ok(report);
+        }
+
+        for account in &report.missing_local {
+            match self
+                .user_repo
+                .get_or_create_user(account.sub, &account.username)
+                .await
+            {
+                Ok(_) => report.created += 1,
+                Err(e) => report.failures.push(ReconcileFailure {
+                    sub: account.sub,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        // Claims guard against confusable usernames; users who never logged
+        // in since they were introduced, or were renamed in Keycloak, need one
+        for account in &synced {
+            match self
+                .user_repo
+                .sync_username(account.sub, &account.username, self.username_policy.held_until())
+                .await
+            {
+                Ok(claimed) => report.usernames_claimed += usize::from(claimed),
+                Err(e) => report.failures.push(ReconcileFailure {
+                    sub: account.sub,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        for &sub in report.orphaned_local.iter().chain(&report.service_accounts) {
+            match self.flag_orphaned_user(sub).await {
+                Ok(()) => report.flagged += 1,
+                Err(e) => report.failures.push(ReconcileFailure {
+                    sub,
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn flag_service_account_users(&self) -> Result<usize, CoreError> {
+        let subs: Vec<Uuid> = self
+            .keycloak_client
+            .list_service_accounts()
+            .await?
+            .into_iter()
+            .map(|account| account.sub)
+            .collect();
+
+        let mut flagged = 0;
+        for user in self.user_repo.get_users_by_subs(&subs).await? {
+            if user.is_deleted() {
+                continue;
+            }
+            self.flag_orphaned_user(user.sub).await?;
+            flagged += 1;
+        }
+        Ok(flagged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        AuditEntry, EmailMessage, KeycloakAccount, Setting, User, UserBatchHit, UserSearchHit,
+        UserStatus, UsernameClaim,
+    };
+    use crate::repository::{DELETED_USER_DISPLAY_NAME, RelationshipRepository};
+    use crate::services::{KeycloakError, ObjectMetadata};
+    use crate::username::username_skeleton;
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    // Mock KeycloakClient
+    #[derive(Clone)]
+    struct MockKeycloakClient {
+        users: Arc<Mutex<HashMap<Uuid, KeycloakUserInfo>>>,
+        should_fail: bool,
+        remaining_updates: Option<Arc<Mutex<usize>>>,
+        delete_failures: Arc<Mutex<usize>>,
+        disabled: Arc<Mutex<Vec<Uuid>>>,
+        service_accounts: Arc<Mutex<HashMap<Uuid, String>>>,
+    }
+
+    impl MockKeycloakClient {
+        fn new() -> Self {
+            Self {
+                users: Arc::new(Mutex::new(HashMap::new())),
+                should_fail: false,
+                remaining_updates: None,
+                delete_failures: Arc::new(Mutex::new(0)),
+                disabled: Arc::new(Mutex::new(Vec::new())),
+                service_accounts: Arc::new(Mutex::new(HashMap::new())),
+            }
+        }
+
+        /// Lets `updates` calls to `update_user_info` succeed, then fails.
+        fn failing_after_updates(mut self, updates: usize) -> Self {
+            self.remaining_updates = Some(Arc::new(Mutex::new(updates)));
+            self
+        }
+
+        fn with_user(self, sub: Uuid, info: KeycloakUserInfo) -> Self {
+            self.users.lock().unwrap().insert(sub, info);
+            self
+        }
+
+        /// Adds the service account of `client_id`, which is not listed.
+        fn with_service_account(self, sub: Uuid, client_id: &str) -> Self {
+            self.service_accounts
+                .lock()
+                .unwrap()
+                .insert(sub, client_id.to_string());
+            self
+        }
+
+        /// Makes the next `failures` calls to `delete_user` fail.
+        fn failing_deletes(self, failures: usize) -> Self {
+            *self.delete_failures.lock().unwrap() = failures;
+            self
+        }
+
+        fn failing() -> Self {
+            Self {
+                users: Arc::new(Mutex::new(HashMap::new())),
+                should_fail: true,
+                remaining_updates: None,
+                delete_failures: Arc::new(Mutex::new(0)),
+                disabled: Arc::new(Mutex::new(Vec::new())),
+                service_accounts: Arc::new(Mutex::new(HashMap::new())),
+            }
+        }
+    }
+
+    impl KeycloakClient for MockKeycloakClient {
+        async fn get_user_info(&self, sub: Uuid) -> Result<KeycloakUserInfo, KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::GetUserError("Keycloak unavailable".into()));
+            }
+            self.users
+                .lock()
+                .unwrap()
+                .get(&sub)
+                .cloned()
+                .ok_or_else(|| KeycloakError::UserNotFound(sub))
+        }
+
+        async fn get_user_id_by_username(&self, username: &str) -> Result<Uuid, KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::GetUserError("Keycloak unavailable".into()));
+            }
+            let users = self.users.lock().unwrap();
+            for (sub, info) in users.iter() {
+                if info.username == username {
+                    return Ok(*sub);
+                }
+            }
+            Err(KeycloakError::UserNotFoundByUsername(username.to_string()))
+        }
+
+        async fn update_user_info(
+            &self,
+            sub: Uuid,
+            update_req: &UpdateUserRequest,
+        ) -> Result<(), KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::UpdateUserError(
+                    "Keycloak unavailable".into(),
+                ));
+            }
+            if let Some(remaining) = &self.remaining_updates {
+                let mut remaining = remaining.lock().unwrap();
+                if *remaining == 0 {
+                    return Err(KeycloakError::UpdateUserError(
+                        "Keycloak unavailable".into(),
+                    ));
+                }
+                *remaining -= 1;
+            }
+            let mut users = self.users.lock().unwrap();
+            if let Some(user) = users.get_mut(&sub) {
+                if let Some(username) = &update_req.username {
+                    user.username = username.clone();
+                }
+                if let Some(email) = &update_req.email {
+                    user.email = email.clone();
+                }
+            }
+            Ok(())
+        }
+
+        async fn update_email(
+            &self,
+            sub: Uuid,
+            email: &str,
+            _verified: bool,
+        ) -> Result<(), KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::UpdateUserError(
+                    "Keycloak unavailable".into(),
+                ));
+            }
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(KeycloakError::UserNotFound(sub))?;
+            user.email = email.to_string();
+            Ok(())
+        }
+
+        async fn delete_user(&self, sub: Uuid) -> Result<(), KeycloakError> {
+            let mut failures = self.delete_failures.lock().unwrap();
+            if self.should_fail || *failures > 0 {
+                *failures = failures.saturating_sub(1);
+                return Err(KeycloakError::DeleteUserError(
+                    "Keycloak unavailable".into(),
+                ));
+            }
+            self.users.lock().unwrap().remove(&sub);
+            Ok(())
+        }
+
+        async fn set_user_enabled(&self, sub: Uuid, enabled: bool) -> Result<(), KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::UpdateUserError(
+                    "Keycloak unavailable".into(),
+                ));
+            }
+            let mut disabled = self.disabled.lock().unwrap();
+            disabled.retain(|&s| s != sub);
+            if !enabled {
+                disabled.push(sub);
+            }
+            Ok(())
+        }
+
+        async fn get_account(&self, sub: Uuid) -> Result<KeycloakAccount, KeycloakError> {
+            if let Some(client_id) = self.service_accounts.lock().unwrap().get(&sub) {
+                return Ok(KeycloakAccount {
+                    sub,
+                    username: format!("service-account-{}", client_id),
+                    service_account_client_id: Some(client_id.clone()),
+                });
+            }
+            let info = self.get_user_info(sub).await?;
+            Ok(KeycloakAccount {
+                sub,
+                username: info.username,
+                service_account_client_id: None,
+            })
+        }
+
+        async fn list_users(
+            &self,
+            first: usize,
+            max: usize,
+        ) -> Result<Vec<KeycloakAccount>, KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::GetUserError("Keycloak unavailable".into()));
+            }
+            let mut accounts: Vec<KeycloakAccount> = self
+                .users
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(sub, info)| KeycloakAccount {
+                    sub: *sub,
+                    username: info.username.clone(),
+                    service_account_client_id: None,
+                })
+                .collect();
+            accounts.sort_by_key(|account| account.sub);
+            Ok(accounts.into_iter().skip(first).take(max).collect())
+        }
+
+        async fn list_service_accounts(&self) -> Result<Vec<KeycloakAccount>, KeycloakError> {
+            if self.should_fail {
+                return Err(KeycloakError::GetUserError("Keycloak unavailable".into()));
+            }
+            Ok(self
+                .service_accounts
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(sub, client_id)| KeycloakAccount {
+                    sub: *sub,
+                    username: format!("service-account-{}", client_id),
+                    service_account_client_id: Some(client_id.clone()),
+                })
+                .collect())
+        }
+    }
+
+    // Mock ContentServiceClient
+    #[derive(Clone)]
+    struct MockContentServiceClient {
+        objects: Arc<Mutex<HashMap<String, ObjectMetadata>>>,
+        signed_urls: Arc<Mutex<usize>>,
+        deleted_pictures: Arc<Mutex<Vec<String>>>,
+        should_fail: bool,
+    }
+
+    impl MockContentServiceClient {
+        fn new() -> Self {
+            Self {
+                objects: Arc::new(Mutex::new(HashMap::new())),
+                signed_urls: Arc::new(Mutex::new(0)),
+                deleted_pictures: Arc::new(Mutex::new(Vec::new())),
+                should_fail: false,
+            }
+        }
+
+        fn failing() -> Self {
+            Self {
+                should_fail: true,
+                ..Self::new()
+            }
+        }
+
+        fn with_object(self, key: &str, content_type: &str, size: u64) -> Self {
+            self.objects.lock().unwrap().insert(
+                key.to_string(),
+                ObjectMetadata {
+                    content_type: content_type.to_string(),
+                    size,
+                },
+            );
+            self
+        }
+
+        fn signed_urls(&self) -> usize {
+            *self.signed_urls.lock().unwrap()
+        }
+
+        fn deleted_pictures(&self) -> Vec<String> {
+            self.deleted_pictures.lock().unwrap().clone()
+        }
+    }
+
+    impl ContentServiceClient for MockContentServiceClient {
+        async fn sign_profile_picture_url(
+            &self,
+            key: &str,
+            action: SignedUrlAction,
+            _expires_in: Duration,
+        ) -> Result<String, String> {
+            if self.should_fail {
+                return Err("Content service unavailable".to_string());
+            }
+            *self.signed_urls.lock().unwrap() += 1;
+            Ok(format!("https://cdn.test/{}?action={:?}", key, action))
+        }
+
+        async fn get_profile_picture_metadata(
+            &self,
+            key: &str,
+        ) -> Result<Option<ObjectMetadata>, String> {
+            if self.should_fail {
+                return Err("Content service unavailable".to_string());
+            }
+            Ok(self.objects.lock().unwrap().get(key).cloned())
+        }
+
+        async fn delete_profile_picture(&self, key: &str) -> Result<(), String> {
+            if self.should_fail {
+                return Err("Content service unavailable".to_string());
+            }
+            self.objects.lock().unwrap().remove(key);
+            self.deleted_pictures
+                .lock()
+                .unwrap()
+                .push(key.to_string());
+            Ok(())
+        }
+    }
+
+    /// Export row and its document, once built.
+    type StoredExport = (DataExport, Option<serde_json::Value>);
+
+    // Mock UserRepository
+    #[derive(Clone)]
+    struct MockUserRepository {
+        users: Arc<Mutex<HashMap<Uuid, User>>>,
+        settings: Arc<Mutex<HashMap<Uuid, Setting>>>,
+        relationships: Arc<Mutex<HashMap<(Uuid, Uuid), Relationship>>>,
+        deletions: Arc<Mutex<HashMap<Uuid, UserDeletion>>>,
+        exports: Arc<Mutex<HashMap<Uuid, StoredExport>>>,
+        audit_log: Arc<Mutex<Vec<AuditEntry>>>,
+        keycloak_repairs: Arc<Mutex<Vec<KeycloakRepair>>>,
+        avatar_cleanups: Arc<Mutex<Vec<String>>>,
+        username_history: Arc<Mutex<Vec<UsernameClaim>>>,
+        email_changes: Arc<Mutex<HashMap<Uuid, PendingEmailChange>>>,
+        outbox: Arc<Mutex<Vec<EmailMessage>>>,
+        fail_updates: bool,
+    }
+
+    impl MockUserRepository {
+        fn new() -> Self {
+            Self {
+                users: Arc::new(Mutex::new(HashMap::new())),
+                settings: Arc::new(Mutex::new(HashMap::new())),
+                relationships: Arc::new(Mutex::new(HashMap::new())),
+                deletions: Arc::new(Mutex::new(HashMap::new())),
+                exports: Arc::new(Mutex::new(HashMap::new())),
+                audit_log: Arc::new(Mutex::new(Vec::new())),
+                keycloak_repairs: Arc::new(Mutex::new(Vec::new())),
+                avatar_cleanups: Arc::new(Mutex::new(Vec::new())),
+                username_history: Arc::new(Mutex::new(Vec::new())),
+                email_changes: Arc::new(Mutex::new(HashMap::new())),
+                outbox: Arc::new(Mutex::new(Vec::new())),
+                fail_updates: false,
+            }
+        }
+
+        fn with_failing_updates(mut self) -> Self {
+            self.fail_updates = true;
+            self
+        }
+
+        fn with_user(self, user: User) -> Self {
+            self.users.lock().unwrap().insert(user.sub, user);
+            self
+        }
+
+        fn with_setting(self, setting: Setting) -> Self {
+            self.settings.lock().unwrap().insert(setting.sub, setting);
+            self
+        }
+
+        fn set_relationship(&self, sub: Uuid, target: Uuid, kind: RelationshipKind) -> Relationship {
+            let now = Utc::now();
+            let relationship = Relationship {
+                user_sub: sub,
+                target_sub: target,
+                kind,
+                created_at: now,
+                updated_at: now,
+            };
+            self.relationships
+                .lock()
+                .unwrap()
+                .insert((sub, target), relationship.clone());
+            relationship
+        }
+
+        fn relationship_kind(&self, sub: Uuid, target: Uuid) -> Option<RelationshipKind> {
+            self.relationships
+                .lock()
+                .unwrap()
+                .get(&(sub, target))
+                .map(|r| r.kind)
+        }
+
+        fn delete_pair(&self, sub: Uuid, target: Uuid) {
+            self.relationships.lock().unwrap().retain(|&(a, b), r| {
+                r.kind == RelationshipKind::Blocked
+                    || !((a == sub && b == target) || (a == target && b == sub))
+            });
+        }
+
+        fn deletion(&self, sub: Uuid) -> Option<UserDeletion> {
+            self.deletions.lock().unwrap().get(&sub).cloned()
+        }
+
+        /// Moves the purge date of a scheduled deletion into the past.
+        fn expire_grace_period(&self, sub: Uuid) {
+            if let Some(deletion) = self.deletions.lock().unwrap().get_mut(&sub) {
+                deletion.purge_after = Utc::now() - chrono::Duration::seconds(1);
+            }
+        }
+    }
+
+    impl AvatarRepository for MockUserRepository {
+        async fn set_avatar(&self, sub: Uuid, key: &str) -> Result<User, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            let mut cleanups = self.avatar_cleanups.lock().unwrap();
+            if let Some(previous) = user.avatar_key.replace(key.to_string())
+                && previous != key
+            {
+                cleanups.push(previous);
+            }
+            cleanups.retain(|queued| queued != key);
+            user.profile_picture.clear();
+            user.updated_at = Utc::now();
+            Ok(user.clone())
+        }
+
+        async fn set_banner(&self, sub: Uuid, key: Option<&str>) -> Result<User, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            let mut cleanups = self.avatar_cleanups.lock().unwrap();
+            if let Some(previous) = user.banner_key.take()
+                && Some(previous.as_str()) != key
+            {
+                cleanups.push(previous);
+            }
+            user.banner_key = key.map(str::to_string);
+            user.updated_at = Utc::now();
+            Ok(user.clone())
+        }
+
+        async fn claim_avatar_cleanups(
+            &self,
+            limit: i64,
+            _lease: Duration,
+        ) -> Result<Vec<String>, sqlx::Error> {
+            Ok(self
+                .avatar_cleanups
+                .lock()
+                .unwrap()
+                .iter()
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn complete_avatar_cleanup(&self, key: &str) -> Result<(), sqlx::Error> {
+            self.avatar_cleanups
+                .lock()
+                .unwrap()
+                .retain(|queued| queued != key);
+            Ok(())
+        }
+
+        async fn record_avatar_cleanup_failure(
+            &self,
+            _key: &str,
+            _error: &str,
+        ) -> Result<(), sqlx::Error> {
+            Ok(())
+        }
+    }
+
+    impl UsernameRepository for MockUserRepository {
+        async fn find_username_conflict(
+            &self,
+            sub: Uuid,
+            username: &str,
+        ) -> Result<Option<UsernameClaim>, sqlx::Error> {
+            let skeleton = username_skeleton(username);
+            let now = Utc::now();
+            Ok(self
+                .username_history
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|claim| {
+                    claim.sub != sub
+                        && username_skeleton(&claim.username) == skeleton
+                        && claim.held_until.is_none_or(|until| until > now)
+                })
+                .cloned())
+        }
+
+        async fn list_username_history(
+            &self,
+            sub: Uuid,
+        ) -> Result<Vec<UsernameClaim>, sqlx::Error> {
+            Ok(self
+                .username_history
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .filter(|claim| claim.sub == sub)
+                .cloned()
+                .collect())
+        }
+
+        async fn last_username_change(
+            &self,
+            sub: Uuid,
+        ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+            Ok(self
+                .username_history
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|claim| claim.sub == sub)
+                .filter_map(|claim| claim.released_at)
+                .max())
+        }
+
+        async fn record_username_change(
+            &self,
+            sub: Uuid,
+            previous: &str,
+            username: &str,
+            held_until: DateTime<Utc>,
+        ) -> Result<bool, sqlx::Error> {
+            let mut history = self.username_history.lock().unwrap();
+            if claimed_by_other(&history, sub, username) {
+                return Ok(false);
+            }
+            history.retain(|claim| claim.sub != sub || claim.released_at.is_some());
+            history.push(UsernameClaim {
+                sub,
+                username: previous.to_string(),
+                claimed_at: Utc::now(),
+                released_at: Some(Utc::now()),
+                held_until: Some(held_until),
+            });
+            history.push(UsernameClaim {
+                sub,
+                username: username.to_string(),
+                claimed_at: Utc::now(),
+                released_at: None,
+                held_until: None,
+            });
+            Ok(true)
+        }
+
+        async fn revert_username_change(
+            &self,
+            sub: Uuid,
+            previous: &str,
+            username: &str,
+        ) -> Result<(), sqlx::Error> {
+            let mut history = self.username_history.lock().unwrap();
+            history.retain(|claim| {
+                claim.sub != sub || claim.username != username || claim.released_at.is_some()
+            });
+            if let Some(claim) = history
+                .iter_mut()
+                .filter(|claim| claim.sub == sub && claim.username == previous)
+                .max_by_key(|claim| claim.released_at)
+            {
+                claim.released_at = None;
+                claim.held_until = None;
+            }
+            Ok(())
+        }
+
+        async fn sync_username(
+            &self,
+            sub: Uuid,
+            username: &str,
+            held_until: DateTime<Utc>,
+        ) -> Result<bool, sqlx::Error> {
+            let mut history = self.username_history.lock().unwrap();
+            let current = history
+                .iter_mut()
+                .find(|claim| claim.sub == sub && claim.released_at.is_none());
+            if let Some(claim) = current {
+                if claim.username == username {
+                    return Ok(false);
+                }
+                claim.released_at = Some(Utc::now());
+                claim.held_until = Some(held_until);
+            }
+            if claimed_by_other(&history, sub, username) {
+                return Ok(false);
+            }
+            history.push(UsernameClaim {
+                sub,
+                username: username.to_string(),
+                claimed_at: Utc::now(),
+                released_at: None,
+                held_until: None,
+            });
+            Ok(true)
+        }
+    }
+
+    /// Mirrors the unique index on the skeletons of current usernames.
+    fn claimed_by_other(history: &[UsernameClaim], sub: Uuid, username: &str) -> bool {
+        let skeleton = username_skeleton(username);
+        history.iter().any(|claim| {
+            claim.sub != sub
+                && claim.released_at.is_none()
+                && username_skeleton(&claim.username) == skeleton
+        })
+    }
+
+    impl EmailChangeRepository for MockUserRepository {
+        async fn request_email_change(
+            &self,
+            change: &PendingEmailChange,
+            verification: &EmailMessage,
+        ) -> Result<(), sqlx::Error> {
+            self.email_changes
+                .lock()
+                .unwrap()
+                .insert(change.sub, change.clone());
+            self.outbox.lock().unwrap().push(verification.clone());
+            Ok(())
+        }
+
+        async fn get_email_change(
+            &self,
+            sub: Uuid,
+        ) -> Result<Option<PendingEmailChange>, sqlx::Error> {
+            Ok(self.email_changes.lock().unwrap().get(&sub).cloned())
+        }
+
+        async fn complete_email_change(
+            &self,
+            sub: Uuid,
+            nonce: Uuid,
+            notice: &EmailMessage,
+        ) -> Result<bool, sqlx::Error> {
+            let mut changes = self.email_changes.lock().unwrap();
+            if changes.get(&sub).is_none_or(|change| change.nonce != nonce) {
+                return Ok(false);
+            }
+            changes.remove(&sub);
+            self.outbox.lock().unwrap().push(notice.clone());
+            Ok(true)
+        }
+    }
+
+    fn clear_suspension(user: &mut User) {
+        user.suspended_at = None;
+        user.suspended_until = None;
+        user.suspended_by = None;
+        user.suspension_reason = None;
+        user.suspension_disabled_keycloak = false;
+    }
+
+    impl SuspensionRepository for MockUserRepository {
+        async fn suspend_user(
+            &self,
+            sub: Uuid,
+            suspension: &Suspension,
+            disabled_keycloak: bool,
+        ) -> Result<User, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            user.suspended_at = Some(suspension.suspended_at);
+            user.suspended_until = suspension.expires_at;
+            user.suspended_by = suspension.suspended_by;
+            user.suspension_reason = Some(suspension.reason.clone());
+            user.suspension_disabled_keycloak = disabled_keycloak;
+            Ok(user.clone())
+        }
+
+        async fn lift_suspension(&self, sub: Uuid) -> Result<User, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            clear_suspension(user);
+            Ok(user.clone())
+        }
+
+        async fn list_expired_suspensions(&self, limit: i64) -> Result<Vec<User>, sqlx::Error> {
+            let now = Utc::now();
+            Ok(self
+                .users
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|user| {
+                    user.suspended_at.is_some()
+                        && user.suspended_until.is_some_and(|until| until <= now)
+                })
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn lift_expired_suspension(&self, sub: Uuid) -> Result<bool, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            if user.suspended_until.is_none_or(|until| until > Utc::now()) {
+                return Ok(false);
+            }
+            clear_suspension(user);
+            Ok(true)
+        }
+    }
+
+    impl AuditRepository for MockUserRepository {
+        async fn record_audit(
+            &self,
+            target: Uuid,
+            action: AuditAction,
+            changes: &[FieldChange],
+            context: &AuditContext,
+        ) -> Result<(), sqlx::Error> {
+            let mut audit_log = self.audit_log.lock().unwrap();
+            let id = audit_log.len() as i64 + 1;
+            audit_log.push(AuditEntry {
+                id,
+                actor_sub: context.actor_sub,
+                target_sub: target,
+                action,
+                changes: changes.to_vec(),
+                ip_address: context.ip_address.clone(),
+                user_agent: context.user_agent.clone(),
+                created_at: Utc::now(),
+            });
+            Ok(())
+        }
+
+        async fn list_audit_entries(
+            &self,
+            query: &AuditLogQuery,
+            before_id: Option<i64>,
+            limit: i64,
+        ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+            Ok(self
+                .audit_log
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .filter(|e| query.actor_sub.is_none_or(|sub| e.actor_sub == sub))
+                .filter(|e| query.target_sub.is_none_or(|sub| e.target_sub == sub))
+                .filter(|e| query.action.is_none_or(|action| e.action == action))
+                .filter(|e| query.from.is_none_or(|from| e.created_at >= from))
+                .filter(|e| query.to.is_none_or(|to| e.created_at < to))
+                .filter(|e| before_id.is_none_or(|id| e.id < id))
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+    }
+
+    impl ExportRepository for MockUserRepository {
+        async fn create_export(&self, sub: Uuid) -> Result<DataExport, sqlx::Error> {
+            let mut exports = self.exports.lock().unwrap();
+            let active = exports.values().find(|(export, _)| {
+                export.sub == sub
+                    && matches!(
+                        export.status,
+                        DataExportStatus::Pending | DataExportStatus::Running
+                    )
+            });
+            if let Some((export, _)) = active {
+                return Ok(export.clone());
+            }
+            let export = DataExport {
+                id: Uuid::new_v4(),
+                sub,
+                status: DataExportStatus::Pending,
+                error: None,
+                created_at: Utc::now(),
+                completed_at: None,
+                expires_at: None,
+            };
+            exports.insert(export.id, (export.clone(), None));
+            Ok(export)
+        }
+
+        async fn get_export(&self, sub: Uuid, id: Uuid) -> Result<Option<DataExport>, sqlx::Error> {
+            Ok(self
+                .exports
+                .lock()
+                .unwrap()
+                .get(&id)
+                .map(|(export, _)| export.clone())
+                .filter(|export| export.sub == sub))
+        }
+
+        async fn claim_pending_exports(
+            &self,
+            limit: i64,
+            _lease: Duration,
+        ) -> Result<Vec<DataExport>, sqlx::Error> {
+            let mut exports = self.exports.lock().unwrap();
+            Ok(exports
+                .values_mut()
+                .filter(|(export, _)| export.status == DataExportStatus::Pending)
+                .take(limit as usize)
+                .map(|(export, _)| {
+                    export.status = DataExportStatus::Running;
+                    export.clone()
+                })
+                .collect())
+        }
+
+        async fn complete_export(
+            &self,
+            id: Uuid,
+            document: &serde_json::Value,
+            expires_at: chrono::DateTime<Utc>,
+        ) -> Result<(), sqlx::Error> {
+            if let Some((export, stored)) = self.exports.lock().unwrap().get_mut(&id) {
+                export.status = DataExportStatus::Completed;
+                export.completed_at = Some(Utc::now());
+                export.expires_at = Some(expires_at);
+                *stored = Some(document.clone());
+            }
+            Ok(())
+        }
+
+        async fn fail_export(&self, id: Uuid, error: &str) -> Result<(), sqlx::Error> {
+            if let Some((export, _)) = self.exports.lock().unwrap().get_mut(&id) {
+                export.status = DataExportStatus::Failed;
+                export.error = Some(error.to_string());
+                export.completed_at = Some(Utc::now());
+            }
+            Ok(())
+        }
+
+        async fn get_export_document(
+            &self,
+            sub: Uuid,
+            id: Uuid,
+        ) -> Result<Option<serde_json::Value>, sqlx::Error> {
+            let now = Utc::now();
+            Ok(self
+                .exports
+                .lock()
+                .unwrap()
+                .get(&id)
+                .filter(|(export, _)| {
+                    export.sub == sub
+                        && export.status == DataExportStatus::Completed
+                        && export.expires_at.is_some_and(|expires_at| expires_at > now)
+                })
+                .and_then(|(_, document)| document.clone()))
+        }
+
+        async fn expire_exports(&self) -> Result<u64, sqlx::Error> {
+            let now = Utc::now();
+            let mut expired = 0;
+            for (export, document) in self.exports.lock().unwrap().values_mut() {
+                if export.status == DataExportStatus::Completed
+                    && export.expires_at.is_some_and(|expires_at| expires_at <= now)
+                {
+                    export.status = DataExportStatus::Expired;
+                    *document = None;
+                    expired += 1;
+                }
+            }
+            Ok(expired)
+        }
+    }
+
+    impl DeletionRepository for MockUserRepository {
+        async fn schedule_deletion(
+            &self,
+            sub: Uuid,
+            purge_after: chrono::DateTime<Utc>,
+        ) -> Result<UserDeletion, sqlx::Error> {
+            let now = Utc::now();
+            if let Some(user) = self.users.lock().unwrap().get_mut(&sub) {
+                user.deleted_at.get_or_insert(now);
+            }
+            Ok(self
+                .deletions
+                .lock()
+                .unwrap()
+                .entry(sub)
+                .or_insert_with(|| UserDeletion {
+                    sub,
+                    requested_at: now,
+                    purge_after,
+                    avatar_deleted_at: None,
+                    keycloak_deleted_at: None,
+                    anonymized_at: None,
+                    attempts: 0,
+                    last_error: None,
+                })
+                .clone())
+        }
+
+        async fn get_deletion(&self, sub: Uuid) -> Result<Option<UserDeletion>, sqlx::Error> {
+            Ok(self.deletion(sub))
+        }
+
+        async fn cancel_deletion(&self, sub: Uuid) -> Result<bool, sqlx::Error> {
+            let mut deletions = self.deletions.lock().unwrap();
+            if deletions.get(&sub).is_none_or(|d| d.purge_started()) {
+                return Ok(false);
+            }
+            deletions.remove(&sub);
+            if let Some(user) = self.users.lock().unwrap().get_mut(&sub) {
+                user.deleted_at = None;
+            }
+            Ok(true)
+        }
+
+        async fn claim_due_deletions(
+            &self,
+            limit: i64,
+            _lease: Duration,
+        ) -> Result<Vec<UserDeletion>, sqlx::Error> {
+            let now = Utc::now();
+            Ok(self
+                .deletions
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|d| d.anonymized_at.is_none() && d.purge_after <= now)
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn mark_deletion_step(&self, sub: Uuid, step: DeletionStep) -> Result<(), sqlx::Error> {
+            if let Some(deletion) = self.deletions.lock().unwrap().get_mut(&sub) {
+                let column = match step {
+                    DeletionStep::Avatar => &mut deletion.avatar_deleted_at,
+                    DeletionStep::Keycloak => &mut deletion.keycloak_deleted_at,
+                };
+                column.get_or_insert(Utc::now());
+            }
+            Ok(())
+        }
+
+        async fn record_deletion_failure(&self, sub: Uuid, error: &str) -> Result<(), sqlx::Error> {
+            if let Some(deletion) = self.deletions.lock().unwrap().get_mut(&sub) {
+                deletion.attempts += 1;
+                deletion.last_error = Some(error.to_string());
+            }
+            Ok(())
+        }
+
+        async fn anonymize_user(&self, sub: Uuid) -> Result<(), sqlx::Error> {
+            if let Some(deletion) = self.deletions.lock().unwrap().get_mut(&sub) {
+                deletion.anonymized_at.get_or_insert(Utc::now());
+                deletion.last_error = None;
+            }
+            if let Some(user) = self.users.lock().unwrap().get_mut(&sub) {
+                user.display_name = DELETED_USER_DISPLAY_NAME.to_string();
+                user.description.clear();
+                user.profile_picture.clear();
+                user.avatar_key = None;
+                user.banner_key = None;
+                user.accent_color = None;
+                user.pronouns = None;
+                user.links.clear();
+                user.privacy = ProfilePrivacy::default();
+                user.custom_status_text = None;
+                user.custom_status_emoji = None;
+                user.custom_status_expires_at = None;
+                user.suspension_reason = None;
+                user.suspended_by = None;
+            }
+            self.settings.lock().unwrap().remove(&sub);
+            self.relationships
+                .lock()
+                .unwrap()
+                .retain(|&(a, b), _| a != sub && b != sub);
+            for entry in self.audit_log.lock().unwrap().iter_mut() {
+                if entry.target_sub == sub {
+                    for change in &mut entry.changes {
+                        change.before = None;
+                        change.after = None;
+                    }
+                }
+                if entry.target_sub == sub || entry.actor_sub == sub {
+                    entry.ip_address = None;
+                    entry.user_agent = None;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl RelationshipRepository for MockUserRepository {
+        async fn get_relationship(
+            &self,
+            sub: Uuid,
+            target: Uuid,
+        ) -> Result<Option<Relationship>, sqlx::Error> {
+            Ok(self.relationships.lock().unwrap().get(&(sub, target)).cloned())
+        }
+
+        async fn list_relationships(&self, sub: Uuid) -> Result<Vec<Relationship>, sqlx::Error> {
+            Ok(self
+                .relationships
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|r| r.user_sub == sub)
+                .cloned()
+                .collect())
+        }
+
+        async fn create_friend_request(
+            &self,
+            sub: Uuid,
+            target: Uuid,
+        ) -> Result<Relationship, sqlx::Error> {
+            self.set_relationship(target, sub, RelationshipKind::PendingIncoming);
+            Ok(self.set_relationship(sub, target, RelationshipKind::PendingOutgoing))
+        }
+
+        async fn accept_friend_request(
+            &self,
+            sub: Uuid,
+            requester: Uuid,
+        ) -> Result<Relationship, sqlx::Error> {
+            self.set_relationship(requester, sub, RelationshipKind::Friend);
+            Ok(self.set_relationship(sub, requester, RelationshipKind::Friend))
+        }
+
+        async fn delete_relationship(&self, sub: Uuid, target: Uuid) -> Result<(), sqlx::Error> {
+            self.delete_pair(sub, target);
+            Ok(())
+        }
+
+        async fn block_user(&self, sub: Uuid, target: Uuid) -> Result<Relationship, sqlx::Error> {
+            self.delete_pair(sub, target);
+            Ok(self.set_relationship(sub, target, RelationshipKind::Blocked))
+        }
+
+        async fn unblock_user(&self, sub: Uuid, target: Uuid) -> Result<(), sqlx::Error> {
+            self.relationships.lock().unwrap().remove(&(sub, target));
+            Ok(())
+        }
+
+        async fn get_blockers(
+            &self,
+            viewer: Uuid,
+            subs: &[Uuid],
+        ) -> Result<Vec<Uuid>, sqlx::Error> {
+            Ok(subs
+                .iter()
+                .copied()
+                .filter(|&sub| self.relationship_kind(sub, viewer) == Some(RelationshipKind::Blocked))
+                .collect())
+        }
+    }
+
+    impl UserRepository for MockUserRepository {
+        async fn create_user(&self, sub: Uuid, username: &str) -> Result<User, sqlx::Error> {
             let now = Utc::now();
             let user = User {
                 sub,
-                display_name: username.to_string(),
-                profile_picture: String::new(),
-                description: String::new(),
-                created_at: now,
-                updated_at: now,
+                display_name: username.to_string(),
+                profile_picture: String::new(),
+                avatar_key: None,
+                banner_key: None,
+                accent_color: None,
+                pronouns: None,
+                links: Vec::new(),
+                privacy: ProfilePrivacy::default(),
+                description: String::new(),
+                status: UserStatus::Online,
+                custom_status_text: None,
+                custom_status_emoji: None,
+                custom_status_expires_at: None,
+                deleted_at: None,
+                suspended_at: None,
+                suspended_until: None,
+                suspended_by: None,
+                suspension_reason: None,
+                suspension_disabled_keycloak: false,
+                created_at: now,
+                updated_at: now,
+            };
+            self.users.lock().unwrap().insert(sub, user.clone());
+            self.settings
+                .lock()
+                .unwrap()
+                .entry(sub)
+                .or_insert_with(|| Setting::defaults(sub));
+            Ok(user)
+        }
+
+        async fn get_user_by_sub(&self, sub: Uuid) -> Result<Option<User>, sqlx::Error> {
+            Ok(self.users.lock().unwrap().get(&sub).cloned())
+        }
+
+        async fn get_users_by_subs(&self, subs: &[Uuid]) -> Result<Vec<User>, sqlx::Error> {
+            let users = self.users.lock().unwrap();
+            Ok(subs
+                .iter()
+                .filter_map(|sub| users.get(sub).cloned())
+                .collect())
+        }
+
+        async fn lookup_users(
+            &self,
+            viewer: Option<Uuid>,
+            subs: &[Uuid],
+            after: i64,
+            limit: i64,
+        ) -> Result<Vec<UserBatchHit>, sqlx::Error> {
+            let users = self.users.lock().unwrap();
+            Ok(subs
+                .iter()
+                .zip(1..)
+                .filter(|(_, position)| *position > after)
+                .filter_map(|(sub, position)| {
+                    let user = users.get(sub).filter(|user| !user.is_deleted())?;
+                    let blocked = viewer.is_some_and(|viewer| {
+                        self.relationship_kind(user.sub, viewer) == Some(RelationshipKind::Blocked)
+                    });
+                    (!blocked).then(|| UserBatchHit {
+                        user: user.clone(),
+                        position,
+                    })
+                })
+                .take(limit as usize)
+                .collect())
+        }
+
+        async fn get_or_create_user(&self, sub: Uuid, username: &str) -> Result<User, sqlx::Error> {
+            if let Some(user) = self.users.lock().unwrap().get(&sub).cloned() {
+                return Ok(user);
+            }
+            self.create_user(sub, username).await
+        }
+
+        async fn list_users(
+            &self,
+            after: Option<Uuid>,
+            limit: i64,
+        ) -> Result<Vec<User>, sqlx::Error> {
+            let mut users: Vec<User> = self
+                .users
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|user| after.is_none_or(|after| user.sub > after))
+                .cloned()
+                .collect();
+            users.sort_by_key(|user| user.sub);
+            users.truncate(limit as usize);
+            Ok(users)
+        }
+
+        async fn search_users(
+            &self,
+            viewer: Option<Uuid>,
+            query: &str,
+            after: SearchCursor,
+            limit: i64,
+        ) -> Result<Vec<UserSearchHit>, sqlx::Error> {
+            let users = self.users.lock().unwrap();
+            let mut hits: Vec<UserSearchHit> = users
+                .values()
+                .filter(|user| {
+                    !user.is_deleted()
+                        && viewer.is_none_or(|viewer| {
+                            self.relationship_kind(user.sub, viewer)
+                                != Some(RelationshipKind::Blocked)
+                        })
+                })
+                .filter_map(|user| {
+                    let name = user.display_name.to_lowercase();
+                    let score = if name.starts_with(query) {
+                        1.0
+                    } else if name.contains(query) {
+                        0.5
+                    } else {
+                        return None;
+                    };
+                    Some(UserSearchHit {
+                        user: user.clone(),
+                        score,
+                    })
+                })
+                .filter(|hit| (hit.score, hit.user.sub) < (after.score, after.sub))
+                .collect();
+            hits.sort_by(|a, b| {
+                b.score
+                    .total_cmp(&a.score)
+                    .then_with(|| b.user.sub.cmp(&a.user.sub))
+            });
+            hits.truncate(limit as usize);
+            Ok(hits)
+        }
+
+        async fn update_user(
+            &self,
+            sub: Uuid,
+            req: UpdateUserRequest,
+        ) -> Result<User, sqlx::Error> {
+            if self.fail_updates {
+                return Err(sqlx::Error::PoolTimedOut);
+            }
+            let mut users = self.users.lock().unwrap();
+            if let Some(user) = users.get_mut(&sub) {
+                if let Some(display_name) = req.display_name {
+                    user.display_name = display_name;
+                }
+                if let Some(profile_picture) = req.profile_picture {
+                    if let Some(previous) = user.avatar_key.take() {
+                        self.avatar_cleanups.lock().unwrap().push(previous);
+                    }
+                    user.profile_picture = profile_picture;
+                }
+                if let Some(description) = req.description {
+                    user.description = description;
+                }
+                if let Some(accent_color) = req.accent_color {
+                    user.accent_color = (!accent_color.is_empty()).then_some(accent_color);
+                }
+                if let Some(pronouns) = req.pronouns {
+                    user.pronouns = (!pronouns.is_empty()).then_some(pronouns);
+                }
+                if let Some(links) = req.links {
+                    user.links = links;
+                }
+                user.updated_at = Utc::now();
+                return Ok(user.clone());
+            }
+            Err(sqlx::Error::RowNotFound)
+        }
+
+        async fn update_status(
+            &self,
+            sub: Uuid,
+            req: UpdateStatusRequest,
+        ) -> Result<User, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            user.status = req.status;
+            user.custom_status_text = req.custom_text;
+            user.custom_status_emoji = req.custom_emoji;
+            user.custom_status_expires_at = req.expires_at;
+            user.updated_at = Utc::now();
+            Ok(user.clone())
+        }
+
+        async fn update_privacy(
+            &self,
+            sub: Uuid,
+            privacy: &ProfilePrivacy,
+        ) -> Result<User, sqlx::Error> {
+            let mut users = self.users.lock().unwrap();
+            let user = users.get_mut(&sub).ok_or(sqlx::Error::RowNotFound)?;
+            user.privacy = privacy.clone();
+            user.updated_at = Utc::now();
+            Ok(user.clone())
+        }
+
+        async fn record_keycloak_repair(
+            &self,
+            sub: Uuid,
+            restore: &UpdateUserRequest,
+            applied: &UpdateUserRequest,
+            _error: &str,
+        ) -> Result<(), sqlx::Error> {
+            let mut repairs = self.keycloak_repairs.lock().unwrap();
+            let id = repairs.iter().map(|repair| repair.id).max().unwrap_or(0) + 1;
+            repairs.push(KeycloakRepair {
+                id,
+                sub,
+                restore: restore.clone(),
+                applied: applied.clone(),
+            });
+            Ok(())
+        }
+
+        async fn claim_keycloak_repairs(
+            &self,
+            limit: i64,
+            _lease: Duration,
+        ) -> Result<Vec<KeycloakRepair>, sqlx::Error> {
+            Ok(self
+                .keycloak_repairs
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn complete_keycloak_repair(&self, id: i64) -> Result<(), sqlx::Error> {
+            self.keycloak_repairs
+                .lock()
+                .unwrap()
+                .retain(|repair| repair.id != id);
+            Ok(())
+        }
+
+        async fn record_keycloak_repair_failure(
+            &self,
+            _id: i64,
+            _error: &str,
+        ) -> Result<(), sqlx::Error> {
+            Ok(())
+        }
+
+        async fn get_setting_by_sub(&self, sub: Uuid) -> Result<Option<Setting>, sqlx::Error> {
+            Ok(self.settings.lock().unwrap().get(&sub).cloned())
+        }
+
+        async fn create_setting(&self, sub: Uuid) -> Result<Setting, sqlx::Error> {
+            let setting = Setting::defaults(sub);
+            self.settings.lock().unwrap().insert(sub, setting.clone());
+            Ok(setting)
+        }
+
+        async fn update_setting(
+            &self,
+            sub: Uuid,
+            req: UpdateSettingRequest,
+        ) -> Result<Setting, sqlx::Error> {
+            let mut settings = self.settings.lock().unwrap();
+            let setting = settings
+                .entry(sub)
+                .or_insert_with(|| Setting::defaults(sub));
+            setting.values.extend(req.changed_values());
+            for key in req.reset_keys() {
+                setting.values.remove(&key);
+            }
+            setting.updated_at = Utc::now();
+            Ok(setting.clone())
+        }
+    }
+
+    fn create_test_user(sub: Uuid) -> User {
+        let now = Utc::now();
+        User {
+            sub,
+            display_name: "Test User".to_string(),
+            profile_picture: "https://example.com/pic.jpg".to_string(),
+            avatar_key: None,
+            banner_key: None,
+            accent_color: None,
+            pronouns: None,
+            links: Vec::new(),
+            privacy: ProfilePrivacy::default(),
+            description: "A test user".to_string(),
+            status: UserStatus::Online,
+            custom_status_text: None,
+            custom_status_emoji: None,
+            custom_status_expires_at: None,
+            deleted_at: None,
+            suspended_at: None,
+            suspended_until: None,
+            suspended_by: None,
+            suspension_reason: None,
+            suspension_disabled_keycloak: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn create_test_setting(sub: Uuid) -> Setting {
+        let mut setting = Setting::defaults(sub);
+        setting.values = settings_values(json!({ "theme": "dark", "lang": "fr" }));
+        setting
+    }
+
+    fn settings_values(value: serde_json::Value) -> crate::models::SettingValues {
+        value.as_object().cloned().unwrap()
+    }
+
+    fn settings_request(value: serde_json::Value) -> UpdateSettingRequest {
+        UpdateSettingRequest {
+            values: settings_values(value),
+        }
+    }
+
+    type TestService =
+        UserServiceImpl<MockUserRepository, MockKeycloakClient, MockContentServiceClient>;
+
+    /// Service over clones of the mocks, which share their state with the
+    /// originals so tests can inspect it afterwards.
+    fn test_service(
+        repo: &MockUserRepository,
+        keycloak: &MockKeycloakClient,
+        content: &MockContentServiceClient,
+    ) -> TestService {
+        UserServiceImpl::new(repo.clone(), keycloak.clone(), content.clone())
+    }
+
+    mod get_user_by_sub {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_user_when_exists() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let content = MockContentServiceClient::new();
+            let keycloak = MockKeycloakClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_by_sub(Uuid::new_v4(), sub).await.unwrap();
+
+            assert_eq!(result.sub, sub);
+            assert_eq!(result.display_name, "Test User");
+        }
+
+        #[tokio::test]
+        async fn returns_not_found_when_user_does_not_exist() {
+            let sub = Uuid::new_v4();
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_by_sub(Uuid::new_v4(), sub).await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+    }
+
+    mod get_users_by_subs {
+        use super::*;
+
+        const VIEWER: Uuid = Uuid::nil();
+
+        fn service_with(subs: &[Uuid]) -> TestService {
+            let repo = subs.iter().fold(MockUserRepository::new(), |repo, sub| {
+                repo.with_user(create_test_user(*sub))
+            });
+            test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            )
+        }
+
+        fn subs_of(page: &UserBatchPage) -> Vec<Uuid> {
+            page.users.iter().map(|user| user.sub).collect()
+        }
+
+        #[tokio::test]
+        async fn keeps_requested_order_and_reports_missing_subs() {
+            let (a, b, missing) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+            let service = service_with(&[a, b]);
+
+            let page = service
+                .get_users_by_subs(
+                    Viewer::User(VIEWER),
+                    &[b, missing, a, b],
+                    BatchOrder::Requested,
+                    None,
+                    10,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(subs_of(&page), vec![b, a]);
+            assert_eq!(page.not_found, vec![missing]);
+            assert!(page.next_cursor.is_none());
+        }
+
+        #[tokio::test]
+        async fn sorts_by_sub_on_request() {
+            let subs: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+            let service = service_with(&subs);
+
+            let page = service
+                .get_users_by_subs(Viewer::Anonymous, &subs, BatchOrder::Sub, None, 10)
+                .await
+                .unwrap();
+
+            let mut sorted = subs.clone();
+            sorted.sort();
+            assert_eq!(subs_of(&page), sorted);
+        }
+
+        #[tokio::test]
+        async fn paginates_with_cursor_and_reports_missing_subs_once() {
+            let found: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+            let missing: Vec<Uuid> = (0..2).map(|_| Uuid::new_v4()).collect();
+            let requested = [found[0], missing[0], found[1], found[2], missing[1]];
+            let service = service_with(&found);
+
+            let first = service
+                .get_users_by_subs(Viewer::Anonymous, &requested, BatchOrder::Requested, None, 2)
+                .await
+                .unwrap();
+            let second = service
+                .get_users_by_subs(
+                    Viewer::Anonymous,
+                    &requested,
+                    BatchOrder::Requested,
+                    first.next_cursor.as_deref(),
+                    2,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(subs_of(&first), vec![found[0], found[1]]);
+            assert_eq!(first.not_found, vec![missing[0]]);
+            assert_eq!(subs_of(&second), vec![found[2]]);
+            assert_eq!(second.not_found, vec![missing[1]]);
+            assert!(second.next_cursor.is_none());
+        }
+
+        #[tokio::test]
+        async fn rejects_invalid_cursor() {
+            let service = service_with(&[]);
+
+            let result = service
+                .get_users_by_subs(
+                    Viewer::Anonymous,
+                    &[Uuid::new_v4()],
+                    BatchOrder::Requested,
+                    Some("garbage"),
+                    10,
+                )
+                .await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+    }
+
+    mod get_user_by_username {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_user_when_exists_in_keycloak_and_db() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_by_username("testuser").await.unwrap();
+
+            assert_eq!(result.sub, sub);
+            assert_eq!(result.display_name, "Test User");
+        }
+
+        #[tokio::test]
+        async fn returns_not_found_when_user_not_in_keycloak() {
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_by_username("nonexistent").await;
+
+            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+        }
+
+        #[tokio::test]
+        async fn returns_not_found_when_user_in_keycloak_but_not_in_db() {
+            let sub = Uuid::new_v4();
+            let keycloak_info = KeycloakUserInfo {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new(); // No user in DB
+            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_by_username("testuser").await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn returns_error_when_keycloak_fails() {
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::failing();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_by_username("testuser").await;
+
+            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+        }
+    }
+
+    mod search_users {
+        use super::*;
+
+        const VIEWER: Uuid = Uuid::nil();
+
+        fn named_user(display_name: &str) -> User {
+            User {
+                display_name: display_name.to_string(),
+                ..create_test_user(Uuid::new_v4())
+            }
+        }
+
+        fn service_with(users: Vec<User>, keycloak: MockKeycloakClient) -> TestService {
+            let repo = users
+                .into_iter()
+                .fold(MockUserRepository::new(), |repo, user| repo.with_user(user));
+            test_service(&repo, &keycloak, &MockContentServiceClient::new())
+        }
+
+        #[tokio::test]
+        async fn ranks_prefix_matches_first() {
+            let service = service_with(
+                vec![named_user("Bob Alice"), named_user("Alice")],
+                MockKeycloakClient::new(),
+            );
+
+            let page = service.search_users(VIEWER, "ali", None, 10).await.unwrap();
+
+            let names: Vec<&str> = page.users.iter().map(|u| u.display_name.as_str()).collect();
+            assert_eq!(names, vec!["Alice", "Bob Alice"]);
+            assert!(page.next_cursor.is_none());
+        }
+
+        #[tokio::test]
+        async fn boosts_exact_username_match() {
+            let alice = named_user("Alice");
+            let bob = named_user("Bob");
+            let keycloak = MockKeycloakClient::new().with_user(
+                bob.sub,
+                KeycloakUserInfo {
+                    username: "alice".to_string(),
+                    email: "bob@example.com".to_string(),
+                },
+            );
+            let service = service_with(vec![alice.clone(), bob.clone()], keycloak);
+
+            let page = service.search_users(VIEWER, "Alice", None, 10).await.unwrap();
+
+            let subs: Vec<Uuid> = page.users.iter().map(|u| u.sub).collect();
+            assert_eq!(subs, vec![bob.sub, alice.sub]);
+        }
+
+        #[tokio::test]
+        async fn deleted_users_are_not_boosted() {
+            let mut alice = named_user("Alice");
+            alice.deleted_at = Some(Utc::now());
+            let keycloak = MockKeycloakClient::new().with_user(
+                alice.sub,
+                KeycloakUserInfo {
+                    username: "ally".to_string(),
+                    email: "alice@example.com".to_string(),
+                },
+            );
+            let service = service_with(vec![alice], keycloak);
+
+            let page = service.search_users(VIEWER, "ally", None, 10).await.unwrap();
+
+            assert!(page.users.is_empty());
+        }
+
+        #[tokio::test]
+        async fn reuses_recent_username_lookups() {
+            let alice = named_user("Alice");
+            let keycloak = MockKeycloakClient::new().with_user(
+                alice.sub,
+                KeycloakUserInfo {
+                    username: "ally".to_string(),
+                    email: "alice@example.com".to_string(),
+                },
+            );
+            let service = service_with(vec![alice.clone()], keycloak.clone());
+            service.search_users(VIEWER, "ally", None, 10).await.unwrap();
+            keycloak.users.lock().unwrap().clear();
+
+            let page = service.search_users(VIEWER, "ally", None, 10).await.unwrap();
+
+            assert_eq!(page.users[0].sub, alice.sub);
+        }
+
+        #[tokio::test]
+        async fn paginates_with_cursor_without_duplicates() {
+            let users: Vec<User> = (0..5).map(|i| named_user(&format!("User {}", i))).collect();
+            let service = service_with(users, MockKeycloakClient::new());
+
+            let first = service.search_users(VIEWER, "user", None, 2).await.unwrap();
+            let second = service
+                .search_users(VIEWER, "user", first.next_cursor.as_deref(), 2)
+                .await
+                .unwrap();
+            let third = service
+                .search_users(VIEWER, "user", second.next_cursor.as_deref(), 2)
+                .await
+                .unwrap();
+
+            let mut subs: Vec<Uuid> = [first.users, second.users, third.users]
+                .concat()
+                .iter()
+                .map(|u| u.sub)
+                .collect();
+            assert_eq!(subs.len(), 5);
+            subs.sort();
+            subs.dedup();
+            assert_eq!(subs.len(), 5);
+            assert!(third.next_cursor.is_none());
+        }
+
+        #[tokio::test]
+        async fn still_searches_when_keycloak_is_unavailable() {
+            let service = service_with(vec![named_user("Alice")], MockKeycloakClient::failing());
+
+            let page = service.search_users(VIEWER, "alice", None, 10).await.unwrap();
+
+            assert_eq!(page.users.len(), 1);
+        }
+
+        #[tokio::test]
+        async fn rejects_empty_query() {
+            let service = service_with(vec![], MockKeycloakClient::new());
+
+            let result = service.search_users(VIEWER, "   ", None, 10).await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+
+        #[tokio::test]
+        async fn rejects_invalid_cursor() {
+            let service = service_with(vec![], MockKeycloakClient::new());
+
+            let result = service.search_users(VIEWER, "alice", Some("garbage"), 10).await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+    }
+
+    mod get_current_user_info {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_basic_info_when_full_info_is_false() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_current_user_info(&user, false).await.unwrap();
+
+            assert_eq!(result["sub"], sub.to_string());
+            assert_eq!(result["display_name"], "Test User");
+            assert!(result.get("username").is_none());
+            assert!(result.get("email").is_none());
+        }
+
+        #[tokio::test]
+        async fn returns_full_info_when_full_info_is_true() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_current_user_info(&user, true).await.unwrap();
+
+            assert_eq!(result["sub"], sub.to_string());
+            assert_eq!(result["display_name"], "Test User");
+            assert_eq!(result["username"], "testuser");
+            assert_eq!(result["email"], "test@example.com");
+        }
+
+        #[tokio::test]
+        async fn returns_keycloak_error_when_keycloak_fails() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::failing();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_current_user_info(&user, true).await;
+
+            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+        }
+    }
+
+    mod update_user {
+        use super::*;
+
+        fn rename(username: &str) -> UpdateUserRequest {
+            UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some(username.to_string()),
+                email: None,
+            }
+        }
+
+        #[tokio::test]
+        async fn updates_local_fields_only() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await.unwrap();
+
+            assert_eq!(result.display_name, "New Name");
+        }
+
+        #[tokio::test]
+        async fn updates_keycloak_fields() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "olduser".to_string(),
+                email: "old@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("newuser".to_string()),
+                email: None,
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            assert!(result.is_ok());
+        }
+
+        #[tokio::test]
+        async fn returns_keycloak_error_when_keycloak_update_fails() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::failing();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("newuser".to_string()),
+                email: None,
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+        }
+
+        #[tokio::test]
+        async fn does_not_update_local_db_when_keycloak_fails() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::failing();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("newuser".to_string()),
+                email: None,
+            };
+
+            let _ = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            // Verify local DB was not updated
+            let stored_user = repo.get_user_by_sub(sub).await.unwrap().unwrap();
+            assert_eq!(stored_user.display_name, "Test User");
+        }
+
+        #[tokio::test]
+        async fn restores_keycloak_when_local_update_fails() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "olduser".to_string(),
+                email: "old@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new()
+                .with_user(user.clone())
+                .with_failing_updates();
+            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("newuser".to_string()),
+                email: Some("new@example.com".to_string()),
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            assert!(matches!(result, Err(CoreError::DatabaseError(_))));
+            let restored = keycloak.get_user_info(sub).await.unwrap();
+            assert_eq!(restored.username, "olduser");
+            assert_eq!(restored.email, "old@example.com");
+            assert!(repo.keycloak_repairs.lock().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn restores_only_the_keycloak_fields_that_changed() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "olduser".to_string(),
+                email: "old@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new()
+                .with_user(user.clone())
+                .with_failing_updates();
+            let keycloak = MockKeycloakClient::new()
+                .with_user(sub, keycloak_info)
+                .failing_after_updates(1);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("newuser".to_string()),
+                email: None,
+            };
+
+            let _ = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            let repairs = repo.keycloak_repairs.lock().unwrap();
+            assert_eq!(repairs[0].restore.username.as_deref(), Some("olduser"));
+            assert!(repairs[0].restore.email.is_none());
+        }
+
+        #[tokio::test]
+        async fn records_repair_when_keycloak_restore_fails() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "olduser".to_string(),
+                email: "old@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new()
+                .with_user(user.clone())
+                .with_failing_updates();
+            let keycloak = MockKeycloakClient::new()
+                .with_user(sub, keycloak_info)
+                .failing_after_updates(1);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("newuser".to_string()),
+                email: None,
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            assert!(matches!(result, Err(CoreError::DatabaseError(_))));
+            assert_eq!(keycloak.get_user_info(sub).await.unwrap().username, "newuser");
+            let repairs = repo.keycloak_repairs.lock().unwrap();
+            assert_eq!(repairs.len(), 1);
+            assert_eq!(repairs[0].sub, sub);
+            assert_eq!(repairs[0].applied.username.as_deref(), Some("newuser"));
+        }
+
+        #[tokio::test]
+        async fn repair_restores_keycloak_and_drops_the_repair() {
+            let sub = Uuid::new_v4();
+            let repo = MockUserRepository::new().with_user(create_test_user(sub));
+            let keycloak = MockKeycloakClient::new().with_user(
+                sub,
+                KeycloakUserInfo {
+                    username: "newuser".to_string(),
+                    email: "old@example.com".to_string(),
+                },
+            );
+            repo.record_keycloak_repair(sub, &rename("olduser"), &rename("newuser"), "timeout")
+                .await
+                .unwrap();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let restored = service.repair_keycloak_users(10).await.unwrap();
+
+            assert_eq!(restored, 1);
+            assert_eq!(keycloak.get_user_info(sub).await.unwrap().username, "olduser");
+            assert!(repo.keycloak_repairs.lock().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn repair_superseded_by_a_later_change_is_dropped() {
+            let sub = Uuid::new_v4();
+            let repo = MockUserRepository::new().with_user(create_test_user(sub));
+            let keycloak = MockKeycloakClient::new().with_user(
+                sub,
+                KeycloakUserInfo {
+                    username: "latest".to_string(),
+                    email: "old@example.com".to_string(),
+                },
+            );
+            repo.record_keycloak_repair(sub, &rename("olduser"), &rename("newuser"), "timeout")
+                .await
+                .unwrap();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let restored = service.repair_keycloak_users(10).await.unwrap();
+
+            assert_eq!(restored, 0);
+            assert_eq!(keycloak.get_user_info(sub).await.unwrap().username, "latest");
+            assert!(repo.keycloak_repairs.lock().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn normalizes_input_before_storing() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("  New Name  ".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await.unwrap();
+
+            assert_eq!(result.display_name, "New Name");
+        }
+
+        #[tokio::test]
+        async fn rejects_invalid_input_without_touching_keycloak_or_db() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+            let keycloak_info = KeycloakUserInfo {
+                username: "olduser".to_string(),
+                email: "old@example.com".to_string(),
+            };
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = UpdateUserRequest {
+                display_name: Some("x".repeat(300)),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some("new user".to_string()),
+                email: None,
+            };
+
+            let result = service.update_user(&user, req, &AuditContext::new(user.sub)).await;
+
+            assert!(matches!(result, Err(CoreError::Validation(errors)) if errors.errors.len() == 2));
+            let stored_user = repo.get_user_by_sub(sub).await.unwrap().unwrap();
+            assert_eq!(stored_user.display_name, "Test User");
+            assert_eq!(keycloak.get_user_info(sub).await.unwrap().username, "olduser");
+        }
+    }
+
+    mod update_user_status {
+        use super::*;
+
+        fn status_request(status: UserStatus) -> UpdateStatusRequest {
+            UpdateStatusRequest {
+                status,
+                custom_text: Some("In a meeting".to_string()),
+                custom_emoji: None,
+                expires_at: None,
+            }
+        }
+
+        #[tokio::test]
+        async fn updates_status_and_custom_text() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service
+                .update_user_status(&user, status_request(UserStatus::DoNotDisturb))
+                .await
+                .unwrap();
+
+            assert_eq!(result.status, UserStatus::DoNotDisturb);
+            assert_eq!(
+                result.custom_status.and_then(|c| c.text),
+                Some("In a meeting".to_string())
+            );
+        }
+
+        #[tokio::test]
+        async fn invisible_user_appears_offline_to_others() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let own = service
+                .update_user_status(&user, status_request(UserStatus::Invisible))
+                .await
+                .unwrap();
+            let seen_by_sub = service.get_user_by_sub(Uuid::new_v4(), sub).await.unwrap();
+            let seen_in_batch = service
+                .get_users_by_subs(
+                    Viewer::User(Uuid::new_v4()),
+                    &[sub],
+                    BatchOrder::Requested,
+                    None,
+                    10,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(own.status, UserStatus::Invisible);
+            assert_eq!(seen_by_sub.status, UserStatus::Offline);
+            assert_eq!(seen_in_batch.users[0].status, UserStatus::Offline);
+        }
+
+        #[tokio::test]
+        async fn rejects_offline_status() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service
+                .update_user_status(&user, status_request(UserStatus::Offline))
+                .await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+    }
+
+    mod get_user_settings {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_settings_when_exist() {
+            let sub = Uuid::new_v4();
+            let setting = create_test_setting(sub);
+
+            let repo = MockUserRepository::new().with_setting(setting);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_settings(sub).await.unwrap();
+
+            assert_eq!(result.sub, sub);
+            assert_eq!(result.get("theme"), Some(&json!("dark")));
+            assert_eq!(result.get("lang"), Some(&json!("fr")));
+        }
+
+        #[tokio::test]
+        async fn returns_defaults_when_settings_do_not_exist() {
+            let sub = Uuid::new_v4();
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_settings(sub).await.unwrap();
+
+            assert_eq!(result.sub, sub);
+            assert_eq!(result.get("theme"), Some(&json!("light")));
+            assert_eq!(result.get("lang"), Some(&json!("en")));
+        }
+
+        #[tokio::test]
+        async fn fills_in_settings_added_after_the_row_was_stored() {
+            let sub = Uuid::new_v4();
+            let setting = create_test_setting(sub);
+
+            let repo = MockUserRepository::new().with_setting(setting);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_user_settings(sub).await.unwrap();
+
+            assert_eq!(result.get("timezone"), Some(&json!("UTC")));
+            assert_eq!(result.get("compact_mode"), Some(&json!(false)));
+        }
+    }
+
+    mod update_user_settings {
+        use super::*;
+
+        #[tokio::test]
+        async fn updates_theme() {
+            let sub = Uuid::new_v4();
+            let setting = create_test_setting(sub);
+
+            let repo = MockUserRepository::new().with_setting(setting);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = settings_request(json!({ "theme": "light" }));
+
+            let result = service.update_user_settings(sub, req, &AuditContext::new(sub)).await.unwrap();
+
+            assert_eq!(result.get("theme"), Some(&json!("light")));
+            assert_eq!(result.get("lang"), Some(&json!("fr")));
+        }
+
+        #[tokio::test]
+        async fn updates_lang() {
+            let sub = Uuid::new_v4();
+            let setting = create_test_setting(sub);
+
+            let repo = MockUserRepository::new().with_setting(setting);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = settings_request(json!({ "lang": "en" }));
+
+            let result = service.update_user_settings(sub, req, &AuditContext::new(sub)).await.unwrap();
+
+            assert_eq!(result.get("theme"), Some(&json!("dark")));
+            assert_eq!(result.get("lang"), Some(&json!("en")));
+        }
+
+        #[tokio::test]
+        async fn creates_settings_when_none_exist() {
+            let sub = Uuid::new_v4();
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = settings_request(json!({ "theme": "dark" }));
+
+            let result = service.update_user_settings(sub, req, &AuditContext::new(sub)).await.unwrap();
+
+            assert_eq!(result.get("theme"), Some(&json!("dark")));
+            assert_eq!(result.get("lang"), Some(&json!("en")));
+        }
+
+        #[tokio::test]
+        async fn updates_new_typed_settings() {
+            let sub = Uuid::new_v4();
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = settings_request(json!({
+                "timezone": "Europe/Paris",
+                "notifications.desktop": false
+            }));
+
+            let result = service.update_user_settings(sub, req, &AuditContext::new(sub)).await.unwrap();
+
+            assert_eq!(result.get("timezone"), Some(&json!("Europe/Paris")));
+            assert_eq!(result.get("notifications.desktop"), Some(&json!(false)));
+        }
+
+        #[tokio::test]
+        async fn resets_setting_to_default_with_null() {
+            let sub = Uuid::new_v4();
+            let setting = create_test_setting(sub);
+
+            let repo = MockUserRepository::new().with_setting(setting);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = settings_request(json!({ "theme": null }));
+
+            let result = service.update_user_settings(sub, req, &AuditContext::new(sub)).await.unwrap();
+
+            assert_eq!(result.get("theme"), Some(&json!("light")));
+        }
+
+        #[tokio::test]
+        async fn rejects_invalid_value_and_names_the_field() {
+            let sub = Uuid::new_v4();
+            let setting = create_test_setting(sub);
+
+            let repo = MockUserRepository::new().with_setting(setting);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let req = settings_request(json!({ "theme": "neon" }));
+
+            let result = service.update_user_settings(sub, req, &AuditContext::new(sub)).await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(msg)) if msg.contains("theme")));
+            let stored = repo.get_setting_by_sub(sub).await.unwrap().unwrap();
+            assert_eq!(stored.get("theme"), Some(&json!("dark")));
+        }
+    }
+
+    mod get_or_create_user {
+        use super::*;
+
+        #[tokio::test]
+        async fn returns_existing_user() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_or_create_user(sub, "testuser").await.unwrap();
+
+            assert_eq!(result.sub, sub);
+            assert_eq!(result.display_name, "Test User");
+        }
+
+        #[tokio::test]
+        async fn creates_new_user_when_not_exists() {
+            let sub = Uuid::new_v4();
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            let result = service.get_or_create_user(sub, "newuser").await.unwrap();
+
+            assert_eq!(result.sub, sub);
+            assert_eq!(result.display_name, "newuser");
+        }
+
+        #[tokio::test]
+        async fn provisions_default_settings_for_new_user() {
+            let sub = Uuid::new_v4();
+
+            let repo = MockUserRepository::new();
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            service.get_or_create_user(sub, "newuser").await.unwrap();
+
+            assert!(repo.get_setting_by_sub(sub).await.unwrap().is_some());
+        }
+    }
+
+    mod user_cache {
+        use super::*;
+
+        #[tokio::test]
+        async fn get_or_create_user_serves_repeated_lookups_from_cache() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user);
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            service.get_or_create_user(sub, "testuser").await.unwrap();
+            repo.users.lock().unwrap().clear();
+            let result = service.get_or_create_user(sub, "testuser").await.unwrap();
+
+            assert_eq!(result.display_name, "Test User");
+            assert_eq!(service.cache_metrics().hits, 1);
+            assert_eq!(service.cache_metrics().misses, 1);
+        }
+
+        #[tokio::test]
+        async fn update_user_invalidates_cached_identity() {
+            let sub = Uuid::new_v4();
+            let user = create_test_user(sub);
+
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &keycloak, &content);
+
+            service.get_user_by_sub(Uuid::new_v4(), sub).await.unwrap();
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            };
+            service.update_user(&user, req, &AuditContext::new(user.sub)).await.unwrap();
+            let result = service.get_user_by_sub(Uuid::new_v4(), sub).await.unwrap();
+
+            assert_eq!(result.display_name, "New Name");
+            assert_eq!(service.cache_metrics().invalidations, 1);
+        }
+    }
+
+    mod relationships {
+        use super::*;
+
+        struct Fixture {
+            repo: MockUserRepository,
+            service: TestService,
+            alice: Uuid,
+            bob: Uuid,
+        }
+
+        fn fixture() -> Fixture {
+            let alice = Uuid::new_v4();
+            let bob = Uuid::new_v4();
+            let repo = MockUserRepository::new()
+                .with_user(create_test_user(alice))
+                .with_user(create_test_user(bob));
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+            Fixture {
+                repo,
+                service,
+                alice,
+                bob,
+            }
+        }
+
+        #[tokio::test]
+        async fn send_friend_request_creates_pending_pair() {
+            let f = fixture();
+
+            let info = f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+
+            assert_eq!(info.kind, RelationshipKind::PendingOutgoing);
+            assert_eq!(info.user.sub, f.bob);
+            assert_eq!(
+                f.repo.relationship_kind(f.bob, f.alice),
+                Some(RelationshipKind::PendingIncoming)
+            );
+        }
+
+        #[tokio::test]
+        async fn send_friend_request_to_self_is_rejected() {
+            let f = fixture();
+
+            let result = f.service.send_friend_request(f.alice, f.alice).await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+
+        #[tokio::test]
+        async fn send_friend_request_to_unknown_user_is_not_found() {
+            let f = fixture();
+
+            let result = f.service.send_friend_request(f.alice, Uuid::new_v4()).await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn send_friend_request_back_accepts_it() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+
+            let info = f.service.send_friend_request(f.bob, f.alice).await.unwrap();
+
+            assert_eq!(info.kind, RelationshipKind::Friend);
+            assert_eq!(
+                f.repo.relationship_kind(f.alice, f.bob),
+                Some(RelationshipKind::Friend)
+            );
+        }
+
+        #[tokio::test]
+        async fn accept_friend_request_makes_both_friends() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+
+            let info = f.service.accept_friend_request(f.bob, f.alice).await.unwrap();
+
+            assert_eq!(info.kind, RelationshipKind::Friend);
+            assert_eq!(
+                f.repo.relationship_kind(f.alice, f.bob),
+                Some(RelationshipKind::Friend)
+            );
+        }
+
+        #[tokio::test]
+        async fn sender_cannot_accept_own_request() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+
+            let result = f.service.accept_friend_request(f.alice, f.bob).await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn decline_and_cancel_remove_both_sides() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+            f.service.decline_friend_request(f.bob, f.alice).await.unwrap();
+            assert!(f.repo.relationship_kind(f.alice, f.bob).is_none());
+
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+            f.service.cancel_friend_request(f.alice, f.bob).await.unwrap();
+            assert!(f.repo.relationship_kind(f.bob, f.alice).is_none());
+        }
+
+        #[tokio::test]
+        async fn remove_friend_requires_friendship() {
+            let f = fixture();
+
+            let result = f.service.remove_friend(f.alice, f.bob).await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn block_replaces_friendship() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+            f.service.accept_friend_request(f.bob, f.alice).await.unwrap();
+
+            let info = f.service.block_user(f.alice, f.bob).await.unwrap();
+
+            assert_eq!(info.kind, RelationshipKind::Blocked);
+            assert!(f.repo.relationship_kind(f.bob, f.alice).is_none());
+        }
+
+        #[tokio::test]
+        async fn blocked_user_cannot_see_blocker() {
+            let f = fixture();
+            f.service.block_user(f.alice, f.bob).await.unwrap();
+
+            let by_sub = f.service.get_user_by_sub(f.bob, f.alice).await;
+            let in_batch = f
+                .service
+                .get_users_by_subs(Viewer::User(f.bob), &[f.alice], BatchOrder::Requested, None, 10)
+                .await
+                .unwrap();
+            let request = f.service.send_friend_request(f.bob, f.alice).await;
+
+            assert!(matches!(by_sub, Err(CoreError::NotFound(_))));
+            assert!(in_batch.users.is_empty());
+            assert_eq!(in_batch.not_found, vec![f.alice]);
+            assert!(matches!(request, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn blocker_still_sees_blocked_user() {
+            let f = fixture();
+            f.service.block_user(f.alice, f.bob).await.unwrap();
+
+            assert!(f.service.get_user_by_sub(f.alice, f.bob).await.is_ok());
+        }
+
+        #[tokio::test]
+        async fn unblock_restores_visibility() {
+            let f = fixture();
+            f.service.block_user(f.alice, f.bob).await.unwrap();
+
+            f.service.unblock_user(f.alice, f.bob).await.unwrap();
+
+            assert!(f.service.get_user_by_sub(f.bob, f.alice).await.is_ok());
+        }
+
+        #[tokio::test]
+        async fn list_relationships_returns_targets() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+
+            let relationships = f.service.list_relationships(f.bob).await.unwrap();
+
+            assert_eq!(relationships.len(), 1);
+            assert_eq!(relationships[0].user.sub, f.alice);
+            assert_eq!(relationships[0].kind, RelationshipKind::PendingIncoming);
+        }
+
+        #[tokio::test]
+        async fn list_relationships_hides_deleted_users() {
+            let f = fixture();
+            f.service.send_friend_request(f.alice, f.bob).await.unwrap();
+            f.repo.users.lock().unwrap().get_mut(&f.alice).unwrap().deleted_at = Some(Utc::now());
+
+            let relationships = f.service.list_relationships(f.bob).await.unwrap();
+
+            assert!(relationships.is_empty());
+        }
+    }
+
+    mod account_deletion {
+        use super::*;
+
+        struct Fixture {
+            repo: MockUserRepository,
+            keycloak: MockKeycloakClient,
+            content: MockContentServiceClient,
+            user: User,
+            other: Uuid,
+        }
+
+        impl Fixture {
+            fn service(&self) -> TestService {
+                test_service(&self.repo, &self.keycloak, &self.content)
+            }
+
+            fn audit(&self) -> AuditContext {
+                AuditContext::new(self.user.sub)
+            }
+        }
+
+        fn fixture(keycloak: MockKeycloakClient) -> Fixture {
+            let user = create_test_user(Uuid::new_v4());
+            let other = Uuid::new_v4();
+            let repo = MockUserRepository::new()
+                .with_user(user.clone())
+                .with_user(create_test_user(other))
+                .with_setting(create_test_setting(user.sub));
+            Fixture {
+                repo,
+                keycloak,
+                content: MockContentServiceClient::new(),
+                user,
+                other,
+            }
+        }
+
+        #[tokio::test]
+        async fn request_schedules_purge_after_grace_period() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f
+                .service()
+                .with_deletion_grace_period(Duration::from_secs(7 * 24 * 3600));
+
+            let before = Utc::now();
+            let info = service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+
+            assert!(info.purge_after >= before + chrono::Duration::days(7));
+            assert!(info.purge_after <= Utc::now() + chrono::Duration::days(7));
+            assert!(f.repo.users.lock().unwrap()[&f.user.sub].is_deleted());
+        }
+
+        #[tokio::test]
+        async fn repeated_request_keeps_original_schedule() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+
+            let first = service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+            let second = service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+
+            assert_eq!(first.purge_after, second.purge_after);
+        }
+
+        #[tokio::test]
+        async fn deleted_user_is_hidden_from_others() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+
+            let by_sub = service.get_user_by_sub(f.other, f.user.sub).await;
+            let in_batch = service
+                .get_users_by_subs(
+                    Viewer::User(f.other),
+                    &[f.user.sub],
+                    BatchOrder::Requested,
+                    None,
+                    10,
+                )
+                .await
+                .unwrap();
+
+            assert!(matches!(by_sub, Err(CoreError::NotFound(_))));
+            assert!(in_batch.users.is_empty());
+        }
+
+        #[tokio::test]
+        async fn cancel_restores_account() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+
+            service.cancel_account_deletion(&f.user, &f.audit()).await.unwrap();
+
+            assert!(f.repo.deletion(f.user.sub).is_none());
+            assert!(service.get_user_by_sub(f.other, f.user.sub).await.is_ok());
+        }
+
+        #[tokio::test]
+        async fn cancel_without_pending_deletion_is_not_found() {
+            let f = fixture(MockKeycloakClient::new());
+
+            let result = f.service().cancel_account_deletion(&f.user, &f.audit()).await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn purge_skips_deletions_in_grace_period() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+
+            let purged = service.purge_deleted_accounts(10).await.unwrap();
+
+            assert_eq!(purged, 0);
+            assert!(f.content.deleted_pictures().is_empty());
+        }
+
+        #[tokio::test]
+        async fn purge_anonymizes_account() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+            f.repo.set_relationship(f.other, f.user.sub, RelationshipKind::Friend);
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+            f.repo.expire_grace_period(f.user.sub);
+
+            let purged = service.purge_deleted_accounts(10).await.unwrap();
+
+            let user = f.repo.users.lock().unwrap()[&f.user.sub].clone();
+            assert_eq!(purged, 1);
+            assert_eq!(user.display_name, DELETED_USER_DISPLAY_NAME);
+            assert!(user.description.is_empty());
+            assert!(f.repo.settings.lock().unwrap().get(&f.user.sub).is_none());
+            assert!(f.repo.relationship_kind(f.other, f.user.sub).is_none());
+            assert!(f.repo.deletion(f.user.sub).unwrap().anonymized_at.is_some());
+        }
+
+        #[tokio::test]
+        async fn purge_clears_suspension_details() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+            if let Some(user) = f.repo.users.lock().unwrap().get_mut(&f.user.sub) {
+                user.suspended_at = Some(Utc::now());
+                user.suspended_by = Some(f.other);
+                user.suspension_reason = Some("Spam sent to john@example.com".to_string());
+            }
+            f.repo.expire_grace_period(f.user.sub);
+
+            service.purge_deleted_accounts(10).await.unwrap();
+
+            let user = f.repo.users.lock().unwrap()[&f.user.sub].clone();
+            assert!(user.suspension_reason.is_none());
+            assert!(user.suspended_by.is_none());
+        }
+
+        #[tokio::test]
+        async fn purge_redacts_audit_entries() {
+            let f = fixture(MockKeycloakClient::new());
+            let service = f.service();
+            let audit = AuditContext {
+                ip_address: Some("203.0.113.7".to_string()),
+                user_agent: Some("Mozilla/5.0".to_string()),
+                ..f.audit()
+            };
+            let req = UpdateUserRequest {
+                display_name: Some("Renamed".to_string()),
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            };
+            service.update_user(&f.user, req, &audit).await.unwrap();
+            service.request_account_deletion(&f.user, &audit).await.unwrap();
+            f.repo.expire_grace_period(f.user.sub);
+
+            service.purge_deleted_accounts(10).await.unwrap();
+
+            let entries = f.repo.audit_log.lock().unwrap().clone();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].changes[0].field, "display_name");
+            assert_eq!(entries[0].changes[0].after, None);
+            assert!(entries.iter().all(|e| e.ip_address.is_none() && e.user_agent.is_none()));
+        }
+
+        #[tokio::test]
+        async fn purge_resumes_after_keycloak_failure() {
+            let f = fixture(MockKeycloakClient::new().failing_deletes(1));
+            let service = f.service();
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+            f.repo.expire_grace_period(f.user.sub);
+
+            let first = service.purge_deleted_accounts(10).await.unwrap();
+            let failed = f.repo.deletion(f.user.sub).unwrap();
+            let second = service.purge_deleted_accounts(10).await.unwrap();
+
+            assert_eq!(first, 0);
+            assert_eq!(failed.attempts, 1);
+            assert!(failed.avatar_deleted_at.is_some());
+            assert!(failed.keycloak_deleted_at.is_none());
+            assert_eq!(second, 1);
+            // The avatar step already succeeded and is not repeated
+            assert_eq!(f.content.deleted_pictures(), vec![f.user.sub.to_string()]);
+        }
+
+        #[tokio::test]
+        async fn cancel_after_purge_started_is_rejected() {
+            let f = fixture(MockKeycloakClient::new().failing_deletes(1));
+            let service = f.service();
+            service.request_account_deletion(&f.user, &f.audit()).await.unwrap();
+            f.repo.expire_grace_period(f.user.sub);
+            service.purge_deleted_accounts(10).await.unwrap();
+
+            let result = service.cancel_account_deletion(&f.user, &f.audit()).await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+    }
+
+    mod data_export {
+        use super::*;
+        use crate::export::{
+            AuditLogSection, EmailChangesSection, ExportSection, RelationshipsSection,
+            SectionFuture, UsernameHistorySection,
+        };
+
+        fn keycloak_info() -> KeycloakUserInfo {
+            KeycloakUserInfo {
+                username: "testuser".to_string(),
+                email: "test@example.com".to_string(),
+            }
+        }
+
+        fn service_for(sub: Uuid, keycloak: MockKeycloakClient) -> TestService {
+            let repo = MockUserRepository::new()
+                .with_user(create_test_user(sub))
+                .with_setting(create_test_setting(sub));
+            test_service(&repo, &keycloak, &MockContentServiceClient::new())
+        }
+
+        #[tokio::test]
+        async fn processed_export_contains_builtin_sections() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new().with_user(sub, keycloak_info()));
+            let export = service.request_data_export(sub).await.unwrap();
+
+            let completed = service.process_data_exports(10).await.unwrap();
+            let info = service.get_data_export(sub, export.id).await.unwrap();
+            let document = service.download_data_export(sub, export.id).await.unwrap();
+
+            assert_eq!(completed, 1);
+            assert_eq!(info.status, DataExportStatus::Completed);
+            assert_eq!(
+                info.download_url,
+                Some(format!("/users/me/exports/{}/download", export.id))
+            );
+            let sections = &document["sections"];
+            assert_eq!(sections["profile"]["display_name"], "Test User");
+            assert_eq!(sections["settings"]["theme"], "dark");
+            assert_eq!(sections["keycloak"]["email"], "test@example.com");
+        }
+
+        #[tokio::test]
+        async fn repeated_request_returns_export_in_progress() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new());
+
+            let first = service.request_data_export(sub).await.unwrap();
+            let second = service.request_data_export(sub).await.unwrap();
+
+            assert_eq!(first.id, second.id);
+            assert_eq!(second.status, DataExportStatus::Pending);
+            assert!(second.download_url.is_none());
+        }
+
+        #[tokio::test]
+        async fn download_before_completion_is_rejected() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new());
+            let export = service.request_data_export(sub).await.unwrap();
+
+            let result = service.download_data_export(sub, export.id).await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+
+        #[tokio::test]
+        async fn export_of_another_user_is_not_found() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new());
+            let export = service.request_data_export(sub).await.unwrap();
+
+            let result = service.get_data_export(Uuid::new_v4(), export.id).await;
+
+            assert!(matches!(result, Err(CoreError::NotFound(_))));
+        }
+
+        #[tokio::test]
+        async fn failing_section_fails_export() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::failing());
+            let export = service.request_data_export(sub).await.unwrap();
+
+            let completed = service.process_data_exports(10).await.unwrap();
+            let info = service.get_data_export(sub, export.id).await.unwrap();
+
+            assert_eq!(completed, 0);
+            assert_eq!(info.status, DataExportStatus::Failed);
+            assert!(info.download_url.is_none());
+        }
+
+        #[tokio::test]
+        async fn missing_keycloak_account_exports_empty_section() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new());
+            let export = service.request_data_export(sub).await.unwrap();
+
+            service.process_data_exports(10).await.unwrap();
+            let document = service.download_data_export(sub, export.id).await.unwrap();
+
+            assert!(document["sections"]["keycloak"].is_null());
+        }
+
+        struct CountSection;
+
+        impl ExportSection<MockUserRepository, MockKeycloakClient> for CountSection {
+            fn name(&self) -> &'static str {
+                "count"
+            }
+
+            fn collect<'a>(
+                &'a self,
+                _: &'a MockUserRepository,
+                _: &'a MockKeycloakClient,
+                _: Uuid,
+            ) -> SectionFuture<'a> {
+                Box::pin(async { Ok(json!(42)) })
+            }
+        }
+
+        #[tokio::test]
+        async fn registered_sections_are_exported() {
+            let sub = Uuid::new_v4();
+            let other = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new()).with_export_registry(
+                ExportRegistry::builtin()
+                    .register(RelationshipsSection)
+                    .register(CountSection),
+            );
+            service.user_repo.set_relationship(sub, other, RelationshipKind::Blocked);
+            let export = service.request_data_export(sub).await.unwrap();
+
+            service.process_data_exports(10).await.unwrap();
+            let document = service.download_data_export(sub, export.id).await.unwrap();
+
+            let sections = &document["sections"];
+            assert_eq!(sections["count"], 42);
+            assert_eq!(sections["relationships"][0]["target_sub"], other.to_string());
+            assert_eq!(sections["relationships"][0]["kind"], "blocked");
+        }
+
+        #[tokio::test]
+        async fn audit_section_hides_where_others_acted_from() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new())
+                .with_export_registry(ExportRegistry::builtin().register(AuditLogSection));
+            let from = |actor_sub| AuditContext {
+                actor_sub,
+                ip_address: Some("203.0.113.7".to_string()),
+                user_agent: None,
+            };
+            let repo = &service.user_repo;
+            for actor in [sub, Uuid::new_v4()] {
+                repo.record_audit(sub, AuditAction::SettingsUpdated, &[], &from(actor))
+                    .await
+                    .unwrap();
+            }
+            let export = service.request_data_export(sub).await.unwrap();
+
+            service.process_data_exports(10).await.unwrap();
+            let document = service.download_data_export(sub, export.id).await.unwrap();
+
+            let entries = &document["sections"]["audit_log"];
+            assert!(entries[0]["ip_address"].is_null());
+            assert_eq!(entries[1]["ip_address"], "203.0.113.7");
+        }
+
+        #[tokio::test]
+        async fn username_history_section_lists_own_usernames() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new())
+                .with_export_registry(ExportRegistry::builtin().register(UsernameHistorySection));
+            let held_until = Utc::now() + chrono::Duration::days(90);
+            let repo = &service.user_repo;
+            repo.sync_username(sub, "first", held_until).await.unwrap();
+            repo.sync_username(sub, "second", held_until).await.unwrap();
+            repo.sync_username(Uuid::new_v4(), "other", held_until).await.unwrap();
+            let export = service.request_data_export(sub).await.unwrap();
+
+            service.process_data_exports(10).await.unwrap();
+            let document = service.download_data_export(sub, export.id).await.unwrap();
+
+            let history = &document["sections"]["username_history"];
+            assert_eq!(history.as_array().unwrap().len(), 2);
+            assert_eq!(history[0]["username"], "second");
+            assert!(history[0]["released_at"].is_null());
+            assert_eq!(history[1]["username"], "first");
+            assert!(history[1]["held_until"].is_string());
+        }
+
+        #[tokio::test]
+        async fn email_changes_section_leaves_out_the_nonce() {
+            let sub = Uuid::new_v4();
+            let service = service_for(sub, MockKeycloakClient::new())
+                .with_export_registry(ExportRegistry::builtin().register(EmailChangesSection));
+            let change = PendingEmailChange {
+                sub,
+                old_email: "old@example.com".to_string(),
+                new_email: "new@example.com".to_string(),
+                nonce: Uuid::new_v4(),
+                requested_at: Utc::now(),
+                expires_at: Utc::now() + chrono::Duration::hours(24),
             };
-            self.users.lock().unwrap().insert(sub, user.clone());
-            Ok(user)
+            service.user_repo.email_changes.lock().unwrap().insert(sub, change);
+            let export = service.request_data_export(sub).await.unwrap();
+
+            service.process_data_exports(10).await.unwrap();
+            let document = service.download_data_export(sub, export.id).await.unwrap();
+
+            let changes = &document["sections"]["email_changes"];
+            assert_eq!(changes[0]["new_email"], "new@example.com");
+            assert_eq!(changes[0]["old_email"], "old@example.com");
+            assert!(changes[0].get("nonce").is_none());
         }
+    }
 
-        async fn get_user_by_sub(&self, sub: Uuid) -> Result<Option<User>, sqlx::Error> {
-            Ok(self.users.lock().unwrap().get(&sub).cloned())
+    mod audit_log {
+        use super::*;
+
+        struct Fixture {
+            repo: MockUserRepository,
+            service: TestService,
+            user: User,
+            audit: AuditContext,
+        }
+
+        fn fixture() -> Fixture {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new()
+                .with_user(user.clone())
+                .with_setting(create_test_setting(user.sub));
+            let keycloak = MockKeycloakClient::new().with_user(
+                user.sub,
+                KeycloakUserInfo {
+                    username: "olduser".to_string(),
+                    email: "old@example.com".to_string(),
+                },
+            );
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
+            let audit = AuditContext {
+                actor_sub: user.sub,
+                ip_address: Some("203.0.113.7".to_string()),
+                user_agent: Some("test-agent/1.0".to_string()),
+            };
+            Fixture {
+                repo,
+                service,
+                user,
+                audit,
+            }
+        }
+
+        fn update_request() -> UpdateUserRequest {
+            UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            }
+        }
+
+        fn entries(repo: &MockUserRepository) -> Vec<AuditEntry> {
+            repo.audit_log.lock().unwrap().clone()
+        }
+
+        #[tokio::test]
+        async fn profile_update_records_before_and_after() {
+            let f = fixture();
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                ..update_request()
+            };
+
+            f.service.update_user(&f.user, req, &f.audit).await.unwrap();
+
+            let entries = entries(&f.repo);
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].action, AuditAction::ProfileUpdated);
+            assert_eq!(entries[0].actor_sub, f.user.sub);
+            assert_eq!(entries[0].ip_address.as_deref(), Some("203.0.113.7"));
+            assert_eq!(entries[0].user_agent.as_deref(), Some("test-agent/1.0"));
+            assert_eq!(
+                entries[0].changes,
+                vec![FieldChange::new(
+                    "display_name",
+                    Some(json!("Test User")),
+                    Some(json!("New Name"))
+                )]
+            );
+        }
+
+        #[tokio::test]
+        async fn keycloak_update_redacts_emails() {
+            let f = fixture();
+            let req = UpdateUserRequest {
+                username: Some("newuser".to_string()),
+                email: Some("new@example.com".to_string()),
+                ..update_request()
+            };
+
+            f.service.update_user(&f.user, req, &f.audit).await.unwrap();
+
+            let entries = entries(&f.repo);
+            assert_eq!(
+                entries[0].changes,
+                vec![FieldChange::new(
+                    "username",
+                    Some(json!("olduser")),
+                    Some(json!("newuser"))
+                )]
+            );
+            assert_eq!(entries[1].action, AuditAction::EmailChangeRequested);
+            assert_eq!(
+                entries[1].changes,
+                vec![FieldChange::new(
+                    "email",
+                    Some(json!("o***@example.com")),
+                    Some(json!("n***@example.com"))
+                )]
+            );
+        }
+
+        #[tokio::test]
+        async fn unchanged_fields_are_not_recorded() {
+            let f = fixture();
+            let req = UpdateUserRequest {
+                display_name: Some(f.user.display_name.clone()),
+                ..update_request()
+            };
+
+            f.service.update_user(&f.user, req, &f.audit).await.unwrap();
+
+            assert!(entries(&f.repo).is_empty());
+        }
+
+        #[tokio::test]
+        async fn settings_reset_is_recorded_without_after_value() {
+            let f = fixture();
+
+            f.service
+                .update_user_settings(f.user.sub, settings_request(json!({ "theme": null })), &f.audit)
+                .await
+                .unwrap();
+
+            let entries = entries(&f.repo);
+            assert_eq!(entries[0].action, AuditAction::SettingsUpdated);
+            assert_eq!(
+                entries[0].changes,
+                vec![FieldChange::new("theme", Some(json!("dark")), None)]
+            );
         }
 
-        async fn get_users_by_subs(&self, subs: &[Uuid]) -> Result<Vec<User>, sqlx::Error> {
-            let users = self.users.lock().unwrap();
-            Ok(subs
-                .iter()
-                .filter_map(|sub| users.get(sub).cloned())
-                .collect())
+        #[tokio::test]
+        async fn account_deletion_and_restore_are_recorded() {
+            let f = fixture();
+
+            f.service
+                .request_account_deletion(&f.user, &f.audit)
+                .await
+                .unwrap();
+            f.service
+                .cancel_account_deletion(&f.user, &f.audit)
+                .await
+                .unwrap();
+
+            let actions: Vec<AuditAction> = entries(&f.repo).iter().map(|e| e.action).collect();
+            assert_eq!(
+                actions,
+                vec![
+                    AuditAction::AccountDeletionRequested,
+                    AuditAction::AccountDeletionCancelled
+                ]
+            );
         }
 
-        async fn get_or_create_user(&self, sub: Uuid, username: &str) -> Result<User, sqlx::Error> {
-            if let Some(user) = self.users.lock().unwrap().get(&sub).cloned() {
-                return Ok(user);
+        #[tokio::test]
+        async fn own_history_is_paginated_most_recent_first() {
+            let f = fixture();
+            for name in ["One", "Two", "Three"] {
+                let req = UpdateUserRequest {
+                    display_name: Some(name.to_string()),
+                    ..update_request()
+                };
+                f.service.update_user(&f.user, req, &f.audit).await.unwrap();
             }
-            self.create_user(sub, username).await
+
+            let first = f.service.get_audit_log(f.user.sub, None, 2).await.unwrap();
+            let second = f
+                .service
+                .get_audit_log(f.user.sub, first.next_cursor.as_deref(), 2)
+                .await
+                .unwrap();
+
+            assert_eq!(first.entries.len(), 2);
+            assert_eq!(first.entries[0].changes[0].after, Some(json!("Three")));
+            assert_eq!(second.entries.len(), 1);
+            assert_eq!(second.entries[0].changes[0].after, Some(json!("One")));
+            assert!(second.next_cursor.is_none());
         }
 
-        async fn update_user(
-            &self,
-            sub: Uuid,
-            req: UpdateUserRequest,
-        ) -> Result<User, sqlx::Error> {
-            let mut users = self.users.lock().unwrap();
-            if let Some(user) = users.get_mut(&sub) {
-                if let Some(display_name) = req.display_name {
-                    user.display_name = display_name;
-                }
-                if let Some(profile_picture) = req.profile_picture {
-                    user.profile_picture = profile_picture;
-                }
-                if let Some(description) = req.description {
-                    user.description = description;
-                }
-                user.updated_at = Utc::now();
-                return Ok(user.clone());
-            }
-            Err(sqlx::Error::RowNotFound)
+        #[tokio::test]
+        async fn own_history_excludes_other_users() {
+            let f = fixture();
+            let other = Uuid::new_v4();
+            f.repo
+                .record_audit(other, AuditAction::SettingsUpdated, &[], &AuditContext::new(other))
+                .await
+                .unwrap();
+
+            let page = f.service.get_audit_log(f.user.sub, None, 10).await.unwrap();
+
+            assert!(page.entries.is_empty());
         }
 
-        async fn get_setting_by_sub(&self, sub: Uuid) -> Result<Option<Setting>, sqlx::Error> {
-            Ok(self.settings.lock().unwrap().get(&sub).cloned())
+        #[tokio::test]
+        async fn query_filters_by_action() {
+            let f = fixture();
+            f.service
+                .request_account_deletion(&f.user, &f.audit)
+                .await
+                .unwrap();
+            f.service
+                .update_user_settings(f.user.sub, settings_request(json!({ "lang": "en" })), &f.audit)
+                .await
+                .unwrap();
+
+            let query = AuditLogQuery {
+                action: Some(AuditAction::SettingsUpdated),
+                ..AuditLogQuery::default()
+            };
+            let page = f.service.query_audit_log(query, None, 10).await.unwrap();
+
+            assert_eq!(page.entries.len(), 1);
+            assert_eq!(page.entries[0].action, AuditAction::SettingsUpdated);
         }
 
-        async fn create_setting(&self, sub: Uuid) -> Result<Setting, sqlx::Error> {
-            let now = Utc::now();
-            let setting = Setting {
-                sub,
-                theme: Some("light".to_string()),
-                lang: Some("en".to_string()),
-                created_at: now,
-                updated_at: now,
+        #[tokio::test]
+        async fn failed_update_is_not_recorded() {
+            let f = fixture();
+            let service = test_service(
+                &f.repo.clone().with_failing_updates(),
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+            let req = UpdateUserRequest {
+                display_name: Some("New Name".to_string()),
+                ..update_request()
             };
-            self.settings.lock().unwrap().insert(sub, setting.clone());
-            Ok(setting)
+
+            let result = service.update_user(&f.user, req, &f.audit).await;
+
+            assert!(result.is_err());
+            assert!(entries(&f.repo).is_empty());
         }
+    }
 
-        async fn update_setting(
-            &self,
-            sub: Uuid,
-            req: UpdateSettingRequest,
-        ) -> Result<Setting, sqlx::Error> {
-            let mut settings = self.settings.lock().unwrap();
-            if let Some(setting) = settings.get_mut(&sub) {
-                if let Some(theme) = req.theme {
-                    setting.theme = Some(theme);
-                }
-                if let Some(lang) = req.lang {
-                    setting.lang = Some(lang);
-                }
-                setting.updated_at = Utc::now();
-                return Ok(setting.clone());
+    mod keycloak_sync {
+        use super::*;
+
+        fn event(kind: KeycloakEventKind, sub: Uuid, username: Option<&str>) -> KeycloakEvent {
+            KeycloakEvent {
+                kind,
+                sub,
+                username: username.map(String::from),
             }
-            Err(sqlx::Error::RowNotFound)
         }
-    }
 
-    fn create_test_user(sub: Uuid) -> User {
-        let now = Utc::now();
-        User {
-            sub,
-            display_name: "Test User".to_string(),
-            profile_picture: "https://example.com/pic.jpg".to_string(),
-            description: "A test user".to_string(),
-            created_at: now,
-            updated_at: now,
+        #[tokio::test]
+        async fn registration_creates_user() {
+            let repo = MockUserRepository::new();
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+            let sub = Uuid::new_v4();
+
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::Registered, sub, Some("john")))
+                .await
+                .unwrap();
+
+            assert_eq!(repo.users.lock().unwrap()[&sub].display_name, "john");
         }
-    }
 
-    fn create_test_setting(sub: Uuid) -> Setting {
-        let now = Utc::now();
-        Setting {
-            sub,
-            theme: Some("dark".to_string()),
-            lang: Some("fr".to_string()),
-            created_at: now,
-            updated_at: now,
+        #[tokio::test]
+        async fn update_of_unknown_user_fetches_username_from_keycloak() {
+            let repo = MockUserRepository::new();
+            let content = MockContentServiceClient::new();
+            let sub = Uuid::new_v4();
+            let keycloak = MockKeycloakClient::new().with_user(
+                sub,
+                KeycloakUserInfo {
+                    username: "renamed".to_string(),
+                    email: "renamed@example.com".to_string(),
+                },
+            );
+            let service = test_service(&repo, &keycloak, &content);
+
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::ProfileUpdated, sub, None))
+                .await
+                .unwrap();
+
+            assert_eq!(repo.users.lock().unwrap()[&sub].display_name, "renamed");
         }
-    }
 
-    mod get_user_by_sub {
-        use super::*;
+        #[tokio::test]
+        async fn update_keeps_existing_user() {
+            let sub = Uuid::new_v4();
+            let repo = MockUserRepository::new().with_user(create_test_user(sub));
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::ProfileUpdated, sub, Some("new")))
+                .await
+                .unwrap();
+
+            assert_eq!(repo.users.lock().unwrap()[&sub].display_name, "Test User");
+        }
 
         #[tokio::test]
-        async fn returns_user_when_exists() {
+        async fn update_moves_username_claim() {
             let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
+            let repo = MockUserRepository::new().with_user(create_test_user(sub));
+            repo.username_history.lock().unwrap().push(UsernameClaim {
+                sub,
+                username: "old".to_string(),
+                claimed_at: Utc::now(),
+                released_at: None,
+                held_until: None,
+            });
+            let content = MockContentServiceClient::new();
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
 
-            let repo = MockUserRepository::new().with_user(user.clone());
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::ProfileUpdated, sub, Some("new")))
+                .await
+                .unwrap();
+
+            let history = repo.username_history.lock().unwrap().clone();
+            assert!(history[0].held_until.is_some());
+            assert_eq!(history[1].username, "new");
+            assert!(history[1].released_at.is_none());
+        }
+
+        #[tokio::test]
+        async fn event_for_vanished_account_creates_nothing() {
+            let repo = MockUserRepository::new();
             let content = MockContentServiceClient::new();
-            let keycloak = MockKeycloakClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+            let sub = Uuid::new_v4();
 
-            let result = service.get_user_by_sub(sub).await.unwrap();
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::Registered, sub, None))
+                .await
+                .unwrap();
 
-            assert_eq!(result.sub, sub);
-            assert_eq!(result.display_name, "Test User");
+            assert!(repo.users.lock().unwrap().is_empty());
         }
 
         #[tokio::test]
-        async fn returns_not_found_when_user_does_not_exist() {
+        async fn deletion_purges_user_immediately() {
             let sub = Uuid::new_v4();
+            let viewer = Uuid::new_v4();
+            let repo = MockUserRepository::new()
+                .with_user(create_test_user(sub))
+                .with_user(create_test_user(viewer))
+                .with_setting(create_test_setting(sub));
+            let content = MockContentServiceClient::new();
+            // Keycloak deletes would fail: the account is already gone there
+            let keycloak = MockKeycloakClient::new().failing_deletes(1);
+            let service = test_service(&repo, &keycloak, &content);
+
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::AccountDeleted, sub, None))
+                .await
+                .unwrap();
+
+            let user = repo.users.lock().unwrap()[&sub].clone();
+            assert!(user.is_deleted());
+            assert_eq!(user.display_name, DELETED_USER_DISPLAY_NAME);
+            assert!(repo.deletion(sub).unwrap().anonymized_at.is_some());
+            assert_eq!(content.deleted_pictures(), vec![sub.to_string()]);
+            let batch = service
+                .get_users_by_subs(Viewer::User(viewer), &[sub], BatchOrder::Requested, None, 10)
+                .await
+                .unwrap();
+            assert!(batch.users.is_empty());
+        }
 
+        #[tokio::test]
+        async fn deletion_of_unknown_user_is_ignored() {
             let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::new();
             let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+            let sub = Uuid::new_v4();
 
-            let result = service.get_user_by_sub(sub).await;
+            service
+                .sync_keycloak_event(&event(KeycloakEventKind::AccountDeleted, sub, None))
+                .await
+                .unwrap();
 
-            assert!(matches!(result, Err(CoreError::NotFound(_))));
+            assert!(repo.deletion(sub).is_none());
         }
     }
 
-    mod get_user_by_username {
+    mod reconcile {
+        use super::*;
+        use crate::services::KeycloakService;
+        use wiremock::matchers::{method, path, path_regex, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        fn keycloak_info(username: &str) -> KeycloakUserInfo {
+            KeycloakUserInfo {
+                username: username.to_string(),
+                email: format!("{}@example.com", username),
+            }
+        }
+
+        struct Fixture {
+            repo: MockUserRepository,
+            keycloak: MockKeycloakClient,
+            synced: Uuid,
+            missing: Uuid,
+            orphaned: Uuid,
+        }
+
+        impl Fixture {
+            fn service(&self) -> TestService {
+                test_service(&self.repo, &self.keycloak, &MockContentServiceClient::new())
+            }
+        }
+
+        /// One user on each side only, one on both.
+        fn fixture() -> Fixture {
+            let (synced, missing, orphaned) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+            Fixture {
+                repo: MockUserRepository::new()
+                    .with_user(create_test_user(synced))
+                    .with_user(create_test_user(orphaned)),
+                keycloak: MockKeycloakClient::new()
+                    .with_user(synced, keycloak_info("synced"))
+                    .with_user(missing, keycloak_info("missing")),
+                synced,
+                missing,
+                orphaned,
+            }
+        }
+
+        #[tokio::test]
+        async fn dry_run_reports_orphans_without_changes() {
+            let f = fixture();
+
+            let report = f.service().reconcile_keycloak_users(true, 1).await.unwrap();
+
+            assert!(report.dry_run);
+            assert_eq!(report.keycloak_users, 2);
+            assert_eq!(report.local_users, 2);
+            assert_eq!(
+                report.missing_local,
+                vec![KeycloakAccount {
+                    sub: f.missing,
+                    username: "missing".to_string(),
+                    service_account_client_id: None,
+                }]
+            );
+            assert_eq!(report.orphaned_local, vec![f.orphaned]);
+            assert_eq!((report.created, report.flagged), (0, 0));
+            assert!(!f.repo.users.lock().unwrap().contains_key(&f.missing));
+            assert!(f.repo.deletion(f.orphaned).is_none());
+        }
+
+        #[tokio::test]
+        async fn fix_creates_missing_rows_and_flags_orphans() {
+            let f = fixture();
+
+            let report = f.service().reconcile_keycloak_users(false, 10).await.unwrap();
+
+            assert_eq!((report.created, report.flagged), (1, 1));
+            assert!(report.failures.is_empty());
+            assert_eq!(f.repo.users.lock().unwrap()[&f.missing].display_name, "missing");
+            let deletion = f.repo.deletion(f.orphaned).unwrap();
+            assert!(deletion.keycloak_deleted_at.is_some());
+            assert!(deletion.purge_after > Utc::now());
+            assert!(f.repo.users.lock().unwrap()[&f.orphaned].is_deleted());
+            assert!(f.repo.deletion(f.synced).is_none());
+        }
+
+        #[tokio::test]
+        async fn fix_backfills_username_claims() {
+            let f = fixture();
+
+            let report = f.service().reconcile_keycloak_users(false, 10).await.unwrap();
+
+            assert_eq!(report.usernames_claimed, 1);
+            let history = f.repo.username_history.lock().unwrap().clone();
+            assert_eq!(history.len(), 1);
+            assert_eq!((history[0].sub, history[0].username.as_str()), (f.synced, "synced"));
+        }
+
+        #[tokio::test]
+        async fn second_run_finds_nothing_left() {
+            let f = fixture();
+            let service = f.service();
+            service.reconcile_keycloak_users(false, 10).await.unwrap();
+
+            let report = service.reconcile_keycloak_users(true, 10).await.unwrap();
+
+            assert!(report.missing_local.is_empty());
+            assert!(report.orphaned_local.is_empty());
+        }
+
+        #[tokio::test]
+        async fn flags_rows_of_service_accounts_only() {
+            let f = fixture();
+            let client = Uuid::new_v4();
+            let repo = f.repo.clone().with_user(User {
+                display_name: "payments-service".to_string(),
+                ..create_test_user(client)
+            });
+            let keycloak = f.keycloak.clone().with_service_account(client, "payments-service");
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
+
+            let dry_run = service.reconcile_keycloak_users(true, 10).await.unwrap();
+            let report = service.reconcile_keycloak_users(false, 10).await.unwrap();
+
+            assert_eq!(dry_run.service_accounts, vec![client]);
+            assert_eq!(report.flagged, 2);
+            let deletion = f.repo.deletion(client).unwrap();
+            assert!(deletion.keycloak_deleted_at.is_some());
+            assert!(f.repo.deletion(f.synced).is_none());
+        }
+
+        #[tokio::test]
+        async fn flags_service_account_rows_without_reconciling() {
+            let f = fixture();
+            let (client, flagged_before) = (Uuid::new_v4(), Uuid::new_v4());
+            let repo = f
+                .repo
+                .clone()
+                .with_user(create_test_user(client))
+                .with_user(User {
+                    deleted_at: Some(Utc::now()),
+                    ..create_test_user(flagged_before)
+                });
+            let keycloak = f
+                .keycloak
+                .clone()
+                .with_service_account(client, "payments")
+                .with_service_account(flagged_before, "billing")
+                .with_service_account(Uuid::new_v4(), "without-row");
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
+
+            let flagged = service.flag_service_account_users().await.unwrap();
+
+            assert_eq!(flagged, 1);
+            assert!(f.repo.deletion(client).is_some());
+            assert!(f.repo.deletion(f.orphaned).is_none());
+            assert!(f.repo.deletion(f.synced).is_none());
+        }
+
+        #[tokio::test]
+        async fn keycloak_failure_aborts_reconciliation() {
+            let f = fixture();
+            let service = test_service(
+                &f.repo,
+                &MockKeycloakClient::failing(),
+                &MockContentServiceClient::new(),
+            );
+
+            let result = service.reconcile_keycloak_users(false, 10).await;
+
+            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+            assert!(f.repo.deletion(f.orphaned).is_none());
+        }
+
+        #[tokio::test]
+        async fn pages_through_keycloak_http_api() {
+            let server = MockServer::start().await;
+            let (synced, missing, orphaned) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+            Mock::given(method("POST"))
+                .and(path("/realms/test/protocol/openid-connect/token"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({ "access_token": "token", "expires_in": 300 })),
+                )
+                .mount(&server)
+                .await;
+            // An account created mid-run shifts `synced` onto the second page
+            for (first, page) in [
+                ("0", json!([{ "id": synced, "username": "synced" }])),
+                ("1", json!([{ "id": synced, "username": "synced" }])),
+                ("2", json!([{ "id": missing, "username": "missing" }])),
+                ("3", json!([])),
+            ] {
+                Mock::given(method("GET"))
+                    .and(path("/admin/realms/test/users"))
+                    .and(query_param("first", first))
+                    .and(query_param("max", "1"))
+                    .respond_with(ResponseTemplate::new(200).set_body_json(page))
+                    .mount(&server)
+                    .await;
+            }
+            Mock::given(method("GET"))
+                .and(path_regex("^/admin/realms/test/users/.+$"))
+                .respond_with(ResponseTemplate::new(404))
+                .mount(&server)
+                .await;
+            let repo = MockUserRepository::new()
+                .with_user(create_test_user(synced))
+                .with_user(create_test_user(orphaned));
+            let keycloak = KeycloakService::new(
+                server.uri(),
+                "test".to_string(),
+                "user-service".to_string(),
+                "secret".to_string(),
+            );
+            let service = UserServiceImpl::new(repo, keycloak, MockContentServiceClient::new());
+
+            let report = service.reconcile_keycloak_users(true, 1).await.unwrap();
+
+            assert_eq!(report.keycloak_users, 2);
+            assert_eq!(report.missing_local.len(), 1);
+            assert_eq!(report.missing_local[0].sub, missing);
+            assert_eq!(report.orphaned_local, vec![orphaned]);
+        }
+    }
+
+    mod avatar {
         use super::*;
+        use crate::avatar::new_avatar_key;
+
+        fn user_with_avatar(key: &str) -> User {
+            let mut user = create_test_user(Uuid::new_v4());
+            user.profile_picture.clear();
+            user.avatar_key = Some(key.to_string());
+            user
+        }
 
         #[tokio::test]
-        async fn returns_user_when_exists_in_keycloak_and_db() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
-            let keycloak_info = KeycloakUserInfo {
-                username: "testuser".to_string(),
-                email: "test@example.com".to_string(),
-            };
+        async fn upload_request_signs_put_url_for_new_key() {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let request = service.request_profile_picture_upload(&user).await.unwrap();
+
+            assert!(is_avatar_key_of(user.sub, &request.key));
+            assert_eq!(
+                request.url,
+                format!("https://cdn.test/{}?action=Put", request.key)
+            );
+            assert!(request.expires_at > chrono::Utc::now());
+        }
 
+        #[tokio::test]
+        async fn confirm_sets_avatar_and_queues_previous_one() {
+            let previous = new_avatar_key(Uuid::new_v4());
+            let user = user_with_avatar(&previous);
+            let key = new_avatar_key(user.sub);
+            let content = MockContentServiceClient::new().with_object(&key, "image/png", 1024);
             let repo = MockUserRepository::new().with_user(user.clone());
-            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+
+            let info = service
+                .confirm_profile_picture(&user, &key, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
+
+            assert_eq!(
+                info.profile_picture,
+                format!("https://cdn.test/{}?action=Get", key)
+            );
+            let stored = repo.users.lock().unwrap().get(&user.sub).cloned().unwrap();
+            assert_eq!(stored.avatar_key.as_deref(), Some(key.as_str()));
+            assert_eq!(*repo.avatar_cleanups.lock().unwrap(), vec![previous]);
+            let entries = repo.audit_log.lock().unwrap().clone();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].changes[0].field, "profile_picture");
+        }
+
+        #[tokio::test]
+        async fn confirm_rejects_key_of_another_user() {
+            let user = create_test_user(Uuid::new_v4());
+            let key = new_avatar_key(Uuid::new_v4());
+            let content = MockContentServiceClient::new().with_object(&key, "image/png", 1024);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+
+            let result = service
+                .confirm_profile_picture(&user, &key, &AuditContext::new(user.sub))
+                .await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
+
+        #[tokio::test]
+        async fn confirm_requires_uploaded_object() {
+            let user = create_test_user(Uuid::new_v4());
+            let key = new_avatar_key(user.sub);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let result = service
+                .confirm_profile_picture(&user, &key, &AuditContext::new(user.sub))
+                .await;
+
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+            let stored = repo.users.lock().unwrap().get(&user.sub).cloned().unwrap();
+            assert_eq!(stored.avatar_key, None);
+        }
+
+        #[tokio::test]
+        async fn confirm_deletes_unsupported_or_oversized_uploads() {
+            let user = create_test_user(Uuid::new_v4());
+            let svg = new_avatar_key(user.sub);
+            let huge = new_avatar_key(user.sub);
+            let content = MockContentServiceClient::new()
+                .with_object(&svg, "image/svg+xml", 1024)
+                .with_object(&huge, "image/png", MAX_AVATAR_SIZE + 1);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+
+            for key in [&svg, &huge] {
+                let result = service
+                    .confirm_profile_picture(&user, key, &AuditContext::new(user.sub))
+                    .await;
+                assert!(matches!(result, Err(CoreError::BadRequest(_))));
+            }
+
+            assert_eq!(content.deleted_pictures(), vec![svg, huge]);
+        }
+
+        #[tokio::test]
+        async fn read_urls_are_signed_once_and_reused() {
+            let key = new_avatar_key(Uuid::new_v4());
+            let user = user_with_avatar(&key);
+            let viewer = Uuid::new_v4();
             let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
 
-            let result = service.get_user_by_username("testuser").await.unwrap();
+            let first = service.get_user_by_sub(viewer, user.sub).await.unwrap();
+            let second = service.get_user_by_sub(viewer, user.sub).await.unwrap();
 
-            assert_eq!(result.sub, sub);
-            assert_eq!(result.display_name, "Test User");
+            assert_eq!(
+                first.profile_picture,
+                format!("https://cdn.test/{}?action=Get", key)
+            );
+            assert_eq!(second.profile_picture, first.profile_picture);
+            assert_eq!(content.signed_urls(), 1);
         }
 
         #[tokio::test]
-        async fn returns_not_found_when_user_not_in_keycloak() {
-            let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::new();
+        async fn external_picture_is_served_as_is() {
+            let user = create_test_user(Uuid::new_v4());
             let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
 
-            let result = service.get_user_by_username("nonexistent").await;
+            let info = service
+                .get_user_by_sub(Uuid::new_v4(), user.sub)
+                .await
+                .unwrap();
 
-            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+            assert_eq!(info.profile_picture, user.profile_picture);
+            assert_eq!(content.signed_urls(), 0);
         }
 
         #[tokio::test]
-        async fn returns_not_found_when_user_in_keycloak_but_not_in_db() {
-            let sub = Uuid::new_v4();
-            let keycloak_info = KeycloakUserInfo {
-                username: "testuser".to_string(),
-                email: "test@example.com".to_string(),
+        async fn signing_failure_hides_the_picture() {
+            let user = user_with_avatar(&new_avatar_key(Uuid::new_v4()));
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::failing(),
+            );
+
+            let info = service
+                .get_user_by_sub(Uuid::new_v4(), user.sub)
+                .await
+                .unwrap();
+
+            assert_eq!(info.profile_picture, "");
+        }
+
+        #[tokio::test]
+        async fn cleanup_deletes_replaced_avatars() {
+            let previous = new_avatar_key(Uuid::new_v4());
+            let user = user_with_avatar(&previous);
+            let content = MockContentServiceClient::new();
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+            let req = UpdateUserRequest {
+                display_name: None,
+                profile_picture: Some("https://example.com/new.jpg".to_string()),
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
             };
+            service
+                .update_user(&user, req, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
 
-            let repo = MockUserRepository::new(); // No user in DB
-            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
+            let deleted = service.cleanup_replaced_avatars(10).await.unwrap();
+
+            assert_eq!(deleted, 1);
+            assert_eq!(content.deleted_pictures(), vec![previous]);
+            assert!(repo.avatar_cleanups.lock().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn failed_cleanup_stays_queued() {
+            let repo = MockUserRepository::new().with_user(create_test_user(Uuid::new_v4()));
+            let content = MockContentServiceClient::failing();
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+            repo.avatar_cleanups
+                .lock()
+                .unwrap()
+                .push("old/avatar".to_string());
+
+            let deleted = service.cleanup_replaced_avatars(10).await.unwrap();
+
+            assert_eq!(deleted, 0);
+            assert_eq!(repo.avatar_cleanups.lock().unwrap().len(), 1);
+        }
+
+        #[tokio::test]
+        async fn purge_deletes_current_avatar() {
+            let key = new_avatar_key(Uuid::new_v4());
+            let user = user_with_avatar(&key);
             let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+            service
+                .request_account_deletion(&user, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
+            repo.expire_grace_period(user.sub);
 
-            let result = service.get_user_by_username("testuser").await;
+            service.purge_deleted_accounts(10).await.unwrap();
 
-            assert!(matches!(result, Err(CoreError::NotFound(_))));
+            assert_eq!(content.deleted_pictures(), vec![user.sub.to_string(), key]);
+        }
+    }
+
+    mod profile_customization {
+        use super::*;
+        use crate::avatar::new_banner_key;
+        use crate::models::ProfileLink;
+
+        fn stored(repo: &MockUserRepository, sub: Uuid) -> User {
+            repo.users.lock().unwrap().get(&sub).cloned().unwrap()
+        }
+
+        fn update_request() -> UpdateUserRequest {
+            UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: None,
+            }
         }
 
         #[tokio::test]
-        async fn returns_error_when_keycloak_fails() {
-            let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::failing();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+        async fn update_sets_and_clears_profile_fields() {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+            let audit = AuditContext::new(user.sub);
+            let links = vec![ProfileLink {
+                label: "GitHub".to_string(),
+                url: "https://github.com/john".to_string(),
+            }];
+            let req = UpdateUserRequest {
+                accent_color: Some("#5865F2".to_string()),
+                pronouns: Some("they/them".to_string()),
+                links: Some(links.clone()),
+                ..update_request()
+            };
 
-            let result = service.get_user_by_username("testuser").await;
+            let info = service.update_user(&user, req, &audit).await.unwrap();
 
-            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
-        }
-    }
+            assert_eq!(info.accent_color.as_deref(), Some("#5865f2"));
+            assert_eq!(info.pronouns.as_deref(), Some("they/them"));
+            assert_eq!(info.links, links);
 
-    mod get_current_user_info {
-        use super::*;
+            let updated = stored(&repo, user.sub);
+            let req = UpdateUserRequest {
+                accent_color: Some(String::new()),
+                links: Some(Vec::new()),
+                ..update_request()
+            };
+            let info = service.update_user(&updated, req, &audit).await.unwrap();
 
-        #[tokio::test]
-        async fn returns_basic_info_when_full_info_is_false() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
+            assert_eq!(info.accent_color, None);
+            assert_eq!(info.pronouns.as_deref(), Some("they/them"));
+            assert!(info.links.is_empty());
+        }
 
-            let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+        #[tokio::test]
+        async fn update_audits_changed_profile_fields_only() {
+            let mut user = create_test_user(Uuid::new_v4());
+            user.pronouns = Some("she/her".to_string());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+            let req = UpdateUserRequest {
+                accent_color: Some("#ffffff".to_string()),
+                pronouns: Some("she/her".to_string()),
+                links: Some(Vec::new()),
+                ..update_request()
+            };
 
-            let result = service.get_current_user_info(&user, false).await.unwrap();
+            service
+                .update_user(&user, req, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
 
-            assert_eq!(result["sub"], sub.to_string());
-            assert_eq!(result["display_name"], "Test User");
-            assert!(result.get("username").is_none());
-            assert!(result.get("email").is_none());
+            let entries = repo.audit_log.lock().unwrap().clone();
+            let fields: Vec<&str> = entries[0]
+                .changes
+                .iter()
+                .map(|change| change.field.as_str())
+                .collect();
+            assert_eq!(fields, vec!["accent_color"]);
         }
 
         #[tokio::test]
-        async fn returns_full_info_when_full_info_is_true() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
-            let keycloak_info = KeycloakUserInfo {
-                username: "testuser".to_string(),
-                email: "test@example.com".to_string(),
-            };
+        async fn banner_upload_uses_banner_key() {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
 
-            let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let request = service.request_banner_upload(&user).await.unwrap();
 
-            let result = service.get_current_user_info(&user, true).await.unwrap();
+            assert!(is_banner_key_of(user.sub, &request.key));
+            assert!(request.url.ends_with("?action=Put"));
+        }
 
-            assert_eq!(result["sub"], sub.to_string());
-            assert_eq!(result["display_name"], "Test User");
-            assert_eq!(result["username"], "testuser");
-            assert_eq!(result["email"], "test@example.com");
+        #[tokio::test]
+        async fn confirmed_banner_is_served_with_signed_url() {
+            let user = create_test_user(Uuid::new_v4());
+            let key = new_banner_key(user.sub);
+            let content = MockContentServiceClient::new().with_object(&key, "image/webp", 4096);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
+
+            let info = service
+                .confirm_banner(&user, &key, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
+
+            let url = format!("https://cdn.test/{}?action=Get", key);
+            assert_eq!(info.banner.as_deref(), Some(url.as_str()));
+            let seen = service
+                .get_user_by_sub(Uuid::new_v4(), user.sub)
+                .await
+                .unwrap();
+            assert_eq!(seen.banner.as_deref(), Some(url.as_str()));
+            assert_eq!(
+                stored(&repo, user.sub).banner_key.as_deref(),
+                Some(key.as_str())
+            );
         }
 
         #[tokio::test]
-        async fn returns_keycloak_error_when_keycloak_fails() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
+        async fn banner_confirmation_rejects_avatar_keys() {
+            let user = create_test_user(Uuid::new_v4());
+            let key = crate::avatar::new_avatar_key(user.sub);
+            let content = MockContentServiceClient::new().with_object(&key, "image/png", 1024);
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &MockKeycloakClient::new(), &content);
 
-            let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::failing();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let result = service
+                .confirm_banner(&user, &key, &AuditContext::new(user.sub))
+                .await;
 
-            let result = service.get_current_user_info(&user, true).await;
+            assert!(matches!(result, Err(CoreError::BadRequest(_))));
+        }
 
-            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+        #[tokio::test]
+        async fn removed_banner_is_queued_for_cleanup() {
+            let key = new_banner_key(Uuid::new_v4());
+            let mut user = create_test_user(Uuid::new_v4());
+            user.banner_key = Some(key.clone());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let info = service
+                .remove_banner(&user, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
+
+            assert_eq!(info.banner, None);
+            assert_eq!(*repo.avatar_cleanups.lock().unwrap(), vec![key]);
+            assert_eq!(repo.audit_log.lock().unwrap().len(), 1);
         }
     }
-
-    mod update_user {
+    mod privacy {
         use super::*;
+        use crate::avatar::new_banner_key;
+        use crate::models::FieldVisibility;
+
+        fn private_user() -> User {
+            let mut user = create_test_user(Uuid::new_v4());
+            user.banner_key = Some(new_banner_key(user.sub));
+            user.pronouns = Some("they/them".to_string());
+            user.privacy = ProfilePrivacy {
+                description: FieldVisibility::OnlyMe,
+                banner: FieldVisibility::OnlyMe,
+                pronouns: FieldVisibility::Authenticated,
+                ..ProfilePrivacy::default()
+            };
+            user
+        }
 
         #[tokio::test]
-        async fn updates_local_fields_only() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
-
+        async fn hidden_fields_are_not_returned_to_others() {
+            let user = private_user();
             let repo = MockUserRepository::new().with_user(user.clone());
-            let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let seen = service
+                .get_user_by_sub(Uuid::new_v4(), user.sub)
+                .await
+                .unwrap();
+
+            assert_eq!(seen.description, "");
+            assert_eq!(seen.banner, None);
+            assert_eq!(seen.pronouns.as_deref(), Some("they/them"));
+            assert_eq!(seen.profile_picture, user.profile_picture);
+            // The hidden banner is not even signed
+            assert_eq!(service.content_client.signed_urls(), 0);
+        }
 
-            let req = UpdateUserRequest {
-                display_name: Some("New Name".to_string()),
-                profile_picture: None,
-                description: None,
-                username: None,
-                email: None,
-            };
+        #[tokio::test]
+        async fn anonymous_lookups_hide_authenticated_fields() {
+            let user = private_user();
+            let keycloak = MockKeycloakClient::new().with_user(
+                user.sub,
+                KeycloakUserInfo {
+                    username: "private".to_string(),
+                    email: "private@example.com".to_string(),
+                },
+            );
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
 
-            let result = service.update_user(&user, req).await.unwrap();
+            let seen = service.get_user_by_username("private").await.unwrap();
 
-            assert_eq!(result.display_name, "New Name");
+            assert_eq!(seen.pronouns, None);
         }
 
         #[tokio::test]
-        async fn updates_keycloak_fields() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
-            let keycloak_info = KeycloakUserInfo {
-                username: "olduser".to_string(),
-                email: "old@example.com".to_string(),
+        async fn allow_listed_users_see_the_field() {
+            let friend = Uuid::new_v4();
+            let mut user = private_user();
+            user.privacy.description = FieldVisibility::AllowList {
+                allowed: vec![friend],
             };
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let batch = service
+                .get_users_by_subs(
+                    Viewer::User(friend),
+                    &[user.sub],
+                    BatchOrder::Requested,
+                    None,
+                    10,
+                )
+                .await
+                .unwrap();
+            let other = service
+                .get_users_by_subs(
+                    Viewer::User(Uuid::new_v4()),
+                    &[user.sub],
+                    BatchOrder::Requested,
+                    None,
+                    10,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(batch.users[0].description, user.description);
+            assert_eq!(other.users[0].description, "");
+        }
 
+        #[tokio::test]
+        async fn service_lookups_only_see_public_fields() {
+            let user = private_user();
             let repo = MockUserRepository::new().with_user(user.clone());
-            let keycloak = MockKeycloakClient::new().with_user(sub, keycloak_info);
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let batch = service
+                .get_users_by_subs(Viewer::Anonymous, &[user.sub], BatchOrder::Requested, None, 10)
+                .await
+                .unwrap();
+
+            assert_eq!(batch.users[0].pronouns, None);
+            assert_eq!(batch.users[0].description, "");
+        }
 
-            let req = UpdateUserRequest {
-                display_name: None,
-                profile_picture: None,
-                description: None,
-                username: Some("newuser".to_string()),
-                email: None,
-            };
+        #[tokio::test]
+        async fn owner_sees_hidden_fields() {
+            let user = private_user();
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
 
-            let result = service.update_user(&user, req).await;
+            let info = service.get_current_user_info(&user, false).await.unwrap();
 
-            assert!(result.is_ok());
+            assert_eq!(info["description"], json!(user.description));
+            assert!(info["banner"].is_string());
         }
 
         #[tokio::test]
-        async fn returns_keycloak_error_when_keycloak_update_fails() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
-
+        async fn update_merges_and_audits_changed_fields() {
+            let user = private_user();
             let repo = MockUserRepository::new().with_user(user.clone());
-            let keycloak = MockKeycloakClient::failing();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
-
-            let req = UpdateUserRequest {
-                display_name: None,
-                profile_picture: None,
-                description: None,
-                username: Some("newuser".to_string()),
-                email: None,
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+            let req = UpdatePrivacyRequest {
+                description: Some(FieldVisibility::OnlyMe),
+                links: Some(FieldVisibility::Authenticated),
+                ..UpdatePrivacyRequest::default()
             };
 
-            let result = service.update_user(&user, req).await;
+            let privacy = service
+                .update_privacy(&user, req, &AuditContext::new(user.sub))
+                .await
+                .unwrap();
+
+            assert_eq!(privacy.links, FieldVisibility::Authenticated);
+            assert_eq!(privacy.banner, FieldVisibility::OnlyMe);
+            let stored = repo.users.lock().unwrap().get(&user.sub).cloned().unwrap();
+            assert_eq!(stored.privacy, privacy);
+            let entries = repo.audit_log.lock().unwrap().clone();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].action, AuditAction::PrivacyUpdated);
+            let fields: Vec<&str> = entries[0]
+                .changes
+                .iter()
+                .map(|change| change.field.as_str())
+                .collect();
+            assert_eq!(fields, vec!["links"]);
+        }
 
-            assert!(matches!(result, Err(CoreError::KeycloakError(_))));
+        #[tokio::test]
+        async fn unchanged_privacy_is_not_audited() {
+            let user = private_user();
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            service
+                .update_privacy(
+                    &user,
+                    UpdatePrivacyRequest::default(),
+                    &AuditContext::new(user.sub),
+                )
+                .await
+                .unwrap();
+
+            assert!(repo.audit_log.lock().unwrap().is_empty());
         }
+    }
+    mod moderation {
+        use super::*;
+        use crate::models::FieldVisibility;
 
         #[tokio::test]
-        async fn does_not_update_local_db_when_keycloak_fails() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
+        async fn admin_view_ignores_privacy_and_deletion() {
+            let mut user = create_test_user(Uuid::new_v4());
+            user.privacy.description = FieldVisibility::OnlyMe;
+            user.deleted_at = Some(Utc::now());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
 
+            let info = service.admin_get_user(user.sub).await.unwrap();
+
+            assert_eq!(info.user.description, user.description);
+            assert!(info.user.is_deleted());
+            assert!(info.keycloak.is_none());
+        }
+
+        #[tokio::test]
+        async fn admin_search_ignores_blocks() {
+            let moderator = Uuid::new_v4();
+            let mut user = create_test_user(Uuid::new_v4());
+            user.display_name = "Mallory".to_string();
             let repo = MockUserRepository::new().with_user(user.clone());
-            let keycloak = MockKeycloakClient::failing();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo.clone(), keycloak, content);
+            repo.set_relationship(user.sub, moderator, RelationshipKind::Blocked);
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let page = service.admin_search_users("mall", None, 10).await.unwrap();
+            let by_sub = service
+                .admin_search_users(&user.sub.to_string(), None, 10)
+                .await
+                .unwrap();
+
+            assert_eq!(page.users.len(), 1);
+            assert_eq!(by_sub.users[0].sub, user.sub);
+        }
+
+        #[tokio::test]
+        async fn admin_search_pages_through_results() {
+            let mut repo = MockUserRepository::new();
+            for _ in 0..3 {
+                let mut user = create_test_user(Uuid::new_v4());
+                user.display_name = "Spammer".to_string();
+                repo = repo.with_user(user);
+            }
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
+
+            let first = service.admin_search_users("spam", None, 2).await.unwrap();
+            let second = service
+                .admin_search_users("spam", first.next_cursor.as_deref(), 2)
+                .await
+                .unwrap();
+
+            assert_eq!(first.users.len(), 2);
+            assert_eq!(second.users.len(), 1);
+            assert!(second.next_cursor.is_none());
+        }
 
+        #[tokio::test]
+        async fn admin_update_is_audited_under_the_moderator() {
+            let moderator = Uuid::new_v4();
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::new(),
+                &MockContentServiceClient::new(),
+            );
             let req = UpdateUserRequest {
-                display_name: Some("New Name".to_string()),
+                display_name: None,
                 profile_picture: None,
-                description: None,
-                username: Some("newuser".to_string()),
+                description: Some(String::new()),
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
                 email: None,
             };
 
-            let _ = service.update_user(&user, req).await;
+            let info = service
+                .admin_update_user(user.sub, req, &AuditContext::new(moderator))
+                .await
+                .unwrap();
 
-            // Verify local DB was not updated
-            let stored_user = repo.get_user_by_sub(sub).await.unwrap().unwrap();
-            assert_eq!(stored_user.display_name, "Test User");
+            assert_eq!(info.user.description, "");
+            let entries = repo.audit_log.lock().unwrap().clone();
+            assert_eq!(entries[0].actor_sub, moderator);
+            assert_eq!(entries[0].target_sub, user.sub);
         }
     }
 
-    mod get_user_settings {
+    mod suspension {
         use super::*;
 
-        #[tokio::test]
-        async fn returns_settings_when_exist() {
-            let sub = Uuid::new_v4();
-            let setting = create_test_setting(sub);
+        fn request(disable_keycloak: bool) -> SuspendUserRequest {
+            SuspendUserRequest {
+                reason: " Spam ".to_string(),
+                expires_at: Some(Utc::now() + chrono::Duration::days(1)),
+                disable_keycloak,
+            }
+        }
 
-            let repo = MockUserRepository::new().with_setting(setting);
+        #[tokio::test]
+        async fn suspension_is_recorded_and_audited() {
+            let moderator = Uuid::new_v4();
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
             let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
-
-            let result = service.get_user_settings(sub).await.unwrap();
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
+
+            let info = service
+                .suspend_user(user.sub, request(false), &AuditContext::new(moderator))
+                .await
+                .unwrap();
+
+            let suspension = info.user.active_suspension().unwrap();
+            assert_eq!(suspension.reason, "Spam");
+            assert_eq!(suspension.suspended_by, Some(moderator));
+            assert!(keycloak.disabled.lock().unwrap().is_empty());
+            let entries = repo.audit_log.lock().unwrap().clone();
+            assert_eq!(entries[0].action, AuditAction::UserSuspended);
+            assert_eq!(entries[0].actor_sub, moderator);
+        }
 
-            assert_eq!(result.sub, sub);
-            assert_eq!(result.theme, Some("dark".to_string()));
-            assert_eq!(result.lang, Some("fr".to_string()));
+        #[tokio::test]
+        async fn suspension_can_disable_keycloak_account() {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = MockKeycloakClient::new();
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
+            let audit = AuditContext::new(Uuid::new_v4());
+
+            service
+                .suspend_user(user.sub, request(true), &audit)
+                .await
+                .unwrap();
+            assert_eq!(*keycloak.disabled.lock().unwrap(), vec![user.sub]);
+
+            let info = service.lift_suspension(user.sub, &audit).await.unwrap();
+            assert!(info.user.active_suspension().is_none());
+            assert!(keycloak.disabled.lock().unwrap().is_empty());
         }
 
         #[tokio::test]
-        async fn returns_not_found_when_settings_do_not_exist() {
-            let sub = Uuid::new_v4();
+        async fn suspension_is_not_recorded_when_keycloak_fails() {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let service = test_service(
+                &repo,
+                &MockKeycloakClient::failing(),
+                &MockContentServiceClient::new(),
+            );
+
+            let result = service
+                .suspend_user(user.sub, request(true), &AuditContext::new(Uuid::new_v4()))
+                .await;
+
+            assert!(result.is_err());
+            assert!(repo.users.lock().unwrap()[&user.sub].suspended_at.is_none());
+        }
 
-            let repo = MockUserRepository::new();
+        #[tokio::test]
+        async fn expired_suspensions_are_lifted() {
+            let mut expired = create_test_user(Uuid::new_v4());
+            expired.suspended_at = Some(Utc::now() - chrono::Duration::days(2));
+            expired.suspended_until = Some(Utc::now() - chrono::Duration::days(1));
+            expired.suspension_reason = Some("Spam".to_string());
+            expired.suspension_disabled_keycloak = true;
+            let mut permanent = create_test_user(Uuid::new_v4());
+            permanent.suspended_at = Some(Utc::now());
+            permanent.suspension_reason = Some("Abuse".to_string());
+            let repo = MockUserRepository::new()
+                .with_user(expired.clone())
+                .with_user(permanent.clone());
             let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            keycloak.disabled.lock().unwrap().push(expired.sub);
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
 
-            let result = service.get_user_settings(sub).await;
+            assert!(expired.active_suspension().is_none());
+            let lifted = service.expire_suspensions(10).await.unwrap();
 
-            assert!(matches!(result, Err(CoreError::NotFound(_))));
+            assert_eq!(lifted, 1);
+            let users = repo.users.lock().unwrap();
+            assert!(users[&expired.sub].suspended_at.is_none());
+            assert!(users[&permanent.sub].active_suspension().is_some());
+            assert!(keycloak.disabled.lock().unwrap().is_empty());
         }
     }
 
-    mod update_user_settings {
+    mod username_policy {
         use super::*;
 
-        #[tokio::test]
-        async fn updates_theme() {
-            let sub = Uuid::new_v4();
-            let setting = create_test_setting(sub);
+        fn rename(username: &str) -> UpdateUserRequest {
+            UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: Some(username.to_string()),
+                email: None,
+            }
+        }
 
-            let repo = MockUserRepository::new().with_setting(setting);
-            let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+        fn keycloak_info(username: &str) -> KeycloakUserInfo {
+            KeycloakUserInfo {
+                username: username.to_string(),
+                email: format!("{}@example.com", username),
+            }
+        }
 
-            let req = UpdateSettingRequest {
-                theme: Some("light".to_string()),
-                lang: None,
-            };
+        struct Fixture {
+            user: User,
+            repo: MockUserRepository,
+            service: TestService,
+        }
+
+        fn fixture(keycloak: MockKeycloakClient) -> Fixture {
+            let user = create_test_user(Uuid::new_v4());
+            let repo = MockUserRepository::new().with_user(user.clone());
+            let keycloak = keycloak.with_user(user.sub, keycloak_info("john"));
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new())
+                .with_username_policy(UsernamePolicy::default().with_blocked(["scam"]));
+            Fixture {
+                user,
+                repo,
+                service,
+            }
+        }
 
-            let result = service.update_user_settings(sub, req).await.unwrap();
+        #[tokio::test]
+        async fn rejects_reserved_and_blocked_usernames() {
+            let Fixture { user, service, .. } = fixture(MockKeycloakClient::new());
+            let audit = AuditContext::new(user.sub);
+
+            let reserved = service.update_user(&user, rename("Adm1n"), &audit).await;
+            let blocked = service.update_user(&user, rename("scammer"), &audit).await;
 
-            assert_eq!(result.theme, Some("light".to_string()));
-            assert_eq!(result.lang, Some("fr".to_string()));
+            assert!(matches!(reserved, Err(CoreError::Conflict(_))));
+            assert!(matches!(blocked, Err(CoreError::Validation(_))));
         }
 
         #[tokio::test]
-        async fn updates_lang() {
-            let sub = Uuid::new_v4();
-            let setting = create_test_setting(sub);
+        async fn username_changes_are_rate_limited() {
+            let Fixture {
+                user,
+                repo,
+                service,
+            } = fixture(MockKeycloakClient::new());
+            let audit = AuditContext::new(user.sub);
+
+            service
+                .update_user(&user, rename("johnny"), &audit)
+                .await
+                .unwrap();
+            let again = service.update_user(&user, rename("jonathan"), &audit).await;
+
+            assert!(matches!(again, Err(CoreError::TooManyRequests(_))));
+            let history = repo.username_history.lock().unwrap().clone();
+            assert_eq!(history[0].username, "john");
+            assert!(history[0].held_until.is_some());
+            assert_eq!(history[1].username, "johnny");
+        }
 
-            let repo = MockUserRepository::new().with_setting(setting);
-            let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+        #[tokio::test]
+        async fn rejects_usernames_taken_by_others() {
+            let other = Uuid::new_v4();
+            let Fixture { user, service, .. } =
+                fixture(MockKeycloakClient::new().with_user(other, keycloak_info("mallory")));
 
-            let req = UpdateSettingRequest {
-                theme: None,
-                lang: Some("en".to_string()),
-            };
+            let result = service
+                .update_user(&user, rename("mallory"), &AuditContext::new(user.sub))
+                .await;
+
+            assert!(matches!(result, Err(CoreError::Conflict(_))));
+        }
+
+        #[tokio::test]
+        async fn released_usernames_are_held_for_their_previous_owner() {
+            let Fixture {
+                user,
+                repo,
+                service,
+            } = fixture(MockKeycloakClient::new());
+            let previous_owner = Uuid::new_v4();
+            repo.username_history.lock().unwrap().push(UsernameClaim {
+                sub: previous_owner,
+                username: "alice.smith".to_string(),
+                claimed_at: Utc::now(),
+                released_at: Some(Utc::now()),
+                held_until: Some(Utc::now() + chrono::Duration::days(30)),
+            });
+
+            let lookalike = service
+                .update_user(&user, rename("A1ice_Smith"), &AuditContext::new(user.sub))
+                .await;
+
+            assert!(matches!(lookalike, Err(CoreError::Conflict(_))));
+        }
 
-            let result = service.update_user_settings(sub, req).await.unwrap();
+        #[tokio::test]
+        async fn case_only_changes_skip_the_policy() {
+            let Fixture {
+                user,
+                repo,
+                service,
+            } = fixture(MockKeycloakClient::new());
+
+            service
+                .update_user(&user, rename("John"), &AuditContext::new(user.sub))
+                .await
+                .unwrap();
+
+            assert!(repo.username_history.lock().unwrap().is_empty());
+        }
 
-            assert_eq!(result.theme, Some("dark".to_string()));
-            assert_eq!(result.lang, Some("en".to_string()));
+        #[tokio::test]
+        async fn failed_rename_gives_the_username_back() {
+            let Fixture {
+                user,
+                repo,
+                service,
+            } = fixture(MockKeycloakClient::new().failing_after_updates(0));
+
+            let result = service
+                .update_user(&user, rename("johnny"), &AuditContext::new(user.sub))
+                .await;
+
+            assert!(result.is_err());
+            let history = repo.username_history.lock().unwrap().clone();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].username, "john");
+            assert!(history[0].released_at.is_none());
         }
     }
 
-    mod get_or_create_user {
+    mod email_verification {
         use super::*;
 
-        #[tokio::test]
-        async fn returns_existing_user() {
-            let sub = Uuid::new_v4();
-            let user = create_test_user(sub);
+        fn change_email(email: &str) -> UpdateUserRequest {
+            UpdateUserRequest {
+                display_name: None,
+                profile_picture: None,
+                description: None,
+                accent_color: None,
+                pronouns: None,
+                links: None,
+                username: None,
+                email: Some(email.to_string()),
+            }
+        }
+
+        struct Fixture {
+            user: User,
+            repo: MockUserRepository,
+            keycloak: MockKeycloakClient,
+            service: TestService,
+            audit: AuditContext,
+        }
 
+        fn fixture() -> Fixture {
+            let user = create_test_user(Uuid::new_v4());
             let repo = MockUserRepository::new().with_user(user.clone());
-            let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+            let keycloak = MockKeycloakClient::new().with_user(
+                user.sub,
+                KeycloakUserInfo {
+                    username: "john".to_string(),
+                    email: "old@example.com".to_string(),
+                },
+            );
+            let service = test_service(&repo, &keycloak, &MockContentServiceClient::new());
+            let audit = AuditContext::new(user.sub);
+            Fixture {
+                user,
+                repo,
+                keycloak,
+                service,
+                audit,
+            }
+        }
 
-            let result = service.get_or_create_user(sub, "testuser").await.unwrap();
+        /// Token of the latest verification link sent.
+        fn sent_token(repo: &MockUserRepository) -> String {
+            let outbox = repo.outbox.lock().unwrap();
+            let body = &outbox.last().unwrap().body;
+            let start = body.find("token=").unwrap() + "token=".len();
+            body[start..].split_whitespace().next().unwrap().to_string()
+        }
 
-            assert_eq!(result.sub, sub);
-            assert_eq!(result.display_name, "Test User");
+        fn keycloak_email(f: &Fixture) -> String {
+            f.keycloak.users.lock().unwrap()[&f.user.sub].email.clone()
         }
 
         #[tokio::test]
-        async fn creates_new_user_when_not_exists() {
-            let sub = Uuid::new_v4();
+        async fn email_change_waits_for_verification() {
+            let f = fixture();
+
+            f.service
+                .update_user(&f.user, change_email("new@example.com"), &f.audit)
+                .await
+                .unwrap();
+
+            assert_eq!(keycloak_email(&f), "old@example.com");
+            assert_eq!(
+                f.repo.email_changes.lock().unwrap()[&f.user.sub].new_email,
+                "new@example.com"
+            );
+            assert_eq!(f.repo.outbox.lock().unwrap()[0].recipient, "new@example.com");
+            let info = f.service.get_current_user_info(&f.user, true).await.unwrap();
+            assert_eq!(info["email"], "old@example.com");
+            assert_eq!(info["pending_email"], "new@example.com");
+        }
 
-            let repo = MockUserRepository::new();
-            let keycloak = MockKeycloakClient::new();
-            let content = MockContentServiceClient::new();
-            let service = UserServiceImpl::new(repo, keycloak, content);
+        #[tokio::test]
+        async fn verification_applies_change_and_notifies_old_address() {
+            let f = fixture();
+            f.service
+                .update_user(&f.user, change_email("new@example.com"), &f.audit)
+                .await
+                .unwrap();
+            let req = VerifyEmailRequest {
+                token: sent_token(&f.repo),
+            };
 
-            let result = service.get_or_create_user(sub, "newuser").await.unwrap();
+            let info = f
+                .service
+                .verify_email_change(&f.user, req, &f.audit)
+                .await
+                .unwrap();
+
+            assert_eq!(info.email, "new@example.com");
+            assert_eq!(keycloak_email(&f), "new@example.com");
+            assert!(f.repo.email_changes.lock().unwrap().is_empty());
+            let notice = f.repo.outbox.lock().unwrap().last().cloned().unwrap();
+            assert_eq!(notice.recipient, "old@example.com");
+            assert!(!notice.body.contains("new@example.com"));
+        }
 
-            assert_eq!(result.sub, sub);
-            assert_eq!(result.display_name, "newuser");
+        #[tokio::test]
+        async fn retried_verification_still_notifies_old_address() {
+            let f = fixture();
+            f.service
+                .update_user(&f.user, change_email("new@example.com"), &f.audit)
+                .await
+                .unwrap();
+            let token = sent_token(&f.repo);
+            // An earlier attempt updated Keycloak, then failed
+            f.keycloak
+                .update_email(f.user.sub, "new@example.com", true)
+                .await
+                .unwrap();
+
+            f.service
+                .verify_email_change(&f.user, VerifyEmailRequest { token }, &f.audit)
+                .await
+                .unwrap();
+
+            let notice = f.repo.outbox.lock().unwrap().last().cloned().unwrap();
+            assert_eq!(notice.recipient, "old@example.com");
+        }
+
+        #[tokio::test]
+        async fn rejects_superseded_and_foreign_tokens() {
+            let f = fixture();
+            f.service
+                .update_user(&f.user, change_email("first@example.com"), &f.audit)
+                .await
+                .unwrap();
+            let superseded = sent_token(&f.repo);
+            f.service
+                .update_user(&f.user, change_email("second@example.com"), &f.audit)
+                .await
+                .unwrap();
+            let other = create_test_user(Uuid::new_v4());
+
+            let replayed = f
+                .service
+                .verify_email_change(&f.user, VerifyEmailRequest { token: superseded }, &f.audit)
+                .await;
+            let foreign = f
+                .service
+                .verify_email_change(
+                    &other,
+                    VerifyEmailRequest {
+                        token: sent_token(&f.repo),
+                    },
+                    &f.audit,
+                )
+                .await;
+
+            assert!(matches!(replayed, Err(CoreError::BadRequest(_))));
+            assert!(matches!(foreign, Err(CoreError::BadRequest(_))));
+            assert_eq!(keycloak_email(&f), "old@example.com");
         }
     }
 }
@@ -0,0 +1,329 @@
+use crate::models::SettingValues;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+/// Type and constraints of a setting value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingKind {
+    /// One of a fixed set of strings.
+    Choice(&'static [&'static str]),
+    Boolean,
+    /// Integer within an inclusive range.
+    Integer { min: i64, max: i64 },
+    /// IANA time zone name such as `Europe/Paris`, or `UTC`.
+    Timezone,
+}
+
+impl SettingKind {
+    fn check(&self, value: &Value) -> Result<(), String> {
+        match self {
+            SettingKind::Choice(allowed) => match value.as_str() {
+                Some(choice) if allowed.contains(&choice) => Ok(()),
+                _ => Err(format!("expected one of {}", allowed.join(", "))),
+            },
+            SettingKind::Boolean => match value {
+                Value::Bool(_) => Ok(()),
+                _ => Err("expected a boolean".to_string()),
+            },
+            SettingKind::Integer { min, max } => match value.as_i64() {
+                Some(n) if (*min..=*max).contains(&n) => Ok(()),
+                _ => Err(format!("expected an integer between {} and {}", min, max)),
+            },
+            SettingKind::Timezone => match value.as_str() {
+                Some(tz) if is_timezone_name(tz) => Ok(()),
+                _ => Err("expected a time zone name such as 'Europe/Paris'".to_string()),
+            },
+        }
+    }
+}
+
+/// Syntactic check of an IANA time zone name. The service has no tz database,
+/// so unknown but well-formed zones are accepted.
+fn is_timezone_name(tz: &str) -> bool {
+    if tz == "UTC" {
+        return true;
+    }
+    let segments: Vec<&str> = tz.split('/').collect();
+    tz.len() <= 64
+        && (2..=3).contains(&segments.len())
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        })
+}
+
+/// A single user setting: its key, type, default value and the schema version
+/// in which its current definition was introduced.
+#[derive(Debug, Clone)]
+pub struct SettingDefinition {
+    pub key: &'static str,
+    pub kind: SettingKind,
+    pub default: Value,
+    pub version: u32,
+}
+
+impl SettingDefinition {
+    pub fn new(key: &'static str, kind: SettingKind, default: Value, version: u32) -> Self {
+        Self {
+            key,
+            kind,
+            default,
+            version,
+        }
+    }
+}
+
+/// Set of settings a user can store.
+///
+/// Values are stored as a JSON object, so adding a setting only requires a new
+/// definition here, not a migration.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsRegistry {
+    definitions: Vec<SettingDefinition>,
+}
+
+static BUILTIN_REGISTRY: LazyLock<SettingsRegistry> = LazyLock::new(|| {
+    SettingsRegistry::default()
+        .register(SettingDefinition::new(
+            "theme",
+            SettingKind::Choice(&["light", "dark", "system"]),
+            Value::from("light"),
+            1,
+        ))
+        .register(SettingDefinition::new(
+            "lang",
+            SettingKind::Choice(&["en", "fr"]),
+            Value::from("en"),
+            1,
+        ))
+        .register(SettingDefinition::new(
+            "timezone",
+            SettingKind::Timezone,
+            Value::from("UTC"),
+            2,
+        ))
+        .register(SettingDefinition::new(
+            "compact_mode",
+            SettingKind::Boolean,
+            Value::from(false),
+            2,
+        ))
+        .register(SettingDefinition::new(
+            "notifications.desktop",
+            SettingKind::Boolean,
+            Value::from(true),
+            2,
+        ))
+        .register(SettingDefinition::new(
+            "notifications.sound",
+            SettingKind::Boolean,
+            Value::from(true),
+            2,
+        ))
+        .register(SettingDefinition::new(
+            "notifications.mentions_only",
+            SettingKind::Boolean,
+            Value::from(false),
+            2,
+        ))
+        .register(SettingDefinition::new(
+            "accessibility.reduced_motion",
+            SettingKind::Boolean,
+            Value::from(false),
+            2,
+        ))
+        .register(SettingDefinition::new(
+            "accessibility.font_scale",
+            SettingKind::Integer { min: 50, max: 200 },
+            Value::from(100),
+            2,
+        ))
+});
+
+impl SettingsRegistry {
+    /// Settings supported by the service.
+    pub fn builtin() -> &'static SettingsRegistry {
+        &BUILTIN_REGISTRY
+    }
+
+    pub fn register(mut self, definition: SettingDefinition) -> Self {
+        self.definitions.retain(|d| d.key != definition.key);
+        self.definitions.push(definition);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SettingDefinition> {
+        self.definitions.iter().find(|d| d.key == key)
+    }
+
+    pub fn definitions(&self) -> &[SettingDefinition] {
+        &self.definitions
+    }
+
+    /// Highest version among the registered definitions.
+    pub fn schema_version(&self) -> u32 {
+        self.definitions.iter().map(|d| d.version).max().unwrap_or(0)
+    }
+
+    /// Checks an update against the registry. A `null` value resets the
+    /// setting to its default and is always accepted for known keys.
+    pub fn validate(&self, updates: &SettingValues) -> Result<(), String> {
+        let errors: Vec<String> = updates
+            .iter()
+            .filter_map(|(key, value)| {
+                let Some(definition) = self.get(key) else {
+                    return Some(format!("unknown setting '{}'", key));
+                };
+                if value.is_null() {
+                    return None;
+                }
+                definition
+                    .kind
+                    .check(value)
+                    .err()
+                    .map(|reason| format!("invalid value for '{}': {}", key, reason))
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Full set of settings for a user: stored values where valid, defaults
+    /// everywhere else. Stored keys that are no longer registered are dropped.
+    pub fn materialize(&self, stored: &SettingValues) -> SettingValues {
+        self.definitions
+            .iter()
+            .map(|definition| {
+                let value = stored
+                    .get(definition.key)
+                    .filter(|value| definition.kind.check(value).is_ok())
+                    .cloned()
+                    .unwrap_or_else(|| definition.default.clone());
+                (definition.key.to_string(), value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn values(value: Value) -> SettingValues {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_values() {
+        let updates = values(json!({
+            "theme": "dark",
+            "timezone": "Europe/Paris",
+            "compact_mode": true,
+            "accessibility.font_scale": 120
+        }));
+
+        assert!(SettingsRegistry::builtin().validate(&updates).is_ok());
+    }
+
+    #[test]
+    fn rejects_value_outside_allowed_choices() {
+        let updates = values(json!({ "theme": "purple" }));
+
+        let err = SettingsRegistry::builtin().validate(&updates).unwrap_err();
+        assert!(err.contains("'theme'"));
+    }
+
+    #[test]
+    fn rejects_value_with_wrong_type() {
+        let updates = values(json!({ "compact_mode": "yes" }));
+
+        let err = SettingsRegistry::builtin().validate(&updates).unwrap_err();
+        assert!(err.contains("'compact_mode'"));
+    }
+
+    #[test]
+    fn rejects_integer_out_of_range() {
+        let updates = values(json!({ "accessibility.font_scale": 1000 }));
+
+        assert!(SettingsRegistry::builtin().validate(&updates).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_timezone() {
+        let updates = values(json!({ "timezone": "not a zone" }));
+
+        assert!(SettingsRegistry::builtin().validate(&updates).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_key() {
+        let updates = values(json!({ "favourite_color": "blue" }));
+
+        let err = SettingsRegistry::builtin().validate(&updates).unwrap_err();
+        assert!(err.contains("'favourite_color'"));
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let updates = values(json!({ "theme": 1, "lang": "xx" }));
+
+        let err = SettingsRegistry::builtin().validate(&updates).unwrap_err();
+        assert!(err.contains("'theme'"));
+        assert!(err.contains("'lang'"));
+    }
+
+    #[test]
+    fn accepts_null_to_reset_known_key() {
+        let updates = values(json!({ "theme": null }));
+
+        assert!(SettingsRegistry::builtin().validate(&updates).is_ok());
+    }
+
+    #[test]
+    fn materialize_fills_defaults_and_keeps_stored_values() {
+        let stored = values(json!({ "theme": "dark" }));
+
+        let materialized = SettingsRegistry::builtin().materialize(&stored);
+
+        assert_eq!(materialized["theme"], "dark");
+        assert_eq!(materialized["lang"], "en");
+        assert_eq!(materialized["compact_mode"], false);
+    }
+
+    #[test]
+    fn materialize_replaces_invalid_and_drops_unknown_values() {
+        let stored = values(json!({ "theme": "purple", "legacy_key": 1 }));
+
+        let materialized = SettingsRegistry::builtin().materialize(&stored);
+
+        assert_eq!(materialized["theme"], "light");
+        assert!(!materialized.contains_key("legacy_key"));
+    }
+
+    #[test]
+    fn register_replaces_existing_definition() {
+        let registry = SettingsRegistry::default()
+            .register(SettingDefinition::new(
+                "theme",
+                SettingKind::Choice(&["light"]),
+                Value::from("light"),
+                1,
+            ))
+            .register(SettingDefinition::new(
+                "theme",
+                SettingKind::Choice(&["light", "dark"]),
+                Value::from("dark"),
+                3,
+            ));
+
+        assert_eq!(registry.definitions().len(), 1);
+        assert_eq!(registry.schema_version(), 3);
+    }
+}
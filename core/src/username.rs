@@ -0,0 +1,173 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+
+/// Default minimum delay between two username changes.
+pub const DEFAULT_USERNAME_CHANGE_COOLDOWN: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default time a released username stays held for its previous owner.
+pub const DEFAULT_USERNAME_HOLD_PERIOD: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Names no user may take, on top of those configured.
+pub const DEFAULT_RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "root",
+    "system",
+    "support",
+    "help",
+    "moderator",
+    "staff",
+    "security",
+    "official",
+];
+
+/// Form of `username` shared by every name that looks the same: case is
+/// folded, separators are dropped and confusable characters are mapped to
+/// one representative, so `J0hn.Doe` and `john_doe` share a skeleton.
+pub fn username_skeleton(username: &str) -> String {
+    let folded: String = username
+        .nfkc()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !matches!(c, '.' | '_' | '-'))
+        .map(|c| match c {
+            '0' | 'ο' | 'о' => 'o',
+            '1' | 'i' | '|' | 'і' | 'ӏ' => 'l',
+            '3' | 'е' => 'e',
+            '5' | '$' => 's',
+            '@' | 'а' | 'α' => 'a',
+            'с' => 'c',
+            'р' | 'ρ' => 'p',
+            'х' | 'χ' => 'x',
+            'у' => 'y',
+            'ν' => 'v',
+            c => c,
+        })
+        .collect();
+    folded.replace("rn", "m").replace("vv", "w")
+}
+
+/// Why a username may not be used at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsernameRejection {
+    /// Looks like a reserved name
+    Reserved,
+    /// Contains a blocklisted term
+    Blocked,
+}
+
+/// Rules applied when a user changes their username.
+#[derive(Debug, Clone)]
+pub struct UsernamePolicy {
+    change_cooldown: Duration,
+    hold_period: Duration,
+    /// Skeletons of reserved names
+    reserved: HashSet<String>,
+    /// Skeletons of blocklisted terms
+    blocked: Vec<String>,
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_USERNAME_CHANGE_COOLDOWN, DEFAULT_USERNAME_HOLD_PERIOD)
+    }
+}
+
+impl UsernamePolicy {
+    pub fn new(change_cooldown: Duration, hold_period: Duration) -> Self {
+        Self {
+            change_cooldown,
+            hold_period,
+            reserved: DEFAULT_RESERVED_USERNAMES
+                .iter()
+                .map(|name| username_skeleton(name))
+                .collect(),
+            blocked: Vec::new(),
+        }
+    }
+
+    /// Adds names that may not be taken, nor anything that looks like them.
+    pub fn with_reserved<S: AsRef<str>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.reserved.extend(
+            names
+                .into_iter()
+                .map(|name| username_skeleton(name.as_ref()))
+                .filter(|skeleton| !skeleton.is_empty()),
+        );
+        self
+    }
+
+    /// Adds terms that may not appear anywhere in a username.
+    pub fn with_blocked<S: AsRef<str>>(mut self, terms: impl IntoIterator<Item = S>) -> Self {
+        self.blocked.extend(
+            terms
+                .into_iter()
+                .map(|term| username_skeleton(term.as_ref()))
+                .filter(|skeleton| !skeleton.is_empty()),
+        );
+        self
+    }
+
+    pub fn check(&self, username: &str) -> Result<(), UsernameRejection> {
+        let skeleton = username_skeleton(username);
+        if self.reserved.contains(&skeleton) {
+            return Err(UsernameRejection::Reserved);
+        }
+        if self.blocked.iter().any(|term| skeleton.contains(term)) {
+            return Err(UsernameRejection::Blocked);
+        }
+        Ok(())
+    }
+
+    /// When a user whose username last changed at `last_change` may change it
+    /// again, if that is still in the future.
+    pub fn next_change_at(&self, last_change: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = last_change + self.change_cooldown;
+        (next > Utc::now()).then_some(next)
+    }
+
+    /// Until when a username released now stays held for its previous owner.
+    pub fn held_until(&self) -> DateTime<Utc> {
+        Utc::now() + self.hold_period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookalike_usernames_share_a_skeleton() {
+        assert_eq!(username_skeleton("J0hn.Doe"), username_skeleton("john_doe"));
+        assert_eq!(username_skeleton("rnallory"), username_skeleton("mallory"));
+        assert_eq!(username_skeleton("paypa1"), username_skeleton("PayPal"));
+        assert_eq!(username_skeleton("аdmin"), username_skeleton("admin"));
+        assert_ne!(username_skeleton("john"), username_skeleton("joan"));
+    }
+
+    #[test]
+    fn rejects_reserved_and_blocked_names() {
+        let policy = UsernamePolicy::default()
+            .with_reserved(["acme"])
+            .with_blocked(["scam", ""]);
+
+        assert_eq!(policy.check("Adm1n"), Err(UsernameRejection::Reserved));
+        assert_eq!(policy.check("ACME"), Err(UsernameRejection::Reserved));
+        assert_eq!(policy.check("totally-5cam"), Err(UsernameRejection::Blocked));
+        assert_eq!(policy.check("admin.fan"), Ok(()));
+        assert_eq!(policy.check("john"), Ok(()));
+    }
+
+    #[test]
+    fn cooldown_runs_from_the_last_change() {
+        let policy = UsernamePolicy::new(Duration::from_secs(3600), Duration::from_secs(60));
+
+        assert!(policy.next_change_at(Utc::now()).is_some());
+        assert!(
+            policy
+                .next_change_at(Utc::now() - chrono::Duration::hours(2))
+                .is_none()
+        );
+    }
+}
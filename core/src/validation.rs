@@ -0,0 +1,609 @@
+use crate::models::{
+    FieldVisibility, ProfileLink, SuspendUserRequest, UpdatePrivacyRequest, UpdateUserRequest,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Column sizes of the `users` table.
+pub const MAX_DISPLAY_NAME_LEN: usize = 255;
+pub const MAX_DESCRIPTION_LEN: usize = 255;
+pub const MAX_PROFILE_PICTURE_LEN: usize = 500;
+pub const MAX_PRONOUNS_LEN: usize = 40;
+pub const MAX_SUSPENSION_REASON_LEN: usize = 500;
+
+/// Limits of the profile links stored as JSON.
+pub const MAX_PROFILE_LINKS: usize = 5;
+pub const MAX_LINK_LABEL_LEN: usize = 32;
+pub const MAX_LINK_URL_LEN: usize = 500;
+
+/// Largest allow-list of a single profile field.
+pub const MAX_VISIBILITY_ALLOW_LIST: usize = 100;
+
+pub const MIN_USERNAME_LEN: usize = 3;
+pub const MAX_USERNAME_LEN: usize = 32;
+pub const MAX_EMAIL_LEN: usize = 254;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Every violation found in a request, reported at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result<T>(self, value: T) -> Result<T, ValidationErrors> {
+        if self.is_empty() { Ok(value) } else { Err(self) }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self
+            .errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+/// Trims and NFC-normalizes user input.
+pub fn normalize(value: &str) -> String {
+    value.trim().nfc().collect()
+}
+
+/// Normalizes an `UpdateUserRequest` and checks it against the storage
+/// constraints of the local DB and Keycloak.
+pub fn validate_update_user_request(
+    req: UpdateUserRequest,
+) -> Result<UpdateUserRequest, ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+
+    let display_name = req.display_name.map(|name| normalize(&name));
+    if let Some(name) = &display_name {
+        if name.is_empty() {
+            errors.add("display_name", "must not be empty");
+        }
+        check_length(&mut errors, "display_name", name, MAX_DISPLAY_NAME_LEN);
+        if name.chars().any(char::is_control) {
+            errors.add("display_name", "must not contain control characters");
+        }
+    }
+
+    let description = req.description.map(|description| normalize(&description));
+    if let Some(description) = &description {
+        check_length(&mut errors, "description", description, MAX_DESCRIPTION_LEN);
+        if description
+            .chars()
+            .any(|c| c.is_control() && c != '\n' && c != '\t')
+        {
+            errors.add("description", "must not contain control characters");
+        }
+    }
+
+    let profile_picture = req.profile_picture.map(|url| url.trim().to_string());
+    if let Some(url) = &profile_picture {
+        check_length(&mut errors, "profile_picture", url, MAX_PROFILE_PICTURE_LEN);
+        if !url.is_empty() && !is_http_url(url) {
+            errors.add("profile_picture", "must be an http(s) URL");
+        }
+    }
+
+    let accent_color = req.accent_color.map(|color| color.trim().to_ascii_lowercase());
+    if let Some(color) = &accent_color
+        && !color.is_empty()
+        && !is_hex_color(color)
+    {
+        errors.add("accent_color", "must be a hex color such as #5865f2");
+    }
+
+    let pronouns = req.pronouns.map(|pronouns| normalize(&pronouns));
+    if let Some(pronouns) = &pronouns {
+        check_length(&mut errors, "pronouns", pronouns, MAX_PRONOUNS_LEN);
+        if pronouns.chars().any(char::is_control) {
+            errors.add("pronouns", "must not contain control characters");
+        }
+    }
+
+    let links = req.links.map(|links| {
+        links
+            .into_iter()
+            .map(|link| ProfileLink {
+                label: normalize(&link.label),
+                url: link.url.trim().to_string(),
+            })
+            .collect::<Vec<_>>()
+    });
+    if let Some(links) = &links {
+        check_links(&mut errors, links);
+    }
+
+    let username = req.username.map(|username| normalize(&username));
+    if let Some(username) = &username {
+        check_username(&mut errors, username);
+    }
+
+    let email = req.email.map(|email| email.trim().to_string());
+    if let Some(email) = &email
+        && !is_valid_email(email)
+    {
+        errors.add("email", "must be a valid email address");
+    }
+
+    errors.into_result(UpdateUserRequest {
+        display_name,
+        profile_picture,
+        description,
+        accent_color,
+        pronouns,
+        links,
+        username,
+        email,
+    })
+}
+
+/// Drops duplicate entries from allow-lists and checks their size.
+pub fn validate_update_privacy_request(
+    mut req: UpdatePrivacyRequest,
+) -> Result<UpdatePrivacyRequest, ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+
+    let fields = [
+        ("description", &mut req.description),
+        ("avatar", &mut req.avatar),
+        ("banner", &mut req.banner),
+        ("accent_color", &mut req.accent_color),
+        ("pronouns", &mut req.pronouns),
+        ("links", &mut req.links),
+    ];
+    for (field, visibility) in fields {
+        if let Some(FieldVisibility::AllowList { allowed }) = visibility {
+            let mut seen = HashSet::new();
+            allowed.retain(|sub| seen.insert(*sub));
+            if allowed.len() > MAX_VISIBILITY_ALLOW_LIST {
+                errors.add(
+                    field,
+                    format!("must allow at most {} users", MAX_VISIBILITY_ALLOW_LIST),
+                );
+            }
+        }
+    }
+
+    errors.into_result(req)
+}
+
+/// Normalizes the reason of a suspension and checks that it ends in the
+/// future.
+pub fn validate_suspend_user_request(
+    req: SuspendUserRequest,
+) -> Result<SuspendUserRequest, ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+
+    let reason = normalize(&req.reason);
+    if reason.is_empty() {
+        errors.add("reason", "must not be empty");
+    }
+    check_length(&mut errors, "reason", &reason, MAX_SUSPENSION_REASON_LEN);
+
+    if req
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+    {
+        errors.add("expires_at", "must be in the future");
+    }
+
+    errors.into_result(SuspendUserRequest { reason, ..req })
+}
+
+/// Whether `username` satisfies the username rules enforced on update.
+pub fn is_valid_username(username: &str) -> bool {
+    let mut errors = ValidationErrors::default();
+    check_username(&mut errors, username);
+    errors.is_empty()
+}
+
+fn check_length(errors: &mut ValidationErrors, field: &str, value: &str, max: usize) {
+    if value.chars().count() > max {
+        errors.add(field, format!("must be at most {} characters", max));
+    }
+}
+
+fn check_links(errors: &mut ValidationErrors, links: &[ProfileLink]) {
+    if links.len() > MAX_PROFILE_LINKS {
+        errors.add(
+            "links",
+            format!("must contain at most {} links", MAX_PROFILE_LINKS),
+        );
+    }
+    for (i, link) in links.iter().enumerate() {
+        let label_field = format!("links[{}].label", i);
+        if link.label.is_empty() {
+            errors.add(&label_field, "must not be empty");
+        }
+        check_length(errors, &label_field, &link.label, MAX_LINK_LABEL_LEN);
+        if link.label.chars().any(char::is_control) {
+            errors.add(&label_field, "must not contain control characters");
+        }
+
+        let url_field = format!("links[{}].url", i);
+        check_length(errors, &url_field, &link.url, MAX_LINK_URL_LEN);
+        if !is_http_url(&link.url) {
+            errors.add(&url_field, "must be an http(s) URL");
+        }
+    }
+}
+
+fn check_username(errors: &mut ValidationErrors, username: &str) {
+    let len = username.chars().count();
+    if !(MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&len) {
+        errors.add(
+            "username",
+            format!(
+                "must be between {} and {} characters",
+                MIN_USERNAME_LEN, MAX_USERNAME_LEN
+            ),
+        );
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        errors.add(
+            "username",
+            "may only contain letters, digits, '.', '_' and '-'",
+        );
+    }
+    if !username.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        errors.add("username", "must start with a letter or a digit");
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    rest.is_some_and(|rest| !rest.is_empty() && !rest.chars().any(char::is_whitespace))
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_valid_email(email: &str) -> bool {
+    if email.len() > MAX_EMAIL_LEN || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_request() -> UpdateUserRequest {
+        UpdateUserRequest {
+            display_name: None,
+            profile_picture: None,
+            description: None,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+            username: None,
+            email: None,
+        }
+    }
+
+    fn fields(errors: &ValidationErrors) -> Vec<&str> {
+        errors.errors.iter().map(|e| e.field.as_str()).collect()
+    }
+
+    #[test]
+    fn accepts_empty_request() {
+        assert!(validate_update_user_request(empty_request()).is_ok());
+    }
+
+    #[test]
+    fn trims_and_normalizes_display_name() {
+        let req = UpdateUserRequest {
+            // "e" followed by a combining acute accent
+            display_name: Some("  Rene\u{301}  ".to_string()),
+            ..empty_request()
+        };
+
+        let req = validate_update_user_request(req).unwrap();
+
+        assert_eq!(req.display_name.as_deref(), Some("Ren\u{e9}"));
+    }
+
+    #[test]
+    fn rejects_whitespace_only_display_name() {
+        let req = UpdateUserRequest {
+            display_name: Some("   ".to_string()),
+            ..empty_request()
+        };
+
+        let errors = validate_update_user_request(req).unwrap_err();
+
+        assert_eq!(fields(&errors), vec!["display_name"]);
+    }
+
+    #[test]
+    fn rejects_control_characters_in_display_name() {
+        let req = UpdateUserRequest {
+            display_name: Some("John\u{0}Doe".to_string()),
+            ..empty_request()
+        };
+
+        assert!(validate_update_user_request(req).is_err());
+    }
+
+    #[test]
+    fn allows_newlines_in_description() {
+        let req = UpdateUserRequest {
+            description: Some("Line one\nLine two".to_string()),
+            ..empty_request()
+        };
+
+        assert!(validate_update_user_request(req).is_ok());
+    }
+
+    #[test]
+    fn rejects_oversized_fields() {
+        let req = UpdateUserRequest {
+            display_name: Some("a".repeat(MAX_DISPLAY_NAME_LEN + 1)),
+            description: Some("a".repeat(MAX_DESCRIPTION_LEN + 1)),
+            profile_picture: Some(format!(
+                "https://example.com/{}",
+                "a".repeat(MAX_PROFILE_PICTURE_LEN)
+            )),
+            ..empty_request()
+        };
+
+        let errors = validate_update_user_request(req).unwrap_err();
+
+        assert_eq!(
+            fields(&errors),
+            vec!["display_name", "description", "profile_picture"]
+        );
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        let req = UpdateUserRequest {
+            display_name: Some("é".repeat(MAX_DISPLAY_NAME_LEN)),
+            ..empty_request()
+        };
+
+        assert!(validate_update_user_request(req).is_ok());
+    }
+
+    #[test]
+    fn rejects_non_http_profile_picture() {
+        let req = UpdateUserRequest {
+            profile_picture: Some("javascript:alert(1)".to_string()),
+            ..empty_request()
+        };
+
+        assert!(validate_update_user_request(req).is_err());
+    }
+
+    #[test]
+    fn normalizes_accent_color() {
+        let req = UpdateUserRequest {
+            accent_color: Some(" #5865F2 ".to_string()),
+            ..empty_request()
+        };
+
+        let req = validate_update_user_request(req).unwrap();
+
+        assert_eq!(req.accent_color.as_deref(), Some("#5865f2"));
+    }
+
+    #[test]
+    fn rejects_malformed_accent_colors() {
+        for color in ["5865f2", "#58f", "#5865g2", "#5865f2ff"] {
+            let req = UpdateUserRequest {
+                accent_color: Some(color.to_string()),
+                ..empty_request()
+            };
+
+            let errors = validate_update_user_request(req).unwrap_err();
+            assert_eq!(fields(&errors), vec!["accent_color"], "{}", color);
+        }
+    }
+
+    #[test]
+    fn accepts_empty_values_clearing_fields() {
+        let req = UpdateUserRequest {
+            accent_color: Some(String::new()),
+            pronouns: Some(" ".to_string()),
+            links: Some(Vec::new()),
+            ..empty_request()
+        };
+
+        let req = validate_update_user_request(req).unwrap();
+
+        assert_eq!(req.accent_color.as_deref(), Some(""));
+        assert_eq!(req.pronouns.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn validates_each_profile_link() {
+        let link = |label: &str, url: &str| ProfileLink {
+            label: label.to_string(),
+            url: url.to_string(),
+        };
+        let req = UpdateUserRequest {
+            links: Some(vec![
+                link(" GitHub ", "https://github.com/john"),
+                link("", "javascript:alert(1)"),
+            ]),
+            ..empty_request()
+        };
+
+        let errors = validate_update_user_request(req).unwrap_err();
+
+        assert_eq!(fields(&errors), vec!["links[1].label", "links[1].url"]);
+    }
+
+    #[test]
+    fn rejects_too_many_profile_links() {
+        let link = ProfileLink {
+            label: "Website".to_string(),
+            url: "https://example.com".to_string(),
+        };
+        let req = UpdateUserRequest {
+            links: Some(vec![link; MAX_PROFILE_LINKS + 1]),
+            ..empty_request()
+        };
+
+        let errors = validate_update_user_request(req).unwrap_err();
+
+        assert_eq!(fields(&errors), vec!["links"]);
+    }
+
+    #[test]
+    fn accepts_valid_username_and_email() {
+        let req = UpdateUserRequest {
+            username: Some(" john.doe_42 ".to_string()),
+            email: Some("john@example.com".to_string()),
+            ..empty_request()
+        };
+
+        let req = validate_update_user_request(req).unwrap();
+
+        assert_eq!(req.username.as_deref(), Some("john.doe_42"));
+    }
+
+    #[test]
+    fn rejects_username_with_invalid_charset() {
+        let req = UpdateUserRequest {
+            username: Some("john doe!".to_string()),
+            ..empty_request()
+        };
+
+        assert!(validate_update_user_request(req).is_err());
+    }
+
+    #[test]
+    fn rejects_too_short_username() {
+        let req = UpdateUserRequest {
+            username: Some("jo".to_string()),
+            ..empty_request()
+        };
+
+        assert!(validate_update_user_request(req).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_emails() {
+        for email in ["plainaddress", "a@b", "@example.com", "a b@example.com", "a@example..com"] {
+            let req = UpdateUserRequest {
+                email: Some(email.to_string()),
+                ..empty_request()
+            };
+            assert!(validate_update_user_request(req).is_err(), "{}", email);
+        }
+    }
+
+    #[test]
+    fn reports_every_violation_at_once() {
+        let req = UpdateUserRequest {
+            display_name: Some("".to_string()),
+            profile_picture: Some("ftp://example.com".to_string()),
+            description: None,
+            accent_color: None,
+            pronouns: None,
+            links: None,
+            username: Some("!".to_string()),
+            email: Some("nope".to_string()),
+        };
+
+        let errors = validate_update_user_request(req).unwrap_err();
+
+        assert!(errors.errors.len() >= 4);
+        assert!(fields(&errors).contains(&"email"));
+        assert!(errors.to_string().contains("profile_picture: must be an http(s) URL"));
+    }
+
+    #[test]
+    fn dedupes_allow_lists() {
+        let friend = uuid::Uuid::new_v4();
+        let req = UpdatePrivacyRequest {
+            pronouns: Some(FieldVisibility::AllowList {
+                allowed: vec![friend, friend],
+            }),
+            ..UpdatePrivacyRequest::default()
+        };
+
+        let req = validate_update_privacy_request(req).unwrap();
+
+        assert_eq!(
+            req.pronouns,
+            Some(FieldVisibility::AllowList {
+                allowed: vec![friend]
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_allow_list() {
+        let req = UpdatePrivacyRequest {
+            links: Some(FieldVisibility::AllowList {
+                allowed: (0..=MAX_VISIBILITY_ALLOW_LIST)
+                    .map(|_| uuid::Uuid::new_v4())
+                    .collect(),
+            }),
+            ..UpdatePrivacyRequest::default()
+        };
+
+        let errors = validate_update_privacy_request(req).unwrap_err();
+
+        assert_eq!(fields(&errors), vec!["links"]);
+    }
+
+    #[test]
+    fn rejects_suspension_in_the_past() {
+        let req = SuspendUserRequest {
+            reason: "  ".to_string(),
+            expires_at: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+            disable_keycloak: false,
+        };
+
+        let errors = validate_suspend_user_request(req).unwrap_err();
+
+        assert_eq!(fields(&errors), vec!["reason", "expires_at"]);
+    }
+}
@@ -82,3 +82,14 @@ Secret name for Keycloak
 {{- include "user-api.fullname" . }}-keycloak
 {{- end }}
 {{- end }}
+
+{{/*
+Secret name for email verification
+*/}}
+{{- define "user-api.emailVerificationSecretName" -}}
+{{- if .Values.emailVerification.existingSecret }}
+{{- .Values.emailVerification.existingSecret }}
+{{- else }}
+{{- include "user-api.fullname" . }}-email-verification
+{{- end }}
+{{- end }}
@@ -1,19 +1,26 @@
 use serde::Deserialize;
 use std::env;
 use std::fmt;
+use std::net::IpAddr;
 
 #[derive(Debug)]
-pub struct ConfigError {
-    pub missing_vars: Vec<&'static str>,
+pub enum ConfigError {
+    /// Required variables that are not set
+    Missing(Vec<&'static str>),
+    /// A variable set to a value that cannot be used
+    Invalid { name: &'static str, reason: String },
 }
 
 impl fmt::Display for ConfigError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Missing required environment variables: {}",
-            self.missing_vars.join(", ")
-        )
+        match self {
+            ConfigError::Missing(names) => write!(
+                f,
+                "Missing required environment variables: {}",
+                names.join(", ")
+            ),
+            ConfigError::Invalid { name, reason } => write!(f, "{} is invalid: {}", name, reason),
+        }
     }
 }
 
@@ -29,6 +36,40 @@ fn require_env(name: &'static str, missing: &mut Vec<&'static str>) -> Option<St
     }
 }
 
+fn optional_env<T: std::str::FromStr>(name: &'static str, default: T) -> Result<T, ConfigError> {
+    match env::var(name) {
+        Ok(val) => val.parse().map_err(|_| ConfigError::Invalid {
+            name,
+            reason: format!("cannot parse {:?}", val),
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// `optional_env` for counts and intervals, which must be greater than 0.
+fn positive_env<T>(name: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: std::str::FromStr + Default + PartialOrd,
+{
+    let value = optional_env(name, default)?;
+    if value <= T::default() {
+        return Err(ConfigError::Invalid {
+            name,
+            reason: "must be greater than 0".to_string(),
+        });
+    }
+    Ok(value)
+}
+
+/// One name per line; blank lines and `#` comments are skipped.
+fn name_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub database_url: String,
@@ -41,6 +82,28 @@ pub struct Config {
     pub keycloak_client_id: String,
     pub keycloak_client_secret: String,
     pub content_service_url: String,
+    pub user_cache_capacity: usize,
+    pub user_cache_ttl_seconds: u64,
+    pub event_sink_file: Option<String>,
+    pub event_dispatch_interval_ms: u64,
+    pub event_dispatch_batch_size: i64,
+    pub account_deletion_grace_days: u64,
+    pub account_purge_interval_seconds: u64,
+    pub data_export_interval_seconds: u64,
+    pub avatar_cleanup_interval_seconds: u64,
+    pub suspension_expiry_interval_seconds: u64,
+    pub keycloak_repair_interval_seconds: u64,
+    pub username_change_cooldown_days: u64,
+    pub username_hold_days: u64,
+    pub reserved_usernames: Vec<String>,
+    pub blocked_usernames: Vec<String>,
+    pub email_verification_secret: String,
+    pub email_verification_ttl_hours: u64,
+    pub email_verification_url: String,
+    pub mailer_file: Option<String>,
+    pub mail_dispatch_interval_seconds: u64,
+    pub keycloak_webhook_secret: Option<String>,
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl Config {
@@ -62,10 +125,70 @@ impl Config {
 
         let content_service_url = require_env("CONTENT_SERVICE_URL", &mut missing);
 
+        let user_cache_capacity = optional_env("USER_CACHE_CAPACITY", 10_000)?;
+        let user_cache_ttl_seconds = optional_env("USER_CACHE_TTL_SECONDS", 60)?;
+
+        let event_sink_file = env::var("EVENT_SINK_FILE").ok();
+        let event_dispatch_interval_ms = positive_env("EVENT_DISPATCH_INTERVAL_MS", 1_000)?;
+        let event_dispatch_batch_size = positive_env("EVENT_DISPATCH_BATCH_SIZE", 100)?;
+
+        let account_deletion_grace_days = optional_env("ACCOUNT_DELETION_GRACE_DAYS", 30)?;
+        let account_purge_interval_seconds =
+            positive_env("ACCOUNT_PURGE_INTERVAL_SECONDS", 3_600)?;
+
+        let data_export_interval_seconds = positive_env("DATA_EXPORT_INTERVAL_SECONDS", 10)?;
+
+        let avatar_cleanup_interval_seconds =
+            positive_env("AVATAR_CLEANUP_INTERVAL_SECONDS", 300)?;
+
+        let suspension_expiry_interval_seconds =
+            positive_env("SUSPENSION_EXPIRY_INTERVAL_SECONDS", 60)?;
+
+        let keycloak_repair_interval_seconds =
+            positive_env("KEYCLOAK_REPAIR_INTERVAL_SECONDS", 60)?;
+
+        let username_change_cooldown_days = optional_env("USERNAME_CHANGE_COOLDOWN_DAYS", 30)?;
+        let username_hold_days = optional_env("USERNAME_HOLD_DAYS", 90)?;
+        let reserved_usernames = env::var("RESERVED_USERNAMES")
+            .map(|names| names.split(',').map(|name| name.trim().to_string()).collect())
+            .unwrap_or_default();
+        let blocked_usernames = match env::var("USERNAME_BLOCKLIST_FILE") {
+            Ok(path) => {
+                let list = std::fs::read_to_string(&path).map_err(|e| ConfigError::Invalid {
+                    name: "USERNAME_BLOCKLIST_FILE",
+                    reason: format!("cannot read {}: {}", path, e),
+                })?;
+                name_list(&list)
+            }
+            Err(_) => Vec::new(),
+        };
+
+        let email_verification_secret = require_env("EMAIL_VERIFICATION_SECRET", &mut missing);
+        let email_verification_ttl_hours = optional_env("EMAIL_VERIFICATION_TTL_HOURS", 24)?;
+        let email_verification_url = env::var("EMAIL_VERIFICATION_URL")
+            .unwrap_or_else(|_| "http://localhost:3000/verify-email".to_string());
+        let mailer_file = env::var("MAILER_FILE").ok();
+        let mail_dispatch_interval_seconds = positive_env("MAIL_DISPATCH_INTERVAL_SECONDS", 5)?;
+
+        let keycloak_webhook_secret = env::var("KEYCLOAK_WEBHOOK_SECRET").ok();
+
+        let trusted_proxies = match env::var("TRUSTED_PROXIES") {
+            Ok(proxies) => proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| {
+                    proxy.parse().map_err(|_| ConfigError::Invalid {
+                        name: "TRUSTED_PROXIES",
+                        reason: format!("{:?} is not an IP address", proxy),
+                    })
+                })
+                .collect::<Result<Vec<IpAddr>, _>>()?,
+            Err(_) => Vec::new(),
+        };
+
         if !missing.is_empty() {
-            return Err(ConfigError {
-                missing_vars: missing,
-            });
+            return Err(ConfigError::Missing(missing));
         }
 
         Ok(Config {
@@ -85,6 +208,28 @@ impl Config {
             keycloak_client_id: keycloak_client_id.unwrap(),
             keycloak_client_secret: keycloak_client_secret.unwrap(),
             content_service_url: content_service_url.unwrap(),
+            user_cache_capacity,
+            user_cache_ttl_seconds,
+            event_sink_file,
+            event_dispatch_interval_ms,
+            event_dispatch_batch_size,
+            account_deletion_grace_days,
+            account_purge_interval_seconds,
+            data_export_interval_seconds,
+            avatar_cleanup_interval_seconds,
+            suspension_expiry_interval_seconds,
+            keycloak_repair_interval_seconds,
+            username_change_cooldown_days,
+            username_hold_days,
+            reserved_usernames,
+            blocked_usernames,
+            email_verification_secret: email_verification_secret.unwrap(),
+            email_verification_ttl_hours,
+            email_verification_url,
+            mailer_file,
+            mail_dispatch_interval_seconds,
+            keycloak_webhook_secret,
+            trusted_proxies,
         })
     }
 }
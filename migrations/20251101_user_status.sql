@@ -0,0 +1,8 @@
+-- Presence status
+CREATE TYPE user_status AS ENUM ('online', 'idle', 'do_not_disturb', 'invisible');
+
+ALTER TABLE users
+    ADD COLUMN status user_status NOT NULL DEFAULT 'online',
+    ADD COLUMN custom_status_text VARCHAR(128),
+    ADD COLUMN custom_status_emoji VARCHAR(64),
+    ADD COLUMN custom_status_expires_at TIMESTAMP WITH TIME ZONE;
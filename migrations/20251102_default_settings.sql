@@ -0,0 +1,4 @@
+-- Provision settings for users created before settings were created with the user
+INSERT INTO param (sub)
+SELECT sub FROM users
+ON CONFLICT (sub) DO NOTHING;
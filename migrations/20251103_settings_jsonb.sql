@@ -0,0 +1,9 @@
+-- Store settings as a JSON object so new settings do not need a migration
+ALTER TABLE param ADD COLUMN settings JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+UPDATE param
+SET settings = jsonb_strip_nulls(jsonb_build_object('theme', theme, 'lang', lang));
+
+ALTER TABLE param
+    DROP COLUMN theme,
+    DROP COLUMN lang;
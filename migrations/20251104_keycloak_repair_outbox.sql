@@ -0,0 +1,16 @@
+-- Keycloak values that could not be restored after a failed user update.
+-- Repairs are replayed by a worker and deleted once applied; `applied` holds
+-- the values the failed update wrote, so a repair superseded by a later
+-- change is dropped instead of replayed.
+CREATE TABLE IF NOT EXISTS keycloak_repair_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    sub UUID NOT NULL,
+    restore JSONB NOT NULL,
+    applied JSONB NOT NULL,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    locked_until TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_keycloak_repair_outbox_created ON keycloak_repair_outbox(created_at);
@@ -0,0 +1,14 @@
+-- Transactional outbox of user domain events
+CREATE TABLE IF NOT EXISTS user_events (
+    id BIGSERIAL PRIMARY KEY,
+    sub UUID NOT NULL,
+    event_type VARCHAR(64) NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE,
+    dispatched_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_user_events_pending ON user_events(id) WHERE dispatched_at IS NULL;
+-- Claims look for older pending events of the same sub
+CREATE INDEX idx_user_events_pending_sub ON user_events(sub, id) WHERE dispatched_at IS NULL;
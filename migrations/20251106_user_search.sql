@@ -0,0 +1,5 @@
+-- Trigram index backing user search on display names
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_users_display_name_trgm
+    ON users USING GIN (lower(display_name) gin_trgm_ops);
@@ -0,0 +1,15 @@
+-- Relationships between users, stored once per side
+CREATE TYPE relationship_kind AS ENUM ('friend', 'pending_outgoing', 'pending_incoming', 'blocked');
+
+CREATE TABLE IF NOT EXISTS relationships (
+    user_sub UUID NOT NULL REFERENCES users(sub) ON DELETE CASCADE,
+    target_sub UUID NOT NULL REFERENCES users(sub) ON DELETE CASCADE,
+    kind relationship_kind NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_sub, target_sub),
+    CHECK (user_sub <> target_sub)
+);
+
+-- Blocks are checked from the target side on every profile read
+CREATE INDEX idx_relationships_blocked ON relationships(target_sub, user_sub) WHERE kind = 'blocked';
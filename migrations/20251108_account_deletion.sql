@@ -0,0 +1,18 @@
+-- Account deletion: soft-delete with a grace period, then a resumable purge
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE IF NOT EXISTS user_deletions (
+    sub UUID PRIMARY KEY REFERENCES users(sub) ON DELETE CASCADE,
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    purge_after TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- Completion time of each purge step, so an interrupted purge resumes
+    -- where it stopped
+    avatar_deleted_at TIMESTAMP WITH TIME ZONE,
+    keycloak_deleted_at TIMESTAMP WITH TIME ZONE,
+    anonymized_at TIMESTAMP WITH TIME ZONE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    locked_until TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_user_deletions_due ON user_deletions(purge_after) WHERE anonymized_at IS NULL;
@@ -0,0 +1,19 @@
+-- GDPR data exports, assembled asynchronously by a background worker
+CREATE TYPE data_export_status AS ENUM ('pending', 'running', 'completed', 'failed', 'expired');
+
+CREATE TABLE IF NOT EXISTS data_exports (
+    id UUID PRIMARY KEY,
+    sub UUID NOT NULL REFERENCES users(sub) ON DELETE CASCADE,
+    status data_export_status NOT NULL DEFAULT 'pending',
+    document JSONB,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMP WITH TIME ZONE,
+    -- The document is dropped once this date has passed
+    expires_at TIMESTAMP WITH TIME ZONE,
+    locked_until TIMESTAMP WITH TIME ZONE
+);
+
+-- At most one export in progress per user
+CREATE UNIQUE INDEX idx_data_exports_active ON data_exports(sub) WHERE status IN ('pending', 'running');
+CREATE INDEX idx_data_exports_queue ON data_exports(created_at) WHERE status IN ('pending', 'running');
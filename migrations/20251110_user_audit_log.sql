@@ -0,0 +1,40 @@
+-- Append-only history of profile and account changes
+CREATE TYPE audit_action AS ENUM (
+    'profile_updated',
+    'settings_updated',
+    'account_deletion_requested',
+    'account_deletion_cancelled'
+);
+
+-- No foreign keys: entries outlive the users they mention
+CREATE TABLE IF NOT EXISTS user_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor_sub UUID NOT NULL,
+    target_sub UUID NOT NULL,
+    action audit_action NOT NULL,
+    -- Array of {field, before, after}, emails redacted
+    changes JSONB NOT NULL DEFAULT '[]',
+    ip_address TEXT,
+    user_agent TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_user_audit_log_target ON user_audit_log(target_sub, id DESC);
+CREATE INDEX idx_user_audit_log_actor ON user_audit_log(actor_sub, id DESC);
+
+-- Account purges redact the entries of the purged user. Updates are only
+-- allowed in a transaction that opted in, and never change who did what when
+CREATE OR REPLACE FUNCTION reject_audit_log_update() RETURNS TRIGGER AS $$
+BEGIN
+    IF current_setting('user_service.audit_redaction', true) = 'on'
+       AND (NEW.id, NEW.actor_sub, NEW.target_sub, NEW.action, NEW.created_at)
+           = (OLD.id, OLD.actor_sub, OLD.target_sub, OLD.action, OLD.created_at) THEN
+        RETURN NEW;
+    END IF;
+    RAISE EXCEPTION 'user_audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_audit_log_append_only
+    BEFORE UPDATE ON user_audit_log
+    FOR EACH ROW EXECUTE FUNCTION reject_audit_log_update();
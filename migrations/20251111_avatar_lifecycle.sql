@@ -0,0 +1,14 @@
+-- Object key of the avatar uploaded through the content service. When set, it
+-- takes precedence over profile_picture, which only holds external URLs.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_key VARCHAR(500);
+
+-- Replaced avatars waiting to be deleted from the content service
+CREATE TABLE IF NOT EXISTS avatar_cleanups (
+    object_key VARCHAR(500) PRIMARY KEY,
+    queued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    locked_until TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_avatar_cleanups_queue ON avatar_cleanups(queued_at);
@@ -0,0 +1,6 @@
+-- Profile customization: banner image, accent color, pronouns and links
+ALTER TABLE users ADD COLUMN IF NOT EXISTS banner_key VARCHAR(500);
+ALTER TABLE users ADD COLUMN IF NOT EXISTS accent_color VARCHAR(7);
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pronouns VARCHAR(40);
+-- Ordered array of {"label", "url"} objects
+ALTER TABLE users ADD COLUMN IF NOT EXISTS links JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,5 @@
+-- Per-field profile visibility, e.g. {"description": {"visibility": "only_me"}}.
+-- Missing fields are visible to everyone.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS privacy JSONB NOT NULL DEFAULT '{}';
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'privacy_updated';
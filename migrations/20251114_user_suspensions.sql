@@ -0,0 +1,15 @@
+-- Account suspensions issued by moderators. A NULL suspended_until means the
+-- suspension lasts until it is lifted.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS suspended_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS suspended_until TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS suspended_by UUID;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS suspension_reason VARCHAR(500);
+-- Whether the Keycloak account has been disabled for the suspension
+ALTER TABLE users ADD COLUMN IF NOT EXISTS suspension_disabled_keycloak BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_users_suspended_until
+    ON users (suspended_until)
+    WHERE suspended_at IS NOT NULL;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'user_suspended';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'suspension_lifted';
@@ -0,0 +1,24 @@
+-- Usernames claimed by each user. The current username has no released_at;
+-- released ones stay held for their previous owner until held_until.
+-- No foreign key: holds outlive the users table row until the account is
+-- purged.
+CREATE TABLE IF NOT EXISTS username_history (
+    id BIGSERIAL PRIMARY KEY,
+    sub UUID NOT NULL,
+    username VARCHAR(255) NOT NULL,
+    -- Lowercased, separator-free form with confusable characters folded
+    skeleton VARCHAR(255) NOT NULL,
+    claimed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    released_at TIMESTAMP WITH TIME ZONE,
+    held_until TIMESTAMP WITH TIME ZONE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_username_history_current
+    ON username_history(sub)
+    WHERE released_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_username_history_skeleton ON username_history(skeleton);
+-- Two users may not hold confusable current usernames at the same time
+CREATE UNIQUE INDEX IF NOT EXISTS idx_username_history_current_skeleton
+    ON username_history(skeleton)
+    WHERE released_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_username_history_sub ON username_history(sub, released_at DESC);
@@ -0,0 +1,25 @@
+-- Email changes waiting for the new address to be confirmed. A user has at
+-- most one; a new request replaces it.
+CREATE TABLE IF NOT EXISTS email_changes (
+    sub UUID PRIMARY KEY REFERENCES users(sub) ON DELETE CASCADE,
+    new_email VARCHAR(254) NOT NULL,
+    -- Address the change notice goes to, recorded when the change is
+    -- requested: by verification time Keycloak may already hold the new one
+    old_email VARCHAR(254) NOT NULL,
+    -- Ties a verification token to this request, so older tokens stop working
+    nonce UUID NOT NULL,
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Emails waiting to be handed to the mailer. Rows are deleted once sent.
+CREATE TABLE IF NOT EXISTS outgoing_emails (
+    id BIGSERIAL PRIMARY KEY,
+    recipient VARCHAR(254) NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE
+);
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'email_change_requested';